@@ -1,6 +1,20 @@
 fn main() {
     tauri_build::build();
 
+    // URL e chave anon do projeto (as mesmas do frontend) vão embutidas no binário; na
+    // inicialização o app as copia para o cofre de segredos
+    println!("cargo:rerun-if-changed=../.env");
+    let _ = dotenv::from_path("../.env");
+    for (from, to) in [
+        ("VITE_SUPABASE_URL", "ALMOX_SUPABASE_URL"),
+        ("VITE_SUPABASE_ANON_KEY", "ALMOX_SUPABASE_ANON_KEY"),
+    ] {
+        println!("cargo:rerun-if-env-changed={}", from);
+        if let Ok(value) = std::env::var(from) {
+            println!("cargo:rustc-env={}={}", to, value);
+        }
+    }
+
     // Copiar libcidbio.dll do exemplo C# para o diretório de build
     // IMPORTANTE: Usar versão do exemplo C# que inclui todas as dependências necessárias
     // A versão em lib/C_C++/win64 NÃO funciona em produção (falta dependências)
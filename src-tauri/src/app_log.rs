@@ -0,0 +1,423 @@
+use chrono::{Local, NaiveDate};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use crate::biometric_error::BiometricError;
+use crate::station_config::app_data_dir;
+
+/// Tamanho máximo de um arquivo de log antes de abrir o próximo do mesmo dia
+const MAX_LOG_FILE_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Arquivos mais antigos que isso são apagados
+const RETENTION: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+
+/// Máximo de arquivos guardados, mesmo dentro do prazo
+const MAX_LOG_FILES: usize = 30;
+
+/// Máximo de linhas devolvidas por `get_recent_logs`
+const MAX_RECENT_LOGS: usize = 2000;
+
+/// Variável de ambiente com o nível de log do app (`error`, `warn`, `info`, `debug`, `trace`)
+const LEVEL_ENV: &str = "ALMOX_LOG_LEVEL";
+
+/// Nome do campo que, seguido de `=` ou `:`, tem o valor omitido do log.
+fn is_secret_field(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name.ends_with("key")
+        || name.ends_with("token")
+        || name.ends_with("secret")
+        || name.ends_with("password")
+        || name == "authorization"
+        || name == "apikey"
+}
+
+/// Trecho longo com maiúsculas, minúsculas e dígitos: base64 de template, JWT ou chave.
+/// Templates sempre caem aqui (o base64 de um template tem centenas de caracteres).
+/// URLs, UUIDs e hashes em hex não entram (não misturam maiúsculas e minúsculas).
+fn looks_like_secret(token: &str) -> bool {
+    token.len() >= 40
+        && token.chars().any(|c| c.is_ascii_uppercase())
+        && token.chars().any(|c| c.is_ascii_lowercase())
+        && token.chars().any(|c| c.is_ascii_digit())
+}
+
+/// Remove da mensagem chaves, tokens e templates antes de ela chegar ao log.
+pub fn redact(message: &str) -> String {
+    fn is_token_char(c: char) -> bool {
+        c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '_' | '-' | '.')
+    }
+
+    let mut out = String::with_capacity(message.len());
+    let mut token = String::new();
+    // Campo sensível visto e separador desde ele (o valor vem depois de `=` ou `:`)
+    let mut secret_field: Option<(bool, String)> = None;
+
+    fn flush(token: &mut String, out: &mut String, secret_field: &mut Option<(bool, String)>) {
+        if token.is_empty() {
+            return;
+        }
+        let value_of_secret = secret_field
+            .take()
+            .map(|(bearer, sep)| bearer || sep.contains('=') || sep.contains(':'))
+            .unwrap_or(false);
+        if token.eq_ignore_ascii_case("bearer") {
+            out.push_str(token);
+            *secret_field = Some((true, String::new()));
+        } else if value_of_secret || looks_like_secret(token) {
+            out.push_str("[omitido]");
+        } else {
+            out.push_str(token);
+            if is_secret_field(token) {
+                *secret_field = Some((false, String::new()));
+            }
+        }
+        token.clear();
+    }
+
+    for c in message.chars() {
+        if is_token_char(c) {
+            token.push(c);
+            continue;
+        }
+        flush(&mut token, &mut out, &mut secret_field);
+        if let Some((_, sep)) = secret_field.as_mut() {
+            sep.push(c);
+            // O valor tem que vir logo depois do campo
+            if sep.len() > 4 {
+                secret_field = None;
+            }
+        }
+        out.push(c);
+    }
+    flush(&mut token, &mut out, &mut secret_field);
+    out
+}
+
+thread_local! {
+    static CONTEXT: RefCell<Vec<(&'static str, String)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Contexto da operação em andamento nesta thread; sai ao ser descartado.
+pub struct LogContext {
+    fields: usize,
+}
+
+impl Drop for LogContext {
+    fn drop(&mut self) {
+        CONTEXT.with(|ctx| {
+            let mut ctx = ctx.borrow_mut();
+            let len = ctx.len().saturating_sub(self.fields);
+            ctx.truncate(len);
+        });
+    }
+}
+
+/// Anexa campos (ex.: `user_id`, `operation_id`) a todo log desta thread até o
+/// `LogContext` devolvido ser descartado. Campos `None` são ignorados.
+pub fn context(fields: &[(&'static str, Option<&str>)]) -> LogContext {
+    let fields: Vec<(&'static str, String)> = fields
+        .iter()
+        .filter_map(|(name, value)| value.map(|v| (*name, v.to_string())))
+        .collect();
+    let count = fields.len();
+    CONTEXT.with(|ctx| ctx.borrow_mut().extend(fields));
+    LogContext { fields: count }
+}
+
+/// Uma linha dos arquivos de log (JSON).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LogEntry {
+    pub timestamp: String,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub context: BTreeMap<String, String>,
+}
+
+fn logs_dir() -> PathBuf {
+    app_data_dir().join("logs")
+}
+
+/// Arquivo de log aberto (um por dia, com sufixo `.N` quando passa do tamanho).
+struct LogFile {
+    date: NaiveDate,
+    index: u32,
+    size: u64,
+    file: Option<File>,
+}
+
+impl LogFile {
+    fn path(date: NaiveDate, index: u32) -> PathBuf {
+        let name = match index {
+            0 => format!("app-{}.log", date),
+            n => format!("app-{}.{}.log", date, n),
+        };
+        logs_dir().join(name)
+    }
+
+    /// Abre o arquivo do dia; continua o último aberto se ainda couber.
+    fn open(date: NaiveDate) -> Self {
+        let mut index = 0;
+        while Self::path(date, index + 1).exists() {
+            index += 1;
+        }
+        let mut log_file = Self {
+            date,
+            index,
+            size: 0,
+            file: None,
+        };
+        log_file.reopen();
+        log_file
+    }
+
+    fn reopen(&mut self) {
+        let path = Self::path(self.date, self.index);
+        let _ = std::fs::create_dir_all(logs_dir());
+        self.file = OpenOptions::new().create(true).append(true).open(&path).ok();
+        self.size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    }
+
+    fn write(&mut self, line: &str) {
+        let today = Local::now().date_naive();
+        if today != self.date {
+            *self = Self::open(today);
+            prune_logs();
+        } else if self.size >= MAX_LOG_FILE_BYTES {
+            self.index += 1;
+            self.reopen();
+            prune_logs();
+        }
+        if let Some(file) = self.file.as_mut() {
+            if writeln!(file, "{}", line).is_ok() {
+                self.size += line.len() as u64 + 1;
+            }
+        }
+    }
+}
+
+/// Arquivos de log, do mais novo para o mais antigo.
+fn log_files() -> Vec<(PathBuf, SystemTime)> {
+    let Ok(entries) = std::fs::read_dir(logs_dir()) else {
+        return Vec::new();
+    };
+    let mut files: Vec<(PathBuf, SystemTime)> = entries
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "log"))
+        .filter_map(|p| {
+            let modified = std::fs::metadata(&p).and_then(|m| m.modified()).ok()?;
+            Some((p, modified))
+        })
+        .collect();
+    files.sort_by_key(|(_, modified)| std::cmp::Reverse(*modified));
+    files
+}
+
+/// Apaga arquivos fora do prazo de retenção ou além do limite de arquivos.
+fn prune_logs() {
+    let now = SystemTime::now();
+    for (index, (path, modified)) in log_files().into_iter().enumerate() {
+        let expired = now.duration_since(modified).map(|age| age > RETENTION).unwrap_or(false);
+        // O mais novo é o arquivo em uso
+        if index > 0 && (expired || index >= MAX_LOG_FILES) {
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+}
+
+/// Logger do app: console + arquivos JSON rotativos em `logs` na pasta de dados.
+struct AppLogger {
+    level: LevelFilter,
+    file: Mutex<LogFile>,
+}
+
+impl AppLogger {
+    fn is_app_target(target: &str) -> bool {
+        target.starts_with(env!("CARGO_CRATE_NAME"))
+    }
+}
+
+impl Log for AppLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // Bibliotecas (reqwest, tauri...) só a partir de `info`
+        let limit = if Self::is_app_target(metadata.target()) {
+            self.level
+        } else {
+            self.level.min(LevelFilter::Info)
+        };
+        metadata.level() <= limit
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let context: BTreeMap<String, String> = CONTEXT.with(|ctx| {
+            ctx.borrow()
+                .iter()
+                .map(|(name, value)| (name.to_string(), redact(value)))
+                .collect()
+        });
+        let entry = LogEntry {
+            timestamp: Local::now().to_rfc3339(),
+            level: record.level().to_string(),
+            target: record.target().to_string(),
+            message: redact(&record.args().to_string()),
+            context,
+        };
+
+        let fields: String = entry.context.iter().map(|(k, v)| format!(" {}={}", k, v)).collect();
+        let _ = writeln!(
+            std::io::stderr(),
+            "[{} {:<5} {}] {}{}",
+            entry.timestamp,
+            entry.level,
+            entry.target,
+            entry.message,
+            fields
+        );
+
+        if let Ok(line) = serde_json::to_string(&entry) {
+            self.file.lock().unwrap_or_else(|p| p.into_inner()).write(&line);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = self.file.lock().unwrap_or_else(|p| p.into_inner()).file.as_mut() {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Move o antigo `biometria.log` (sem rotação) para a pasta de logs, onde a retenção vale.
+fn adopt_legacy_log() {
+    let legacy = app_data_dir().join("biometria.log");
+    if legacy.exists() {
+        let _ = std::fs::create_dir_all(logs_dir());
+        let _ = std::fs::rename(&legacy, logs_dir().join("biometria-legado.log"));
+    }
+}
+
+/// Nível sem `ALMOX_LOG_LEVEL`: `debug` no desenvolvimento, `info` no executável de release
+const DEFAULT_LEVEL: LevelFilter = if cfg!(debug_assertions) { LevelFilter::Debug } else { LevelFilter::Info };
+
+/// Instala o logger do app. Nível em `ALMOX_LOG_LEVEL` (padrão `DEFAULT_LEVEL`).
+pub fn init() {
+    let level = std::env::var(LEVEL_ENV)
+        .ok()
+        .and_then(|v| v.parse::<LevelFilter>().ok())
+        .unwrap_or(DEFAULT_LEVEL);
+
+    adopt_legacy_log();
+    prune_logs();
+
+    let logger = AppLogger {
+        level,
+        file: Mutex::new(LogFile::open(Local::now().date_naive())),
+    };
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+fn read_entries(path: &Path) -> Vec<LogEntry> {
+    let Ok(file) = File::open(path) else {
+        return Vec::new();
+    };
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect()
+}
+
+/// Comando Tauri: últimas linhas de log para o painel de diagnóstico (mais antigas primeiro).
+///
+/// `min_level` filtra pelo nível (padrão `info`); `user_id` e `operation_id` pelo contexto.
+#[tauri::command]
+pub async fn get_recent_logs(
+    limit: Option<usize>,
+    min_level: Option<String>,
+    user_id: Option<String>,
+    operation_id: Option<String>,
+) -> Result<Vec<LogEntry>, BiometricError> {
+    let limit = limit.unwrap_or(200).min(MAX_RECENT_LOGS);
+    let min_level = match min_level {
+        Some(level) => level
+            .parse::<Level>()
+            .map_err(|_| BiometricError::Config(format!("Nível de log inválido: {}", level)))?,
+        None => Level::Info,
+    };
+
+    tokio::task::spawn_blocking(move || {
+        let matches = |entry: &LogEntry| {
+            let field = |name: &str, wanted: &Option<String>| {
+                wanted.as_ref().map(|w| entry.context.get(name) == Some(w)).unwrap_or(true)
+            };
+            entry.level.parse::<Level>().map(|l| l <= min_level).unwrap_or(false)
+                && field("user_id", &user_id)
+                && field("operation_id", &operation_id)
+        };
+
+        let mut recent = Vec::new();
+        for (path, _) in log_files() {
+            let entries = read_entries(&path);
+            recent.extend(entries.into_iter().rev().filter(|e| matches(e)));
+            if recent.len() >= limit {
+                break;
+            }
+        }
+        recent.truncate(limit);
+        recent.reverse();
+        Ok(recent)
+    })
+    .await
+    .map_err(|e| BiometricError::Io(format!("Erro ao ler logs: {}", e)))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_of_secret_fields_are_omitted() {
+        assert_eq!(redact("service_key=abc123 user=ana"), "service_key=[omitido] user=ana");
+        assert_eq!(redact("password: hunter2"), "password: [omitido]");
+        assert_eq!(redact("{\"access_token\":\"abc\"}"), "{\"access_token\":\"[omitido]\"}");
+    }
+
+    #[test]
+    fn bearer_tokens_are_omitted() {
+        assert_eq!(redact("Authorization: Bearer abc.def.ghi"), "Authorization: Bearer [omitido]");
+    }
+
+    #[test]
+    fn long_base64_is_omitted() {
+        let template = "AbCdEf0123456789AbCdEf0123456789AbCdEf0123456789AbCdEf01+/";
+        let redacted = redact(&format!("template {} recebido", template));
+        assert!(!redacted.contains(template));
+        assert_eq!(redacted, "template [omitido] recebido");
+    }
+
+    #[test]
+    fn ids_urls_and_plain_text_are_kept() {
+        for message in [
+            "user_id=550e8400-e29b-41d4-a716-446655440000",
+            "HTTP GET https://projeto.supabase.co/rest/v1/rpc/biometric_user_templates",
+            "hash 9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08",
+            "monkey business",
+            "Biometria validada. score=16000 percent=80",
+        ] {
+            assert_eq!(redact(message), message);
+        }
+    }
+}
@@ -0,0 +1,700 @@
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::biometric_error::BiometricError;
+use crate::biometric_receipt::StationKey;
+use crate::station_config::{app_data_dir, station_id};
+
+/// `prev_hash` do primeiro registro da cadeia
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Tipo de evento auditado.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditCategory {
+    /// Cadastro, substituição e remoção de digitais
+    Enrollment,
+    /// Verificação 1:1 e identificação 1:N
+    Verification,
+    /// Entrega de material ao funcionário
+    Delivery,
+    /// Demais movimentações de estoque (devolução, troca, transferência...)
+    Stock,
+    /// Mudança de configuração da estação
+    Config,
+    /// Bloqueios, reenvios e liberações do supervisor
+    Security,
+    /// Informado pela tela (`record_audit_event`), sem confirmação do backend
+    Frontend,
+}
+
+/// Conteúdo de um registro, na ordem em que entra no hash.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AuditEntry {
+    /// Sequência contínua a partir de 1
+    pub seq: u64,
+    /// Momento do evento (RFC 3339, horário local)
+    pub timestamp: String,
+    pub station_id: String,
+    pub category: AuditCategory,
+    pub action: String,
+    pub user_id: Option<String>,
+    pub data: serde_json::Value,
+    /// Hash do registro anterior (`GENESIS_HASH` no primeiro)
+    pub prev_hash: String,
+}
+
+impl AuditEntry {
+    fn digest(&self) -> String {
+        let bytes = serde_json::to_vec(self).unwrap_or_default();
+        format!("{:x}", Sha256::digest(bytes))
+    }
+}
+
+/// Linha de `audit.jsonl`: o registro e o SHA-256 dele.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AuditRecord {
+    #[serde(flatten)]
+    pub entry: AuditEntry,
+    pub hash: String,
+}
+
+/// Último registro gravado, guardado fora do log para detectar registros apagados do fim.
+///
+/// O cabeçalho é assinado com a chave da estação: quem editar o log consegue recalcular os
+/// hashes, mas não um cabeçalho que confira com o novo último registro.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct ChainHead {
+    seq: u64,
+    hash: String,
+    /// Assinatura Ed25519 de `seq` e `hash` (base64)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<String>,
+}
+
+impl ChainHead {
+    fn genesis() -> Self {
+        Self::new(0, GENESIS_HASH.to_string())
+    }
+
+    fn new(seq: u64, hash: String) -> Self {
+        Self { seq, hash, signature: None }
+    }
+
+    fn message(&self) -> Vec<u8> {
+        format!("audit-head:{}:{}", self.seq, self.hash).into_bytes()
+    }
+
+    fn signed(mut self, key: Option<&StationKey>) -> Self {
+        self.signature = key.map(|key| key.sign(&self.message()));
+        self
+    }
+
+    fn signed_by(&self, key: &StationKey) -> bool {
+        self.signature
+            .as_deref()
+            .map(|signature| key.verify(&self.message(), signature))
+            .unwrap_or(false)
+    }
+}
+
+/// Estado da cadeia em memória.
+struct Chain {
+    /// Cabeçalho (carregado no primeiro registro)
+    head: Option<ChainHead>,
+    /// Eventos de antes de `init`, ainda sem `seq` e `prev_hash`, na ordem em que aconteceram
+    pending: Vec<AuditEntry>,
+}
+
+impl Chain {
+    /// Encadeia e grava `entry`; uma falha de gravação só vai para o log do app.
+    fn write(&mut self, log: &Path, head: &Path, entry: AuditEntry, key: Option<&StationKey>) {
+        let current = self.head.get_or_insert_with(load_head);
+        let record = link(current, entry);
+        match append(log, head, &record, key) {
+            Ok(new_head) => *current = new_head,
+            Err(e) => log::warn!(
+                "⚠️ Falha ao gravar auditoria ({:?} {}): {}",
+                record.entry.category, record.entry.action, e
+            ),
+        }
+    }
+
+    /// Grava os eventos guardados, na ordem em que aconteceram.
+    fn write_pending(&mut self, log: &Path, head: &Path, key: Option<&StationKey>) {
+        for entry in std::mem::take(&mut self.pending) {
+            self.write(log, head, entry, key);
+        }
+    }
+}
+
+/// Cadeia da estação (carregada no primeiro registro)
+static CHAIN: Mutex<Chain> = Mutex::new(Chain { head: None, pending: Vec::new() });
+
+/// Chave que assina o cabeçalho (definida por `init` na inicialização do app)
+static HEAD_KEY: OnceLock<StationKey> = OnceLock::new();
+
+fn chain() -> std::sync::MutexGuard<'static, Chain> {
+    CHAIN.lock().unwrap_or_else(|p| p.into_inner())
+}
+
+/// Passa a assinar o cabeçalho da cadeia com a chave da estação e grava os eventos que
+/// chegaram antes dela (abertura ou recriação do cofre que guarda a chave).
+pub fn init(key: &StationKey) {
+    let mut chain = chain();
+    let _ = HEAD_KEY.set(key.clone());
+    chain.write_pending(&log_path(), &head_path(), HEAD_KEY.get());
+}
+
+/// Grava os eventos de antes de `init` com o cabeçalho sem assinatura, quando a chave da
+/// estação não vai chegar (cofre que não abre): melhor um cabeçalho que a verificação
+/// recusa do que perder o motivo da falha.
+pub fn flush_pending() {
+    let mut chain = chain();
+    if !chain.pending.is_empty() {
+        log::warn!("⚠️ Chave da estação indisponível: auditoria gravada sem assinar o cabeçalho");
+        chain.write_pending(&log_path(), &head_path(), HEAD_KEY.get());
+    }
+}
+
+fn log_path() -> PathBuf {
+    app_data_dir().join("audit.jsonl")
+}
+
+fn head_path() -> PathBuf {
+    app_data_dir().join("audit_head.json")
+}
+
+fn read_head(path: &Path) -> Option<ChainHead> {
+    let content = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Cabeçalho salvo ou, sem ele, o último registro legível do log.
+fn load_head() -> ChainHead {
+    if let Some(head) = read_head(&head_path()) {
+        return head;
+    }
+    let Ok(file) = std::fs::File::open(log_path()) else {
+        return ChainHead::genesis();
+    };
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str::<AuditRecord>(&line).ok())
+        .last()
+        .map(|r| ChainHead::new(r.entry.seq, r.hash))
+        .unwrap_or_else(ChainHead::genesis)
+}
+
+/// Evento com o horário de agora, ainda fora da cadeia (`seq` e `prev_hash` vêm de `link`).
+fn new_entry(category: AuditCategory, action: &str, user_id: Option<&str>, data: serde_json::Value) -> AuditEntry {
+    AuditEntry {
+        seq: 0,
+        timestamp: Local::now().to_rfc3339(),
+        station_id: station_id(),
+        category,
+        action: action.to_string(),
+        user_id: user_id.map(str::to_string),
+        data,
+        prev_hash: String::new(),
+    }
+}
+
+/// Encadeia o evento depois de `head` e calcula o hash.
+fn link(head: &ChainHead, mut entry: AuditEntry) -> AuditRecord {
+    entry.seq = head.seq + 1;
+    entry.prev_hash = head.hash.clone();
+    AuditRecord {
+        hash: entry.digest(),
+        entry,
+    }
+}
+
+/// Grava o registro no fim do log e o novo cabeçalho (assinado, se houver chave).
+fn append(log: &Path, head: &Path, record: &AuditRecord, key: Option<&StationKey>) -> Result<ChainHead, BiometricError> {
+    if let Some(dir) = log.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(log)?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+    file.sync_data()?;
+
+    let new_head = ChainHead::new(record.entry.seq, record.hash.clone()).signed(key);
+    std::fs::write(head, serde_json::to_string(&new_head)?)?;
+    Ok(new_head)
+}
+
+/// Acrescenta um evento ao log de auditoria (`audit.jsonl` na pasta de dados).
+///
+/// Cada registro leva o hash do anterior; uma falha de gravação só vai para o log
+/// e não interrompe a operação auditada. Antes de `init` (sem a chave que assina o
+/// cabeçalho) o evento fica guardado em memória, com o horário em que aconteceu.
+pub fn record_event(category: AuditCategory, action: &str, user_id: Option<&str>, data: serde_json::Value) {
+    let entry = new_entry(category, action, user_id, data);
+    let mut chain = chain();
+    match HEAD_KEY.get() {
+        Some(key) => chain.write(&log_path(), &head_path(), entry, Some(key)),
+        None => chain.pending.push(entry),
+    }
+}
+
+/// Problema encontrado por `verify_audit_log`.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditIssueKind {
+    /// Linha que não é um registro válido
+    Unreadable,
+    /// Conteúdo alterado: o hash não confere
+    HashMismatch,
+    /// `prev_hash` não aponta para o registro anterior (inserção ou troca de ordem)
+    BrokenChain,
+    /// Sequência com buraco (registros apagados do meio)
+    Gap,
+    /// Registros apagados do fim (o cabeçalho aponta além do último registro)
+    Truncated,
+    /// Cabeçalho ausente ou sem assinatura válida da estação
+    InvalidHead,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct AuditIssue {
+    /// Linha do arquivo (1 = primeira); 0 para problemas do arquivo todo
+    pub line: usize,
+    pub seq: Option<u64>,
+    pub kind: AuditIssueKind,
+    pub message: String,
+}
+
+/// Resultado de `verify_audit_log`.
+#[derive(Serialize, Clone, Debug)]
+pub struct AuditVerification {
+    pub valid: bool,
+    pub records: usize,
+    pub last_seq: Option<u64>,
+    pub issues: Vec<AuditIssue>,
+}
+
+/// Percorre o log conferindo hash, encadeamento e sequência de cada registro, e o último
+/// registro contra o cabeçalho assinado.
+pub fn verify_chain() -> Result<AuditVerification, BiometricError> {
+    let key = HEAD_KEY
+        .get()
+        .ok_or_else(|| BiometricError::Config("Chave da estação não carregada".to_string()))?;
+    verify_files(&log_path(), &head_path(), key)
+}
+
+fn verify_files(log: &Path, head: &Path, key: &StationKey) -> Result<AuditVerification, BiometricError> {
+    let lines: Vec<String> = match std::fs::File::open(log) {
+        Ok(file) => BufReader::new(file).lines().collect::<Result<_, _>>()?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut issues = Vec::new();
+    let mut records = 0;
+    let mut last: Option<ChainHead> = None;
+    // `None` depois de uma linha ilegível: o elo seguinte não tem com o que comparar
+    let mut expected: Option<ChainHead> = Some(ChainHead::genesis());
+
+    for (index, line) in lines.iter().enumerate() {
+        let number = index + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: AuditRecord = match serde_json::from_str(line) {
+            Ok(record) => record,
+            Err(e) => {
+                issues.push(AuditIssue {
+                    line: number,
+                    seq: None,
+                    kind: AuditIssueKind::Unreadable,
+                    message: format!("Registro ilegível: {}", e),
+                });
+                expected = None;
+                continue;
+            }
+        };
+        records += 1;
+        let seq = record.entry.seq;
+
+        if record.entry.digest() != record.hash {
+            issues.push(AuditIssue {
+                line: number,
+                seq: Some(seq),
+                kind: AuditIssueKind::HashMismatch,
+                message: format!("Registro {} foi alterado", seq),
+            });
+        }
+        if let Some(previous) = &expected {
+            if seq != previous.seq + 1 {
+                issues.push(AuditIssue {
+                    line: number,
+                    seq: Some(seq),
+                    kind: AuditIssueKind::Gap,
+                    message: format!("Esperado registro {}, encontrado {}", previous.seq + 1, seq),
+                });
+            } else if record.entry.prev_hash != previous.hash {
+                issues.push(AuditIssue {
+                    line: number,
+                    seq: Some(seq),
+                    kind: AuditIssueKind::BrokenChain,
+                    message: format!("Registro {} não aponta para o registro anterior", seq),
+                });
+            }
+        }
+
+        let head = ChainHead::new(seq, record.hash);
+        expected = Some(head.clone());
+        last = Some(head);
+    }
+
+    let last_seq = last.as_ref().map(|h| h.seq).unwrap_or(0);
+    match read_head(head) {
+        None if last_seq > 0 => issues.push(AuditIssue {
+            line: 0,
+            seq: None,
+            kind: AuditIssueKind::InvalidHead,
+            message: "Cabeçalho da cadeia ausente ou ilegível".to_string(),
+        }),
+        None => {}
+        Some(saved) if !saved.signed_by(key) => issues.push(AuditIssue {
+            line: 0,
+            seq: Some(saved.seq),
+            kind: AuditIssueKind::InvalidHead,
+            message: "Cabeçalho da cadeia sem assinatura válida da estação".to_string(),
+        }),
+        Some(saved) if saved.seq > last_seq => issues.push(AuditIssue {
+            line: 0,
+            seq: Some(saved.seq),
+            kind: AuditIssueKind::Truncated,
+            message: format!("Registros {} a {} foram apagados do fim do log", last_seq + 1, saved.seq),
+        }),
+        Some(saved) if saved.seq < last_seq => issues.push(AuditIssue {
+            line: 0,
+            seq: Some(saved.seq + 1),
+            kind: AuditIssueKind::BrokenChain,
+            message: format!("Registros {} a {} não constam do cabeçalho da cadeia", saved.seq + 1, last_seq),
+        }),
+        Some(saved) if last.as_ref().map(|h| &h.hash) != Some(&saved.hash) && last_seq > 0 => {
+            issues.push(AuditIssue {
+                line: 0,
+                seq: Some(saved.seq),
+                kind: AuditIssueKind::HashMismatch,
+                message: format!("Registro {} não confere com o cabeçalho da cadeia", saved.seq),
+            })
+        }
+        Some(_) => {}
+    }
+
+    Ok(AuditVerification {
+        valid: issues.is_empty(),
+        records,
+        last_seq: last.map(|h| h.seq),
+        issues,
+    })
+}
+
+/// Resultado de `export_audit_log`.
+#[derive(Serialize, Clone, Debug)]
+pub struct AuditExport {
+    /// Arquivo JSON-lines gerado (linhas copiadas sem alteração, com os hashes)
+    pub path: String,
+    pub records: usize,
+    pub first_seq: Option<u64>,
+    pub last_seq: Option<u64>,
+}
+
+/// Aceita data (`2025-01-31`, dia inteiro no horário local) ou RFC 3339.
+/// `end` = fim do dia para datas sem horário.
+fn parse_bound(value: &str, end: bool) -> Result<DateTime<FixedOffset>, BiometricError> {
+    if let Ok(moment) = DateTime::parse_from_rfc3339(value) {
+        return Ok(moment);
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| BiometricError::Config(format!("Data inválida: {} (use AAAA-MM-DD ou RFC 3339)", value)))?;
+    let date = if end { date.succ_opt().unwrap_or(date) } else { date };
+    Local
+        .from_local_datetime(&date.and_time(chrono::NaiveTime::MIN))
+        .earliest()
+        .map(|moment| moment.fixed_offset())
+        .ok_or_else(|| BiometricError::Config(format!("Data inválida: {}", value)))
+}
+
+/// Copia os registros de `from` a `to` para `dir`; sem log ainda, a exportação sai vazia.
+fn export_range(log: &Path, dir: &Path, from: &str, to: &str) -> Result<AuditExport, BiometricError> {
+    let start = parse_bound(from, false)?;
+    let end = parse_bound(to, true)?;
+    // Data sem horário em `to` vale o dia inteiro: fim exclusivo no dia seguinte
+    let end_inclusive = DateTime::parse_from_rfc3339(to).is_ok();
+
+    let lines: Vec<String> = match std::fs::File::open(log) {
+        Ok(file) => BufReader::new(file).lines().collect::<Result<_, _>>()?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let mut exported = Vec::new();
+    let mut seqs = Vec::new();
+    for line in lines {
+        let Ok(record) = serde_json::from_str::<AuditRecord>(&line) else {
+            continue;
+        };
+        let Ok(moment) = DateTime::parse_from_rfc3339(&record.entry.timestamp) else {
+            continue;
+        };
+        if moment >= start && (moment < end || (end_inclusive && moment == end)) {
+            seqs.push(record.entry.seq);
+            exported.push(line);
+        }
+    }
+
+    std::fs::create_dir_all(dir)?;
+    let name = format!("audit_{}_{}.jsonl", from, to).replace([':', '+'], "-");
+    let path = dir.join(name);
+    let mut content = exported.join("\n");
+    if !content.is_empty() {
+        content.push('\n');
+    }
+    std::fs::write(&path, content)?;
+
+    Ok(AuditExport {
+        path: path.to_string_lossy().to_string(),
+        records: exported.len(),
+        first_seq: seqs.first().copied(),
+        last_seq: seqs.last().copied(),
+    })
+}
+
+/// Comando Tauri: confere a integridade do log de auditoria (alterações, buracos e cortes).
+#[tauri::command]
+pub async fn verify_audit_log() -> Result<AuditVerification, BiometricError> {
+    let verification = tokio::task::spawn_blocking(verify_chain)
+        .await
+        .map_err(|e| BiometricError::Io(format!("Erro ao verificar auditoria: {}", e)))??;
+    if verification.valid {
+        log::info!("🔗 Log de auditoria íntegro ({} registros)", verification.records);
+    } else {
+        log::warn!("🚨 Log de auditoria com {} problema(s)", verification.issues.len());
+    }
+    Ok(verification)
+}
+
+/// Comando Tauri: exporta os registros de um período (`from`/`to` em AAAA-MM-DD ou RFC 3339,
+/// inclusive) para `audit_exports` na pasta de dados.
+#[tauri::command]
+pub async fn export_audit_log(from: String, to: String) -> Result<AuditExport, BiometricError> {
+    let export = tokio::task::spawn_blocking(move || {
+        export_range(&log_path(), &app_data_dir().join("audit_exports"), &from, &to)
+    })
+        .await
+        .map_err(|e| BiometricError::Io(format!("Erro ao exportar auditoria: {}", e)))??;
+    log::info!("📤 {} registro(s) de auditoria exportados para {}", export.records, export.path);
+    Ok(export)
+}
+
+/// Comando Tauri: registra um evento do frontend (entregas e movimentações de estoque).
+///
+/// Sempre na categoria `frontend`: a tela não consegue gravar eventos que pareçam vir do
+/// backend (cadastros, verificações, segurança...).
+#[tauri::command]
+pub fn record_audit_event(
+    action: String,
+    user_id: Option<String>,
+    data: Option<serde_json::Value>,
+) -> Result<(), BiometricError> {
+    if action.trim().is_empty() {
+        return Err(BiometricError::Config("Informe a ação auditada".to_string()));
+    }
+    record_event(
+        AuditCategory::Frontend,
+        &action,
+        user_id.as_deref(),
+        data.unwrap_or(serde_json::Value::Null),
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Files {
+        dir: PathBuf,
+        log: PathBuf,
+        head: PathBuf,
+    }
+
+    impl Files {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("audit-test-{}-{}", std::process::id(), name));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            Self {
+                log: dir.join("audit.jsonl"),
+                head: dir.join("audit_head.json"),
+                dir,
+            }
+        }
+
+        /// Grava `count` registros encadeados e assinados com `key`
+        fn write_chain(&self, key: &StationKey, count: u64) {
+            let mut head = ChainHead::genesis();
+            for n in 0..count {
+                let entry = new_entry(AuditCategory::Stock, "test", None, serde_json::json!({ "n": n }));
+                let record = link(&head, entry);
+                head = append(&self.log, &self.head, &record, Some(key)).unwrap();
+            }
+        }
+
+        fn records(&self) -> Vec<AuditRecord> {
+            std::fs::read_to_string(&self.log)
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        }
+
+        fn rewrite(&self, records: &[AuditRecord]) {
+            let lines: Vec<String> = records.iter().map(|r| serde_json::to_string(r).unwrap()).collect();
+            std::fs::write(&self.log, lines.join("\n") + "\n").unwrap();
+        }
+
+        fn verify(&self, key: &StationKey) -> AuditVerification {
+            verify_files(&self.log, &self.head, key).unwrap()
+        }
+    }
+
+    impl Drop for Files {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn kinds(verification: &AuditVerification) -> Vec<AuditIssueKind> {
+        verification.issues.iter().map(|issue| issue.kind).collect()
+    }
+
+    #[test]
+    fn intact_chain_is_valid() {
+        let key = StationKey::from_bytes([1; 32]);
+        let files = Files::new("intact");
+        files.write_chain(&key, 3);
+
+        let verification = files.verify(&key);
+        assert!(verification.valid, "{:?}", verification.issues);
+        assert_eq!(verification.records, 3);
+        assert_eq!(verification.last_seq, Some(3));
+    }
+
+    #[test]
+    fn edited_record_is_detected() {
+        let key = StationKey::from_bytes([1; 32]);
+        let files = Files::new("edited");
+        files.write_chain(&key, 3);
+        let mut records = files.records();
+        records[1].entry.data = serde_json::json!({ "n": 99 });
+        files.rewrite(&records);
+
+        assert!(kinds(&files.verify(&key)).contains(&AuditIssueKind::HashMismatch));
+    }
+
+    #[test]
+    fn recomputed_chain_does_not_match_the_signed_head() {
+        let key = StationKey::from_bytes([1; 32]);
+        let files = Files::new("recomputed");
+        files.write_chain(&key, 3);
+
+        // Edita o registro 2 e recalcula os hashes dali em diante
+        let mut records = files.records();
+        records[1].entry.data = serde_json::json!({ "n": 99 });
+        for index in 1..records.len() {
+            records[index].entry.prev_hash = records[index - 1].hash.clone();
+            records[index].hash = records[index].entry.digest();
+        }
+        files.rewrite(&records);
+        assert_eq!(kinds(&files.verify(&key)), vec![AuditIssueKind::HashMismatch]);
+
+        // Um cabeçalho novo sem a chave da estação também não passa
+        let forged = ChainHead::new(3, records[2].hash.clone()).signed(Some(&StationKey::from_bytes([2; 32])));
+        std::fs::write(&files.head, serde_json::to_string(&forged).unwrap()).unwrap();
+        assert_eq!(kinds(&files.verify(&key)), vec![AuditIssueKind::InvalidHead]);
+    }
+
+    #[test]
+    fn removed_records_are_detected() {
+        let key = StationKey::from_bytes([1; 32]);
+        let files = Files::new("removed");
+        files.write_chain(&key, 4);
+        let records = files.records();
+
+        files.rewrite(&[records[0].clone(), records[2].clone(), records[3].clone()]);
+        assert!(kinds(&files.verify(&key)).contains(&AuditIssueKind::Gap));
+
+        files.rewrite(&records[..2]);
+        assert_eq!(kinds(&files.verify(&key)), vec![AuditIssueKind::Truncated]);
+    }
+
+    #[test]
+    fn missing_head_is_reported() {
+        let key = StationKey::from_bytes([1; 32]);
+        let files = Files::new("no-head");
+        files.write_chain(&key, 2);
+        std::fs::remove_file(&files.head).unwrap();
+
+        assert_eq!(kinds(&files.verify(&key)), vec![AuditIssueKind::InvalidHead]);
+    }
+
+    #[test]
+    fn export_without_log_is_empty() {
+        let files = Files::new("export");
+        let export = export_range(&files.log, &files.dir.join("exports"), "2026-01-01", "2026-01-31").unwrap();
+        assert_eq!(export.records, 0);
+        assert_eq!(export.first_seq, None);
+    }
+    #[test]
+    fn events_from_before_the_key_keep_their_order_and_time() {
+        let key = StationKey::from_bytes([1; 32]);
+        let files = Files::new("pending");
+        let mut chain = Chain {
+            head: Some(ChainHead::genesis()),
+            pending: Vec::new(),
+        };
+        for action in ["secret_store_reset", "secret_store_unreadable"] {
+            chain.pending.push(new_entry(AuditCategory::Security, action, None, serde_json::json!({})));
+        }
+        let timestamps: Vec<String> = chain.pending.iter().map(|e| e.timestamp.clone()).collect();
+
+        chain.write_pending(&files.log, &files.head, Some(&key));
+        let later = new_entry(AuditCategory::Config, "later", None, serde_json::json!({}));
+        chain.write(&files.log, &files.head, later, Some(&key));
+
+        assert!(chain.pending.is_empty());
+        let records = files.records();
+        let actions: Vec<&str> = records.iter().map(|r| r.entry.action.as_str()).collect();
+        assert_eq!(actions, ["secret_store_reset", "secret_store_unreadable", "later"]);
+        assert_eq!(records[0].entry.timestamp, timestamps[0]);
+        assert_eq!(records[1].entry.timestamp, timestamps[1]);
+        let verification = files.verify(&key);
+        assert!(verification.valid, "{:?}", verification.issues);
+    }
+
+    #[test]
+    fn events_flushed_without_the_key_leave_an_unsigned_head() {
+        let key = StationKey::from_bytes([1; 32]);
+        let files = Files::new("unsigned");
+        let mut chain = Chain {
+            head: Some(ChainHead::genesis()),
+            pending: vec![new_entry(AuditCategory::Security, "secret_store_unreadable", None, serde_json::json!({}))],
+        };
+
+        chain.write_pending(&files.log, &files.head, None);
+
+        assert_eq!(files.records().len(), 1);
+        assert_eq!(kinds(&files.verify(&key)), vec![AuditIssueKind::InvalidHead]);
+    }
+}
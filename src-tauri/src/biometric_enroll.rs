@@ -0,0 +1,697 @@
+use serde::Serialize;
+use tauri::Emitter;
+
+use crate::app_log;
+use crate::audit_log::{record_event, AuditCategory};
+use crate::biometric_error::BiometricError;
+use crate::biometric_identify::load_candidates;
+use crate::biometric_image::ImageOptions;
+use crate::biometric_reader::{FingerprintCapture, ReaderInfo};
+use crate::biometric_sdk;
+use crate::biometric_service::{
+    encode_image, ensure_reader_ready, notify_aborted, read_reader_info, BiometricOutcome,
+};
+use crate::biometric_templates::{
+    deactivate_templates, finger_label, load_enrolled_fingers, require_supervisor, store_templates,
+};
+use crate::biometric_worker::{AbortReason, BiometricWorker};
+use crate::supabase::{block_on, Supabase};
+use crate::supabase_auth::SupabaseAuth;
+use crate::verification_policy::PolicyCache;
+
+/// Capturas pedidas no cadastro (o CIDBIO_MergeTemplates combina exatamente três)
+pub const ENROLL_SAMPLES: usize = 3;
+
+/// Qualidade mínima da melhor captura para cadastrar
+pub const MIN_ENROLL_QUALITY: i32 = 60;
+
+/// Similaridade mínima (%) entre cada par de capturas. O frontend pode pedir
+/// mais (`enroll_consistency_percent`), nunca menos.
+pub const MIN_CONSISTENCY_PERCENT: i32 = 40;
+
+/// Comparação entre duas capturas do cadastro (índices a partir de 1).
+#[derive(Serialize, Clone, Debug)]
+pub struct PairScore {
+    pub first: usize,
+    pub second: usize,
+    pub score: i32,
+    pub percent: i32,
+}
+
+/// Como as capturas do cadastro se saíram (vai no resultado para o frontend).
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct EnrollmentReport {
+    /// Qualidade de cada captura, na ordem
+    pub sample_qualities: Vec<i32>,
+    /// Qualidade do cadastro (melhor captura)
+    pub quality: i32,
+    /// Similaridade de cada par de capturas
+    pub pair_scores: Vec<PairScore>,
+    /// Menor similaridade (%) entre os pares
+    pub consistency: i32,
+    pub consistency_threshold: i32,
+    /// `true` se as capturas viraram um único template pelo SDK
+    pub merged: bool,
+    /// Quantos templates foram (ou seriam) gravados
+    pub stored_templates: usize,
+    /// Outro usuário com digital parecida demais (cadastro bloqueado)
+    pub duplicate: Option<DuplicateMatch>,
+}
+
+/// Digital já cadastrada para outro usuário.
+#[derive(Serialize, Clone, Debug)]
+pub struct DuplicateMatch {
+    pub user_id: String,
+    pub score: i32,
+    pub percent: i32,
+}
+
+/// Parâmetros comuns a cada dedo cadastrado.
+pub struct EnrollContext<'a> {
+    pub user_id: &'a str,
+    pub base_id: Option<&'a str>,
+    pub db: &'a Supabase,
+    pub capture_timeout: Option<std::time::Duration>,
+    pub image_options: &'a ImageOptions,
+    pub consistency_threshold: i32,
+    /// Similaridade com outro usuário que bloqueia o cadastro, da política de verificação
+    /// (`VerificationPolicy::duplicate_percent`); o frontend não escolhe
+    pub duplicate_percent: i32,
+}
+
+/// Resultado do cadastro de um dedo.
+#[derive(Serialize, Clone, Debug)]
+pub struct FingerEnrollment {
+    pub finger: String,
+    pub label: String,
+    pub success: bool,
+    pub outcome: BiometricOutcome,
+    pub reason: String,
+    /// Score/percentual contra a digital de outro usuário (só em `duplicate`)
+    pub score: Option<i32>,
+    pub percent: Option<i32>,
+    pub quality: Option<i32>,
+    pub enrollment: Option<EnrollmentReport>,
+}
+
+impl FingerEnrollment {
+    fn new(finger: &str, outcome: BiometricOutcome, reason: String) -> Self {
+        Self {
+            finger: finger.to_string(),
+            label: finger_label(finger).to_string(),
+            success: outcome == BiometricOutcome::Enrolled,
+            outcome,
+            reason,
+            score: None,
+            percent: None,
+            quality: None,
+            enrollment: None,
+        }
+    }
+}
+
+/// Similaridade exigida entre as capturas: a pedida pelo frontend, se for maior que o mínimo.
+pub fn consistency_threshold(requested: Option<i32>) -> i32 {
+    requested.map_or(MIN_CONSISTENCY_PERCENT, |p| p.max(MIN_CONSISTENCY_PERCENT))
+}
+
+/// Desfecho das capturas do cadastro.
+pub enum EnrollmentCapture {
+    /// Templates prontos para gravar
+    Accepted {
+        templates: Vec<String>,
+        report: EnrollmentReport,
+    },
+    /// Capturas com qualidade baixa ou que não parecem ser do mesmo dedo
+    Rejected { reason: String, report: EnrollmentReport },
+    /// Operador cancelou ou ninguém colocou o dedo
+    Aborted(AbortReason),
+}
+
+/// Faz as capturas do cadastro, confere se todas são do mesmo dedo e as combina.
+///
+/// - Cada par de capturas é comparado; se algum ficar abaixo de
+///   `consistency_threshold` (%), o cadastro é recusado
+/// - A qualidade do cadastro é a da melhor captura (mínimo `MIN_ENROLL_QUALITY`)
+/// - As capturas são combinadas pelo SDK (CIDBIO_MergeTemplates); se o leitor
+///   não suportar ou não conseguir combinar, todas as capturas são gravadas
+pub fn capture_enrollment(
+    app: &tauri::AppHandle,
+    worker: &BiometricWorker,
+    finger_name: &str,
+    capture_timeout: Option<std::time::Duration>,
+    image_options: &ImageOptions,
+    consistency_threshold: i32,
+) -> Result<EnrollmentCapture, BiometricError> {
+    let mut samples: Vec<FingerprintCapture> = Vec::with_capacity(ENROLL_SAMPLES);
+
+    for i in 1..=ENROLL_SAMPLES {
+        log::debug!("=== INÍCIO CAPTURA {}/{} ===", i, ENROLL_SAMPLES);
+
+        // Avisar frontend para pedir o dedo
+        if i == 1 {
+            let _ = app.emit("biometric-instruction", format!("Coloque o {} no leitor", finger_name));
+        } else {
+            let _ = app.emit(
+                "biometric-instruction",
+                format!("Coloque o {} novamente ({}/{})", finger_name, i, ENROLL_SAMPLES),
+            );
+        }
+
+        let capture = match worker.capture(capture_timeout) {
+            Ok(capture) => capture,
+            Err(e) => {
+                if let Some(reason) = AbortReason::from_error(&e) {
+                    return Ok(EnrollmentCapture::Aborted(reason));
+                }
+                log::debug!("Erro na captura {}: {}", i, e);
+                let _ = app.emit("biometric-instruction", format!("❌ Erro na captura {}: {}", i, e.message()));
+                return Err(e);
+            }
+        };
+
+        log::info!("Captura {}/{}: Qualidade {}", i, ENROLL_SAMPLES, capture.quality);
+        log::debug!("Qualidade captura {}/{}: {}", i, ENROLL_SAMPLES, capture.quality);
+
+        // Emitir imagem para o frontend
+        if let Some(encoded) = encode_image(capture.image.as_ref(), image_options) {
+            let _ = app.emit("biometric-image", encoded);
+        }
+
+        samples.push(capture);
+        log::debug!("=== FIM CAPTURA {}/{} ===", i, ENROLL_SAMPLES);
+    }
+
+    evaluate_samples(worker, samples, consistency_threshold)
+}
+
+/// Confere as capturas do cadastro (mesmo dedo, qualidade) e as combina em um template.
+pub fn evaluate_samples(
+    worker: &BiometricWorker,
+    samples: Vec<FingerprintCapture>,
+    consistency_threshold: i32,
+) -> Result<EnrollmentCapture, BiometricError> {
+    let sample_qualities: Vec<i32> = samples.iter().map(|s| s.quality).collect();
+    let mut report = EnrollmentReport {
+        quality: sample_qualities.iter().copied().max().unwrap_or(0),
+        sample_qualities,
+        consistency: 100,
+        consistency_threshold,
+        ..Default::default()
+    };
+
+    // Conferir se todas as capturas são do mesmo dedo
+    for a in 0..samples.len() {
+        for b in (a + 1)..samples.len() {
+            let score = worker.match_templates(&samples[a].template, &samples[b].template)?;
+            let percent = biometric_sdk::score_to_percent(score);
+            log::debug!("Consistência captura {}×{}: score={} percent={}", a + 1, b + 1, score, percent);
+            report.consistency = report.consistency.min(percent);
+            report.pair_scores.push(PairScore {
+                first: a + 1,
+                second: b + 1,
+                score,
+                percent,
+            });
+        }
+    }
+
+    if report.consistency < consistency_threshold {
+        let worst = report
+            .pair_scores
+            .iter()
+            .min_by_key(|p| p.percent)
+            .map(|p| format!("{}×{}", p.first, p.second))
+            .unwrap_or_default();
+        log::debug!(
+            "Capturas inconsistentes: {}% < {}% (par {})",
+            report.consistency, consistency_threshold, worst
+        );
+        return Ok(EnrollmentCapture::Rejected {
+            reason: format!(
+                "As leituras não parecem ser do mesmo dedo (leituras {}: {}%). Use sempre o mesmo dedo e tente novamente.",
+                worst, report.consistency
+            ),
+            report,
+        });
+    }
+
+    if report.quality < MIN_ENROLL_QUALITY {
+        log::debug!("Qualidade insuficiente: {}", report.quality);
+        return Ok(EnrollmentCapture::Rejected {
+            reason: format!(
+                "Qualidade insuficiente (Melhor: {}%). Tente novamente com mais precisão.",
+                report.quality
+            ),
+            report,
+        });
+    }
+
+    // Combinar as capturas em um único template
+    let [first, second, third] = [0, 1, 2].map(|i| samples[i].template.clone());
+    let templates = match worker.merge_templates([first, second, third]) {
+        Ok(merged) => {
+            log::debug!("Capturas combinadas em um único template (CIDBIO_MergeTemplates)");
+            report.merged = true;
+            vec![merged]
+        }
+        Err(e @ (BiometricError::UnavailableFeature | BiometricError::Merging | BiometricError::FeatureDisabled)) => {
+            log::warn!("⚠️ Templates não combinados ({}); gravando as {} capturas", e, ENROLL_SAMPLES);
+            samples.into_iter().map(|s| s.template).collect()
+        }
+        Err(e) => return Err(e),
+    };
+    report.stored_templates = templates.len();
+
+    Ok(EnrollmentCapture::Accepted { templates, report })
+}
+
+/// Procura os templates novos entre as digitais de outros usuários da base
+/// (ou de todos, sem base). Devolve o usuário mais parecido se passar de `threshold` (%).
+pub fn find_duplicate(
+    worker: &BiometricWorker,
+    db: &Supabase,
+    user_id: &str,
+    base_id: Option<&str>,
+    templates: &[String],
+    threshold: i32,
+) -> Result<Option<DuplicateMatch>, BiometricError> {
+    let candidates = block_on(load_candidates(db, base_id))?;
+    log::debug!(
+        "Checagem de duplicidade: {} templates (base {:?}, mínimo {}%)",
+        candidates.len(),
+        base_id,
+        threshold
+    );
+
+    let mut best: Option<DuplicateMatch> = None;
+    for candidate in candidates.iter().filter(|c| c.user_id != user_id) {
+        for template in templates {
+            let score = match worker.match_templates(&candidate.template, template) {
+                Ok(score) => score,
+                Err(BiometricError::InvalidTemplate) => {
+                    log::debug!("Template inválido ignorado (user_id={})", candidate.user_id);
+                    break;
+                }
+                Err(e) => return Err(e),
+            };
+            if best.as_ref().map(|b| score > b.score).unwrap_or(true) {
+                best = Some(DuplicateMatch {
+                    user_id: candidate.user_id.clone(),
+                    score,
+                    percent: biometric_sdk::score_to_percent(score),
+                });
+            }
+        }
+    }
+
+    Ok(best.filter(|b| b.percent >= threshold))
+}
+
+/// Registra o conflito em `biometric_enrollment_conflicts` para o supervisor revisar.
+/// Falhas aqui só vão para o log: o cadastro continua bloqueado de qualquer forma.
+pub async fn record_conflict(
+    db: &Supabase,
+    user_id: &str,
+    base_id: Option<&str>,
+    duplicate: &DuplicateMatch,
+    quality: i32,
+) {
+    let body = serde_json::json!({
+        "user_id": user_id,
+        "matched_user_id": duplicate.user_id,
+        "base_id": base_id,
+        "score": duplicate.score,
+        "percent": duplicate.percent,
+        "quality": quality,
+        "status": "pending"
+    });
+
+    if let Err(e) = db.from("biometric_enrollment_conflicts").insert(&body).await {
+        log::warn!("⚠️ Falha ao registrar conflito de cadastro: {}", e);
+    }
+}
+
+/// Cadastra um dedo: capturas, consistência, checagem de duplicidade e gravação.
+///
+/// `finger_name` é como o dedo aparece nas instruções ("dedo", "indicador direito"...).
+pub fn enroll_finger(
+    app: &tauri::AppHandle,
+    worker: &BiometricWorker,
+    ctx: &EnrollContext,
+    finger: &str,
+    finger_name: &str,
+) -> Result<FingerEnrollment, BiometricError> {
+    let capture = capture_enrollment(
+        app,
+        worker,
+        finger_name,
+        ctx.capture_timeout,
+        ctx.image_options,
+        ctx.consistency_threshold,
+    )?;
+    let (templates, mut report) = match capture {
+        EnrollmentCapture::Accepted { templates, report } => (templates, report),
+        EnrollmentCapture::Aborted(reason) => {
+            let message = notify_aborted(app, reason);
+            return Ok(FingerEnrollment::new(finger, reason.into(), message));
+        }
+        EnrollmentCapture::Rejected { reason, report } => {
+            return Ok(FingerEnrollment {
+                quality: Some(report.quality),
+                enrollment: Some(report),
+                ..FingerEnrollment::new(finger, BiometricOutcome::Rejected, reason)
+            });
+        }
+    };
+
+    // A mesma digital não pode ficar cadastrada para dois usuários
+    let _ = app.emit("biometric-instruction", "Verificando cadastro...");
+    let duplicate = find_duplicate(
+        worker,
+        ctx.db,
+        ctx.user_id,
+        ctx.base_id,
+        &templates,
+        ctx.duplicate_percent,
+    )?;
+    if let Some(duplicate) = duplicate {
+        log::warn!(
+            "🚫 Cadastro bloqueado: digital de {} já cadastrada para {} ({}%)",
+            ctx.user_id, duplicate.user_id, duplicate.percent
+        );
+        block_on(record_conflict(ctx.db, ctx.user_id, ctx.base_id, &duplicate, report.quality));
+        record_event(
+            AuditCategory::Enrollment,
+            "enrollment_duplicate",
+            Some(ctx.user_id),
+            serde_json::json!({ "finger": finger, "base_id": ctx.base_id, "duplicate": duplicate }),
+        );
+
+        let reason = format!(
+            "Esta digital já está cadastrada para outro funcionário ({}%). O caso foi enviado para revisão do supervisor.",
+            duplicate.percent
+        );
+        let (score, percent) = (duplicate.score, duplicate.percent);
+        report.duplicate = Some(duplicate);
+        return Ok(FingerEnrollment {
+            score: Some(score),
+            percent: Some(percent),
+            quality: Some(report.quality),
+            enrollment: Some(report),
+            ..FingerEnrollment::new(finger, BiometricOutcome::Duplicate, reason)
+        });
+    }
+
+    block_on(store_templates(ctx.db, ctx.user_id, finger, &templates, report.quality))?;
+    log::info!("✅ Dedo {} cadastrado (qualidade {})", finger_label(finger), report.quality);
+    record_event(
+        AuditCategory::Enrollment,
+        "finger_enrolled",
+        Some(ctx.user_id),
+        serde_json::json!({
+            "finger": finger,
+            "quality": report.quality,
+            "consistency": report.consistency,
+            "merged": report.merged,
+            "stored_templates": report.stored_templates
+        }),
+    );
+
+    let reason = format!(
+        "Biometria cadastrada com sucesso! (Qualidade: {}%, consistência: {}%)",
+        report.quality, report.consistency
+    );
+    Ok(FingerEnrollment {
+        quality: Some(report.quality),
+        enrollment: Some(report),
+        ..FingerEnrollment::new(finger, BiometricOutcome::Enrolled, reason)
+    })
+}
+
+/// Resultado de uma sessão de cadastro de vários dedos.
+#[derive(Serialize)]
+pub struct EnrollmentSessionResult {
+    pub user_id: String,
+    /// Um resultado por dedo tentado, na ordem pedida
+    pub fingers: Vec<FingerEnrollment>,
+    /// Quantos dedos foram cadastrados nesta sessão
+    pub enrolled: usize,
+    /// `false` se a sessão parou antes do último dedo (cancelada, timeout ou duplicidade)
+    pub completed: bool,
+    pub reader: Option<ReaderInfo>,
+}
+
+/// Parâmetros de `enroll_fingers`
+struct EnrollSessionRequest {
+    user_id: String,
+    fingers: Vec<String>,
+    base_id: Option<String>,
+    replace_existing: bool,
+    capture_timeout: Option<std::time::Duration>,
+    image_options: ImageOptions,
+    consistency_threshold: i32,
+}
+
+/// Comando Tauri: cadastra vários dedos do usuário em uma só sessão.
+///
+/// - Os dedos são cadastrados na ordem de `fingers`; antes de cada um, a janela
+///   do funcionário recebe `biometric-enrollment-finger` e a instrução com o nome do dedo
+/// - Dedos que já têm template são pulados, a não ser com `replace_existing`, que exige um
+///   supervisor logado (os templates antigos só são desativados depois que o novo é gravado)
+/// - Um dedo recusado (qualidade/consistência) não interrompe a sessão; cancelamento,
+///   timeout ou digital de outro usuário interrompem
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn enroll_fingers(
+    app: tauri::AppHandle,
+    worker: tauri::State<'_, BiometricWorker>,
+    auth: tauri::State<'_, SupabaseAuth>,
+    policy: tauri::State<'_, PolicyCache>,
+    user_id: String,
+    fingers: Vec<String>,
+    base_id: Option<String>,
+    replace_existing: Option<bool>,
+    capture_timeout_secs: Option<u64>,
+    image_options: Option<ImageOptions>,
+    enroll_consistency_percent: Option<i32>,
+) -> Result<EnrollmentSessionResult, BiometricError> {
+    let worker = worker.inner().clone();
+    let auth = auth.inner().clone();
+    let policy = policy.inner().clone();
+    let request = EnrollSessionRequest {
+        user_id,
+        fingers,
+        base_id,
+        replace_existing: replace_existing.unwrap_or(false),
+        capture_timeout: capture_timeout_secs.map(std::time::Duration::from_secs),
+        image_options: image_options.unwrap_or_default(),
+        consistency_threshold: consistency_threshold(enroll_consistency_percent),
+    };
+
+    tokio::task::spawn_blocking(move || enroll_fingers_blocking(app, &worker, &auth, &policy, request))
+        .await
+        .map_err(|e| {
+            log::debug!("Erro ao executar sessão de cadastro: {}", e);
+            BiometricError::WorkerUnavailable
+        })?
+}
+
+fn enroll_fingers_blocking(
+    app: tauri::AppHandle,
+    worker: &BiometricWorker,
+    auth: &SupabaseAuth,
+    policy: &PolicyCache,
+    request: EnrollSessionRequest,
+) -> Result<EnrollmentSessionResult, BiometricError> {
+    let EnrollSessionRequest {
+        user_id,
+        fingers,
+        base_id,
+        replace_existing,
+        capture_timeout,
+        image_options,
+        consistency_threshold,
+    } = request;
+    let _log_context = app_log::context(&[("user_id", Some(&user_id))]);
+
+    let mut unique_fingers: Vec<String> = Vec::with_capacity(fingers.len());
+    for finger in fingers {
+        if !unique_fingers.contains(&finger) {
+            unique_fingers.push(finger);
+        }
+    }
+    if unique_fingers.is_empty() {
+        return Err(BiometricError::Config("Nenhum dedo informado para o cadastro".to_string()));
+    }
+
+    log::debug!(
+        "enroll_fingers() user_id={} fingers={:?} replace_existing={}",
+        user_id, unique_fingers, replace_existing
+    );
+
+    ensure_reader_ready(worker)?;
+    let reader = read_reader_info(worker);
+    let db = block_on(auth.connect())?;
+    let existing = block_on(load_enrolled_fingers(&db, &user_id))?;
+
+    // Trocar um dedo já cadastrado é decisão de supervisor; confere antes de capturar qualquer coisa
+    if replace_existing && existing.iter().any(|f| unique_fingers.contains(&f.finger)) {
+        block_on(require_supervisor(&db))?;
+    }
+
+    let ctx = EnrollContext {
+        user_id: &user_id,
+        base_id: base_id.as_deref(),
+        db: &db,
+        capture_timeout,
+        image_options: &image_options,
+        consistency_threshold,
+        duplicate_percent: block_on(policy.get(&db)).duplicate_percent(base_id.as_deref()),
+    };
+
+    let total = unique_fingers.len();
+    let mut results = Vec::with_capacity(total);
+    let mut completed = true;
+
+    for (index, finger) in unique_fingers.iter().enumerate() {
+        let label = finger_label(finger);
+        let previous = existing.iter().find(|f| &f.finger == finger);
+
+        if previous.is_some() && !replace_existing {
+            log::debug!("Dedo {} já cadastrado; pulando", finger);
+            results.push(FingerEnrollment::new(
+                finger,
+                BiometricOutcome::Skipped,
+                format!("{} já cadastrado", label),
+            ));
+            continue;
+        }
+
+        let _ = app.emit(
+            "biometric-enrollment-finger",
+            serde_json::json!({ "finger": finger, "label": label, "index": index + 1, "total": total }),
+        );
+        let _ = app.emit(
+            "biometric-instruction",
+            format!("Próximo dedo: {} ({}/{})", label, index + 1, total),
+        );
+
+        let result = enroll_finger(&app, worker, &ctx, finger, &label.to_lowercase())?;
+
+        // Substituição: só desativa os templates antigos depois que o novo foi gravado
+        if result.success {
+            if let Some(previous) = previous {
+                for old in &previous.templates {
+                    block_on(deactivate_templates(&db, &user_id, finger, Some(&old.id)))?;
+                }
+                log::debug!("Dedo {}: {} template(s) antigo(s) desativado(s)", finger, previous.templates.len());
+            }
+        }
+
+        let stop = matches!(
+            result.outcome,
+            BiometricOutcome::Cancelled | BiometricOutcome::Timeout | BiometricOutcome::Duplicate
+        );
+        results.push(result);
+        if stop {
+            completed = false;
+            break;
+        }
+    }
+
+    let enrolled = results.iter().filter(|r| r.success).count();
+    log::debug!("Sessão de cadastro: {}/{} dedos cadastrados", enrolled, total);
+    let _ = app.emit(
+        "biometric-instruction",
+        format!("Cadastro concluído: {} de {} dedos", enrolled, total),
+    );
+
+    Ok(EnrollmentSessionResult {
+        user_id,
+        fingers: results,
+        enrolled,
+        completed,
+        reader,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::biometric_reader::fake::{FakeCapture, FakeReader};
+    use crate::biometric_service::best_match;
+    use crate::biometric_templates::RemoteTemplate;
+    use crate::verification_policy::{Operation, VerificationPolicy};
+
+    fn fake_worker(captures: Vec<FakeCapture>) -> BiometricWorker {
+        BiometricWorker::spawn(Box::new(FakeReader::new(captures)), |_| {})
+    }
+
+    fn enroll(worker: &BiometricWorker) -> EnrollmentCapture {
+        let samples = (0..ENROLL_SAMPLES)
+            .map(|_| worker.capture(None).expect("captura do cadastro"))
+            .collect();
+        evaluate_samples(worker, samples, MIN_CONSISTENCY_PERCENT).expect("avaliação do cadastro")
+    }
+
+    fn stored(templates: Vec<String>) -> Vec<RemoteTemplate> {
+        templates
+            .into_iter()
+            .enumerate()
+            .map(|(i, template)| RemoteTemplate {
+                id: format!("t{}", i),
+                template,
+                quality: 80,
+                finger: Some("right_index".into()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn enrolled_finger_verifies_and_another_finger_does_not() {
+        let worker = fake_worker(vec![
+            FakeCapture::finger("joao", 82),
+            FakeCapture::finger("joao", 75),
+            FakeCapture::finger("joao", 90),
+            FakeCapture::finger("joao", 70),
+            FakeCapture::finger("maria", 88),
+        ]);
+
+        let EnrollmentCapture::Accepted { templates, report } = enroll(&worker) else {
+            panic!("cadastro deveria ser aceito");
+        };
+        assert!(report.merged);
+        assert_eq!(report.stored_templates, 1);
+        assert_eq!(report.consistency, 80);
+        let templates = stored(templates);
+
+        let min_percent = VerificationPolicy::built_in()
+            .resolve(Operation::Delivery, None, None, None)
+            .min_percent;
+
+        let live = worker.capture(None).unwrap();
+        let (_, percent, matched) = best_match(&worker, &templates, &live.template).unwrap();
+        assert!(percent >= min_percent, "{}% < {}%", percent, min_percent);
+        assert_eq!(matched.map(|t| t.id.as_str()), Some("t0"));
+
+        let impostor = worker.capture(None).unwrap();
+        let (_, percent, _) = best_match(&worker, &templates, &impostor.template).unwrap();
+        assert!(percent < min_percent, "{}% >= {}%", percent, min_percent);
+    }
+
+    #[test]
+    fn samples_from_different_fingers_are_rejected() {
+        let worker = fake_worker(vec![
+            FakeCapture::finger("joao", 80),
+            FakeCapture::finger("maria", 80),
+            FakeCapture::finger("joao", 80),
+        ]);
+
+        let EnrollmentCapture::Rejected { report, .. } = enroll(&worker) else {
+            panic!("leituras de dedos diferentes não podem virar cadastro");
+        };
+        assert_eq!(report.consistency, 5);
+        assert_eq!(report.stored_templates, 0);
+    }
+}
@@ -0,0 +1,373 @@
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+
+/// Erros do fluxo biométrico.
+///
+/// Cada variante tem um código estável (`code()`) para o frontend decidir o que
+/// fazer e uma mensagem pt-BR (`message()`) para mostrar ao operador. Os códigos
+/// negativos do cidbiolib (`CIDBIO_ERROR_*`) têm uma variante cada.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BiometricError {
+    // --- Códigos de retorno do cidbiolib (cidbioconf.h) ---
+    /// CIDBIO_ERROR_UNKNOWN (-1)
+    SdkUnknown,
+    /// CIDBIO_ERROR_NO_DEVICE (-2)
+    NoDevice,
+    /// CIDBIO_ERROR_NULL_ARGUMENT (-3)
+    NullArgument,
+    /// CIDBIO_ERROR_INVALID_ARGUMENT (-4)
+    InvalidArgument,
+    /// CIDBIO_ERROR_CAPTURE (-5)
+    Capture,
+    /// CIDBIO_ERROR_CAPTURE_TIMEOUT (-6); também usado para o timeout da estação
+    CaptureTimeout,
+    /// CIDBIO_ERROR_COMM_USB (-7)
+    CommUsb,
+    /// CIDBIO_ERROR_IO_ON_HOST (-8)
+    IoOnHost,
+    /// CIDBIO_ERROR_TEMPLATE_ALREADY_ENROLLED (-9)
+    TemplateAlreadyEnrolled,
+    /// CIDBIO_ERROR_MERGING (-10)
+    Merging,
+    /// CIDBIO_ERROR_MATCHING (-11)
+    Matching,
+    /// CIDBIO_ERROR_INVALID_FW_FILE (-12)
+    InvalidFirmwareFile,
+    /// CIDBIO_ERROR_NO_SPACE_LEFT_ON_DEVICE (-13)
+    NoSpaceLeftOnDevice,
+    /// CIDBIO_ERROR_NO_TEMPLATE_WITH_ID (-14)
+    NoTemplateWithId,
+    /// CIDBIO_ERROR_INVALID_ERRNO (-15)
+    InvalidErrno,
+    /// CIDBIO_ERROR_UNAVAILABLE_FEATURE (-16)
+    UnavailableFeature,
+    /// CIDBIO_ERROR_PREVIOUS_FW_VERSION (-17)
+    PreviousFirmwareVersion,
+    /// CIDBIO_ERROR_NOT_IDENTIFIED (-18)
+    NotIdentified,
+    /// CIDBIO_ERROR_BUSY (-19); também usado quando outra captura ocupa o leitor
+    Busy,
+    /// CIDBIO_ERROR_CAPTURE_CANCELED (-20); também usado para `cancel_biometric_capture`
+    CaptureCanceled,
+    /// CIDBIO_ERROR_NO_FINGER_DETECTED (-21)
+    NoFingerDetected,
+    /// CIDBIO_ERROR_INVALID_TEMPLATE (-22)
+    InvalidTemplate,
+    /// Código negativo não documentado no cidbioconf.h
+    UnknownSdkCode(i32),
+
+    // --- Ambiente / instalação ---
+    /// Driver iDBio não instalado (CIDBIO_Init retornou -2 sem driver)
+    #[cfg_attr(not(all(feature = "biometric", target_os = "windows")), allow(dead_code))]
+    DriverNotInstalled,
+    /// Porta encontrada e SDK inicializado, mas a captura falha com -1 (driver com problema)
+    DriverMalfunction { port: String },
+    /// Nenhuma porta COM respondeu como leitor
+    ReaderNotFound,
+    /// Build sem a feature `biometric` (ou fora do Windows, onde não há libcidbio)
+    #[cfg_attr(all(feature = "biometric", target_os = "windows"), allow(dead_code))]
+    FeatureDisabled,
+    /// A thread dona do SDK não está respondendo
+    WorkerUnavailable,
+
+    // --- Rede / dados / configuração ---
+    /// Sem sessão do Supabase no backend, ou sessão expirada sem renovação possível
+    Unauthenticated,
+    /// O usuário logado não tem o papel exigido (ex.: supervisor)
+    Forbidden(String),
+    /// Falha de rede ou status HTTP de erro
+    Http { status: Option<u16>, detail: String },
+    /// Resposta ou arquivo em formato inesperado
+    Parse(String),
+    /// Configuração ausente ou inválida
+    Config(String),
+    /// Erro de arquivo/sistema operacional
+    Io(String),
+}
+
+impl BiometricError {
+    /// Mapeia um código de retorno negativo do cidbiolib.
+    /// Retorna `None` para sucesso (0) e avisos (positivos).
+    pub fn from_sdk_code(code: i32) -> Option<Self> {
+        let error = match code {
+            c if c >= 0 => return None,
+            -1 => BiometricError::SdkUnknown,
+            -2 => BiometricError::NoDevice,
+            -3 => BiometricError::NullArgument,
+            -4 => BiometricError::InvalidArgument,
+            -5 => BiometricError::Capture,
+            -6 => BiometricError::CaptureTimeout,
+            -7 => BiometricError::CommUsb,
+            -8 => BiometricError::IoOnHost,
+            -9 => BiometricError::TemplateAlreadyEnrolled,
+            -10 => BiometricError::Merging,
+            -11 => BiometricError::Matching,
+            -12 => BiometricError::InvalidFirmwareFile,
+            -13 => BiometricError::NoSpaceLeftOnDevice,
+            -14 => BiometricError::NoTemplateWithId,
+            -15 => BiometricError::InvalidErrno,
+            -16 => BiometricError::UnavailableFeature,
+            -17 => BiometricError::PreviousFirmwareVersion,
+            -18 => BiometricError::NotIdentified,
+            -19 => BiometricError::Busy,
+            -20 => BiometricError::CaptureCanceled,
+            -21 => BiometricError::NoFingerDetected,
+            -22 => BiometricError::InvalidTemplate,
+            other => BiometricError::UnknownSdkCode(other),
+        };
+        Some(error)
+    }
+
+    /// Falha de rede sem resposta do servidor (sem internet, DNS, timeout): dá para
+    /// seguir com os dados locais.
+    pub fn is_offline(&self) -> bool {
+        matches!(self, BiometricError::Http { status: None, .. })
+    }
+
+    /// Código de retorno do cidbiolib correspondente, se houver
+    pub fn sdk_code(&self) -> Option<i32> {
+        let code = match self {
+            BiometricError::SdkUnknown => -1,
+            BiometricError::NoDevice => -2,
+            BiometricError::NullArgument => -3,
+            BiometricError::InvalidArgument => -4,
+            BiometricError::Capture => -5,
+            BiometricError::CaptureTimeout => -6,
+            BiometricError::CommUsb => -7,
+            BiometricError::IoOnHost => -8,
+            BiometricError::TemplateAlreadyEnrolled => -9,
+            BiometricError::Merging => -10,
+            BiometricError::Matching => -11,
+            BiometricError::InvalidFirmwareFile => -12,
+            BiometricError::NoSpaceLeftOnDevice => -13,
+            BiometricError::NoTemplateWithId => -14,
+            BiometricError::InvalidErrno => -15,
+            BiometricError::UnavailableFeature => -16,
+            BiometricError::PreviousFirmwareVersion => -17,
+            BiometricError::NotIdentified => -18,
+            BiometricError::Busy => -19,
+            BiometricError::CaptureCanceled => -20,
+            BiometricError::NoFingerDetected => -21,
+            BiometricError::InvalidTemplate => -22,
+            BiometricError::UnknownSdkCode(code) => *code,
+            _ => return None,
+        };
+        Some(code)
+    }
+
+    /// Código estável, usado pelo frontend para decidir o que fazer
+    pub fn code(&self) -> &'static str {
+        match self {
+            BiometricError::SdkUnknown => "SDK_UNKNOWN",
+            BiometricError::NoDevice => "NO_DEVICE",
+            BiometricError::NullArgument => "NULL_ARGUMENT",
+            BiometricError::InvalidArgument => "INVALID_ARGUMENT",
+            BiometricError::Capture => "CAPTURE",
+            BiometricError::CaptureTimeout => "CAPTURE_TIMEOUT",
+            BiometricError::CommUsb => "COMM_USB",
+            BiometricError::IoOnHost => "IO_ON_HOST",
+            BiometricError::TemplateAlreadyEnrolled => "TEMPLATE_ALREADY_ENROLLED",
+            BiometricError::Merging => "MERGING",
+            BiometricError::Matching => "MATCHING",
+            BiometricError::InvalidFirmwareFile => "INVALID_FW_FILE",
+            BiometricError::NoSpaceLeftOnDevice => "NO_SPACE_LEFT_ON_DEVICE",
+            BiometricError::NoTemplateWithId => "NO_TEMPLATE_WITH_ID",
+            BiometricError::InvalidErrno => "INVALID_ERRNO",
+            BiometricError::UnavailableFeature => "UNAVAILABLE_FEATURE",
+            BiometricError::PreviousFirmwareVersion => "PREVIOUS_FW_VERSION",
+            BiometricError::NotIdentified => "NOT_IDENTIFIED",
+            BiometricError::Busy => "BUSY",
+            BiometricError::CaptureCanceled => "CAPTURE_CANCELED",
+            BiometricError::NoFingerDetected => "NO_FINGER_DETECTED",
+            BiometricError::InvalidTemplate => "INVALID_TEMPLATE",
+            BiometricError::UnknownSdkCode(_) => "SDK_UNDOCUMENTED",
+            BiometricError::DriverNotInstalled => "DRIVER_NOT_INSTALLED",
+            BiometricError::DriverMalfunction { .. } => "DRIVER_MALFUNCTION",
+            BiometricError::ReaderNotFound => "READER_NOT_FOUND",
+            BiometricError::FeatureDisabled => "FEATURE_DISABLED",
+            BiometricError::WorkerUnavailable => "WORKER_UNAVAILABLE",
+            BiometricError::Unauthenticated => "UNAUTHENTICATED",
+            BiometricError::Forbidden(_) => "FORBIDDEN",
+            BiometricError::Http { .. } => "HTTP",
+            BiometricError::Parse(_) => "PARSE",
+            BiometricError::Config(_) => "CONFIG",
+            BiometricError::Io(_) => "IO",
+        }
+    }
+
+    /// Mensagem para o operador (pt-BR)
+    pub fn message(&self) -> String {
+        match self {
+            BiometricError::SdkUnknown => {
+                "Erro desconhecido no leitor biométrico. Verifique a conexão USB e tente novamente.".to_string()
+            }
+            BiometricError::NoDevice => "⚠️ DRIVER INSTALADO MAS LEITOR NÃO DETECTADO\n\n\
+                ✅ Driver instalado no sistema\n\
+                ❌ SDK não consegue detectar o leitor\n\n\
+                POSSÍVEIS CAUSAS:\n\
+                1. Leitor não está conectado corretamente\n\
+                2. Cabo USB com problema\n\
+                3. Porta USB sem energia suficiente\n\
+                4. Driver precisa ser reinstalado\n\n\
+                SOLUÇÃO:\n\
+                1. Desconecte o leitor USB\n\
+                2. Aguarde 10 segundos\n\
+                3. Conecte em outra porta USB (preferencialmente USB 2.0)\n\
+                4. Aguarde o Windows reconhecer o dispositivo\n\
+                5. Teste novamente\n\n\
+                Se o problema persistir, reinstale o driver."
+                .to_string(),
+            BiometricError::NullArgument => "Erro interno: argumento nulo enviado ao leitor.".to_string(),
+            BiometricError::InvalidArgument => "Erro interno: argumento inválido enviado ao leitor.".to_string(),
+            BiometricError::Capture => "Falha ao capturar a digital. Limpe o sensor e tente novamente.".to_string(),
+            BiometricError::CaptureTimeout => "Tempo esgotado aguardando a digital.".to_string(),
+            BiometricError::CommUsb => {
+                "Falha de comunicação USB com o leitor. Reconecte o cabo e tente novamente.".to_string()
+            }
+            BiometricError::IoOnHost => {
+                "Erro de entrada/saída no computador ao acessar o leitor. Reinicie o aplicativo.".to_string()
+            }
+            BiometricError::TemplateAlreadyEnrolled => "Esta digital já está cadastrada no leitor.".to_string(),
+            BiometricError::Merging => "Não foi possível combinar as leituras da digital. Refaça o cadastro.".to_string(),
+            BiometricError::Matching => "Não foi possível comparar as digitais.".to_string(),
+            BiometricError::InvalidFirmwareFile => "Arquivo de firmware inválido.".to_string(),
+            BiometricError::NoSpaceLeftOnDevice => "Memória do leitor cheia.".to_string(),
+            BiometricError::NoTemplateWithId => "Nenhuma digital com este identificador no leitor.".to_string(),
+            BiometricError::InvalidErrno => "Código de erro inválido informado ao leitor.".to_string(),
+            BiometricError::UnavailableFeature => "Recurso não disponível neste modelo de leitor.".to_string(),
+            BiometricError::PreviousFirmwareVersion => "Firmware informado é anterior ao instalado no leitor.".to_string(),
+            BiometricError::NotIdentified => "Digital não identificada.".to_string(),
+            BiometricError::Busy => "Leitor ocupado: há uma captura em andamento. Aguarde ou cancele a captura.".to_string(),
+            BiometricError::CaptureCanceled => "Captura cancelada pelo operador.".to_string(),
+            BiometricError::NoFingerDetected => "Nenhum dedo detectado no leitor.".to_string(),
+            BiometricError::InvalidTemplate => "Template biométrico inválido ou corrompido.".to_string(),
+            BiometricError::UnknownSdkCode(code) => {
+                format!("Erro no leitor biométrico (código {}). Tente reconectar o leitor USB.", code)
+            }
+            BiometricError::DriverNotInstalled => "⚠️ DRIVER iDBio NÃO INSTALADO\n\n\
+                ❌ Driver não está instalado\n\n\
+                SOLUÇÃO:\n\
+                1. Clique no botão '💾 Instalar Driver' no painel de diagnóstico\n\
+                2. REINICIE O WINDOWS após instalar\n\
+                3. Reconecte o leitor USB\n\
+                4. Teste novamente"
+                .to_string(),
+            BiometricError::DriverMalfunction { port } => format!(
+                "⚠️ PROBLEMA DE DRIVER DETECTADO\n\n\
+                ✅ Porta detectada: {}\n\
+                ✅ SDK inicializado com sucesso\n\
+                ❌ Falha ao capturar digital (erro -1)\n\n\
+                Isso confirma que:\n\
+                - O leitor está conectado na porta correta ({})\n\
+                - A comunicação serial funciona\n\
+                - MAS o DRIVER não está instalado/funcionando\n\n\
+                SOLUÇÃO:\n\
+                1. Clique no botão '💾 Instalar Driver'\n\
+                2. Siga as instruções do instalador (requer admin)\n\
+                3. Reinicie o aplicativo após instalar\n\
+                4. Teste novamente",
+                port, port
+            ),
+            BiometricError::ReaderNotFound => "Não foi possível detectar o leitor em nenhuma porta COM. \
+                Verifique se o driver está instalado e o leitor está conectado."
+                .to_string(),
+            BiometricError::FeatureDisabled => "Funcionalidade biométrica não está habilitada nesta build".to_string(),
+            BiometricError::WorkerUnavailable => {
+                "O serviço do leitor biométrico parou de responder. Reinicie o aplicativo.".to_string()
+            }
+            BiometricError::Unauthenticated => "Sessão expirada. Faça login novamente.".to_string(),
+            BiometricError::Forbidden(detail) => format!("Permissão negada: {}", detail),
+            BiometricError::Http { status: Some(status), detail } => {
+                format!("Erro de comunicação com o servidor (status {}): {}", status, detail)
+            }
+            BiometricError::Http { status: None, detail } => {
+                format!("Erro de comunicação com o servidor: {}", detail)
+            }
+            BiometricError::Parse(detail) => format!("Resposta em formato inesperado: {}", detail),
+            BiometricError::Config(detail) => format!("Configuração inválida: {}", detail),
+            BiometricError::Io(detail) => format!("Erro de arquivo: {}", detail),
+        }
+    }
+
+    /// Indica que o leitor precisa ser reinicializado antes da próxima operação
+    pub fn is_device_fault(&self) -> bool {
+        matches!(
+            self,
+            BiometricError::SdkUnknown
+                | BiometricError::NoDevice
+                | BiometricError::CommUsb
+                | BiometricError::IoOnHost
+                | BiometricError::DriverNotInstalled
+                | BiometricError::UnknownSdkCode(_)
+        )
+    }
+}
+
+/// Converte um código de retorno do cidbiolib em `Result`.
+/// Sucesso e avisos (>= 0) retornam o próprio código.
+pub fn check_sdk(code: i32) -> Result<i32, BiometricError> {
+    match BiometricError::from_sdk_code(code) {
+        Some(error) => Err(error),
+        None => Ok(code),
+    }
+}
+
+impl std::fmt::Display for BiometricError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.sdk_code() {
+            Some(code) => write!(f, "[{} / {}] {}", self.code(), code, self.message()),
+            None => write!(f, "[{}] {}", self.code(), self.message()),
+        }
+    }
+}
+
+impl std::error::Error for BiometricError {}
+
+/// Serializa como `{ code, message, sdk_code, detail }` para o frontend
+impl Serialize for BiometricError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let detail = match self {
+            BiometricError::Http { detail, .. }
+            | BiometricError::Forbidden(detail)
+            | BiometricError::Parse(detail)
+            | BiometricError::Config(detail)
+            | BiometricError::Io(detail) => Some(detail.clone()),
+            BiometricError::DriverMalfunction { port } => Some(port.clone()),
+            _ => None,
+        };
+
+        let mut state = serializer.serialize_struct("BiometricError", 4)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.message())?;
+        state.serialize_field("sdk_code", &self.sdk_code())?;
+        state.serialize_field("detail", &detail)?;
+        state.end()
+    }
+}
+
+impl From<reqwest::Error> for BiometricError {
+    fn from(e: reqwest::Error) -> Self {
+        BiometricError::Http {
+            status: e.status().map(|s| s.as_u16()),
+            detail: e.to_string(),
+        }
+    }
+}
+
+impl From<serde_json::Error> for BiometricError {
+    fn from(e: serde_json::Error) -> Self {
+        BiometricError::Parse(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for BiometricError {
+    fn from(e: std::io::Error) -> Self {
+        BiometricError::Io(e.to_string())
+    }
+}
+
+impl From<rusqlite::Error> for BiometricError {
+    fn from(e: rusqlite::Error) -> Self {
+        BiometricError::Io(format!("Erro no banco local: {}", e))
+    }
+}
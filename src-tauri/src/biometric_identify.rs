@@ -0,0 +1,564 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::app_log;
+use crate::biometric_error::BiometricError;
+use crate::biometric_image::{EncodedImage, ImageOptions};
+use crate::biometric_lockout::{record_anomaly, FailureOutcome, LockoutTracker};
+use crate::biometric_reader::{FingerprintCapture, ReaderInfo};
+use crate::biometric_receipt::{StationKey, VerificationReceipt};
+use crate::biometric_sdk;
+use crate::biometric_service::{
+    encode_image, ensure_reader_ready, notify_aborted, read_reader_info,
+    record_verification, BiometricOutcome, VerificationRecord,
+};
+use crate::biometric_worker::{AbortReason, BiometricWorker};
+use crate::supabase::{block_on, Supabase};
+use crate::supabase_auth::SupabaseAuth;
+use crate::verification_policy::{AppliedPolicy, Criticality, Operation, PolicyCache};
+
+/// Por quanto tempo os candidatos de uma base ficam em memória
+const CANDIDATE_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Margem mínima (pontos percentuais) entre o melhor usuário e o segundo.
+/// O frontend pode pedir uma margem maior, nunca menor.
+const MIN_MARGIN_PERCENT: i32 = 10;
+
+/// Um template candidato à identificação.
+#[derive(Deserialize, Clone, Debug)]
+pub struct CandidateTemplate {
+    pub user_id: String,
+    pub template: String,
+}
+
+struct CachedCandidates {
+    loaded_at: Instant,
+    templates: Arc<Vec<CandidateTemplate>>,
+}
+
+/// Templates candidatos da identificação 1:N, por base, guardados em memória.
+///
+/// Evita baixar todos os templates da base a cada funcionário que chega ao balcão.
+#[derive(Clone, Default)]
+pub struct CandidateCache {
+    entries: Arc<Mutex<HashMap<Option<String>, CachedCandidates>>>,
+}
+
+impl CandidateCache {
+    fn get(&self, base_id: &Option<String>) -> Option<Arc<Vec<CandidateTemplate>>> {
+        let entries = self.entries.lock().unwrap_or_else(|p| p.into_inner());
+        entries
+            .get(base_id)
+            .filter(|cached| cached.loaded_at.elapsed() < CANDIDATE_CACHE_TTL)
+            .map(|cached| cached.templates.clone())
+    }
+
+    fn put(&self, base_id: Option<String>, templates: Vec<CandidateTemplate>) -> Arc<Vec<CandidateTemplate>> {
+        let templates = Arc::new(templates);
+        let mut entries = self.entries.lock().unwrap_or_else(|p| p.into_inner());
+        entries.insert(
+            base_id,
+            CachedCandidates {
+                loaded_at: Instant::now(),
+                templates: templates.clone(),
+            },
+        );
+        templates
+    }
+}
+
+#[derive(Serialize)]
+pub struct IdentificationResult {
+    pub success: bool,
+    pub outcome: BiometricOutcome,
+    pub reason: String,
+    /// Usuário reconhecido (só quando `success`)
+    pub user_id: Option<String>,
+    /// Score bruto (0-20000) do melhor usuário
+    pub score: Option<i32>,
+    pub percent: Option<i32>,
+    /// Diferença, em pontos percentuais, para o segundo usuário mais parecido
+    pub margin: Option<i32>,
+    /// Usuário mais parecido depois do melhor
+    pub runner_up_user_id: Option<String>,
+    /// Quantos usuários foram comparados
+    pub candidates: usize,
+    pub quality: Option<i32>,
+    pub fingerprint_image: Option<EncodedImage>,
+    pub reader: Option<ReaderInfo>,
+    /// Política de verificação aplicada
+    pub policy: Option<AppliedPolicy>,
+    /// Comprovante assinado pela estação (só quando `success`)
+    pub receipt: Option<VerificationReceipt>,
+}
+
+impl IdentificationResult {
+    fn empty(outcome: BiometricOutcome, reason: String) -> Self {
+        Self {
+            success: false,
+            outcome,
+            reason,
+            user_id: None,
+            score: None,
+            percent: None,
+            margin: None,
+            runner_up_user_id: None,
+            candidates: 0,
+            quality: None,
+            fingerprint_image: None,
+            reader: None,
+            policy: None,
+            receipt: None,
+        }
+    }
+}
+
+/// Parâmetros de `identify_fingerprint`
+struct IdentifyRequest {
+    base_id: Option<String>,
+    requested_min_percent: Option<i32>,
+    operation: Operation,
+    criticality: Option<Criticality>,
+    min_margin: i32,
+    refresh_candidates: bool,
+    capture_timeout: Option<Duration>,
+    image_options: ImageOptions,
+}
+
+/// Comando Tauri: identifica quem colocou o dedo (1:N), sem escolher o funcionário antes.
+///
+/// - Candidatos: templates dos usuários ativos da `base_id` (ou de todos, sem base),
+///   guardados em memória por alguns minutos (`refresh_candidates` força recarregar)
+/// - Captura uma vez e compara com cada template via `CIDBIO_MatchTemplates`
+/// - Reconhece o melhor usuário se passar do mínimo da política de verificação para a
+///   `operation` (obrigatória; `min_percent` só pode aumentar) e ficar pelo menos `min_margin` pontos à frente do segundo
+///   (`min_margin` também só pode aumentar)
+/// - Identificação aceita devolve um comprovante (`receipt`) assinado com a chave da estação,
+///   sem o qual o banco não registra a verificação
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn identify_fingerprint(
+    app: tauri::AppHandle,
+    worker: tauri::State<'_, BiometricWorker>,
+    cache: tauri::State<'_, CandidateCache>,
+    policy: tauri::State<'_, PolicyCache>,
+    station_key: tauri::State<'_, StationKey>,
+    lockout: tauri::State<'_, LockoutTracker>,
+    auth: tauri::State<'_, SupabaseAuth>,
+    base_id: Option<String>,
+    min_percent: Option<i32>,
+    operation: Operation,
+    criticality: Option<Criticality>,
+    min_margin: Option<i32>,
+    refresh_candidates: Option<bool>,
+    capture_timeout_secs: Option<u64>,
+    image_options: Option<ImageOptions>,
+) -> Result<IdentificationResult, BiometricError> {
+    let worker = worker.inner().clone();
+    let cache = cache.inner().clone();
+    let policy = policy.inner().clone();
+    let station_key = station_key.inner().clone();
+    let lockout = lockout.inner().clone();
+    let auth = auth.inner().clone();
+    let request = IdentifyRequest {
+        base_id,
+        requested_min_percent: min_percent,
+        operation,
+        criticality,
+        min_margin: min_margin.map_or(MIN_MARGIN_PERCENT, |m| m.max(MIN_MARGIN_PERCENT)),
+        refresh_candidates: refresh_candidates.unwrap_or(false),
+        capture_timeout: capture_timeout_secs.map(Duration::from_secs),
+        image_options: image_options.unwrap_or_default(),
+    };
+
+    tokio::task::spawn_blocking(move || {
+        let services = IdentifyServices {
+            cache: &cache,
+            policy: &policy,
+            station_key: &station_key,
+            lockout: &lockout,
+            auth: &auth,
+        };
+        identify_fingerprint_blocking(app, &worker, services, request)
+    })
+    .await
+    .map_err(|e| {
+        log::debug!("Erro ao executar identificação biométrica: {}", e);
+        BiometricError::WorkerUnavailable
+    })?
+}
+
+/// Estado gerenciado usado pela identificação
+struct IdentifyServices<'a> {
+    cache: &'a CandidateCache,
+    policy: &'a PolicyCache,
+    station_key: &'a StationKey,
+    lockout: &'a LockoutTracker,
+    auth: &'a SupabaseAuth,
+}
+
+fn identify_fingerprint_blocking(
+    app: tauri::AppHandle,
+    worker: &BiometricWorker,
+    services: IdentifyServices,
+    request: IdentifyRequest,
+) -> Result<IdentificationResult, BiometricError> {
+    use tauri::Emitter;
+
+    let IdentifyServices {
+        cache,
+        policy,
+        station_key,
+        lockout,
+        auth,
+    } = services;
+
+    let IdentifyRequest {
+        base_id,
+        requested_min_percent,
+        operation,
+        criticality,
+        min_margin,
+        refresh_candidates,
+        capture_timeout,
+        image_options,
+    } = request;
+    let _log_context = app_log::context(&[("base_id", base_id.as_deref())]);
+
+    // Estação bloqueada não identifica ninguém até a liberação do supervisor
+    if let Some(lock) = lockout.check_station()? {
+        log::warn!("🔒 Identificação recusada: estação bloqueada ({})", lock.reason);
+        return Ok(IdentificationResult::empty(
+            BiometricOutcome::Locked,
+            format!(
+                "Estação bloqueada após {} tentativas sem sucesso. Chame o supervisor para liberar.",
+                lock.failures
+            ),
+        ));
+    }
+
+    // 1) política e candidatos (cache ou Supabase)
+    let db = block_on(auth.connect())?;
+    let applied_policy = block_on(policy.get(&db))
+        .resolve(operation, base_id.as_deref(), criticality, requested_min_percent);
+    let min_percent = applied_policy.min_percent;
+
+    log::debug!(
+        "identify_fingerprint() base_id={:?} min_percent={} (regra {:?}) min_margin={}",
+        base_id, min_percent, applied_policy.rule_id, min_margin
+    );
+
+    let candidates = match cache.get(&base_id).filter(|_| !refresh_candidates) {
+        Some(cached) => cached,
+        None => {
+            let loaded = block_on(load_candidates(&db, base_id.as_deref()))?;
+            cache.put(base_id.clone(), loaded)
+        }
+    };
+    log::debug!("Candidatos: {} templates", candidates.len());
+
+    if candidates.is_empty() {
+        return Ok(IdentificationResult::empty(
+            BiometricOutcome::Rejected,
+            "Nenhuma digital cadastrada para esta base.".to_string(),
+        ));
+    }
+
+    // 2) leitor e captura
+    ensure_reader_ready(worker)?;
+    let reader = read_reader_info(worker);
+
+    let _ = app.emit("biometric-instruction", "Coloque o dedo no leitor");
+    let FingerprintCapture { template: live_template, quality, image } = match worker.capture(capture_timeout) {
+        Ok(capture) => capture,
+        Err(e) => {
+            if let Some(reason) = AbortReason::from_error(&e) {
+                let message = notify_aborted(&app, reason);
+                return Ok(IdentificationResult::empty(reason.into(), message));
+            }
+            log::debug!("Erro na captura para identificação: {}", e);
+            return Err(e);
+        }
+    };
+    log::debug!("Captura OK. quality={}", quality);
+
+    let fingerprint_image = encode_image(image.as_ref(), &image_options);
+    if let Some(encoded) = &fingerprint_image {
+        let _ = app.emit("biometric-image", encoded);
+    }
+    let _ = app.emit("biometric-instruction", "Identificando...");
+
+    // 3) melhor score de cada usuário e decisão
+    let ranking = rank_candidates(worker, &candidates, &live_template)?;
+    let Decision {
+        success,
+        mut reason,
+        best_user,
+        best_raw,
+        best_percent,
+        margin,
+        runner_up,
+    } = decide(&ranking, min_percent, min_margin);
+    log::debug!(
+        "Identificação: best={} percent={} margin={} runner_up={:?} success={}",
+        best_user, best_percent, margin, runner_up, success
+    );
+
+    // 4) bloqueios: o usuário reconhecido não pode estar bloqueado, e cada recusa conta para
+    // a estação (sem usuário, a identificação não conta para ninguém em particular)
+    let mut outcome = if success { BiometricOutcome::Identified } else { BiometricOutcome::Rejected };
+    let user_lock = if success { lockout.check(best_user)? } else { None };
+    let success = if let Some(lock) = user_lock {
+        log::warn!("🔒 Identificação de {} recusada: biometria bloqueada ({})", best_user, lock.reason);
+        outcome = BiometricOutcome::Locked;
+        reason = "Biometria bloqueada após tentativas sem sucesso. Chame o supervisor para liberar.".to_string();
+        false
+    } else if success {
+        lockout.register_success(best_user)?;
+        true
+    } else {
+        if let FailureOutcome::StationLocked(lock) = lockout.register_station_failure(&reason)? {
+            block_on(record_anomaly(
+                Some(&db),
+                "station_locked",
+                None,
+                None,
+                serde_json::json!({ "failures": lock.failures, "reason": lock.reason }),
+            ));
+            outcome = BiometricOutcome::Locked;
+            reason = format!(
+                "{}. Estação bloqueada após {} tentativas sem sucesso. Chame o supervisor para liberar.",
+                reason, lock.failures
+            );
+        }
+        false
+    };
+
+    let receipt = if success {
+        match station_key.issue(
+            best_user,
+            &live_template,
+            None,
+            best_raw,
+            best_percent,
+            &applied_policy,
+            reader.as_ref().map(|r| r.serial_number.as_str()),
+            reader.as_ref().map(|r| r.model.as_str()),
+            image.as_ref(),
+        ) {
+            Ok(receipt) => {
+                if let Err(e) = block_on(station_key.store(&db, &receipt)) {
+                    log::warn!("⚠️ Falha ao gravar comprovante {}: {}", receipt.payload.receipt_id, e);
+                }
+                Some(receipt)
+            }
+            Err(e) => {
+                log::warn!("⚠️ Falha ao emitir comprovante da identificação: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let result = IdentificationResult {
+        success,
+        outcome,
+        reason,
+        user_id: success.then(|| best_user.to_string()),
+        score: Some(best_raw),
+        percent: Some(best_percent),
+        margin: Some(margin),
+        runner_up_user_id: runner_up.map(str::to_string),
+        candidates: ranking.len(),
+        quality: Some(quality),
+        fingerprint_image,
+        reader,
+        policy: Some(applied_policy.clone()),
+        receipt,
+    };
+
+    block_on(record_verification(
+        &db,
+        &VerificationRecord {
+            success: result.success,
+            outcome: result.outcome,
+            score: result.score,
+            percent: result.percent,
+            quality: result.quality,
+            operation: Some(operation),
+            min_percent: Some(applied_policy.min_percent),
+            policy_rule: applied_policy.rule_id.as_deref(),
+            ..VerificationRecord::new("identify", result.user_id.as_deref(), result.reader.as_ref())
+        },
+        result.receipt.as_ref(),
+    ));
+    Ok(result)
+}
+
+/// Melhor score (bruto) de cada usuário, do mais parecido para o menos parecido.
+fn rank_candidates<'a>(
+    worker: &BiometricWorker,
+    candidates: &'a [CandidateTemplate],
+    live_template: &str,
+) -> Result<Vec<(&'a str, i32)>, BiometricError> {
+    let mut best_by_user: HashMap<&str, i32> = HashMap::new();
+    for candidate in candidates {
+        let raw = match worker.match_templates(&candidate.template, live_template) {
+            Ok(raw) => raw,
+            // Um template corrompido não pode impedir a identificação dos demais
+            Err(BiometricError::InvalidTemplate) => {
+                log::debug!("Template inválido ignorado (user_id={})", candidate.user_id);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let best = best_by_user.entry(candidate.user_id.as_str()).or_insert(raw);
+        if raw > *best {
+            *best = raw;
+        }
+    }
+
+    let mut ranking: Vec<(&str, i32)> = best_by_user.into_iter().collect();
+    ranking.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    Ok(ranking)
+}
+
+/// Resultado da comparação com os candidatos, antes dos bloqueios
+struct Decision<'a> {
+    success: bool,
+    reason: String,
+    /// Usuário mais parecido (vazio se não havia candidato válido)
+    best_user: &'a str,
+    best_raw: i32,
+    best_percent: i32,
+    /// Pontos percentuais à frente do segundo
+    margin: i32,
+    runner_up: Option<&'a str>,
+}
+
+/// Reconhece o melhor usuário se ele passar do mínimo e ficar `min_margin` pontos à frente
+/// do segundo.
+fn decide<'a>(ranking: &[(&'a str, i32)], min_percent: i32, min_margin: i32) -> Decision<'a> {
+    let (best_user, best_raw) = ranking.first().copied().unwrap_or(("", 0));
+    let best_percent = biometric_sdk::score_to_percent(best_raw);
+    let runner_up = ranking.get(1).copied();
+    let margin = best_percent - runner_up.map(|(_, raw)| biometric_sdk::score_to_percent(raw)).unwrap_or(0);
+
+    let (success, reason) = if best_percent < min_percent {
+        (false, format!("Digital não reconhecida (melhor score {}%, mínimo {}%)", best_percent, min_percent))
+    } else if margin < min_margin {
+        (
+            false,
+            format!("Digital ambígua: dois usuários muito parecidos (margem {}%, mínimo {}%)", margin, min_margin),
+        )
+    } else {
+        (true, format!("Funcionário identificado ({}%)", best_percent))
+    };
+    Decision {
+        success,
+        reason,
+        best_user,
+        best_raw,
+        best_percent,
+        margin,
+        runner_up: runner_up.map(|(user, _)| user),
+    }
+}
+
+/// Baixa os templates candidatos (função `biometric_candidate_templates`): usuários
+/// ativos da base, ou todos sem base.
+pub async fn load_candidates(db: &Supabase, base_id: Option<&str>) -> Result<Vec<CandidateTemplate>, BiometricError> {
+    let mut query = db.rpc_query("biometric_candidate_templates").select("user_id,template");
+    if let Some(base_id) = base_id {
+        query = query.arg("p_base_id", base_id);
+    }
+    query.fetch_all().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::biometric_reader::fake::{FakeCapture, FakeReader};
+
+    fn fake_worker(captures: Vec<FakeCapture>) -> BiometricWorker {
+        BiometricWorker::spawn(Box::new(FakeReader::new(captures)), |_| {})
+    }
+
+    fn candidate(user_id: &str, template: &str) -> CandidateTemplate {
+        CandidateTemplate {
+            user_id: user_id.to_string(),
+            template: template.to_string(),
+        }
+    }
+
+    /// Captura ao vivo do dedo `finger` e o ranking dos candidatos contra ela
+    fn rank(finger: &str, candidates: &[CandidateTemplate]) -> Vec<(String, i32)> {
+        let worker = fake_worker(vec![FakeCapture::finger(finger, 80)]);
+        worker.ensure_ready(None).unwrap();
+        let live = worker.capture(None).unwrap().template;
+        rank_candidates(&worker, candidates, &live)
+            .unwrap()
+            .into_iter()
+            .map(|(user, raw)| (user.to_string(), raw))
+            .collect()
+    }
+
+    #[test]
+    fn ranking_keeps_the_best_score_of_each_user() {
+        let candidates = [
+            candidate("bruno", "polegar#10"),
+            candidate("ana", "polegar#11"),
+            candidate("ana", "indicador#12"),
+            candidate("carla", "medio#13"),
+        ];
+        let ranking = rank("indicador", &candidates);
+        assert_eq!(
+            ranking,
+            vec![("ana".to_string(), 16000), ("bruno".to_string(), 1000), ("carla".to_string(), 1000)]
+        );
+
+        let ranking: Vec<(&str, i32)> = ranking.iter().map(|(user, raw)| (user.as_str(), *raw)).collect();
+        let decision = decide(&ranking, 60, MIN_MARGIN_PERCENT);
+        assert!(decision.success);
+        assert_eq!((decision.best_user, decision.best_percent), ("ana", 80));
+        assert_eq!((decision.runner_up, decision.margin), (Some("bruno"), 75));
+    }
+
+    #[test]
+    fn two_users_with_the_same_finger_are_ambiguous() {
+        let candidates = [candidate("ana", "indicador#10"), candidate("bruno", "indicador#11")];
+        let ranking = rank("indicador", &candidates);
+        let ranking: Vec<(&str, i32)> = ranking.iter().map(|(user, raw)| (user.as_str(), *raw)).collect();
+
+        let decision = decide(&ranking, 60, MIN_MARGIN_PERCENT);
+        assert!(!decision.success);
+        assert_eq!(decision.margin, 0);
+        assert!(decision.reason.contains("ambígua"));
+    }
+
+    #[test]
+    fn below_the_minimum_is_not_recognized() {
+        let candidates = [candidate("ana", "polegar#10")];
+        let ranking = rank("indicador", &candidates);
+        let ranking: Vec<(&str, i32)> = ranking.iter().map(|(user, raw)| (user.as_str(), *raw)).collect();
+
+        let decision = decide(&ranking, 60, MIN_MARGIN_PERCENT);
+        assert!(!decision.success);
+        assert_eq!((decision.best_percent, decision.runner_up), (5, None));
+        assert!(decision.reason.contains("não reconhecida"));
+
+        let empty = decide(&[], 60, MIN_MARGIN_PERCENT);
+        assert!(!empty.success);
+        assert_eq!(empty.best_user, "");
+    }
+
+    #[test]
+    fn margin_requested_above_the_gap_rejects() {
+        let ranking = [("ana", 18000), ("bruno", 16000)];
+        assert!(decide(&ranking, 60, MIN_MARGIN_PERCENT).success);
+        assert!(!decide(&ranking, 60, 15).success);
+    }
+}
@@ -0,0 +1,243 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::biometric_error::BiometricError;
+use crate::util::base64_engine;
+
+/// Resolução do sensor iDBio (datasheet Control iD)
+pub const IDBIO_DPI: u32 = 500;
+
+/// Metros por polegada, para gravar o DPI no chunk pHYs do PNG
+const METERS_PER_INCH: f64 = 0.0254;
+
+/// Imagem da digital como o leitor entrega: tons de cinza, 1 byte por pixel, sem cabeçalho.
+#[derive(Clone, Debug)]
+pub struct FingerprintImage {
+    pub width: u32,
+    pub height: u32,
+    pub dpi: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Formato da imagem devolvida ao frontend.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageFormat {
+    /// PNG em tons de cinza, com o DPI gravado
+    #[default]
+    Png,
+    /// Buffer cru `width * height` (para quem precisa reprocessar a imagem)
+    Raw,
+}
+
+/// Como a imagem da captura deve ser devolvida.
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct ImageOptions {
+    #[serde(default)]
+    pub format: ImageFormat,
+    /// Se informado, inclui uma miniatura PNG com este lado maior (px)
+    #[serde(default)]
+    pub thumbnail_max_side: Option<u32>,
+}
+
+/// Imagem codificada para o frontend (evento `biometric-image` e `fingerprint_image`).
+#[derive(Serialize, Clone, Debug)]
+pub struct EncodedImage {
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+    pub dpi: u32,
+    /// Conteúdo em base64 (PNG ou buffer cru, conforme `format`)
+    pub data: String,
+    /// Miniatura, sempre em PNG
+    pub thumbnail: Option<Box<EncodedImage>>,
+}
+
+impl FingerprintImage {
+    /// Monta a imagem a partir do buffer do SDK.
+    /// Retorna `None` se as dimensões não baterem com o tamanho do buffer.
+    pub fn from_raw(pixels: Vec<u8>, width: u32, height: u32, dpi: u32) -> Option<Self> {
+        if width == 0 || height == 0 || pixels.len() != (width as usize) * (height as usize) {
+            return None;
+        }
+        Some(Self { width, height, dpi, pixels })
+    }
+
+    /// Codifica como PNG em tons de cinza (8 bits), com o DPI no chunk pHYs.
+    pub fn to_png(&self) -> Result<Vec<u8>, BiometricError> {
+        let mut out = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut out, self.width, self.height);
+            encoder.set_color(png::ColorType::Grayscale);
+            encoder.set_depth(png::BitDepth::Eight);
+            let pixels_per_meter = (self.dpi as f64 / METERS_PER_INCH).round() as u32;
+            encoder.set_pixel_dims(Some(png::PixelDimensions {
+                xppu: pixels_per_meter,
+                yppu: pixels_per_meter,
+                unit: png::Unit::Meter,
+            }));
+
+            let mut writer = encoder
+                .write_header()
+                .map_err(|e| BiometricError::Io(format!("Erro ao gerar PNG da digital: {}", e)))?;
+            writer
+                .write_image_data(&self.pixels)
+                .map_err(|e| BiometricError::Io(format!("Erro ao gerar PNG da digital: {}", e)))?;
+        }
+        Ok(out)
+    }
+
+    /// Reduz a imagem (média por bloco) para que o lado maior tenha no máximo `max_side` px.
+    pub fn thumbnail(&self, max_side: u32) -> FingerprintImage {
+        let max_side = max_side.max(1);
+        let longest = self.width.max(self.height);
+        if longest <= max_side {
+            return self.clone();
+        }
+
+        let width = ((self.width as u64 * max_side as u64) / longest as u64).max(1) as u32;
+        let height = ((self.height as u64 * max_side as u64) / longest as u64).max(1) as u32;
+
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for ty in 0..height {
+            let y0 = ty * self.height / height;
+            let y1 = ((ty + 1) * self.height / height).max(y0 + 1);
+            for tx in 0..width {
+                let x0 = tx * self.width / width;
+                let x1 = ((tx + 1) * self.width / width).max(x0 + 1);
+
+                let mut sum = 0u32;
+                for y in y0..y1 {
+                    let row = (y * self.width) as usize;
+                    for x in x0..x1 {
+                        sum += self.pixels[row + x as usize] as u32;
+                    }
+                }
+                pixels.push((sum / ((y1 - y0) * (x1 - x0))) as u8);
+            }
+        }
+
+        FingerprintImage {
+            width,
+            height,
+            dpi: self.dpi * width / self.width,
+            pixels,
+        }
+    }
+
+    /// Codifica conforme `options` (formato e miniatura opcional).
+    pub fn encode(&self, options: &ImageOptions) -> Result<EncodedImage, BiometricError> {
+        let engine = base64_engine();
+        let data = match options.format {
+            ImageFormat::Png => engine.encode(self.to_png()?),
+            ImageFormat::Raw => engine.encode(&self.pixels),
+        };
+
+        let thumbnail = match options.thumbnail_max_side {
+            Some(max_side) => {
+                let thumb = self.thumbnail(max_side);
+                Some(Box::new(EncodedImage {
+                    format: ImageFormat::Png,
+                    width: thumb.width,
+                    height: thumb.height,
+                    dpi: thumb.dpi,
+                    data: engine.encode(thumb.to_png()?),
+                    thumbnail: None,
+                }))
+            }
+            None => None,
+        };
+
+        Ok(EncodedImage {
+            format: options.format,
+            width: self.width,
+            height: self.height,
+            dpi: self.dpi,
+            data,
+            thumbnail,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decodifica o PNG: (largura, altura, DPI do pHYs, pixels)
+    fn decode_png(bytes: &[u8]) -> (u32, u32, Option<u32>, Vec<u8>) {
+        let mut reader = png::Decoder::new(std::io::Cursor::new(bytes)).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut pixels).unwrap();
+        pixels.truncate(frame.buffer_size());
+        assert_eq!((frame.color_type, frame.bit_depth), (png::ColorType::Grayscale, png::BitDepth::Eight));
+
+        let info = reader.info();
+        let dpi = info.pixel_dims.map(|dims| {
+            assert_eq!(dims.unit, png::Unit::Meter);
+            assert_eq!(dims.xppu, dims.yppu);
+            (dims.xppu as f64 * METERS_PER_INCH).round() as u32
+        });
+        (info.width, info.height, dpi, pixels)
+    }
+
+    fn gradient(width: u32, height: u32) -> FingerprintImage {
+        let pixels = (0..width * height).map(|i| (i * 10) as u8).collect();
+        FingerprintImage::from_raw(pixels, width, height, IDBIO_DPI).unwrap()
+    }
+
+    #[test]
+    fn raw_buffer_must_match_the_dimensions() {
+        assert!(FingerprintImage::from_raw(vec![0; 6], 3, 2, IDBIO_DPI).is_some());
+        assert!(FingerprintImage::from_raw(vec![0; 5], 3, 2, IDBIO_DPI).is_none());
+        assert!(FingerprintImage::from_raw(Vec::new(), 0, 0, IDBIO_DPI).is_none());
+    }
+
+    #[test]
+    fn png_round_trip_keeps_size_pixels_and_dpi() {
+        let image = gradient(5, 3);
+        let (width, height, dpi, pixels) = decode_png(&image.to_png().unwrap());
+        assert_eq!((width, height), (5, 3));
+        assert_eq!(dpi, Some(IDBIO_DPI));
+        assert_eq!(pixels, image.pixels);
+
+        let custom = FingerprintImage { dpi: 700, ..image };
+        assert_eq!(decode_png(&custom.to_png().unwrap()).2, Some(700));
+    }
+
+    #[test]
+    fn thumbnail_averages_blocks_and_scales_dpi() {
+        let image = gradient(4, 2);
+        let thumb = image.thumbnail(2);
+        assert_eq!((thumb.width, thumb.height), (2, 1));
+        // Blocos 2x2: (0 + 10 + 40 + 50) / 4 e (20 + 30 + 60 + 70) / 4
+        assert_eq!(thumb.pixels, [25, 45]);
+        assert_eq!(thumb.dpi, IDBIO_DPI / 2);
+
+        let same = image.thumbnail(10);
+        assert_eq!((same.width, same.height, same.dpi), (4, 2, IDBIO_DPI));
+        assert_eq!(same.pixels, image.pixels);
+    }
+
+    #[test]
+    fn encode_returns_raw_or_png_with_a_png_thumbnail() {
+        let engine = base64_engine();
+        let image = gradient(6, 4);
+
+        let raw = image
+            .encode(&ImageOptions { format: ImageFormat::Raw, thumbnail_max_side: Some(3) })
+            .unwrap();
+        assert_eq!(engine.decode(&raw.data).unwrap(), image.pixels);
+        let thumb = raw.thumbnail.expect("miniatura pedida");
+        assert_eq!(thumb.format, ImageFormat::Png);
+        let (width, height, dpi, _) = decode_png(&engine.decode(&thumb.data).unwrap());
+        assert_eq!((width, height), (thumb.width, thumb.height));
+        assert_eq!((width, height), (3, 2));
+        assert_eq!(dpi, Some(thumb.dpi));
+
+        let png = image.encode(&ImageOptions::default()).unwrap();
+        assert!(png.thumbnail.is_none());
+        let (width, height, dpi, pixels) = decode_png(&engine.decode(&png.data).unwrap());
+        assert_eq!((width, height, dpi), (6, 4, Some(IDBIO_DPI)));
+        assert_eq!(pixels, image.pixels);
+    }
+}
@@ -0,0 +1,595 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::audit_log::{record_event, AuditCategory};
+use crate::biometric_error::BiometricError;
+use crate::biometric_templates::require_supervisor;
+use crate::station_config::{app_data_dir, station_id};
+use crate::supabase::Supabase;
+use crate::supabase_auth::SupabaseAuth;
+
+/// Falhas de um usuário dentro de `FAILURE_WINDOW` que bloqueiam a verificação dele
+pub const MAX_USER_FAILURES: usize = 5;
+
+/// Falhas na estação (qualquer usuário) dentro de `FAILURE_WINDOW` que bloqueiam a estação
+pub const MAX_STATION_FAILURES: usize = 15;
+
+/// Janela em que as falhas são contadas
+pub const FAILURE_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+/// Por quanto tempo um template capturado é lembrado para detectar reenvio
+const REPLAY_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Limite de templates lembrados (os mais antigos saem primeiro)
+const REPLAY_MAX_ENTRIES: usize = 2000;
+
+/// Falhas (`scope` `user` ou `station`, em segundos Unix) e bloqueios ativos. A estação
+/// usa `user_id` vazio. Ficam no disco para que fechar o app não zere as tentativas.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS lockout_failures (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        scope TEXT NOT NULL,
+        user_id TEXT NOT NULL,
+        failed_at INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS lockout_failures_scope ON lockout_failures (scope, user_id, failed_at);
+    CREATE TABLE IF NOT EXISTS lockout_locks (
+        scope TEXT NOT NULL,
+        user_id TEXT NOT NULL,
+        locked_at TEXT NOT NULL,
+        failures INTEGER NOT NULL,
+        reason TEXT NOT NULL,
+        PRIMARY KEY (scope, user_id)
+    );
+";
+
+/// Bloqueio ativo (de um usuário ou da estação).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LockInfo {
+    /// Quando o bloqueio começou (RFC 3339)
+    pub locked_at: String,
+    /// Falhas na janela quando o bloqueio começou
+    pub failures: usize,
+    pub reason: String,
+}
+
+/// Formato do antigo `lockouts.json`, importado uma vez para o banco.
+#[derive(Deserialize, Default)]
+struct LegacyLocks {
+    #[serde(default)]
+    users: HashMap<String, LockInfo>,
+    #[serde(default)]
+    station: Option<LockInfo>,
+}
+
+struct SeenTemplate {
+    user_id: String,
+    seen_at: Instant,
+}
+
+/// Resultado de registrar uma falha.
+pub enum FailureOutcome {
+    /// Falha contada; `remaining` tentativas até o bloqueio do usuário (numa identificação
+    /// sem usuário, até o da estação)
+    Counted { remaining: usize },
+    UserLocked(LockInfo),
+    StationLocked(LockInfo),
+}
+
+/// Mesmo template (byte a byte) enviado de novo: sensores nunca repetem uma captura.
+#[derive(Serialize, Clone, Debug)]
+pub struct ReplaySuspicion {
+    /// Usuário da captura anterior com o mesmo template
+    pub previous_user_id: Option<String>,
+    /// Há quantos segundos o template foi visto (ausente se é igual a um template cadastrado)
+    pub seconds_ago: Option<u64>,
+}
+
+/// Usuário bloqueado (para a tela do supervisor).
+#[derive(Serialize, Clone, Debug)]
+pub struct LockedUser {
+    pub user_id: String,
+    #[serde(flatten)]
+    pub lock: LockInfo,
+}
+
+/// Bloqueios ativos na estação.
+#[derive(Serialize, Clone, Debug)]
+pub struct LockoutStatus {
+    pub station_id: String,
+    pub users: Vec<LockedUser>,
+    pub station: Option<LockInfo>,
+}
+
+/// Contadores de falhas por usuário e por estação, bloqueios e detecção de reenvio.
+///
+/// Gerenciado pelo Tauri. Falhas e bloqueios ficam em `lockouts.db` na pasta de dados, então
+/// reiniciar o app não zera as tentativas; bloqueios só saem por liberação do supervisor
+/// (`override_biometric_lockout`). Os templates vistos (reenvio) ficam só em memória.
+#[derive(Clone)]
+pub struct LockoutTracker {
+    conn: Arc<Mutex<Connection>>,
+    /// SHA-256 dos templates capturados recentemente, na ordem em que chegaram
+    seen_templates: Arc<Mutex<VecDeque<(String, SeenTemplate)>>>,
+}
+
+fn read_lock(row: &rusqlite::Row) -> rusqlite::Result<LockInfo> {
+    Ok(LockInfo {
+        locked_at: row.get("locked_at")?,
+        failures: row.get::<_, i64>("failures")? as usize,
+        reason: row.get("reason")?,
+    })
+}
+
+fn insert_lock(conn: &Connection, scope: &str, user_id: &str, lock: &LockInfo) -> Result<(), BiometricError> {
+    conn.execute(
+        "INSERT OR REPLACE INTO lockout_locks (scope, user_id, locked_at, failures, reason)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![scope, user_id, lock.locked_at, lock.failures as i64, lock.reason],
+    )?;
+    Ok(())
+}
+
+impl LockoutTracker {
+    /// Abre `lockouts.db` (importando o antigo `lockouts.json`, se existir).
+    pub fn open() -> Result<Self, BiometricError> {
+        std::fs::create_dir_all(app_data_dir())?;
+        let tracker = Self::with_connection(Connection::open(app_data_dir().join("lockouts.db"))?)?;
+        tracker.import_legacy(&app_data_dir().join("lockouts.json"))?;
+
+        let status = tracker.status()?;
+        if !status.users.is_empty() || status.station.is_some() {
+            log::warn!(
+                "🔒 {} usuário(s) com biometria bloqueada{}",
+                status.users.len(),
+                if status.station.is_some() { " e estação bloqueada" } else { "" }
+            );
+        }
+        Ok(tracker)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self, BiometricError> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            seen_templates: Arc::default(),
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    /// Traz os bloqueios do `lockouts.json` de versões anteriores e apaga o arquivo.
+    fn import_legacy(&self, path: &Path) -> Result<(), BiometricError> {
+        let Ok(content) = std::fs::read_to_string(path) else {
+            return Ok(());
+        };
+        let legacy: LegacyLocks = serde_json::from_str(&content).unwrap_or_else(|e| {
+            log::warn!("⚠️ Bloqueios biométricos inválidos em {:?}: {}", path, e);
+            LegacyLocks::default()
+        });
+        {
+            let conn = self.lock();
+            for (user_id, lock) in &legacy.users {
+                insert_lock(&conn, "user", user_id, lock)?;
+            }
+            if let Some(lock) = &legacy.station {
+                insert_lock(&conn, "station", "", lock)?;
+            }
+        }
+        std::fs::remove_file(path)?;
+        log::info!("🔒 Bloqueios de {:?} importados para lockouts.db", path);
+        Ok(())
+    }
+
+    /// Bloqueio que impede a verificação do usuário (da estação ou dele).
+    pub fn check(&self, user_id: &str) -> Result<Option<LockInfo>, BiometricError> {
+        let lock = self
+            .lock()
+            .query_row(
+                "SELECT locked_at, failures, reason FROM lockout_locks
+                 WHERE scope = 'station' OR (scope = 'user' AND user_id = ?1)
+                 ORDER BY scope = 'station' DESC LIMIT 1",
+                params![user_id],
+                read_lock,
+            )
+            .optional()?;
+        Ok(lock)
+    }
+
+    /// Bloqueio da estação (a identificação 1:N não tem usuário para conferir antes).
+    pub fn check_station(&self) -> Result<Option<LockInfo>, BiometricError> {
+        let lock = self
+            .lock()
+            .query_row(
+                "SELECT locked_at, failures, reason FROM lockout_locks WHERE scope = 'station'",
+                [],
+                read_lock,
+            )
+            .optional()?;
+        Ok(lock)
+    }
+
+    /// Conta uma verificação recusada; bloqueia o usuário ou a estação ao passar do limite.
+    pub fn register_failure(&self, user_id: &str, reason: &str) -> Result<FailureOutcome, BiometricError> {
+        self.register_failure_at(Some(user_id), reason, chrono::Utc::now().timestamp())
+    }
+
+    /// Conta uma identificação (1:N) recusada: sem usuário reconhecido, só a estação conta.
+    pub fn register_station_failure(&self, reason: &str) -> Result<FailureOutcome, BiometricError> {
+        self.register_failure_at(None, reason, chrono::Utc::now().timestamp())
+    }
+
+    fn register_failure_at(
+        &self,
+        user_id: Option<&str>,
+        reason: &str,
+        now: i64,
+    ) -> Result<FailureOutcome, BiometricError> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+
+        tx.execute(
+            "DELETE FROM lockout_failures WHERE failed_at <= ?1",
+            params![now - FAILURE_WINDOW.as_secs() as i64],
+        )?;
+        tx.execute(
+            "INSERT INTO lockout_failures (scope, user_id, failed_at) VALUES ('station', '', ?1)",
+            params![now],
+        )?;
+        if let Some(user_id) = user_id {
+            tx.execute(
+                "INSERT INTO lockout_failures (scope, user_id, failed_at) VALUES ('user', ?1, ?2)",
+                params![user_id, now],
+            )?;
+        }
+        let count = |scope: &str, user_id: &str| -> Result<usize, BiometricError> {
+            let count: i64 = tx.query_row(
+                "SELECT COUNT(*) FROM lockout_failures WHERE scope = ?1 AND user_id = ?2",
+                params![scope, user_id],
+                |row| row.get(0),
+            )?;
+            Ok(count as usize)
+        };
+        let station_count = count("station", "")?;
+        let user_count = user_id.map(|user_id| count("user", user_id)).transpose()?;
+        log::debug!(
+            "Falha registrada: user_id={:?} ({:?}/{}) estação ({}/{}) motivo={}",
+            user_id, user_count, MAX_USER_FAILURES, station_count, MAX_STATION_FAILURES, reason
+        );
+
+        let lock = |failures| LockInfo {
+            locked_at: chrono::Local::now().to_rfc3339(),
+            failures,
+            reason: reason.to_string(),
+        };
+
+        let outcome = if station_count >= MAX_STATION_FAILURES {
+            let info = lock(station_count);
+            insert_lock(&tx, "station", "", &info)?;
+            tx.execute("DELETE FROM lockout_failures WHERE scope = 'station'", [])?;
+            log::warn!("🔒 Estação bloqueada: {} falhas biométricas em {:?}", station_count, FAILURE_WINDOW);
+            FailureOutcome::StationLocked(info)
+        } else if let Some((user_id, user_count)) = user_id.zip(user_count).filter(|&(_, c)| c >= MAX_USER_FAILURES) {
+            let info = lock(user_count);
+            insert_lock(&tx, "user", user_id, &info)?;
+            tx.execute(
+                "DELETE FROM lockout_failures WHERE scope = 'user' AND user_id = ?1",
+                params![user_id],
+            )?;
+            log::warn!("🔒 Biometria bloqueada para {}: {} falhas em {:?}", user_id, user_count, FAILURE_WINDOW);
+            FailureOutcome::UserLocked(info)
+        } else {
+            FailureOutcome::Counted {
+                remaining: match user_count {
+                    Some(user_count) => MAX_USER_FAILURES - user_count,
+                    None => MAX_STATION_FAILURES - station_count,
+                },
+            }
+        };
+        tx.commit()?;
+        Ok(outcome)
+    }
+
+    /// Verificação aceita: zera as falhas do usuário.
+    pub fn register_success(&self, user_id: &str) -> Result<(), BiometricError> {
+        self.lock().execute(
+            "DELETE FROM lockout_failures WHERE scope = 'user' AND user_id = ?1",
+            params![user_id],
+        )?;
+        Ok(())
+    }
+
+    /// Guarda o template capturado e avisa se ele já foi visto (possível reenvio).
+    pub fn check_replay(&self, user_id: &str, template: &str) -> Option<ReplaySuspicion> {
+        let hash = format!("{:x}", Sha256::digest(template.as_bytes()));
+        let mut seen_templates = self.seen_templates.lock().unwrap_or_else(|p| p.into_inner());
+
+        while seen_templates
+            .front()
+            .map(|(_, seen)| seen.seen_at.elapsed() > REPLAY_WINDOW)
+            .unwrap_or(false)
+            || seen_templates.len() >= REPLAY_MAX_ENTRIES
+        {
+            seen_templates.pop_front();
+        }
+
+        let previous = seen_templates.iter().find(|(h, _)| *h == hash).map(|(_, seen)| ReplaySuspicion {
+            previous_user_id: Some(seen.user_id.clone()),
+            seconds_ago: Some(seen.seen_at.elapsed().as_secs()),
+        });
+        seen_templates.push_back((
+            hash,
+            SeenTemplate {
+                user_id: user_id.to_string(),
+                seen_at: Instant::now(),
+            },
+        ));
+        previous
+    }
+
+    /// Libera o usuário (ou a estação, sem `user_id`). Retorna `false` se não havia bloqueio.
+    pub fn override_lock(&self, user_id: Option<&str>) -> Result<bool, BiometricError> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        let (scope, user_id) = match user_id {
+            Some(user_id) => ("user", user_id),
+            None => ("station", ""),
+        };
+        tx.execute(
+            "DELETE FROM lockout_failures WHERE scope = ?1 AND user_id = ?2",
+            params![scope, user_id],
+        )?;
+        let released = tx.execute(
+            "DELETE FROM lockout_locks WHERE scope = ?1 AND user_id = ?2",
+            params![scope, user_id],
+        )? > 0;
+        tx.commit()?;
+        Ok(released)
+    }
+
+    pub fn status(&self) -> Result<LockoutStatus, BiometricError> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT user_id, locked_at, failures, reason FROM lockout_locks WHERE scope = 'user' ORDER BY locked_at",
+        )?;
+        let users = stmt
+            .query_map([], |row| {
+                Ok(LockedUser {
+                    user_id: row.get("user_id")?,
+                    lock: read_lock(row)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        let station = conn
+            .query_row(
+                "SELECT locked_at, failures, reason FROM lockout_locks WHERE scope = 'station'",
+                [],
+                read_lock,
+            )
+            .optional()?;
+        Ok(LockoutStatus {
+            station_id: station_id(),
+            users,
+            station,
+        })
+    }
+}
+
+/// Registra um evento suspeito em `biometric_anomalies` para revisão.
+/// Falhas aqui só vão para o log; sem conexão (`db` ausente), fica só no log de auditoria.
+pub async fn record_anomaly(
+    db: Option<&Supabase>,
+    kind: &str,
+    user_id: Option<&str>,
+    supervisor_id: Option<&str>,
+    detail: serde_json::Value,
+) {
+    record_event(
+        AuditCategory::Security,
+        kind,
+        user_id,
+        serde_json::json!({ "supervisor_id": supervisor_id, "detail": detail }),
+    );
+    let body = serde_json::json!({
+        "kind": kind,
+        "user_id": user_id,
+        "station_id": station_id(),
+        "supervisor_id": supervisor_id,
+        "detail": detail
+    });
+
+    let Some(db) = db else {
+        log::debug!("Anomalia {} registrada só na auditoria (offline)", kind);
+        return;
+    };
+    log::debug!("Registrando anomalia biométrica ({})", kind);
+    if let Err(e) = db.from("biometric_anomalies").insert(&body).await {
+        log::warn!("⚠️ Falha ao registrar anomalia biométrica: {}", e);
+    }
+}
+
+/// Comando Tauri: bloqueios ativos na estação (usuários e a própria estação).
+#[tauri::command]
+pub fn get_biometric_lockouts(lockout: tauri::State<'_, LockoutTracker>) -> Result<LockoutStatus, BiometricError> {
+    lockout.status()
+}
+
+/// Comando Tauri: supervisor libera a verificação de um usuário (ou da estação, sem `user_id`).
+///
+/// O supervisor é o usuário logado, conferido no banco (`biometric_is_supervisor`) antes de
+/// mexer no bloqueio; sem conexão, nada é liberado. O motivo é obrigatório e a liberação fica
+/// registrada em `biometric_anomalies`.
+#[tauri::command]
+pub async fn override_biometric_lockout(
+    lockout: tauri::State<'_, LockoutTracker>,
+    auth: tauri::State<'_, SupabaseAuth>,
+    user_id: Option<String>,
+    reason: String,
+) -> Result<bool, BiometricError> {
+    let supervisor_id = auth.user_id().ok_or(BiometricError::Unauthenticated)?;
+    if reason.trim().is_empty() {
+        return Err(BiometricError::Config(
+            "Informe o motivo da liberação".to_string(),
+        ));
+    }
+    let db = auth.connect().await?;
+    require_supervisor(&db).await?;
+
+    let released = lockout.override_lock(user_id.as_deref())?;
+    log::debug!(
+        "override_biometric_lockout() user_id={:?} supervisor_id={} liberado={} motivo={}",
+        user_id, supervisor_id, released, reason
+    );
+    if !released {
+        return Ok(false);
+    }
+    log::info!("🔓 Bloqueio biométrico liberado por {} ({:?})", supervisor_id, user_id);
+
+    record_anomaly(
+        Some(&db),
+        "lock_override",
+        user_id.as_deref(),
+        Some(&supervisor_id),
+        serde_json::json!({ "reason": reason }),
+    )
+    .await;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker() -> LockoutTracker {
+        LockoutTracker::with_connection(Connection::open_in_memory().unwrap()).unwrap()
+    }
+
+    fn fail(tracker: &LockoutTracker, user_id: &str, now: i64) -> FailureOutcome {
+        tracker.register_failure_at(Some(user_id), "digital não confere", now).unwrap()
+    }
+
+    #[test]
+    fn user_is_locked_after_max_failures_until_override() {
+        let tracker = tracker();
+        for attempt in 1..MAX_USER_FAILURES {
+            match fail(&tracker, "ana", 1000) {
+                FailureOutcome::Counted { remaining } => assert_eq!(remaining, MAX_USER_FAILURES - attempt),
+                _ => panic!("bloqueou antes do limite"),
+            }
+        }
+        assert!(tracker.check("ana").unwrap().is_none());
+        assert!(matches!(fail(&tracker, "ana", 1000), FailureOutcome::UserLocked(_)));
+
+        let lock = tracker.check("ana").unwrap().expect("usuário bloqueado");
+        assert_eq!(lock.failures, MAX_USER_FAILURES);
+        assert!(tracker.check("bruno").unwrap().is_none());
+        assert_eq!(tracker.status().unwrap().users.len(), 1);
+
+        assert!(tracker.override_lock(Some("ana")).unwrap());
+        assert!(tracker.check("ana").unwrap().is_none());
+        assert!(!tracker.override_lock(Some("ana")).unwrap());
+    }
+
+    #[test]
+    fn success_and_window_reset_the_user_count() {
+        let tracker = tracker();
+        for _ in 1..MAX_USER_FAILURES {
+            fail(&tracker, "ana", 1000);
+        }
+        tracker.register_success("ana").unwrap();
+        assert!(matches!(fail(&tracker, "ana", 1000), FailureOutcome::Counted { remaining } if remaining == MAX_USER_FAILURES - 1));
+
+        for _ in 2..MAX_USER_FAILURES {
+            fail(&tracker, "ana", 1000);
+        }
+        let later = 1000 + FAILURE_WINDOW.as_secs() as i64;
+        assert!(matches!(fail(&tracker, "ana", later), FailureOutcome::Counted { remaining } if remaining == MAX_USER_FAILURES - 1));
+    }
+
+    #[test]
+    fn station_is_locked_by_failures_of_different_users() {
+        let tracker = tracker();
+        let mut outcome = None;
+        for attempt in 0..MAX_STATION_FAILURES {
+            outcome = Some(fail(&tracker, &format!("user-{}", attempt), 1000));
+        }
+        assert!(matches!(outcome, Some(FailureOutcome::StationLocked(_))));
+        assert!(tracker.check("qualquer").unwrap().is_some());
+        assert!(tracker.status().unwrap().station.is_some());
+
+        assert!(tracker.override_lock(None).unwrap());
+        assert!(tracker.check("qualquer").unwrap().is_none());
+    }
+
+    #[test]
+    fn identification_failures_count_only_for_the_station() {
+        let tracker = tracker();
+        for attempt in 1..MAX_STATION_FAILURES {
+            match tracker.register_failure_at(None, "digital não reconhecida", 1000).unwrap() {
+                FailureOutcome::Counted { remaining } => assert_eq!(remaining, MAX_STATION_FAILURES - attempt),
+                _ => panic!("bloqueou antes do limite"),
+            }
+        }
+        assert!(tracker.check_station().unwrap().is_none());
+        assert!(matches!(fail(&tracker, "ana", 1000), FailureOutcome::StationLocked(_)));
+        assert!(tracker.check_station().unwrap().is_some());
+        assert!(tracker.status().unwrap().users.is_empty());
+
+        assert!(tracker.override_lock(None).unwrap());
+        assert!(tracker.check_station().unwrap().is_none());
+    }
+
+    #[test]
+    fn failures_and_locks_survive_reopening() {
+        let path = std::env::temp_dir().join(format!("lockouts-test-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        {
+            let tracker = LockoutTracker::with_connection(Connection::open(&path).unwrap()).unwrap();
+            for _ in 1..MAX_USER_FAILURES {
+                tracker.register_failure("ana", "digital não confere").unwrap();
+            }
+            for _ in 0..MAX_USER_FAILURES {
+                tracker.register_failure("bruno", "digital não confere").unwrap();
+            }
+        }
+        let tracker = LockoutTracker::with_connection(Connection::open(&path).unwrap()).unwrap();
+        assert!(tracker.check("bruno").unwrap().is_some());
+        assert!(matches!(
+            tracker.register_failure("ana", "digital não confere").unwrap(),
+            FailureOutcome::UserLocked(_)
+        ));
+        drop(tracker);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn legacy_json_locks_are_imported_once() {
+        let path = std::env::temp_dir().join(format!("lockouts-test-{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{"users":{"ana":{"locked_at":"2026-01-01T00:00:00-03:00","failures":5,"reason":"x"}},"station":null}"#,
+        )
+        .unwrap();
+        let tracker = tracker();
+        tracker.import_legacy(&path).unwrap();
+        assert_eq!(tracker.check("ana").unwrap().map(|lock| lock.failures), Some(5));
+        assert!(!path.exists());
+        tracker.import_legacy(&path).unwrap();
+    }
+
+    #[test]
+    fn repeated_template_is_reported_as_replay() {
+        let tracker = tracker();
+        assert!(tracker.check_replay("ana", "template-a").is_none());
+        assert!(tracker.check_replay("ana", "template-b").is_none());
+        let replay = tracker.check_replay("bruno", "template-a").expect("reenvio");
+        assert_eq!(replay.previous_user_id.as_deref(), Some("ana"));
+    }
+}
@@ -0,0 +1,256 @@
+use serde::Serialize;
+use std::sync::Arc;
+
+use crate::biometric_error::BiometricError;
+use crate::biometric_image::FingerprintImage;
+use crate::biometric_sdk::CidBioReader;
+use crate::biometric_simulator::SimulatedReader;
+
+/// Resultado de uma captura no leitor.
+#[derive(Clone, Debug)]
+pub struct FingerprintCapture {
+    /// Template em base64 (formato do cidbiolib)
+    pub template: String,
+    /// Qualidade 0-100 reportada pelo leitor
+    pub quality: i32,
+    /// Imagem da digital com dimensões e DPI (ausente se o leitor não devolveu)
+    pub image: Option<FingerprintImage>,
+}
+
+/// Versão do SDK iDBio empacotado com o app (`IDBIO_SDK/VERSION_*`)
+pub const IDBIO_SDK_VERSION: &str = "1.4.3";
+
+/// Identificação do leitor conectado, para rastrear qual aparelho está em qual estação.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ReaderInfo {
+    /// Backend do leitor (libcidbio ou simulador)
+    pub backend: String,
+    /// Porta serial usada (ex.: "COM3"), se conhecida
+    pub port: Option<String>,
+    pub model: String,
+    pub serial_number: String,
+    pub firmware_version: String,
+    pub sdk_version: String,
+}
+
+/// Função que interrompe uma captura em andamento.
+///
+/// É chamada de outra thread enquanto `capture` está bloqueado.
+pub type CancelHandle = Arc<dyn Fn() + Send + Sync>;
+
+/// Operações que o fluxo biométrico precisa de um leitor.
+///
+/// A implementação real (`CidBioReader`) chama a libcidbio; o simulador
+/// (`SimulatedReader`) serve capturas a partir de arquivos de fixture,
+/// permitindo rodar cadastro/validação sem o hardware iDBio.
+pub trait FingerprintReader: Send {
+    /// Nome do backend (para logs e diagnóstico)
+    fn name(&self) -> &'static str;
+
+    /// Define a porta serial usada no próximo `init` (ex.: "COM3")
+    fn set_port(&mut self, port: &str) -> Result<(), BiometricError>;
+
+    /// Inicializa o leitor
+    fn init(&mut self) -> Result<(), BiometricError>;
+
+    /// Libera o leitor
+    fn terminate(&mut self);
+
+    /// Procura a porta onde o leitor está conectado
+    fn detect_port(&mut self) -> Option<String>;
+
+    /// Captura imagem e template
+    fn capture(&mut self) -> Result<FingerprintCapture, BiometricError>;
+
+    /// Compara dois templates e retorna o score bruto (0-20000)
+    fn match_templates(&mut self, stored: &str, live: &str) -> Result<i32, BiometricError>;
+
+    /// Combina três capturas do mesmo dedo em um único template
+    fn merge_templates(&mut self, first: &str, second: &str, third: &str) -> Result<String, BiometricError>;
+
+    /// Modelo, número de série e firmware do leitor (exige `init`)
+    fn device_info(&mut self) -> Result<ReaderInfo, BiometricError>;
+
+    /// Handle para cancelar a captura em andamento a partir de outra thread
+    fn cancel_handle(&self) -> CancelHandle;
+}
+
+/// Cria o leitor da estação.
+///
+/// Se `IDBIO_SIMULATOR` apontar para um diretório de fixtures, usa o simulador;
+/// caso contrário, usa a libcidbio. O leitor é entregue à `BiometricWorker`,
+/// que passa a ser a única dona dele.
+pub fn create_reader() -> Box<dyn FingerprintReader> {
+    match std::env::var("IDBIO_SIMULATOR") {
+        Ok(dir) if !dir.trim().is_empty() => match SimulatedReader::from_dir(&dir) {
+            Ok(sim) => {
+                log::warn!("🧪 Leitor biométrico SIMULADO (fixtures em {})", dir);
+                Box::new(sim)
+            }
+            Err(e) => {
+                log::error!("Erro ao carregar fixtures do simulador ({}): {}. Usando libcidbio.", dir, e);
+                Box::new(CidBioReader::new())
+            }
+        },
+        _ => Box::new(CidBioReader::new()),
+    }
+}
+
+/// Leitor em memória para os testes: serve capturas roteirizadas, sem fixtures em disco.
+#[cfg(test)]
+pub mod fake {
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use super::{CancelHandle, FingerprintCapture, FingerprintReader, ReaderInfo, IDBIO_SDK_VERSION};
+    use crate::biometric_error::BiometricError;
+
+    /// Uma captura roteirizada.
+    #[derive(Clone, Debug)]
+    pub struct FakeCapture {
+        finger: String,
+        quality: i32,
+        delay: Duration,
+        error: Option<BiometricError>,
+    }
+
+    impl FakeCapture {
+        /// Captura do dedo `finger`; capturas do mesmo rótulo "batem" entre si
+        pub fn finger(finger: &str, quality: i32) -> Self {
+            Self {
+                finger: finger.to_string(),
+                quality,
+                delay: Duration::ZERO,
+                error: None,
+            }
+        }
+
+        /// Ninguém põe o dedo: bloqueia até ser cancelada
+        pub fn hang() -> Self {
+            Self::finger("ninguem", 0).delayed(Duration::from_secs(60))
+        }
+
+        /// Fica bloqueada por `delay` (respeitando cancelamento) antes de devolver
+        pub fn delayed(mut self, delay: Duration) -> Self {
+            self.delay = delay;
+            self
+        }
+
+        pub fn error(error: BiometricError) -> Self {
+            Self {
+                error: Some(error),
+                ..Self::finger("erro", 0)
+            }
+        }
+    }
+
+    /// Leitor falso e determinístico. O template de cada captura é `"<dedo>#<n>"`:
+    /// nunca se repete, como no sensor real, e o rótulo decide o score da comparação.
+    pub struct FakeReader {
+        captures: VecDeque<FakeCapture>,
+        served: usize,
+        initialized: bool,
+        cancelled: Arc<AtomicBool>,
+    }
+
+    impl FakeReader {
+        pub fn new(captures: Vec<FakeCapture>) -> Self {
+            Self {
+                captures: captures.into(),
+                served: 0,
+                initialized: false,
+                cancelled: Arc::new(AtomicBool::new(false)),
+            }
+        }
+    }
+
+    fn finger_of(template: &str) -> &str {
+        template.split('#').next().unwrap_or(template)
+    }
+
+    impl FingerprintReader for FakeReader {
+        fn name(&self) -> &'static str {
+            "fake"
+        }
+
+        fn set_port(&mut self, _port: &str) -> Result<(), BiometricError> {
+            Ok(())
+        }
+
+        fn init(&mut self) -> Result<(), BiometricError> {
+            self.initialized = true;
+            Ok(())
+        }
+
+        fn terminate(&mut self) {
+            self.initialized = false;
+        }
+
+        fn detect_port(&mut self) -> Option<String> {
+            Some("FAKE".to_string())
+        }
+
+        fn capture(&mut self) -> Result<FingerprintCapture, BiometricError> {
+            if !self.initialized {
+                return Err(BiometricError::SdkUnknown);
+            }
+            let capture = self.captures.pop_front().ok_or(BiometricError::NoFingerDetected)?;
+            self.served += 1;
+
+            // Como o CIDBIO_CancelCapture: só vale para a captura em andamento
+            self.cancelled.store(false, Ordering::SeqCst);
+            let started = Instant::now();
+            while started.elapsed() < capture.delay {
+                if self.cancelled.swap(false, Ordering::SeqCst) {
+                    return Err(BiometricError::CaptureCanceled);
+                }
+                std::thread::sleep(Duration::from_millis(5));
+            }
+
+            if let Some(error) = capture.error {
+                return Err(error);
+            }
+            Ok(FingerprintCapture {
+                template: format!("{}#{}", capture.finger, self.served),
+                quality: capture.quality,
+                image: None,
+            })
+        }
+
+        fn match_templates(&mut self, stored: &str, live: &str) -> Result<i32, BiometricError> {
+            Ok(if stored == live {
+                20000
+            } else if finger_of(stored) == finger_of(live) {
+                16000
+            } else {
+                1000
+            })
+        }
+
+        fn merge_templates(&mut self, first: &str, second: &str, third: &str) -> Result<String, BiometricError> {
+            let finger = finger_of(first);
+            if finger_of(second) != finger || finger_of(third) != finger {
+                return Err(BiometricError::Merging);
+            }
+            Ok(format!("{}#merged", finger))
+        }
+
+        fn device_info(&mut self) -> Result<ReaderInfo, BiometricError> {
+            Ok(ReaderInfo {
+                backend: self.name().to_string(),
+                port: Some("FAKE".to_string()),
+                model: "fake".to_string(),
+                serial_number: "FAKE-0001".to_string(),
+                firmware_version: "0".to_string(),
+                sdk_version: IDBIO_SDK_VERSION.to_string(),
+            })
+        }
+
+        fn cancel_handle(&self) -> CancelHandle {
+            let cancelled = self.cancelled.clone();
+            Arc::new(move || cancelled.store(true, Ordering::SeqCst))
+        }
+    }
+}
@@ -0,0 +1,490 @@
+use base64::Engine;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::biometric_error::BiometricError;
+use crate::biometric_image::FingerprintImage;
+use crate::secret_store::{SecretStore, STATION_SIGNING_KEY};
+use crate::station_config::{app_data_dir, station_id};
+use crate::supabase::Supabase;
+use crate::supabase_auth::SupabaseAuth;
+use crate::verification_policy::{AppliedPolicy, Operation};
+use crate::util::{base64_engine, random_bytes, random_hex_id};
+
+/// Versão do formato do comprovante (muda se os campos assinados mudarem). A versão 2
+/// acrescentou `template_sha256`; comprovantes da versão 1 continuam conferindo.
+pub const RECEIPT_VERSION: u32 = 2;
+
+/// Campos assinados do comprovante, na ordem em que são serializados.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ReceiptPayload {
+    pub version: u32,
+    pub receipt_id: String,
+    pub station_id: String,
+    pub user_id: String,
+    pub operation: Option<Operation>,
+    /// Registro que a biometria autorizou (ex.: id da solicitação entregue)
+    pub operation_id: Option<String>,
+    pub score: i32,
+    pub percent: i32,
+    pub min_percent: i32,
+    pub policy_rule: Option<String>,
+    pub reader_serial: Option<String>,
+    pub reader_model: Option<String>,
+    /// Momento da verificação (RFC 3339)
+    pub issued_at: String,
+    /// SHA-256 (hex) da imagem crua capturada, se o leitor devolveu imagem
+    pub image_sha256: Option<String>,
+    /// SHA-256 (hex) do template capturado; o banco só aceita essa captura como atualização
+    /// adaptativa (`biometric_refresh_template`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_sha256: Option<String>,
+}
+
+/// Comprovante de verificação assinado com a chave Ed25519 da estação.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct VerificationReceipt {
+    pub payload: ReceiptPayload,
+    /// Chave pública da estação (base64)
+    pub public_key: String,
+    /// Assinatura Ed25519 do JSON de `payload` (base64)
+    pub signature: String,
+}
+
+/// Resultado de `verify_receipt`.
+#[derive(Serialize, Clone, Debug)]
+pub struct ReceiptVerification {
+    pub valid: bool,
+    pub reason: String,
+    pub receipt_id: String,
+    pub station_id: String,
+    pub user_id: String,
+}
+
+/// Chave de assinatura da estação (gerenciada pelo Tauri).
+///
+/// Gerada na primeira execução e guardada no cofre de segredos (`secret_store`). A chave
+/// pública é registrada uma única vez em `station_keys` por um supervisor
+/// (`register_station_key`); até lá o banco recusa os comprovantes da estação.
+#[derive(Clone)]
+pub struct StationKey {
+    key: Arc<SigningKey>,
+}
+
+/// Arquivo onde a chave ficava em texto puro antes do cofre
+fn legacy_key_path() -> PathBuf {
+    app_data_dir().join("station_signing.key")
+}
+
+fn decode_key(encoded: &str) -> Result<SigningKey, BiometricError> {
+    let bytes: [u8; 32] = base64_engine()
+        .decode(encoded.trim())
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| BiometricError::Config("Chave da estação inválida".to_string()))?;
+    Ok(SigningKey::from_bytes(&bytes))
+}
+
+impl StationKey {
+    /// Lê a chave da estação do cofre ou gera uma nova.
+    ///
+    /// Uma chave antiga em `station_signing.key` é movida para o cofre e o arquivo apagado.
+    pub fn load_or_create(store: &SecretStore) -> Result<Self, BiometricError> {
+        let legacy = legacy_key_path();
+        let key = if let Some(encoded) = store.get(STATION_SIGNING_KEY) {
+            decode_key(&encoded)?
+        } else if let Ok(encoded) = std::fs::read_to_string(&legacy) {
+            let key = decode_key(&encoded)?;
+            store.set(STATION_SIGNING_KEY, encoded.trim())?;
+            std::fs::remove_file(&legacy)?;
+            log::info!("🔑 Chave de assinatura da estação movida para o cofre de segredos");
+            key
+        } else {
+            let bytes = random_bytes::<32>()?;
+            store.set(STATION_SIGNING_KEY, &base64_engine().encode(bytes))?;
+            log::info!("🔑 Chave de assinatura da estação criada no cofre de segredos");
+            SigningKey::from_bytes(&bytes)
+        };
+        Ok(Self { key: Arc::new(key) })
+    }
+
+    /// Chave fixa, para os testes
+    #[cfg(test)]
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self {
+            key: Arc::new(SigningKey::from_bytes(&bytes)),
+        }
+    }
+
+    pub fn public_key(&self) -> String {
+        base64_engine().encode(self.key.verifying_key().to_bytes())
+    }
+
+    /// Assinatura Ed25519 de `message` (base64).
+    pub fn sign(&self, message: &[u8]) -> String {
+        base64_engine().encode(self.key.sign(message).to_bytes())
+    }
+
+    /// Confere uma assinatura feita por `sign` com esta chave.
+    pub fn verify(&self, message: &[u8], signature: &str) -> bool {
+        base64_engine()
+            .decode(signature)
+            .ok()
+            .and_then(|b| <[u8; 64]>::try_from(b).ok())
+            .map(|b| self.key.verifying_key().verify(message, &Signature::from_bytes(&b)).is_ok())
+            .unwrap_or(false)
+    }
+
+    /// Assina o comprovante de uma verificação aceita.
+    #[allow(clippy::too_many_arguments)]
+    pub fn issue(
+        &self,
+        user_id: &str,
+        template: &str,
+        operation_id: Option<&str>,
+        score: i32,
+        percent: i32,
+        policy: &AppliedPolicy,
+        reader_serial: Option<&str>,
+        reader_model: Option<&str>,
+        image: Option<&FingerprintImage>,
+    ) -> Result<VerificationReceipt, BiometricError> {
+        let payload = ReceiptPayload {
+            version: RECEIPT_VERSION,
+            receipt_id: random_hex_id()?,
+            station_id: station_id(),
+            user_id: user_id.to_string(),
+            operation: Some(policy.operation),
+            operation_id: operation_id.map(str::to_string),
+            score,
+            percent,
+            min_percent: policy.min_percent,
+            policy_rule: policy.rule_id.clone(),
+            reader_serial: reader_serial.map(str::to_string),
+            reader_model: reader_model.map(str::to_string),
+            issued_at: chrono::Utc::now().to_rfc3339(),
+            image_sha256: image.map(|img| format!("{:x}", Sha256::digest(&img.pixels))),
+            template_sha256: Some(format!("{:x}", Sha256::digest(template.as_bytes()))),
+        };
+        let message = serde_json::to_vec(&payload)?;
+        let signature = self.sign(&message);
+
+        Ok(VerificationReceipt {
+            payload,
+            public_key: self.public_key(),
+            signature,
+        })
+    }
+
+    /// Grava o comprovante em `biometric_receipts`, ligado ao `operation_id`. Reenviar um
+    /// comprovante já gravado não falha (a sincronização offline repete o envio).
+    ///
+    /// O RLS só aceita o comprovante se `public_key` for a chave registrada da estação.
+    pub async fn store(&self, db: &Supabase, receipt: &VerificationReceipt) -> Result<(), BiometricError> {
+        let body = serde_json::json!({
+            "receipt_id": receipt.payload.receipt_id,
+            "operation_id": receipt.payload.operation_id,
+            "user_id": receipt.payload.user_id,
+            "station_id": receipt.payload.station_id,
+            "payload": receipt.payload,
+            "public_key": receipt.public_key,
+            "signature": receipt.signature
+        });
+        log::debug!("Gravando comprovante {}", receipt.payload.receipt_id);
+        db.from("biometric_receipts").upsert(&body, Some("receipt_id"), true).await
+    }
+}
+
+/// Chave pública registrada em `station_keys` para a estação (ausente se nunca foi registrada).
+pub async fn registered_key(db: &Supabase, station_id: &str) -> Result<Option<String>, BiometricError> {
+    #[derive(Deserialize)]
+    struct Row {
+        public_key: String,
+    }
+    let row: Option<Row> = db
+        .from("station_keys")
+        .select("public_key")
+        .eq("station_id", station_id)
+        .fetch_one()
+        .await?;
+    Ok(row.map(|row| row.public_key))
+}
+
+/// Chaves de `station_keys` já vistas por esta estação, para conferir comprovantes sem conexão
+fn known_keys_path() -> PathBuf {
+    app_data_dir().join("station_keys.json")
+}
+
+fn read_known_keys(path: &Path) -> HashMap<String, String> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Guarda a chave registrada de uma estação no arquivo de chaves conhecidas.
+fn remember_key(path: &Path, station_id: &str, public_key: &str) -> Result<(), BiometricError> {
+    let mut keys = read_known_keys(path);
+    if keys.get(station_id).map(String::as_str) == Some(public_key) {
+        return Ok(());
+    }
+    keys.insert(station_id.to_string(), public_key.to_string());
+    std::fs::write(path, serde_json::to_string_pretty(&keys)?)?;
+    Ok(())
+}
+
+/// Confere um comprovante sem conexão: com a chave informada ou, sem ela, com a última chave
+/// de `station_keys` vista para a estação do comprovante.
+fn check_receipt_offline(
+    receipt: &VerificationReceipt,
+    public_key: Option<&str>,
+    known_keys: &HashMap<String, String>,
+) -> ReceiptVerification {
+    let key = public_key
+        .filter(|k| !k.trim().is_empty())
+        .or_else(|| known_keys.get(&receipt.payload.station_id).map(String::as_str));
+    let mut verification = check_receipt(receipt, key);
+    if key.is_none() {
+        verification.reason =
+            "Chave da estação desconhecida: informe a chave pública ou confira uma vez com conexão".to_string();
+    }
+    verification
+}
+
+/// Confere a assinatura de um comprovante com a chave registrada da estação que o emitiu.
+///
+/// `registered_key` vem sempre de `station_keys` (`registered_key`): a chave que viaja no
+/// comprovante não vale sozinha, senão um comprovante forjado com uma chave qualquer passaria.
+pub fn check_receipt(receipt: &VerificationReceipt, registered_key: Option<&str>) -> ReceiptVerification {
+    let result = |valid: bool, reason: &str| ReceiptVerification {
+        valid,
+        reason: reason.to_string(),
+        receipt_id: receipt.payload.receipt_id.clone(),
+        station_id: receipt.payload.station_id.clone(),
+        user_id: receipt.payload.user_id.clone(),
+    };
+
+    if !(1..=RECEIPT_VERSION).contains(&receipt.payload.version) {
+        return result(false, "Versão de comprovante desconhecida");
+    }
+    let Some(registered_key) = registered_key else {
+        return result(false, "Estação sem chave registrada em station_keys");
+    };
+    if registered_key.trim() != receipt.public_key {
+        return result(false, "Comprovante assinado por outra chave que não a da estação");
+    }
+
+    let key = base64_engine()
+        .decode(registered_key.trim())
+        .ok()
+        .and_then(|b| <[u8; 32]>::try_from(b).ok())
+        .and_then(|b| VerifyingKey::from_bytes(&b).ok());
+    let Some(key) = key else {
+        return result(false, "Chave pública inválida");
+    };
+    let signature = base64_engine()
+        .decode(&receipt.signature)
+        .ok()
+        .and_then(|b| <[u8; 64]>::try_from(b).ok())
+        .map(|b| Signature::from_bytes(&b));
+    let Some(signature) = signature else {
+        return result(false, "Assinatura inválida");
+    };
+    let Ok(message) = serde_json::to_vec(&receipt.payload) else {
+        return result(false, "Conteúdo do comprovante inválido");
+    };
+
+    match key.verify(&message, &signature) {
+        Ok(()) => result(true, "Assinatura válida"),
+        Err(_) => result(false, "Assinatura não confere: comprovante alterado"),
+    }
+}
+
+/// Comando Tauri: confere um comprovante com a chave que `station_keys` tem para a estação
+/// dele (precisa de conexão).
+#[tauri::command]
+pub async fn verify_receipt(
+    auth: tauri::State<'_, SupabaseAuth>,
+    receipt: VerificationReceipt,
+) -> Result<ReceiptVerification, BiometricError> {
+    let db = auth.connect().await?;
+    let key = registered_key(&db, &receipt.payload.station_id).await?;
+    if let Some(key) = &key {
+        if let Err(e) = remember_key(&known_keys_path(), &receipt.payload.station_id, key) {
+            log::warn!("⚠️ Não foi possível guardar a chave da estação {}: {}", receipt.payload.station_id, e);
+        }
+    }
+    let verification = check_receipt(&receipt, key.as_deref());
+    log::debug!(
+        "verify_receipt() receipt_id={} valid={} ({})",
+        verification.receipt_id, verification.valid, verification.reason
+    );
+    Ok(verification)
+}
+
+/// Comando Tauri: confere um comprovante sem conexão, com a `public_key` informada (base64,
+/// de uma fonte confiável, ex.: exportada de `station_keys`) ou com a chave da estação guardada
+/// na última conferência online (`verify_receipt`, `register_station_key`).
+#[tauri::command]
+pub fn verify_receipt_offline(receipt: VerificationReceipt, public_key: Option<String>) -> ReceiptVerification {
+    let verification = check_receipt_offline(&receipt, public_key.as_deref(), &read_known_keys(&known_keys_path()));
+    log::debug!(
+        "verify_receipt_offline() receipt_id={} informed_key={} valid={} ({})",
+        verification.receipt_id, public_key.is_some(), verification.valid, verification.reason
+    );
+    verification
+}
+
+/// Comando Tauri: chave pública desta estação (base64), para registrar com os auditores.
+#[tauri::command]
+pub fn get_station_public_key(key: tauri::State<'_, StationKey>) -> String {
+    key.public_key()
+}
+
+/// Comando Tauri: registra a chave pública desta estação em `station_keys` (só supervisores).
+///
+/// O registro é feito uma vez pela função `biometric_register_station_key` e não muda depois;
+/// retorna `false` se a mesma chave já estava registrada. Outra chave para a estação é recusada.
+#[tauri::command]
+pub async fn register_station_key(
+    key: tauri::State<'_, StationKey>,
+    auth: tauri::State<'_, SupabaseAuth>,
+) -> Result<bool, BiometricError> {
+    let db = auth.connect().await?;
+    let registered: bool = db
+        .rpc(
+            "biometric_register_station_key",
+            &serde_json::json!({ "p_station_id": station_id(), "p_public_key": key.public_key() }),
+        )
+        .await?;
+    if registered {
+        log::info!("🔑 Chave da estação {} registrada em station_keys", station_id());
+    }
+    if let Err(e) = remember_key(&known_keys_path(), &station_id(), &key.public_key()) {
+        log::warn!("⚠️ Não foi possível guardar a chave desta estação: {}", e);
+    }
+    Ok(registered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(seed: u8) -> StationKey {
+        StationKey::from_bytes([seed; 32])
+    }
+
+    fn receipt(key: &StationKey) -> VerificationReceipt {
+        let policy = AppliedPolicy {
+            rule_id: None,
+            source: crate::verification_policy::PolicySource::BuiltIn,
+            operation: Operation::Delivery,
+            base_id: None,
+            criticality: None,
+            min_percent: 60,
+            requested_min_percent: None,
+        };
+        key.issue("ana", "dGVtcGxhdGU=", Some("solicitacao-1"), 16000, 80, &policy, Some("SN1"), Some("iDBio"), None)
+            .unwrap()
+    }
+
+    #[test]
+    fn receipt_signed_by_the_registered_key_is_valid() {
+        let station = key(1);
+        let receipt = receipt(&station);
+        assert!(check_receipt(&receipt, Some(&station.public_key())).valid);
+    }
+
+    #[test]
+    fn receipt_without_a_registered_key_is_rejected() {
+        let receipt = receipt(&key(1));
+        assert!(!check_receipt(&receipt, None).valid);
+    }
+
+    #[test]
+    fn self_signed_forgery_is_rejected() {
+        let station = key(1);
+        let forged = receipt(&key(2));
+        assert!(!check_receipt(&forged, Some(&station.public_key())).valid);
+
+        // Trocar só a chave embutida não ajuda: a assinatura é conferida com a registrada
+        let mut forged = forged;
+        forged.public_key = station.public_key();
+        assert!(!check_receipt(&forged, Some(&station.public_key())).valid);
+    }
+
+    #[test]
+    fn captured_template_hash_is_signed() {
+        let station = key(1);
+        let mut receipt = receipt(&station);
+        assert_eq!(
+            receipt.payload.template_sha256.as_deref(),
+            Some(format!("{:x}", Sha256::digest(b"dGVtcGxhdGU=")).as_str())
+        );
+        receipt.payload.template_sha256 = Some(format!("{:x}", Sha256::digest(b"outro")));
+        assert!(!check_receipt(&receipt, Some(&station.public_key())).valid);
+    }
+
+    #[test]
+    fn version_one_receipt_still_checks() {
+        let station = key(1);
+        let mut receipt = receipt(&station);
+        receipt.payload.version = 1;
+        receipt.payload.template_sha256 = None;
+        receipt.signature = station.sign(&serde_json::to_vec(&receipt.payload).unwrap());
+        assert!(check_receipt(&receipt, Some(&station.public_key())).valid);
+    }
+
+    #[test]
+    fn altered_payload_is_rejected() {
+        let station = key(1);
+        let mut receipt = receipt(&station);
+        receipt.payload.percent = 99;
+        assert!(!check_receipt(&receipt, Some(&station.public_key())).valid);
+    }
+
+    #[test]
+    fn offline_check_uses_the_informed_key_before_the_known_one() {
+        let station = key(1);
+        let receipt = receipt(&station);
+        let known = HashMap::from([(receipt.payload.station_id.clone(), key(2).public_key())]);
+
+        assert!(check_receipt_offline(&receipt, Some(&station.public_key()), &known).valid);
+        assert!(!check_receipt_offline(&receipt, None, &known).valid);
+        assert!(!check_receipt_offline(&receipt, Some(&key(3).public_key()), &HashMap::new()).valid);
+    }
+
+    #[test]
+    fn offline_check_falls_back_to_the_known_key() {
+        let station = key(1);
+        let receipt = receipt(&station);
+        let known = HashMap::from([(receipt.payload.station_id.clone(), station.public_key())]);
+        assert!(check_receipt_offline(&receipt, None, &known).valid);
+        assert!(check_receipt_offline(&receipt, Some(" "), &known).valid);
+
+        let unknown = check_receipt_offline(&receipt, None, &HashMap::new());
+        assert!(!unknown.valid);
+        assert!(unknown.reason.contains("desconhecida"));
+    }
+
+    #[test]
+    fn known_keys_round_trip() {
+        let path = std::env::temp_dir().join(format!("station-keys-test-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        assert!(read_known_keys(&path).is_empty());
+
+        remember_key(&path, "estacao-a", "chave-1").unwrap();
+        remember_key(&path, "estacao-b", "chave-2").unwrap();
+        remember_key(&path, "estacao-a", "chave-3").unwrap();
+        let keys = read_known_keys(&path);
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys["estacao-a"], "chave-3");
+        assert_eq!(keys["estacao-b"], "chave-2");
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -1,30 +1,25 @@
+#[cfg(all(feature = "biometric", target_os = "windows"))]
 use std::ffi::{CStr, CString};
-use std::fs::OpenOptions;
-use std::io::Write;
+#[cfg(all(feature = "biometric", target_os = "windows"))]
 use std::os::raw::{c_char, c_int, c_uchar, c_uint};
+use tauri::State;
 
-fn log_biometric(message: &str) {
-    let mut log_dir = std::env::var("PROGRAMDATA")
-        .map(|p| std::path::PathBuf::from(p).join("AlmoxarifadoDesktop"))
-        .unwrap_or_else(|_| std::env::temp_dir().join("AlmoxarifadoDesktop"));
-
-    if let Err(e) = std::fs::create_dir_all(&log_dir) {
-        log::warn!("Falha ao criar diretório de log biométrico: {}", e);
-        return;
-    }
-
-    log_dir.push("biometria.log");
-
-    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(&log_dir) {
-        let _ = writeln!(file, "[{}] {}", chrono::Local::now().to_rfc3339(), message);
-    }
-}
+use crate::biometric_error::BiometricError;
+#[cfg(all(feature = "biometric", target_os = "windows"))]
+use crate::biometric_error::check_sdk;
+#[cfg(all(feature = "biometric", target_os = "windows"))]
+use crate::biometric_image::{FingerprintImage, IDBIO_DPI};
+use crate::biometric_reader::{CancelHandle, FingerprintCapture, FingerprintReader, ReaderInfo};
+use crate::biometric_worker::{BiometricWorker, ReaderState};
+use crate::station_config::StationConfig;
 
-#[cfg(feature = "biometric")]
-/// ATENÇÃO:
-/// - A DLL libcidbio.dll será carregada dinamicamente pelo Windows em runtime
-/// - Os nomes das funções (`CIDBIO_*`) batem com o `cidbiolib.h`.
-/// - Usando extern "system" que é stdcall em win32 e C em win64
+#[cfg(all(feature = "biometric", target_os = "windows"))]
+// ATENÇÃO:
+// - A DLL libcidbio.dll será carregada dinamicamente pelo Windows em runtime
+// - Só existe para Windows (e `raw-dylib` só é aceito lá): nos outros sistemas o `CidBioReader`
+//   cai nos stubs `FeatureDisabled`; use o simulador (`IDBIO_SIMULATOR`)
+// - Os nomes das funções (`CIDBIO_*`) batem com o `cidbiolib.h`.
+// - Usando extern "system" que é stdcall em win32 e C em win64
 #[link(name = "libcidbio", kind = "raw-dylib")]
 extern "system" {
     fn CIDBIO_SetSerialCommPort(port: *const c_char) -> c_int;
@@ -46,6 +41,24 @@ extern "system" {
         score: *mut c_int,
     ) -> c_int;
 
+    /// Combina três templates do mesmo dedo em um (cidbiolib.h: CIDBIO_MergeTemplates)
+    fn CIDBIO_MergeTemplates(
+        t1: *const c_char,
+        t2: *const c_char,
+        t3: *const c_char,
+        t_final: *mut *mut c_char,
+    ) -> c_int;
+
+    /// Cancela uma captura em andamento (pode ser chamada de outra thread)
+    fn CIDBIO_CancelCapture() -> c_int;
+
+    /// Versão do firmware, número de série e modelo do leitor
+    fn CIDBIO_GetDeviceInfo(
+        version: *mut *mut c_char,
+        serial_number: *mut *mut c_char,
+        model: *mut *mut c_char,
+    ) -> c_int;
+
     /// Free helpers (cidbiolib.h)
     fn CIDBIO_FreeByteArray(array: *mut c_uchar) -> c_int;
     fn CIDBIO_FreeString(array: *mut c_char) -> c_int;
@@ -173,39 +186,18 @@ fn detect_biometric_port() -> Option<String> {
     log::warn!("⚠️ Não foi possível identificar o leitor pelo nome do dispositivo");
     log::info!("🔍 Testando portas COM encontradas no sistema...");
     
-    unsafe {
-        // Terminar SDK anterior
-        let _ = CIDBIO_Terminate();
-        std::thread::sleep(std::time::Duration::from_millis(500));
-        
-        for (device_path, port, friendly_name) in &com_devices {
-            log::info!("🔌 Testando {} ({})...", port, friendly_name);
-            
-            let c_port = match CString::new(port.as_str()) {
-                Ok(p) => p,
-                Err(_) => continue,
-            };
-            
-            let set_result = CIDBIO_SetSerialCommPort(c_port.as_ptr());
-            if set_result != 0 {
-                log::debug!("   ❌ SetSerialCommPort falhou (código {})", set_result);
-                continue;
-            }
-            
-            std::thread::sleep(std::time::Duration::from_millis(300));
-            let init_result = CIDBIO_Init();
-            
-            if init_result == 0 || init_result == 1 {
-                log::info!("✅✅✅ LEITOR BIOMÉTRICO DETECTADO EM {} ✅✅✅", port);
-                log::info!("📍 Nome: {}", friendly_name);
-                log::info!("🔗 Caminho: {}", device_path);
-                let _ = CIDBIO_Terminate();
-                std::thread::sleep(std::time::Duration::from_millis(500));
-                return Some(port.clone());
-            }
-            
-            let _ = CIDBIO_Terminate();
-            std::thread::sleep(std::time::Duration::from_millis(100));
+    // Terminar SDK anterior
+    terminate_sdk();
+    std::thread::sleep(std::time::Duration::from_millis(500));
+
+    for (device_path, port, friendly_name) in &com_devices {
+        log::info!("🔌 Testando {} ({})...", port, friendly_name);
+
+        if probe_port(port) {
+            log::info!("✅✅✅ LEITOR BIOMÉTRICO DETECTADO EM {} ✅✅✅", port);
+            log::info!("📍 Nome: {}", friendly_name);
+            log::info!("🔗 Caminho: {}", device_path);
+            return Some(port.clone());
         }
     }
     
@@ -217,127 +209,118 @@ fn detect_biometric_port() -> Option<String> {
     None
 }
 
-#[cfg(all(feature = "biometric", not(target_os = "windows")))]
+#[cfg(all(feature = "biometric", target_os = "windows"))]
+/// Testa se o leitor responde em `port` (SetSerialCommPort + Init). Deixa o SDK terminado.
+fn probe_port(port: &str) -> bool {
+    let c_port = match CString::new(port) {
+        Ok(p) => p,
+        Err(_) => return false,
+    };
+
+    unsafe {
+        let set_result = CIDBIO_SetSerialCommPort(c_port.as_ptr());
+        if set_result != 0 {
+            log::debug!("   ❌ SetSerialCommPort falhou (código {})", set_result);
+            return false;
+        }
+
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        let init_result = CIDBIO_Init();
+        let _ = CIDBIO_Terminate();
+
+        if init_result == 0 || init_result == 1 {
+            std::thread::sleep(std::time::Duration::from_millis(500));
+            true
+        } else {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            false
+        }
+    }
+}
+
+#[cfg(not(all(feature = "biometric", target_os = "windows")))]
+fn probe_port(_port: &str) -> bool {
+    false
+}
+
+#[cfg(not(all(feature = "biometric", target_os = "windows")))]
 fn detect_biometric_port() -> Option<String> {
     log::error!("Detecção automática de porta disponível apenas no Windows");
     None
 }
 
-#[cfg(feature = "biometric")]
 /// Comando Tauri: Reinicializa o SDK biométrico
 /// Útil quando o sensor é reconectado ou muda de porta USB
 #[tauri::command]
-pub fn reinitialize_biometric_sdk() -> Result<String, String> {
-    log_biometric("reinitialize_biometric_sdk() called");
+pub fn reinitialize_biometric_sdk(worker: State<'_, BiometricWorker>) -> Result<String, BiometricError> {
     log::info!("🔄 Reinicializando SDK biométrico...");
     
-    unsafe {
-        // Terminar SDK atual
-        let _ = CIDBIO_Terminate();
-        std::thread::sleep(std::time::Duration::from_millis(500));
-    }
-    
-    // Inicializar novamente (vai detectar a porta automaticamente)
-    let port = std::env::var("IDBIO_PORT").ok();
-    init_sdk(port.as_deref())?;
+    // Terminar SDK atual e inicializar novamente (na thread do SDK)
+    worker.reinitialize(reader_port())?;
     
     Ok("SDK biométrico reinicializado com sucesso".to_string())
 }
 
-#[cfg(not(feature = "biometric"))]
-#[tauri::command]
-pub fn reinitialize_biometric_sdk() -> Result<String, String> {
-    Err("Funcionalidade biométrica não está habilitada nesta build".to_string())
-}
-
-#[cfg(feature = "biometric")]
 /// Comando Tauri: Testa a conexão com o leitor biométrico
 /// Retorna informações detalhadas sobre o status da conexão
 #[tauri::command]
-pub fn test_biometric_connection() -> Result<String, String> {
+pub fn test_biometric_connection(worker: State<'_, BiometricWorker>) -> Result<String, BiometricError> {
     use serde_json::json;
     
-    log_biometric("test_biometric_connection() called");
-    log::info!("🔬 Testando conexão com o leitor biométrico...");
-    
+    log::info!("🔬 Testando conexão com o leitor biométrico ({})...", worker.reader_name());
+
     // Verificar se consegue detectar a porta
-    let detected_port = detect_biometric_port();
-    
-    if detected_port.is_none() {
-        log_biometric("detect_biometric_port() returned None");
-        return Err("Não foi possível detectar o leitor em nenhuma porta COM1-COM20. Verifique se o driver está instalado e o leitor está conectado.".to_string());
-    }
+    let port = match worker.detect_port()? {
+        Some(port) => port,
+        None => {
+            log::debug!("detect_biometric_port() returned None");
+            return Err(BiometricError::ReaderNotFound);
+        }
+    };
     
-    let port = detected_port.unwrap();
-    log_biometric(&format!("Porta detectada: {}", port));
     log::info!("✅ Porta detectada: {}", port);
     
     // Tentar inicializar
-    match init_sdk(Some(&port)) {
-        Ok(_) => {
-            log_biometric("init_sdk() OK");
-            log::info!("✅ SDK inicializado com sucesso");
-            
-            // Aguardar um pouco mais para o hardware estabilizar
-            log::info!("⏳ Aguardando estabilização do hardware...");
-            std::thread::sleep(std::time::Duration::from_millis(1000));
-            
-            // Tentar uma captura de teste (sem salvar)
-            log::info!("🔬 Testando captura (coloque o dedo no leitor)...");
-            match capture_with_sdk() {
-                Ok((_, quality, _)) => {
-                    log_biometric(&format!("capture_with_sdk() OK. quality={}", quality));
-                    let result = json!({
-                        "success": true,
-                        "port": port,
-                        "quality": quality,
-                        "message": format!("Leitor funcionando corretamente na porta {}. Qualidade da captura: {}%", port, quality)
-                    });
-                    Ok(result.to_string())
-                },
-                Err(e) => {
-                    log_biometric(&format!("capture_with_sdk() error: {}", e));
-                    if e.contains("código de erro -1") || e.contains("código: -1") {
-                        Err(format!(
-                            "⚠️ PROBLEMA DE DRIVER DETECTADO\n\n\
-                            ✅ Porta detectada: {}\n\
-                            ✅ SDK inicializado com sucesso\n\
-                            ❌ Falha ao capturar digital (erro -1)\n\n\
-                            Isso confirma que:\n\
-                            - O leitor está conectado na porta correta ({})\n\
-                            - A comunicação serial funciona\n\
-                            - MAS o DRIVER não está instalado/funcionando\n\n\
-                            SOLUÇÃO:\n\
-                            1. Clique no botão '💾 Instalar Driver'\n\
-                            2. Siga as instruções do instalador (requer admin)\n\
-                            3. Reinicie o aplicativo após instalar\n\
-                            4. Teste novamente\n\n\
-                            Detalhes técnicos: {}", 
-                            port, port, e
-                        ))
-                    } else {
-                        Err(format!("SDK inicializado na porta {} mas falha na captura: {}. Tente posicionar o dedo no leitor.", port, e))
-                    }
-                }
-            }
+    if let Err(e) = worker.ensure_ready(Some(port.clone())) {
+        log::debug!("init_sdk() error: {}", e);
+        return Err(e);
+    }
+
+    log::info!("✅ SDK inicializado com sucesso");
+    
+    // Aguardar um pouco mais para o hardware estabilizar
+    log::info!("⏳ Aguardando estabilização do hardware...");
+    std::thread::sleep(std::time::Duration::from_millis(1000));
+    
+    // Tentar uma captura de teste (sem salvar)
+    log::info!("🔬 Testando captura (coloque o dedo no leitor)...");
+    match worker.capture(None) {
+        Ok(FingerprintCapture { quality, .. }) => {
+            log::debug!("capture_with_sdk() OK. quality={}", quality);
+            let result = json!({
+                "success": true,
+                "port": port,
+                "quality": quality,
+                "message": format!("Leitor funcionando corretamente na porta {}. Qualidade da captura: {}%", port, quality)
+            });
+            Ok(result.to_string())
         },
         Err(e) => {
-            log_biometric(&format!("init_sdk() error: {}", e));
-            Err(format!("Porta {} detectada mas falha na inicialização: {}", port, e))
+            log::debug!("capture_with_sdk() error: {}", e);
+            // -1 com porta e Init OK: comunicação serial funciona, driver não
+            if e == BiometricError::SdkUnknown {
+                Err(BiometricError::DriverMalfunction { port })
+            } else {
+                Err(e)
+            }
         }
     }
 }
 
-#[cfg(not(feature = "biometric"))]
-#[tauri::command]
-pub fn test_biometric_connection() -> Result<String, String> {
-    Err("Funcionalidade biométrica não está habilitada nesta build".to_string())
-}
-
 #[cfg(all(feature = "biometric", target_os = "windows"))]
 /// Lista todas as portas COM disponíveis no sistema Windows
 #[tauri::command]
-pub fn list_com_ports() -> Result<String, String> {
+pub fn list_com_ports() -> Result<String, BiometricError> {
     use serde_json::json;
     use std::process::Command;
     
@@ -345,9 +328,9 @@ pub fn list_com_ports() -> Result<String, String> {
     
     // Usar comando mode do Windows para listar portas COM
     let output = Command::new("cmd")
-        .args(&["/C", "mode"])
+        .args(["/C", "mode"])
         .output()
-        .map_err(|e| format!("Erro ao executar comando mode: {}", e))?;
+        .map_err(|e| BiometricError::Io(format!("Erro ao executar comando mode: {}", e)))?;
     
     let output_str = String::from_utf8_lossy(&output.stdout);
     
@@ -386,110 +369,92 @@ pub fn list_com_ports() -> Result<String, String> {
 
 #[cfg(not(all(feature = "biometric", target_os = "windows")))]
 #[tauri::command]
-pub fn list_com_ports() -> Result<String, String> {
-    Err("Listagem de portas COM disponível apenas no Windows".to_string())
+pub fn list_com_ports() -> Result<String, BiometricError> {
+    Err(BiometricError::UnavailableFeature)
 }
 
-#[cfg(feature = "biometric")]
+#[cfg(all(feature = "biometric", target_os = "windows"))]
 /// Inicializa o SDK de biometria (porta opcional, ex.: "COM3").
-/// Se nenhuma porta for especificada, tenta detectar automaticamente.
-/// Se falhar, tenta fazer Terminate + Init novamente (útil para reconexão USB).
-pub fn init_sdk(_port: Option<&str>) -> Result<(), String> {
+/// Com porta, chama CIDBIO_SetSerialCommPort antes do Init; sem porta, o SDK
+/// detecta o leitor sozinho.
+pub fn init_sdk(port: Option<&str>) -> Result<(), BiometricError> {
     unsafe {
-        log_biometric("init_sdk() called");
+        log::debug!("init_sdk() called (porta {:?})", port);
         // Sempre terminar antes de inicializar para liberar qualquer handle COM anterior
         // (evita CreateFile error: 5 / Access Denied em reloads do tauri dev)
         let _ = CIDBIO_Terminate();
         std::thread::sleep(std::time::Duration::from_millis(300));
-        
-        log::info!("🔧 Inicializando SDK biométrico (detecção automática)...");
-        log::debug!("Chamando CIDBIO_Init() - SDK detectará o leitor automaticamente");
+
+        if let Some(port) = port {
+            log::info!("🔧 Inicializando SDK biométrico na porta {}...", port);
+            let c_port = CString::new(port).map_err(|_| BiometricError::InvalidArgument)?;
+            let r = CIDBIO_SetSerialCommPort(c_port.as_ptr());
+            if let Err(e) = check_sdk(r) {
+                log::debug!("CIDBIO_SetSerialCommPort({}) error: {}", port, r);
+                return Err(e);
+            }
+        } else {
+            log::info!("🔧 Inicializando SDK biométrico (detecção automática)...");
+            log::debug!("Chamando CIDBIO_Init() - SDK detectará o leitor automaticamente");
+        }
+
         let r = CIDBIO_Init();
         log::debug!("CIDBIO_Init retornou: {}", r);
         
-        if r == 1 {
-            log_biometric("CIDBIO_Init returned 1 (already init)");
-            log::warn!("SDK já estava inicializado (CIDBIO_WARNING_ALREADY_INIT). Continuando...");
-            std::thread::sleep(std::time::Duration::from_millis(500));
-            return Ok(());
-        } else if r == 0 {
-            log_biometric("CIDBIO_Init returned 0 (success)");
-            log::info!("✅ SDK inicializado com sucesso!");
-            std::thread::sleep(std::time::Duration::from_millis(800));
-            return Ok(());
-        } else {
-            log_biometric(&format!("CIDBIO_Init error: {}", r));
-            // Erro na inicialização
-            log::error!("❌ CIDBIO_Init falhou com código: {}", r);
-            
-            if r == -2 {
-                let driver_check = crate::sdk_manager::check_driver_installed();
-                
-                if driver_check {
-                    return Err(format!(
-                        "⚠️ DRIVER INSTALADO MAS LEITOR NÃO DETECTADO\n\n\
-                        ✅ Driver instalado no sistema\n\
-                        ❌ SDK não consegue detectar o leitor\n\n\
-                        POSSÍVEIS CAUSAS:\n\
-                        1. Leitor não está conectado corretamente\n\
-                        2. Cabo USB com problema\n\
-                        3. Porta USB sem energia suficiente\n\
-                        4. Driver precisa ser reinstalado\n\n\
-                        SOLUÇÃO:\n\
-                        1. Desconecte o leitor USB\n\
-                        2. Aguarde 10 segundos\n\
-                        3. Conecte em outra porta USB (preferencialmente USB 2.0)\n\
-                        4. Aguarde o Windows reconhecer o dispositivo\n\
-                        5. Teste novamente\n\n\
-                        Se o problema persistir, reinstale o driver.\n\n\
-                        Código de erro: CIDBIO_Init retornou -2 (CIDBIO_ERROR_NO_DEVICE)"
-                    ));
-                } else {
-                    return Err(format!(
-                        "⚠️ DRIVER iDBio NÃO INSTALADO\n\n\
-                        ❌ Driver não está instalado\n\n\
-                        SOLUÇÃO:\n\
-                        1. Clique no botão '💾 Instalar Driver' no painel de diagnóstico\n\
-                        2. REINICIE O WINDOWS após instalar\n\
-                        3. Reconecte o leitor USB\n\
-                        4. Teste novamente\n\n\
-                        Código de erro: CIDBIO_Init retornou -2 (CIDBIO_ERROR_NO_DEVICE)"
-                    ));
-                }
+        match check_sdk(r) {
+            Ok(1) => {
+                log::warn!("SDK já estava inicializado (CIDBIO_WARNING_ALREADY_INIT). Continuando...");
+                std::thread::sleep(std::time::Duration::from_millis(500));
+                Ok(())
+            }
+            Ok(_) => {
+                log::info!("✅ SDK inicializado com sucesso!");
+                std::thread::sleep(std::time::Duration::from_millis(800));
+                Ok(())
+            }
+            Err(BiometricError::NoDevice) if !crate::sdk_manager::check_driver_installed() => {
+                log::error!("❌ CIDBIO_Init falhou com código: {} (driver não instalado)", r);
+                Err(BiometricError::DriverNotInstalled)
+            }
+            Err(e) => {
+                log::error!("❌ CIDBIO_Init falhou com código: {}", r);
+                Err(e)
             }
-            
-            // Outros erros
-            return Err(format!(
-                "Erro ao inicializar SDK biométrico. Código: {}. Verifique se o leitor está conectado.",
-                r
-            ));
         }
     }
 }
 
-#[cfg(feature = "biometric")]
 /// Comando Tauri: Inicializa ou reinicializa o SDK biométrico
 /// Útil para reconectar o sensor após desconexão USB ou mudança de porta
 #[tauri::command]
-pub fn initialize_biometric_sdk() -> Result<String, String> {
-    let port = std::env::var("IDBIO_PORT").ok();
-    init_sdk(port.as_deref())?;
+pub fn initialize_biometric_sdk(worker: State<'_, BiometricWorker>) -> Result<String, BiometricError> {
+    worker.ensure_ready(reader_port())?;
     Ok("SDK biométrico inicializado com sucesso".to_string())
 }
 
-#[cfg(not(feature = "biometric"))]
+/// Comando Tauri: Identificação do leitor (modelo, série, firmware e versão do SDK)
+#[tauri::command]
+pub async fn get_reader_info(worker: State<'_, BiometricWorker>) -> Result<ReaderInfo, BiometricError> {
+    log::debug!("get_reader_info() called");
+    let worker = worker.inner().clone();
+    tokio::task::spawn_blocking(move || worker.reader_info())
+        .await
+        .map_err(|_| BiometricError::WorkerUnavailable)?
+}
+
+/// Comando Tauri: Estado atual do leitor (mudanças também chegam pelo evento `biometric-state`)
 #[tauri::command]
-pub fn initialize_biometric_sdk() -> Result<String, String> {
-    Err("Funcionalidade biométrica não está habilitada nesta build".to_string())
+pub fn get_biometric_state(worker: State<'_, BiometricWorker>) -> ReaderState {
+    worker.state()
 }
 
-#[cfg(not(feature = "biometric"))]
+#[cfg(not(all(feature = "biometric", target_os = "windows")))]
 /// Stub: Inicializa o SDK de biometria (desabilitado - feature 'biometric' não está ativa).
-pub fn init_sdk(_port: Option<&str>) -> Result<(), String> {
-    Err("Funcionalidade biométrica não está habilitada nesta build".to_string())
+pub fn init_sdk(_port: Option<&str>) -> Result<(), BiometricError> {
+    Err(BiometricError::FeatureDisabled)
 }
 
-#[cfg(feature = "biometric")]
+#[cfg(all(feature = "biometric", target_os = "windows"))]
 /// Opcional: encerrar SDK (se quiser chamar em shutdown).
 pub fn terminate_sdk() {
     unsafe {
@@ -497,18 +462,218 @@ pub fn terminate_sdk() {
     }
 }
 
-#[cfg(not(feature = "biometric"))]
+#[cfg(not(all(feature = "biometric", target_os = "windows")))]
 /// Stub: encerrar SDK (desabilitado).
 pub fn terminate_sdk() {
     // Nada a fazer quando biometric está desabilitado
 }
 
-#[cfg(feature = "biometric")]
-/// Captura uma digital e retorna (template_base64, qualidade, image_base64).
+#[cfg(all(feature = "biometric", target_os = "windows"))]
+/// Interrompe a captura em andamento (a captura retorna CIDBIO_ERROR_CAPTURE_CANCELED).
+pub fn cancel_capture_sdk() {
+    unsafe {
+        let r = CIDBIO_CancelCapture();
+        log::debug!("CIDBIO_CancelCapture returned {}", r);
+    }
+}
+
+#[cfg(all(feature = "biometric", target_os = "windows"))]
+/// Combina três templates do mesmo dedo em um único template (base64).
+pub fn merge_templates_with_sdk(first: &str, second: &str, third: &str) -> Result<String, BiometricError> {
+    unsafe {
+        let c_first = CString::new(first).map_err(|_| BiometricError::InvalidTemplate)?;
+        let c_second = CString::new(second).map_err(|_| BiometricError::InvalidTemplate)?;
+        let c_third = CString::new(third).map_err(|_| BiometricError::InvalidTemplate)?;
+        let mut merged_ptr: *mut c_char = std::ptr::null_mut();
+
+        let r = CIDBIO_MergeTemplates(c_first.as_ptr(), c_second.as_ptr(), c_third.as_ptr(), &mut merged_ptr);
+        if let Err(e) = check_sdk(r) {
+            log::debug!("CIDBIO_MergeTemplates error: {}", r);
+            return Err(e);
+        }
+        if merged_ptr.is_null() {
+            log::debug!("CIDBIO_MergeTemplates returned null pointer");
+            return Err(BiometricError::Merging);
+        }
+
+        let merged = CStr::from_ptr(merged_ptr).to_string_lossy().into_owned();
+        let _ = CIDBIO_FreeString(merged_ptr);
+        Ok(merged)
+    }
+}
+
+#[cfg(not(all(feature = "biometric", target_os = "windows")))]
+/// Stub: combinar templates (desabilitado).
+pub fn merge_templates_with_sdk(_first: &str, _second: &str, _third: &str) -> Result<String, BiometricError> {
+    Err(BiometricError::FeatureDisabled)
+}
+
+#[cfg(all(feature = "biometric", target_os = "windows"))]
+/// Lê (firmware, número de série, modelo) do leitor via CIDBIO_GetDeviceInfo.
+pub fn device_info_sdk() -> Result<(String, String, String), BiometricError> {
+    unsafe {
+        let mut version_ptr: *mut c_char = std::ptr::null_mut();
+        let mut serial_ptr: *mut c_char = std::ptr::null_mut();
+        let mut model_ptr: *mut c_char = std::ptr::null_mut();
+
+        let r = CIDBIO_GetDeviceInfo(&mut version_ptr, &mut serial_ptr, &mut model_ptr);
+        if let Err(e) = check_sdk(r) {
+            log::debug!("CIDBIO_GetDeviceInfo error: {}", r);
+            return Err(e);
+        }
+
+        // Copia e libera cada string devolvida pelo SDK
+        let take = |ptr: *mut c_char| {
+            if ptr.is_null() {
+                return String::new();
+            }
+            let value = CStr::from_ptr(ptr).to_string_lossy().trim().to_string();
+            let _ = CIDBIO_FreeString(ptr);
+            value
+        };
+
+        Ok((take(version_ptr), take(serial_ptr), take(model_ptr)))
+    }
+}
+
+#[cfg(not(all(feature = "biometric", target_os = "windows")))]
+/// Stub: informações do leitor (desabilitado).
+pub fn device_info_sdk() -> Result<(String, String, String), BiometricError> {
+    Err(BiometricError::FeatureDisabled)
+}
+
+#[cfg(not(all(feature = "biometric", target_os = "windows")))]
+/// Stub: cancelar captura (desabilitado).
+pub fn cancel_capture_sdk() {
+    // Nada a fazer quando biometric está desabilitado
+}
+
+/// Porta fixada pela variável `IDBIO_PORT` (vazia conta como ausente).
+fn pinned_port() -> Option<String> {
+    std::env::var("IDBIO_PORT").ok().filter(|p| !p.trim().is_empty())
+}
+
+/// Porta do leitor: a de `IDBIO_PORT` ou, sem ela, a última detectada (configuração da estação).
+pub fn reader_port() -> Option<String> {
+    pinned_port().or_else(|| StationConfig::load().biometric_port)
+}
+
+/// Leitor real: encaminha as chamadas para a libcidbio.
+///
+/// A porta vem de `IDBIO_PORT` (fixa) ou da configuração da estação (última
+/// porta detectada). Se a porta salva parar de responder, o leitor é procurado
+/// de novo e a nova porta é salva.
+pub struct CidBioReader {
+    port: Option<String>,
+    /// Porta definida por `IDBIO_PORT`: nunca é trocada pela detecção automática
+    pinned: bool,
+}
+
+impl CidBioReader {
+    pub fn new() -> Self {
+        let pinned = pinned_port().is_some();
+        Self {
+            port: reader_port(),
+            pinned,
+        }
+    }
+}
+
+impl Default for CidBioReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FingerprintReader for CidBioReader {
+    fn name(&self) -> &'static str {
+        "libcidbio"
+    }
+
+    fn set_port(&mut self, port: &str) -> Result<(), BiometricError> {
+        self.port = Some(port.to_string());
+        Ok(())
+    }
+
+    fn init(&mut self) -> Result<(), BiometricError> {
+        match init_sdk(self.port.as_deref()) {
+            Err(e) if !self.pinned && self.port.is_some() => {
+                log::warn!(
+                    "⚠️ Leitor não respondeu na porta salva {:?} ({}). Detectando novamente...",
+                    self.port, e
+                );
+                match self.detect_port() {
+                    Some(port) => init_sdk(Some(&port)),
+                    None => Err(e),
+                }
+            }
+            result => result,
+        }
+    }
+
+    fn terminate(&mut self) {
+        terminate_sdk();
+    }
+
+    fn detect_port(&mut self) -> Option<String> {
+        // Porta conhecida primeiro: evita testar todas as portas COM
+        if let Some(port) = self.port.clone() {
+            terminate_sdk();
+            if probe_port(&port) {
+                log::info!("✅ Leitor respondeu na porta conhecida {}", port);
+                return Some(port);
+            }
+            log::warn!("⚠️ Leitor não respondeu na porta conhecida {}", port);
+        }
+
+        let detected = detect_biometric_port();
+        if let Some(port) = detected.as_ref().filter(|_| !self.pinned) {
+            self.port = Some(port.clone());
+            StationConfig::remember_biometric_port(port);
+        }
+        detected
+    }
+
+    fn capture(&mut self) -> Result<FingerprintCapture, BiometricError> {
+        capture_with_sdk()
+    }
+
+    fn match_templates(&mut self, stored: &str, live: &str) -> Result<i32, BiometricError> {
+        compare_templates_with_sdk(stored, live).map(|(raw, _)| raw)
+    }
+
+    fn merge_templates(&mut self, first: &str, second: &str, third: &str) -> Result<String, BiometricError> {
+        merge_templates_with_sdk(first, second, third)
+    }
+
+    fn device_info(&mut self) -> Result<ReaderInfo, BiometricError> {
+        let (firmware_version, serial_number, model) = device_info_sdk()?;
+        let info = ReaderInfo {
+            backend: self.name().to_string(),
+            port: self.port.clone(),
+            model,
+            serial_number,
+            firmware_version,
+            sdk_version: crate::biometric_reader::IDBIO_SDK_VERSION.to_string(),
+        };
+        log::debug!(
+            "Leitor: modelo={} serie={} firmware={} sdk={} porta={:?}",
+            info.model, info.serial_number, info.firmware_version, info.sdk_version, info.port
+        );
+        Ok(info)
+    }
+
+    fn cancel_handle(&self) -> CancelHandle {
+        std::sync::Arc::new(cancel_capture_sdk)
+    }
+}
+
+#[cfg(all(feature = "biometric", target_os = "windows"))]
+/// Captura uma digital (template base64, qualidade e imagem base64).
 /// Se falhar com erro -1 (SDK não inicializado), tenta reinicializar automaticamente.
-pub fn capture_with_sdk() -> Result<(String, i32, String), String> {
+pub fn capture_with_sdk() -> Result<FingerprintCapture, BiometricError> {
     unsafe {
-        log_biometric("capture_with_sdk() called");
+        log::debug!("capture_with_sdk() called");
         let mut tmpl_ptr: *mut c_char = std::ptr::null_mut();
         let mut img_ptr: *mut c_uchar = std::ptr::null_mut();
         let mut w: c_uint = 0;
@@ -524,7 +689,7 @@ pub fn capture_with_sdk() -> Result<(String, i32, String), String> {
         );
 
         if r == -1 {
-            log_biometric("CIDBIO_CaptureImageAndTemplate returned -1; reinitializing");
+            log::debug!("CIDBIO_CaptureImageAndTemplate returned -1; reinitializing");
             // Erro -1 geralmente indica SDK não inicializado
             log::warn!("CIDBIO_CaptureImageAndTemplate retornou -1 (SDK não inicializado). Tentando reinicializar...");
             
@@ -532,8 +697,7 @@ pub fn capture_with_sdk() -> Result<(String, i32, String), String> {
             let _ = CIDBIO_Terminate();
             std::thread::sleep(std::time::Duration::from_millis(500));
             
-            let port = std::env::var("IDBIO_PORT").ok();
-            init_sdk(port.as_deref())?;
+            init_sdk(reader_port().as_deref())?;
             
             // Tentar capturar novamente
             log::info!("SDK reinicializado. Tentando captura novamente...");
@@ -545,36 +709,30 @@ pub fn capture_with_sdk() -> Result<(String, i32, String), String> {
                 &mut quality,
             );
             
-            if r2 != 0 {
-                log_biometric(&format!("Capture retry failed: {}", r2));
-                return Err(format!(
-                    "CIDBIO_CaptureImageAndTemplate falhou mesmo após reinicialização. Código: {}. Verifique se o leitor está conectado.",
-                    r2
-                ));
+            if let Err(e) = check_sdk(r2) {
+                log::debug!("Capture retry failed: {}", r2);
+                return Err(e);
             }
-        } else if r != 0 {
-            log_biometric(&format!("CIDBIO_CaptureImageAndTemplate error: {}", r));
-            return Err(format!(
-                "CIDBIO_CaptureImageAndTemplate retornou código de erro {}",
-                r
-            ));
+        } else if let Err(e) = check_sdk(r) {
+            log::debug!("CIDBIO_CaptureImageAndTemplate error: {}", r);
+            return Err(e);
         }
 
         if tmpl_ptr.is_null() {
-            log_biometric("Template returned null pointer");
-            return Err("Template retornou ponteiro nulo".into());
+            log::debug!("Template returned null pointer");
+            return Err(BiometricError::InvalidTemplate);
         }
 
         let c_str = CStr::from_ptr(tmpl_ptr);
         let template = c_str.to_string_lossy().into_owned();
 
-        // Converter imagem para base64
-        let image_base64 = if !img_ptr.is_null() && w > 0 && h > 0 {
+        // Copia a imagem (w*h bytes em tons de cinza) antes de liberar o buffer do SDK
+        let image = if !img_ptr.is_null() && w > 0 && h > 0 {
             let img_size = (w * h) as usize;
             let img_slice = std::slice::from_raw_parts(img_ptr, img_size);
-            base64::Engine::encode(&base64::engine::general_purpose::STANDARD, img_slice)
+            FingerprintImage::from_raw(img_slice.to_vec(), w, h, IDBIO_DPI)
         } else {
-            String::new()
+            None
         };
 
         // Libera memória alocada pelo SDK
@@ -583,31 +741,33 @@ pub fn capture_with_sdk() -> Result<(String, i32, String), String> {
             let _ = CIDBIO_FreeByteArray(img_ptr);
         }
 
-        Ok((template, quality as i32, image_base64))
+        Ok(FingerprintCapture {
+            template,
+            quality: quality as i32,
+            image,
+        })
     }
 }
 
-#[cfg(not(feature = "biometric"))]
+#[cfg(not(all(feature = "biometric", target_os = "windows")))]
 /// Stub: Captura digital (desabilitado).
-pub fn capture_with_sdk() -> Result<(String, i32), String> {
-    Err("Funcionalidade biométrica não está habilitada nesta build".to_string())
+pub fn capture_with_sdk() -> Result<FingerprintCapture, BiometricError> {
+    Err(BiometricError::FeatureDisabled)
 }
 
-#[cfg(feature = "biometric")]
+#[cfg(all(feature = "biometric", target_os = "windows"))]
 /// Compara dois templates e retorna (score_bruto, porcentagem_0_a_100).
-pub fn compare_templates_with_sdk(stored: &str, live: &str) -> Result<(i32, i32), String> {
+pub fn compare_templates_with_sdk(stored: &str, live: &str) -> Result<(i32, i32), BiometricError> {
     unsafe {
-        let c_stored = CString::new(stored).map_err(|e| e.to_string())?;
-        let c_live = CString::new(live).map_err(|e| e.to_string())?;
+        let c_stored = CString::new(stored).map_err(|_| BiometricError::InvalidTemplate)?;
+        let c_live = CString::new(live).map_err(|_| BiometricError::InvalidTemplate)?;
         let mut raw_score: c_int = 0;
 
         let r = CIDBIO_MatchTemplates(c_stored.as_ptr(), c_live.as_ptr(), &mut raw_score);
 
-        if r != 0 {
-            return Err(format!(
-                "CIDBIO_MatchTemplate retornou código de erro {}",
-                r
-            ));
+        if let Err(e) = check_sdk(r) {
+            log::debug!("CIDBIO_MatchTemplates error: {}", r);
+            return Err(e);
         }
 
         let percent = score_to_percent(raw_score);
@@ -615,10 +775,10 @@ pub fn compare_templates_with_sdk(stored: &str, live: &str) -> Result<(i32, i32)
     }
 }
 
-#[cfg(not(feature = "biometric"))]
+#[cfg(not(all(feature = "biometric", target_os = "windows")))]
 /// Stub: Compara templates (desabilitado).
-pub fn compare_templates_with_sdk(_stored: &str, _live: &str) -> Result<(i32, i32), String> {
-    Err("Funcionalidade biométrica não está habilitada nesta build".to_string())
+pub fn compare_templates_with_sdk(_stored: &str, _live: &str) -> Result<(i32, i32), BiometricError> {
+    Err(BiometricError::FeatureDisabled)
 }
 
 
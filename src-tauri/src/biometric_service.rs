@@ -1,300 +1,558 @@
-use serde::{Deserialize, Serialize};
-use std::fs::OpenOptions;
-use std::io::Write;
-
+use serde::Serialize;
+
+use crate::app_log;
+use crate::audit_log::{record_event, AuditCategory};
+use crate::biometric_enroll::{consistency_threshold, enroll_finger, EnrollContext, EnrollmentReport};
+use crate::biometric_error::BiometricError;
+use crate::biometric_image::{EncodedImage, FingerprintImage, ImageOptions};
+use crate::biometric_lockout::{record_anomaly, FailureOutcome, LockoutTracker, ReplaySuspicion};
+use crate::biometric_reader::{FingerprintCapture, ReaderInfo};
+use crate::biometric_receipt::{StationKey, VerificationReceipt};
 use crate::biometric_sdk;
-
-fn log_biometric(message: &str) {
-    let mut log_dir = std::env::var("PROGRAMDATA")
-        .map(|p| std::path::PathBuf::from(p).join("AlmoxarifadoDesktop"))
-        .unwrap_or_else(|_| std::env::temp_dir().join("AlmoxarifadoDesktop"));
-
-    if let Err(e) = std::fs::create_dir_all(&log_dir) {
-        log::warn!("Falha ao criar diretório de log biométrico: {}", e);
-        return;
-    }
-
-    log_dir.push("biometria.log");
-
-    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(&log_dir) {
-        let _ = writeln!(file, "[{}] {}", chrono::Local::now().to_rfc3339(), message);
-    }
+use crate::biometric_templates::{
+    load_user_templates, refresh_after_verification, RemoteTemplate, TemplateRefresh, DEFAULT_FINGER,
+};
+use crate::biometric_worker::{AbortReason, BiometricWorker};
+use crate::supabase::{block_on, Supabase};
+use crate::supabase_auth::SupabaseAuth;
+use crate::template_cache::TemplateCache;
+use crate::util::new_uuid;
+use crate::verification_policy::{load_operation_context, AppliedPolicy, Criticality, Operation, PolicyCache};
+
+/// Como a validação/cadastro terminou.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BiometricOutcome {
+    /// Digital conferida com os templates do usuário
+    Validated,
+    /// Digital cadastrada
+    Enrolled,
+    /// Score ou qualidade abaixo do mínimo
+    Rejected,
+    /// Operador cancelou a captura
+    Cancelled,
+    /// Ninguém colocou o dedo dentro do timeout
+    Timeout,
+    /// Digital reconhecida entre os candidatos (1:N)
+    Identified,
+    /// Cadastro bloqueado: a digital já pertence a outro usuário
+    Duplicate,
+    /// Dedo já cadastrado, pulado na sessão de cadastro
+    Skipped,
+    /// Verificação bloqueada por excesso de falhas (libera só o supervisor)
+    Locked,
+    /// Template repetido: possível reenvio de uma captura antiga
+    ReplaySuspected,
 }
 
-#[derive(Deserialize)]
-#[allow(dead_code)]
-struct RemoteTemplate {
-    id: String,
-    template: String,
-    quality: i32,
+impl From<AbortReason> for BiometricOutcome {
+    fn from(reason: AbortReason) -> Self {
+        match reason {
+            AbortReason::Cancelled => BiometricOutcome::Cancelled,
+            AbortReason::Timeout => BiometricOutcome::Timeout,
+        }
+    }
 }
 
 #[derive(Serialize)]
 pub struct BiometricValidationResult {
     pub success: bool,
+    pub outcome: BiometricOutcome,
     pub reason: String,
     pub score: Option<i32>,
     pub percent: Option<i32>,
     pub quality: Option<i32>,
     pub enrolled: bool,
-    pub fingerprint_image: Option<String>,
+    pub fingerprint_image: Option<EncodedImage>,
+    /// Leitor usado na captura (modelo, série, firmware)
+    pub reader: Option<ReaderInfo>,
+    /// Qualidade e consistência das capturas (só no cadastro)
+    pub enrollment: Option<EnrollmentReport>,
+    /// Template atualizado a partir desta verificação (atualização adaptativa)
+    pub template_refresh: Option<TemplateRefresh>,
+    /// Política de verificação aplicada (ausente no cadastro)
+    pub policy: Option<AppliedPolicy>,
+    /// Comprovante assinado pela estação (só em verificação aceita)
+    pub receipt: Option<VerificationReceipt>,
+    /// Conferida com o cache local, sem internet: a verificação (e o comprovante) sobem
+    /// na próxima sincronização e a entrega deve ser conciliada depois
+    pub offline: bool,
 }
 
 /// Comando Tauri: valida a biometria do usuário ou cadastra se não existir.
 ///
 /// - Fala DIRETO com o Supabase (sem depender do site / Next)
-/// - Busca templates na tabela `biometric_templates` via REST
+/// - Busca os templates pela função `biometric_user_templates`, com o JWT do usuário logado
+///   (sessão entregue por `set_supabase_session`)
 /// - Se não tiver nenhum, captura e cadastra
 /// - Se tiver, captura e compara via SDK
-/// - Exige `min_percent` (ex.: 90) de similaridade mínima
+/// - A similaridade mínima vem da política de verificação (`biometric_policies`),
+///   por `operation` (obrigatória), `base_id` e `criticality`; `min_percent` do frontend só pode
+///   aumentar a exigência. Com `operation_id`, operação, base e criticidade vêm do Supabase
+///   (`biometric_operation_context`); offline, vale a regra mais exigente da base. A política
+///   aplicada volta em `policy`
 /// - ASYNC para não bloquear a UI durante a captura
+/// - O leitor é acessado pela thread dona do SDK (`BiometricWorker`)
+/// - Captura cancelada (`cancel_biometric_capture`) ou sem dedo dentro de
+///   `capture_timeout_secs` resolve com `outcome` `cancelled`/`timeout`
+/// - Cada verificação é registrada em `biometric_verifications`, com o leitor usado
+/// - Imagens (`biometric-image` e `fingerprint_image`) saem em PNG por padrão;
+///   `image_options` pede o buffer cru e/ou uma miniatura
+/// - No cadastro, as três capturas precisam bater entre si (`enroll_consistency_percent`,
+///   no mínimo 40%, só pode aumentar) e são combinadas em um único template; `enrollment` traz os scores
+/// - Antes de gravar, a digital é comparada com as dos outros usuários da `base_id`; a
+///   partir do menor mínimo que a política aceita na base o cadastro é bloqueado
+///   (`outcome` `duplicate`) e o conflito fica registrado para o supervisor
+/// - Uma verificação bem acima do mínimo, com qualidade melhor que a do template,
+///   vira mais uma amostra do dedo (`adaptive_refresh`, ligado por padrão)
+/// - Falhas seguidas bloqueiam a verificação do usuário (ou da estação) até a
+///   liberação do supervisor; um template repetido é tratado como possível reenvio
+/// - Verificação aceita devolve um comprovante (`receipt`) assinado com a chave Ed25519
+///   da estação, ligado ao `operation_id` (ex.: a solicitação entregue) e gravado em
+///   `biometric_receipts`
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn validate_or_enroll_fingerprint(
     app: tauri::AppHandle,
+    worker: tauri::State<'_, BiometricWorker>,
+    policy: tauri::State<'_, PolicyCache>,
+    lockout: tauri::State<'_, LockoutTracker>,
+    station_key: tauri::State<'_, StationKey>,
+    auth: tauri::State<'_, SupabaseAuth>,
+    cache: tauri::State<'_, TemplateCache>,
     user_id: String,
-    min_percent: i32,
+    min_percent: Option<i32>,
+    operation: Operation,
+    criticality: Option<Criticality>,
     finger_id: Option<String>,
-    supabase_url: String,
-    service_key: String,
-) -> Result<BiometricValidationResult, String> {
+    capture_timeout_secs: Option<u64>,
+    image_options: Option<ImageOptions>,
+    enroll_consistency_percent: Option<i32>,
+    base_id: Option<String>,
+    adaptive_refresh: Option<bool>,
+    operation_id: Option<String>,
+) -> Result<BiometricValidationResult, BiometricError> {
+    let worker = worker.inner().clone();
+    let policy = policy.inner().clone();
+    let lockout = lockout.inner().clone();
+    let station_key = station_key.inner().clone();
+    let auth = auth.inner().clone();
+    let cache = cache.inner().clone();
+    let request = FingerprintRequest {
+        user_id,
+        requested_min_percent: min_percent,
+        operation,
+        criticality,
+        finger_id,
+        capture_timeout: capture_timeout_secs.map(std::time::Duration::from_secs),
+        image_options: image_options.unwrap_or_default(),
+        enroll_consistency_percent: consistency_threshold(enroll_consistency_percent),
+        base_id,
+        adaptive_refresh: adaptive_refresh.unwrap_or(true),
+        operation_id,
+    };
+
     // Executar todo o processamento bloqueante em uma thread separada
     tokio::task::spawn_blocking(move || {
-        validate_or_enroll_fingerprint_blocking(app, user_id, min_percent, finger_id, supabase_url, service_key)
+        let services = ValidationServices {
+            policy: &policy,
+            lockout: &lockout,
+            station_key: &station_key,
+            auth: &auth,
+            cache: &cache,
+        };
+        validate_or_enroll_fingerprint_blocking(app, &worker, services, request)
     })
     .await
-    .map_err(|e| format!("Erro ao executar tarefa biométrica: {}", e))?
+    .map_err(|e| {
+        log::debug!("Erro ao executar tarefa biométrica: {}", e);
+        BiometricError::WorkerUnavailable
+    })?
 }
 
-/// Função bloqueante interna que executa a validação/cadastro biométrico
-fn validate_or_enroll_fingerprint_blocking(
-    app: tauri::AppHandle,
+/// Comando Tauri: cancela a captura biométrica em andamento.
+/// Retorna `false` se não havia captura para cancelar.
+#[tauri::command]
+pub fn cancel_biometric_capture(worker: tauri::State<'_, BiometricWorker>) -> bool {
+    log::debug!("cancel_biometric_capture() called");
+    worker.cancel_capture()
+}
+
+/// Parâmetros de `validate_or_enroll_fingerprint`
+struct FingerprintRequest {
     user_id: String,
-    min_percent: i32,
+    requested_min_percent: Option<i32>,
+    operation: Operation,
+    criticality: Option<Criticality>,
     finger_id: Option<String>,
-    supabase_url: String,
-    service_key: String,
-) -> Result<BiometricValidationResult, String> {
-    log_biometric(&format!(
-        "validate_or_enroll_fingerprint() user_id={} min_percent={} enrolled_finger={:?}",
-        user_id, min_percent, finger_id
-    ));
-    // 1) inicializar SDK (porta opcional via env, ex.: "COM3")
-    // Se falhar, tentar reinicializar (útil quando o sensor é reconectado)
-    let port = std::env::var("IDBIO_PORT").ok();
-    if let Err(e) = biometric_sdk::init_sdk(port.as_deref()) {
-        log_biometric(&format!("init_sdk() error: {}. Tentando reinicializar...", e));
+    capture_timeout: Option<std::time::Duration>,
+    image_options: ImageOptions,
+    enroll_consistency_percent: i32,
+    base_id: Option<String>,
+    adaptive_refresh: bool,
+    operation_id: Option<String>,
+}
+
+/// Avisa as duas janelas que a captura foi interrompida e devolve a mensagem.
+pub fn notify_aborted(app: &tauri::AppHandle, reason: AbortReason) -> String {
+    use tauri::Emitter;
+
+    let message = BiometricError::from(reason).message();
+    log::debug!("Captura interrompida: {:?}", reason);
+    let _ = app.emit("biometric-capture-aborted", serde_json::json!({ "reason": reason, "message": message }));
+    let _ = app.emit("biometric-instruction", message.clone());
+    message
+}
+
+/// Resultado de uma captura interrompida
+fn aborted_result(app: &tauri::AppHandle, reason: AbortReason) -> BiometricValidationResult {
+    let message = notify_aborted(app, reason);
+    BiometricValidationResult {
+        success: false,
+        outcome: reason.into(),
+        reason: message,
+        score: None,
+        percent: None,
+        quality: None,
+        enrolled: false,
+        fingerprint_image: None,
+        reader: None,
+        enrollment: None,
+        template_refresh: None,
+        policy: None,
+        receipt: None,
+        offline: false,
+    }
+}
+
+/// Codifica a imagem da captura; uma falha aqui não derruba a validação
+pub fn encode_image(image: Option<&FingerprintImage>, options: &ImageOptions) -> Option<EncodedImage> {
+    let image = image?;
+    match image.encode(options) {
+        Ok(encoded) => Some(encoded),
+        Err(e) => {
+            log::debug!("Erro ao codificar imagem da digital: {}", e);
+            None
+        }
+    }
+}
+
+/// Inicializa o leitor; se falhar, tenta reinicializar (útil quando o sensor é reconectado)
+pub fn ensure_reader_ready(worker: &BiometricWorker) -> Result<(), BiometricError> {
+    log::debug!("Leitor: {} (estado {:?})", worker.reader_name(), worker.state());
+    let port = biometric_sdk::reader_port();
+    if let Err(e) = worker.ensure_ready(port.clone()) {
         log::warn!("Falha na inicialização do SDK: {}. Tentando reinicializar...", e);
-        
-        // Terminar e aguardar o driver liberar o device
-        biometric_sdk::terminate_sdk();
+
+        // Aguardar o driver liberar o device
         std::thread::sleep(std::time::Duration::from_millis(1500));
-        
-        // Segunda tentativa
-        if let Err(e2) = biometric_sdk::init_sdk(port.as_deref()) {
-            log_biometric(&format!("Segunda tentativa falhou: {}. Aguardando mais e tentando novamente...", e2));
-            biometric_sdk::terminate_sdk();
+
+        // Segunda tentativa (terminate + init)
+        if let Err(e2) = worker.reinitialize(port.clone()) {
+            log::debug!("Segunda tentativa falhou: {}. Aguardando mais e tentando novamente...", e2);
             std::thread::sleep(std::time::Duration::from_millis(2000));
-            
+
             // Terceira e última tentativa
-            biometric_sdk::init_sdk(port.as_deref())?;
+            worker.reinitialize(port)?;
         }
     }
+    Ok(())
+}
 
-    log_biometric("SDK inicializado com sucesso");
-    log_biometric(&format!("SUPABASE_URL recebido: {}...", &supabase_url.chars().take(20).collect::<String>()));
-    log_biometric("SERVICE_KEY recebido do frontend");
-
-    // 2) buscar templates do usuário no Supabase (REST direto, sincrono via reqwest blocking)
-    let client = reqwest::blocking::Client::builder()
-        .default_headers({
-            let mut h = reqwest::header::HeaderMap::new();
-            let api_key = reqwest::header::HeaderValue::from_str(&service_key)
-                .map_err(|e| format!("Erro ao montar header apikey: {e}"))?;
-            h.insert("apikey", api_key.clone());
-            let auth = format!("Bearer {}", service_key);
-            let auth_val = reqwest::header::HeaderValue::from_str(&auth)
-                .map_err(|e| format!("Erro ao montar header Authorization: {e}"))?;
-            h.insert(reqwest::header::AUTHORIZATION, auth_val);
-            h
-        })
-        .build()
-        .map_err(|e| {
-            let msg = format!("Erro ao criar client HTTP: {e}");
-            log_biometric(&msg);
-            msg
-        })?;
-
-    let list_url = format!(
-        "{}/rest/v1/biometric_templates?user_id=eq.{}&select=id,template,quality",
-        supabase_url, user_id
-    );
-
-    log_biometric(&format!("HTTP GET templates: {}", list_url));
-
-    let resp = client
-        .get(&list_url)
-        .send()
-        .map_err(|e| {
-            let msg = format!("Erro HTTP ao buscar templates: {e}");
-            log_biometric(&msg);
-            msg
-        })?;
-
-    if !resp.status().is_success() {
-        log_biometric(&format!("HTTP templates failed status: {}", resp.status()));
-        return Err(format!(
-            "Falha ao buscar templates (status {}): {:?}",
-            resp.status(),
-            resp.text().ok()
-        ));
+/// Modelo/série/firmware do leitor, registrados no log (ausente se o leitor não responder)
+pub fn read_reader_info(worker: &BiometricWorker) -> Option<ReaderInfo> {
+    match worker.reader_info() {
+        Ok(info) => {
+            log::info!(
+                "🪪 [SDK] Leitor {} série {} firmware {} (SDK {})",
+                info.model, info.serial_number, info.firmware_version, info.sdk_version
+            );
+            Some(info)
+        }
+        Err(e) => {
+            log::debug!("Não foi possível ler as informações do leitor: {}", e);
+            None
+        }
     }
+}
 
-    let templates: Vec<RemoteTemplate> = resp
-        .json()
-        .map_err(|e| {
-            let msg = format!("Erro ao parsear templates: {e}");
-            log_biometric(&msg);
-            msg
-        })?;
+/// Estado gerenciado usado pela validação
+struct ValidationServices<'a> {
+    policy: &'a PolicyCache,
+    lockout: &'a LockoutTracker,
+    station_key: &'a StationKey,
+    auth: &'a SupabaseAuth,
+    cache: &'a TemplateCache,
+}
 
-    log_biometric(&format!("Templates encontrados: {}", templates.len()));
+/// Templates do usuário direto do Supabase, com o JWT do usuário logado
+async fn load_online(auth: &SupabaseAuth, user_id: &str) -> Result<(Supabase, Vec<RemoteTemplate>), BiometricError> {
+    let db = auth.connect().await?;
+    let templates = load_user_templates(&db, user_id).await?;
+    Ok((db, templates))
+}
 
-    // 3) se não tiver template -> capturar e cadastrar (ENROLLMENT ROBUSTO)
-    if templates.is_empty() {
-        use tauri::Emitter;
-        log_biometric("Nenhum template encontrado. Iniciando cadastro.");
-        
-        let mut best_template = String::new();
-        let mut best_quality = -1;
-
-        // Loop de 3 capturas
-        for i in 1..=3 {
-            log_biometric(&format!("=== INÍCIO CAPTURA {}/3 ===", i));
-            
-            // Avisar frontend para pedir o dedo
-            log_biometric(&format!("Emitindo: Coloque o Dedo ({}/3)", i));
-            if i == 1 {
-                let _ = app.emit("biometric-instruction", "Coloque o dedo no leitor");
-            } else {
-                let _ = app.emit("biometric-instruction", format!("Coloque o dedo novamente ({}/3)", i));
+/// Função bloqueante interna que executa a validação/cadastro biométrico
+fn validate_or_enroll_fingerprint_blocking(
+    app: tauri::AppHandle,
+    worker: &BiometricWorker,
+    services: ValidationServices,
+    request: FingerprintRequest,
+) -> Result<BiometricValidationResult, BiometricError> {
+    let ValidationServices {
+        policy,
+        lockout,
+        station_key,
+        auth,
+        cache,
+    } = services;
+    let FingerprintRequest {
+        user_id,
+        requested_min_percent,
+        operation,
+        criticality,
+        finger_id,
+        capture_timeout,
+        image_options,
+        enroll_consistency_percent,
+        base_id,
+        adaptive_refresh,
+        operation_id,
+    } = request;
+    let _log_context = app_log::context(&[("user_id", Some(&user_id)), ("operation_id", operation_id.as_deref())]);
+
+    log::debug!(
+        "validate_or_enroll_fingerprint() user_id={} operation={:?} requested_min_percent={:?} enrolled_finger={:?}",
+        user_id, operation, requested_min_percent, finger_id
+    );
+    // 1) inicializar SDK (porta de `IDBIO_PORT` ou a última detectada)
+    ensure_reader_ready(worker)?;
+    log::debug!("SDK inicializado com sucesso");
+
+    // Identificação do leitor: vai para o log e para o registro da verificação
+    let reader = read_reader_info(worker);
+
+    // 2) buscar templates do usuário no Supabase ou, sem internet, no cache local
+    let (db, templates) = match block_on(load_online(auth, &user_id)) {
+        Ok((db, templates)) => {
+            if let Err(e) = cache.store_user(&user_id, &templates) {
+                log::warn!("⚠️ Erro ao atualizar o cache local de templates: {}", e);
             }
-
-            log_biometric(&format!("Chamando capture_with_sdk() para captura {}/3", i));
-            let (tmpl, quality, img_base64) = match biometric_sdk::capture_with_sdk() {
-                Ok(res) => {
-                    log_biometric(&format!("capture_with_sdk() retornou OK para captura {}/3", i));
-                    res
-                },
-                Err(e) => {
-                    log_biometric(&format!("Erro na captura {}: {}", i, e));
-                    let _ = app.emit("biometric-instruction", format!("❌ Erro na captura {}: {}", i, e));
-                    return Err(e);
-                }
-            };
-
-            log::info!("Captura {}/3: Qualidade {}", i, quality);
-            log_biometric(&format!("Qualidade captura {}/3: {}", i, quality));
-
-            if quality > best_quality {
-                best_quality = quality;
-                best_template = tmpl.clone();
+            (Some(db), templates)
+        }
+        Err(e) if e.is_offline() => {
+            let cached = cache.user_templates(&user_id)?;
+            if cached.is_empty() {
+                log::debug!("Sem conexão e sem templates de {} no cache local", user_id);
+                return Err(e);
             }
-            
-            // Emitir imagem para o frontend
-            if !img_base64.is_empty() {
-                let _ = app.emit("biometric-image", img_base64);
+            log::warn!("📴 Sem conexão com o Supabase ({}); conferindo com o cache local", e);
+            (None, cached)
+        }
+        Err(e) => return Err(e),
+    };
+    let offline = db.is_none();
+
+    // Mínimo exigido: da política, nunca só do frontend (offline, a última conhecida)
+    let current_policy = match &db {
+        Some(db) => block_on(policy.get(db)),
+        None => policy.cached(),
+    };
+    // Ligada a uma entidade, a operação, a base e a criticidade vêm do Supabase, não da tela;
+    // offline, sem como conferir, vale a regra mais exigente da base
+    let (applied_policy, base_id) = match (&operation_id, &db) {
+        (Some(operation_id), Some(db)) => {
+            let context = block_on(load_operation_context(db, operation_id))?.ok_or_else(|| {
+                BiometricError::Config(format!("Operação {} não encontrada no Supabase", operation_id))
+            })?;
+            if (context.operation, context.criticality) != (operation, criticality) || context.base_id != base_id {
+                log::warn!(
+                    "⚠️ Operação {} da tela ({:?}, {:?}, base {:?}) difere do Supabase ({:?}); usando o Supabase",
+                    operation_id, operation, criticality, base_id, context
+                );
             }
-
-            log_biometric(&format!("Leitura {}/3 concluída", i));
-            
-            log_biometric(&format!("=== FIM CAPTURA {}/3 ===", i));
+            let applied = current_policy.resolve(
+                context.operation,
+                context.base_id.as_deref(),
+                context.criticality,
+                requested_min_percent,
+            );
+            (applied, context.base_id)
         }
-
-        // Validar qualidade mínima (60% exigido)
-        if best_quality < 60 {
-            log_biometric(&format!("Qualidade insuficiente: {}", best_quality));
-            return Ok(BiometricValidationResult {
-                success: false,
-                reason: format!("Qualidade insuficiente (Melhor: {}%). Tente novamente com mais precisão.", best_quality),
-                score: None,
-                percent: None,
-                quality: Some(best_quality),
-                enrolled: false,
-                fingerprint_image: None,
-            });
+        (Some(_), None) => {
+            let applied = current_policy.strictest(operation, base_id.as_deref(), requested_min_percent);
+            (applied, base_id)
         }
+        (None, _) => {
+            let applied = current_policy.resolve(operation, base_id.as_deref(), criticality, requested_min_percent);
+            (applied, base_id)
+        }
+    };
+    let min_percent = applied_policy.min_percent;
+    log::debug!(
+        "Política aplicada: regra {:?} ({:?}) min_percent={}",
+        applied_policy.rule_id, applied_policy.source, min_percent
+    );
 
-        let register_url = format!("{}/rest/v1/biometric_templates", supabase_url);
-        let body = serde_json::json!({
-            "user_id": user_id,
-            "template": best_template,
-            "quality": best_quality,
-            "user_id": user_id,
-            "template": best_template,
-            "quality": best_quality,
-            "finger": finger_id.unwrap_or_else(|| "right_index".to_string())
-        });
+    log::debug!("Templates encontrados: {} (offline: {})", templates.len(), offline);
 
-        log_biometric("HTTP POST register template");
+    // 3) se não tiver template -> capturar, conferir e cadastrar
+    if templates.is_empty() {
+        log::debug!("Nenhum template encontrado. Iniciando cadastro.");
+        // Só chega aqui online: offline sem templates já voltou com o erro de conexão
+        let Some(db) = db.as_ref() else {
+            return Err(BiometricError::Config("Cadastro exige conexão com o Supabase".to_string()));
+        };
+
+        let finger = finger_id.unwrap_or_else(|| DEFAULT_FINGER.to_string());
+        let ctx = EnrollContext {
+            user_id: &user_id,
+            base_id: base_id.as_deref(),
+            db,
+            capture_timeout,
+            image_options: &image_options,
+            consistency_threshold: enroll_consistency_percent,
+            duplicate_percent: current_policy.duplicate_percent(base_id.as_deref()),
+        };
+        let enrollment = enroll_finger(&app, worker, &ctx, &finger, "dedo")?;
 
-        let resp = client
-            .post(&register_url)
-            .json(&body)
-            .send()
-            .map_err(|e| {
-                let msg = format!("Erro HTTP ao registrar template: {e}");
-                log_biometric(&msg);
-                msg
-            })?;
+        return Ok(BiometricValidationResult {
+            success: enrollment.success,
+            outcome: enrollment.outcome,
+            reason: enrollment.reason,
+            score: enrollment.score,
+            percent: enrollment.percent,
+            quality: enrollment.quality,
+            enrolled: enrollment.success,
+            fingerprint_image: None,
+            reader,
+            enrollment: enrollment.enrollment,
+            template_refresh: None,
+            policy: None,
+            receipt: None,
+            offline: false,
+        });
+    }
 
-        if !resp.status().is_success() {
-            log_biometric(&format!("HTTP register failed status: {}", resp.status()));
-            return Err(format!(
-                "Falha ao registrar template (status {}): {:?}",
-                resp.status(),
-                resp.text().ok()
-            ));
+    // 4) se já tem templates -> conferir bloqueio, capturar e comparar
+    // Registra a verificação (online, devolve o id da linha em `biometric_verifications`)
+    let record = |result: &mut BiometricValidationResult| {
+        result.offline = offline;
+        let record = VerificationRecord {
+            success: result.success,
+            outcome: result.outcome,
+            score: result.score,
+            percent: result.percent,
+            quality: result.quality,
+            operation: Some(applied_policy.operation),
+            min_percent: Some(applied_policy.min_percent),
+            policy_rule: applied_policy.rule_id.as_deref(),
+            offline,
+            ..VerificationRecord::new("verify", Some(&user_id), result.reader.as_ref())
+        };
+        match &db {
+            Some(db) => block_on(record_verification(db, &record, result.receipt.as_ref())),
+            None => {
+                queue_offline_verification(cache, &record, result.receipt.as_ref());
+                None
+            }
         }
-
-        return Ok(BiometricValidationResult {
-            success: true,
-            reason: format!("Biometria cadastrada com sucesso! (Qualidade: {}%)", best_quality),
+    };
+    let finish = |mut result: BiometricValidationResult| {
+        record(&mut result);
+        Ok(result)
+    };
+
+    if let Some(lock) = lockout.check(&user_id)? {
+        log::debug!("Verificação bloqueada para {}: {:?}", user_id, lock);
+        return finish(BiometricValidationResult {
+            success: false,
+            outcome: BiometricOutcome::Locked,
+            reason: format!(
+                "Biometria bloqueada desde {} ({} tentativas sem sucesso). Chame o supervisor para liberar.",
+                lock.locked_at, lock.failures
+            ),
             score: None,
             percent: None,
-            quality: Some(best_quality),
-            enrolled: true,
+            quality: None,
+            enrolled: false,
             fingerprint_image: None,
+            reader,
+            enrollment: None,
+            template_refresh: None,
+            policy: Some(applied_policy.clone()),
+            receipt: None,
+            offline: false,
         });
     }
 
-    // 4) se já tem templates -> capturar e comparar
-    log_biometric("Template encontrado. Iniciando captura para validação.");
-    let (live_template, live_quality, live_image) = biometric_sdk::capture_with_sdk().map_err(|e| {
-        log_biometric(&format!("Erro na captura para validação: {}", e));
-        e
-    })?;
+    log::debug!("Template encontrado. Iniciando captura para validação.");
+    let FingerprintCapture {
+        template: live_template,
+        quality: live_quality,
+        image: raw_image,
+    } = match worker.capture(capture_timeout) {
+        Ok(capture) => capture,
+        Err(e) => {
+            if let Some(reason) = AbortReason::from_error(&e) {
+                return Ok(aborted_result(&app, reason));
+            }
+            log::debug!("Erro na captura para validação: {}", e);
+            return Err(e);
+        }
+    };
 
-    log_biometric(&format!("Captura OK. quality={}", live_quality));
+    log::debug!("Captura OK. quality={}", live_quality);
 
-    let mut best_raw = 0;
-    let mut best_percent = 0;
+    let live_image = encode_image(raw_image.as_ref(), &image_options);
+    if let Some(encoded) = &live_image {
+        use tauri::Emitter;
+        let _ = app.emit("biometric-image", encoded);
+    }
 
-    for t in &templates {
-        let (raw, percent) = biometric_sdk::compare_templates_with_sdk(&t.template, &live_template)
-            .map_err(|e| {
-                log_biometric(&format!("Erro no compare_templates_with_sdk: {}", e));
-                e
-            })?;
-        if percent > best_percent {
-            best_percent = percent;
-            best_raw = raw;
-        }
+    // Sensores nunca devolvem o mesmo template duas vezes: repetição é reenvio
+    let replay = lockout.check_replay(&user_id, &live_template).or_else(|| {
+        templates.iter().any(|t| t.template == live_template).then_some(ReplaySuspicion {
+            previous_user_id: None,
+            seconds_ago: None,
+        })
+    });
+    if let Some(suspicion) = replay {
+        log::warn!("🚨 Template repetido na verificação de {}: possível reenvio {:?}", user_id, suspicion);
+        block_on(record_anomaly(
+            db.as_ref(),
+            "replay_suspected",
+            Some(&user_id),
+            None,
+            serde_json::json!(suspicion),
+        ));
+        let mut result = BiometricValidationResult {
+            success: false,
+            outcome: BiometricOutcome::ReplaySuspected,
+            reason: "Leitura suspeita: esta mesma digital já foi enviada antes.".to_string(),
+            score: None,
+            percent: None,
+            quality: Some(live_quality),
+            enrolled: false,
+            fingerprint_image: live_image,
+            reader,
+            enrollment: None,
+            template_refresh: None,
+            policy: Some(applied_policy.clone()),
+            receipt: None,
+            offline: false,
+        };
+        apply_failure(lockout, db.as_ref(), &user_id, &mut result)?;
+        return finish(result);
     }
 
+    let (best_raw, best_percent, best_template) = best_match(worker, &templates, &live_template)?;
+
     if best_percent < min_percent {
-        log_biometric(&format!("Score abaixo do mínimo: {} < {}", best_percent, min_percent));
-        return Ok(BiometricValidationResult {
+        log::debug!("Score abaixo do mínimo: {} < {}", best_percent, min_percent);
+        let mut result = BiometricValidationResult {
             success: false,
+            outcome: BiometricOutcome::Rejected,
             reason: format!(
                 "Score {}% abaixo do mínimo de {}%",
                 best_percent, min_percent
@@ -303,19 +561,339 @@ fn validate_or_enroll_fingerprint_blocking(
             percent: Some(best_percent),
             quality: Some(live_quality),
             enrolled: false,
-            fingerprint_image: Some(live_image.clone()),
-        });
+            fingerprint_image: live_image,
+            reader,
+            enrollment: None,
+            template_refresh: None,
+            policy: Some(applied_policy.clone()),
+            receipt: None,
+            offline: false,
+        };
+        apply_failure(lockout, db.as_ref(), &user_id, &mut result)?;
+        return finish(result);
     }
 
-    log_biometric(&format!("Biometria validada. score={} percent={}", best_raw, best_percent));
-    Ok(BiometricValidationResult {
+    log::debug!("Biometria validada. score={} percent={}", best_raw, best_percent);
+    lockout.register_success(&user_id)?;
+
+    // Comprovante: liga esta verificação ao registro que ela autorizou (`operation_id`)
+    let receipt = match station_key.issue(
+        &user_id,
+        &live_template,
+        operation_id.as_deref(),
+        best_raw,
+        best_percent,
+        &applied_policy,
+        reader.as_ref().map(|r| r.serial_number.as_str()),
+        reader.as_ref().map(|r| r.model.as_str()),
+        raw_image.as_ref(),
+    ) {
+        Ok(receipt) => {
+            // Offline, o comprovante sobe junto com a verificação guardada
+            if let Some(db) = &db {
+                if let Err(e) = block_on(station_key.store(db, &receipt)) {
+                    log::warn!("⚠️ Falha ao gravar comprovante {}: {}", receipt.payload.receipt_id, e);
+                }
+            }
+            log::info!("🧾 Comprovante {} emitido para {}", receipt.payload.receipt_id, user_id);
+            record_event(
+                AuditCategory::Verification,
+                "receipt_issued",
+                Some(&user_id),
+                serde_json::json!({
+                    "receipt_id": receipt.payload.receipt_id,
+                    "operation_id": receipt.payload.operation_id,
+                    "signature": receipt.signature
+                }),
+            );
+            Some(receipt)
+        }
+        Err(e) => {
+            log::warn!("⚠️ Falha ao emitir comprovante biométrico: {}", e);
+            None
+        }
+    };
+
+    let mut result = BiometricValidationResult {
         success: true,
+        outcome: BiometricOutcome::Validated,
         reason: "Biometria validada com sucesso.".into(),
         score: Some(best_raw),
         percent: Some(best_percent),
         quality: Some(live_quality),
         enrolled: false,
-        fingerprint_image: Some(live_image),
-    })
+        fingerprint_image: live_image,
+        reader,
+        enrollment: None,
+        template_refresh: None,
+        policy: Some(applied_policy.clone()),
+        receipt,
+        offline: false,
+    };
+    let verification_id = record(&mut result);
+
+    // Atualização adaptativa, presa à verificação recém-registrada: não pode derrubar uma
+    // validação que já passou (offline não: o cache é só leitura até a próxima sincronização)
+    result.template_refresh = match (best_template.filter(|_| adaptive_refresh), &db, verification_id) {
+        (Some(matched), Some(db), Some(verification_id)) => block_on(refresh_after_verification(
+            db,
+            &verification_id,
+            &user_id,
+            &templates,
+            matched,
+            &live_template,
+            live_quality,
+            best_percent,
+            min_percent,
+        ))
+        .unwrap_or_else(|e| {
+            log::warn!("⚠️ Falha na atualização adaptativa do template: {}", e);
+            None
+        }),
+        _ => None,
+    };
+    Ok(result)
+}
+
+/// Compara a captura com cada template do usuário e devolve o mais parecido:
+/// (score bruto, percentual, template). Sem templates, `(0, 0, None)`.
+pub fn best_match<'t>(
+    worker: &BiometricWorker,
+    templates: &'t [RemoteTemplate],
+    live_template: &str,
+) -> Result<(i32, i32, Option<&'t RemoteTemplate>), BiometricError> {
+    let mut best_raw = 0;
+    let mut best_percent = 0;
+    let mut best_template: Option<&RemoteTemplate> = None;
+
+    for t in templates {
+        let raw = worker.match_templates(&t.template, live_template)
+            .map_err(|e| {
+                log::debug!("Erro no compare_templates_with_sdk: {}", e);
+                e
+            })?;
+        let percent = biometric_sdk::score_to_percent(raw);
+        if percent > best_percent {
+            best_percent = percent;
+            best_raw = raw;
+            best_template = Some(t);
+        }
+    }
+
+    Ok((best_raw, best_percent, best_template))
+}
+
+/// Conta a falha do usuário; ao atingir o limite, troca o resultado por `locked`.
+fn apply_failure(
+    lockout: &LockoutTracker,
+    db: Option<&Supabase>,
+    user_id: &str,
+    result: &mut BiometricValidationResult,
+) -> Result<(), BiometricError> {
+    let (kind, lock) = match lockout.register_failure(user_id, &result.reason)? {
+        FailureOutcome::Counted { remaining } => {
+            result.reason = format!("{} ({} tentativa(s) restante(s))", result.reason, remaining);
+            return Ok(());
+        }
+        FailureOutcome::UserLocked(lock) => ("user_locked", lock),
+        FailureOutcome::StationLocked(lock) => ("station_locked", lock),
+    };
+
+    block_on(record_anomaly(
+        db,
+        kind,
+        Some(user_id),
+        None,
+        serde_json::json!({ "failures": lock.failures, "reason": lock.reason }),
+    ));
+    result.outcome = BiometricOutcome::Locked;
+    result.reason = format!(
+        "{}. Biometria bloqueada após {} tentativas sem sucesso. Chame o supervisor para liberar.",
+        result.reason, lock.failures
+    );
+    Ok(())
+}
+
+/// Linha de `biometric_verifications`: uma por verificação (1:1) ou identificação (1:N).
+#[derive(Serialize)]
+pub struct VerificationRecord<'a> {
+    /// Usuário conferido (1:1) ou identificado (1:N; ausente se ninguém foi reconhecido)
+    pub user_id: Option<&'a str>,
+    /// "verify" ou "identify"
+    pub mode: &'static str,
+    pub success: bool,
+    pub outcome: BiometricOutcome,
+    pub score: Option<i32>,
+    pub percent: Option<i32>,
+    pub quality: Option<i32>,
+    /// Operação autorizada e mínimo exigido pela política
+    pub operation: Option<Operation>,
+    pub min_percent: Option<i32>,
+    pub policy_rule: Option<&'a str>,
+    /// Conferida com o cache local, sem internet
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub offline: bool,
+    pub reader_backend: Option<&'a str>,
+    pub reader_port: Option<&'a str>,
+    pub reader_model: Option<&'a str>,
+    pub reader_serial: Option<&'a str>,
+    pub reader_firmware: Option<&'a str>,
+    pub sdk_version: Option<&'a str>,
+}
+
+impl<'a> VerificationRecord<'a> {
+    pub fn new(mode: &'static str, user_id: Option<&'a str>, reader: Option<&'a ReaderInfo>) -> Self {
+        Self {
+            user_id,
+            mode,
+            success: false,
+            outcome: BiometricOutcome::Rejected,
+            score: None,
+            percent: None,
+            quality: None,
+            operation: None,
+            min_percent: None,
+            policy_rule: None,
+            offline: false,
+            reader_backend: reader.map(|r| r.backend.as_str()),
+            reader_port: reader.and_then(|r| r.port.as_deref()),
+            reader_model: reader.map(|r| r.model.as_str()),
+            reader_serial: reader.map(|r| r.serial_number.as_str()),
+            reader_firmware: reader.map(|r| r.firmware_version.as_str()),
+            sdk_version: reader.map(|r| r.sdk_version.as_str()),
+        }
+    }
+}
+
+/// Registra a verificação em `biometric_verifications` (com o leitor usado) e devolve o id
+/// da linha. Falhas aqui só vão para o log: não invalidam uma biometria já conferida.
+pub async fn record_verification(
+    db: &Supabase,
+    record: &VerificationRecord<'_>,
+    receipt: Option<&VerificationReceipt>,
+) -> Option<String> {
+    let row = serde_json::to_value(record).unwrap_or_default();
+    record_event(AuditCategory::Verification, record.mode, record.user_id, row.clone());
+
+    let inserted = async {
+        let id = new_uuid()?;
+        insert_verification(db, &id, None, &row, receipt).await
+    };
+    match inserted.await {
+        Ok(id) => Some(id),
+        Err(e) => {
+            log::warn!("⚠️ Falha ao registrar verificação: {}", e);
+            None
+        }
+    }
+}
+
+/// Grava uma linha de `biometric_verifications` pela função `biometric_record_verification`
+/// (o app não insere direto). Verificação aceita vai com o comprovante: o banco confere a
+/// assinatura com a chave registrada da estação antes de aceitar.
+///
+/// O `id` é gerado na estação e `occurred_at` é quando a verificação foi feita (ausente, vale
+/// a hora do envio): reenviar a mesma verificação não cria outra linha.
+pub async fn insert_verification(
+    db: &Supabase,
+    id: &str,
+    occurred_at: Option<&str>,
+    record: &serde_json::Value,
+    receipt: Option<&VerificationReceipt>,
+) -> Result<String, BiometricError> {
+    let payload = receipt.map(|r| serde_json::to_string(&r.payload)).transpose()?;
+    db.rpc(
+        "biometric_record_verification",
+        &serde_json::json!({
+            "p_id": id,
+            "p_occurred_at": occurred_at,
+            "p_record": record,
+            "p_receipt_payload": payload,
+            "p_receipt_signature": receipt.map(|r| r.signature.as_str()),
+        }),
+    )
+    .await
 }
 
+/// Verificação feita offline: vai para a auditoria e fica no cache local até a próxima
+/// sincronização enviar a linha e o comprovante.
+fn queue_offline_verification(cache: &TemplateCache, record: &VerificationRecord, receipt: Option<&VerificationReceipt>) {
+    let row = serde_json::to_value(record).unwrap_or_default();
+    record_event(AuditCategory::Verification, record.mode, record.user_id, row.clone());
+    match cache.queue_verification(&row, receipt) {
+        Ok(id) => log::info!("📴 Verificação offline {} guardada para sincronizar ({:?})", id, record.user_id),
+        Err(e) => log::warn!("⚠️ Erro ao guardar verificação offline: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::biometric_reader::fake::{FakeCapture, FakeReader};
+    use crate::biometric_simulator::{fixtures, SimulatedReader};
+
+    fn template(id: &str, template: &str) -> RemoteTemplate {
+        RemoteTemplate {
+            id: id.to_string(),
+            template: template.to_string(),
+            quality: 80,
+            finger: None,
+        }
+    }
+
+    fn simulated_worker(name: &str) -> BiometricWorker {
+        let dir = fixtures::write(
+            name,
+            &[
+                ("01.json", fixtures::finger("joao", "J1", 88)),
+                ("02.json", fixtures::finger("joao", "J2", 75)),
+                ("03.json", fixtures::finger("maria", "M1", 91)),
+            ],
+        );
+        BiometricWorker::spawn(Box::new(SimulatedReader::from_dir(dir).unwrap()), |_| {})
+    }
+
+    #[test]
+    fn simulated_reader_comes_up_and_identifies_itself() {
+        let worker = simulated_worker("service-ready");
+        ensure_reader_ready(&worker).unwrap();
+
+        let info = read_reader_info(&worker).expect("leitor simulado responde");
+        assert_eq!(info.backend, "simulador");
+        assert_eq!(info.serial_number, "SIM-00000000");
+    }
+
+    #[test]
+    fn simulated_capture_matches_only_the_same_finger() {
+        let worker = simulated_worker("service-match");
+        ensure_reader_ready(&worker).unwrap();
+        let live = worker.capture(None).unwrap();
+        assert_eq!(live.quality, 88);
+
+        let templates = [template("maria", "M1"), template("joao", "J2")];
+        let (raw, percent, best) = best_match(&worker, &templates, &live.template).unwrap();
+        assert_eq!(best.map(|t| t.id.as_str()), Some("joao"));
+        assert_eq!((raw, percent), (16000, 80));
+
+        let strangers = [template("maria", "M1")];
+        let (_, percent, _) = best_match(&worker, &strangers, &live.template).unwrap();
+        assert_eq!(percent, biometric_sdk::score_to_percent(1000));
+    }
+
+    #[test]
+    fn best_match_prefers_the_highest_score_with_the_fake_reader() {
+        let worker = BiometricWorker::spawn(Box::new(FakeReader::new(vec![FakeCapture::finger("ana", 90)])), |_| {});
+        let live = worker.capture(None).unwrap();
+
+        let templates = [
+            template("outro", "bruno#9"),
+            template("mesmo-dedo", "ana#7"),
+            template("igual", &live.template),
+        ];
+        let (raw, percent, best) = best_match(&worker, &templates, &live.template).unwrap();
+        assert_eq!(best.map(|t| t.id.as_str()), Some("igual"));
+        assert_eq!((raw, percent), (20000, 100));
+
+        assert!(matches!(best_match(&worker, &[], &live.template).unwrap(), (0, 0, None)));
+    }
+}
@@ -0,0 +1,347 @@
+use base64::Engine;
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::biometric_error::{check_sdk, BiometricError};
+use crate::biometric_image::{FingerprintImage, IDBIO_DPI};
+use crate::biometric_reader::{CancelHandle, FingerprintCapture, FingerprintReader, ReaderInfo, IDBIO_SDK_VERSION};
+use crate::util::base64_engine;
+
+/// Score devolvido quando os dois templates são idênticos
+const SCORE_IDENTICAL: i32 = 20000;
+/// Score devolvido para capturas diferentes do mesmo dedo
+const SCORE_SAME_FINGER: i32 = 16000;
+/// Score devolvido para dedos diferentes (ou templates desconhecidos)
+const SCORE_DIFFERENT_FINGER: i32 = 1000;
+
+/// Uma captura simulada, lida de um arquivo `.json` do diretório de fixtures.
+///
+/// ```json
+/// { "finger": "joao_right_index", "template": "AAAB...", "quality": 82, "image": "", "width": 0, "height": 0, "error": null, "delay_ms": 0 }
+/// ```
+///
+/// - `finger`: rótulo do dedo; templates com o mesmo rótulo "batem" entre si
+/// - `image`: buffer cru `width * height` em tons de cinza, em base64 (opcional)
+/// - `dpi`: resolução da imagem (padrão: a do iDBio)
+/// - `error`: se presente (negativo), a captura falha com esse código do cidbiolib
+/// - `delay_ms`: tempo que a captura fica bloqueada (simula o dedo demorando a ser posto)
+#[derive(Deserialize, Clone, Debug)]
+struct Fixture {
+    finger: String,
+    #[serde(default)]
+    template: String,
+    #[serde(default)]
+    quality: i32,
+    #[serde(default)]
+    image: String,
+    #[serde(default)]
+    width: u32,
+    #[serde(default)]
+    height: u32,
+    #[serde(default = "default_dpi")]
+    dpi: u32,
+    #[serde(default)]
+    error: Option<i32>,
+    #[serde(default)]
+    delay_ms: u64,
+}
+
+fn default_dpi() -> u32 {
+    IDBIO_DPI
+}
+
+/// Leitor simulado e determinístico.
+///
+/// As capturas são servidas na ordem alfabética dos arquivos do diretório,
+/// voltando ao início quando acabam.
+pub struct SimulatedReader {
+    fixtures: Vec<Fixture>,
+    next: usize,
+    port: Option<String>,
+    initialized: bool,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl SimulatedReader {
+    /// Carrega todos os arquivos `.json` de `dir` como fixtures.
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self, BiometricError> {
+        let dir = dir.as_ref();
+        let mut paths: Vec<_> = std::fs::read_dir(dir)
+            .map_err(|e| BiometricError::Config(format!("Erro ao ler diretório de fixtures {:?}: {}", dir, e)))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.extension().map(|ext| ext == "json").unwrap_or(false))
+            .collect();
+        paths.sort();
+
+        let mut fixtures = Vec::with_capacity(paths.len());
+        for path in &paths {
+            let content = std::fs::read_to_string(path)
+                .map_err(|e| BiometricError::Config(format!("Erro ao ler fixture {:?}: {}", path, e)))?;
+            let fixture: Fixture = serde_json::from_str(&content)
+                .map_err(|e| BiometricError::Parse(format!("Fixture inválida {:?}: {}", path, e)))?;
+            fixtures.push(fixture);
+        }
+
+        if fixtures.is_empty() {
+            return Err(BiometricError::Config(format!("Nenhuma fixture .json encontrada em {:?}", dir)));
+        }
+
+        Ok(Self {
+            fixtures,
+            next: 0,
+            port: None,
+            initialized: false,
+            cancelled: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    fn finger_of(&self, template: &str) -> Option<&str> {
+        self.fixtures
+            .iter()
+            .find(|f| f.error.is_none() && f.template == template)
+            .map(|f| f.finger.as_str())
+    }
+}
+
+impl FingerprintReader for SimulatedReader {
+    fn name(&self) -> &'static str {
+        "simulador"
+    }
+
+    fn set_port(&mut self, port: &str) -> Result<(), BiometricError> {
+        self.port = Some(port.to_string());
+        Ok(())
+    }
+
+    fn init(&mut self) -> Result<(), BiometricError> {
+        log::info!("🧪 [SIMULADOR] init (porta {:?})", self.port);
+        self.initialized = true;
+        Ok(())
+    }
+
+    fn terminate(&mut self) {
+        self.initialized = false;
+    }
+
+    fn detect_port(&mut self) -> Option<String> {
+        Some(self.port.clone().unwrap_or_else(|| "SIMULADOR".to_string()))
+    }
+
+    fn capture(&mut self) -> Result<FingerprintCapture, BiometricError> {
+        if !self.initialized {
+            return Err(BiometricError::SdkUnknown);
+        }
+
+        let fixture = self.fixtures[self.next % self.fixtures.len()].clone();
+        self.next += 1;
+
+        log::info!("🧪 [SIMULADOR] captura #{} (dedo {})", self.next, fixture.finger);
+
+        // Espera o "dedo" respeitando cancelamento, como o CIDBIO_CancelCapture
+        self.cancelled.store(false, Ordering::SeqCst);
+        let started = Instant::now();
+        while started.elapsed() < Duration::from_millis(fixture.delay_ms) {
+            if self.cancelled.swap(false, Ordering::SeqCst) {
+                return Err(BiometricError::CaptureCanceled);
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+
+        // Códigos positivos são avisos do SDK e não impedem a captura
+        if let Some(code) = fixture.error {
+            check_sdk(code)?;
+        }
+
+        let image = base64_engine()
+            .decode(&fixture.image)
+            .ok()
+            .and_then(|pixels| FingerprintImage::from_raw(pixels, fixture.width, fixture.height, fixture.dpi));
+        if image.is_none() && !fixture.image.is_empty() {
+            log::warn!("🧪 [SIMULADOR] Imagem da fixture {} não bate com {}x{}", fixture.finger, fixture.width, fixture.height);
+        }
+
+        Ok(FingerprintCapture {
+            template: fixture.template,
+            quality: fixture.quality,
+            image,
+        })
+    }
+
+    fn match_templates(&mut self, stored: &str, live: &str) -> Result<i32, BiometricError> {
+        if stored == live {
+            return Ok(SCORE_IDENTICAL);
+        }
+
+        let score = match (self.finger_of(stored), self.finger_of(live)) {
+            (Some(a), Some(b)) if a == b => SCORE_SAME_FINGER,
+            _ => SCORE_DIFFERENT_FINGER,
+        };
+        Ok(score)
+    }
+
+    fn merge_templates(&mut self, first: &str, second: &str, third: &str) -> Result<String, BiometricError> {
+        // O template "combinado" é o da primeira captura, desde que as três sejam do mesmo dedo
+        let finger = self.finger_of(first);
+        if finger.is_none() || self.finger_of(second) != finger || self.finger_of(third) != finger {
+            return Err(BiometricError::Merging);
+        }
+        Ok(first.to_string())
+    }
+
+    fn device_info(&mut self) -> Result<ReaderInfo, BiometricError> {
+        if !self.initialized {
+            return Err(BiometricError::SdkUnknown);
+        }
+        Ok(ReaderInfo {
+            backend: self.name().to_string(),
+            port: self.port.clone(),
+            model: "iDBio (simulado)".to_string(),
+            serial_number: "SIM-00000000".to_string(),
+            firmware_version: "0.0.0-sim".to_string(),
+            sdk_version: IDBIO_SDK_VERSION.to_string(),
+        })
+    }
+
+    fn cancel_handle(&self) -> CancelHandle {
+        let cancelled = self.cancelled.clone();
+        Arc::new(move || cancelled.store(true, Ordering::SeqCst))
+    }
+}
+
+/// Diretórios de fixtures para os testes que rodam sobre o simulador.
+#[cfg(test)]
+pub mod fixtures {
+    use std::path::PathBuf;
+
+    /// Cria um diretório temporário novo com as fixtures `(arquivo, conteúdo)` e devolve o caminho.
+    pub fn write(name: &str, fixtures: &[(&str, serde_json::Value)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("almox-sim-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, content) in fixtures {
+            std::fs::write(dir.join(file), content.to_string()).unwrap();
+        }
+        dir
+    }
+
+    /// Captura bem-sucedida do dedo `finger` com o template `template`
+    pub fn finger(finger: &str, template: &str, quality: i32) -> serde_json::Value {
+        serde_json::json!({ "finger": finger, "template": template, "quality": quality })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn reader(name: &str, fixtures: &[(&str, serde_json::Value)]) -> SimulatedReader {
+        let mut reader = SimulatedReader::from_dir(fixtures::write(name, fixtures)).unwrap();
+        reader.init().unwrap();
+        reader
+    }
+
+    #[test]
+    fn captures_follow_file_order_and_wrap_around() {
+        let mut reader = reader(
+            "order",
+            &[
+                ("b.json", fixtures::finger("maria", "M1", 70)),
+                ("a.json", fixtures::finger("joao", "J1", 90)),
+                ("notas.txt", json!("ignorado")),
+            ],
+        );
+
+        let templates: Vec<_> = (0..3).map(|_| reader.capture().unwrap().template).collect();
+        assert_eq!(templates, ["J1", "M1", "J1"]);
+    }
+
+    #[test]
+    fn capture_and_device_info_need_init() {
+        let dir = fixtures::write("init", &[("a.json", fixtures::finger("joao", "J1", 90))]);
+        let mut reader = SimulatedReader::from_dir(dir).unwrap();
+        assert_eq!(reader.capture().unwrap_err(), BiometricError::SdkUnknown);
+        assert_eq!(reader.device_info().unwrap_err(), BiometricError::SdkUnknown);
+
+        reader.set_port("COM7").unwrap();
+        reader.init().unwrap();
+        assert_eq!(reader.device_info().unwrap().port.as_deref(), Some("COM7"));
+        assert_eq!(reader.capture().unwrap().quality, 90);
+    }
+
+    #[test]
+    fn empty_or_invalid_fixture_dirs_are_rejected() {
+        let empty = fixtures::write("empty", &[("leia-me.txt", json!("sem fixtures"))]);
+        assert!(matches!(SimulatedReader::from_dir(empty), Err(BiometricError::Config(_))));
+
+        let invalid = fixtures::write("invalid", &[("a.json", json!({ "template": "sem dedo" }))]);
+        assert!(matches!(SimulatedReader::from_dir(invalid), Err(BiometricError::Parse(_))));
+    }
+
+    #[test]
+    fn scores_follow_the_finger_labels() {
+        let mut reader = reader(
+            "scores",
+            &[
+                ("a.json", fixtures::finger("joao", "J1", 90)),
+                ("b.json", fixtures::finger("joao", "J2", 85)),
+                ("c.json", fixtures::finger("maria", "M1", 80)),
+            ],
+        );
+
+        assert_eq!(reader.match_templates("J1", "J1").unwrap(), SCORE_IDENTICAL);
+        assert_eq!(reader.match_templates("J1", "J2").unwrap(), SCORE_SAME_FINGER);
+        assert_eq!(reader.match_templates("J1", "M1").unwrap(), SCORE_DIFFERENT_FINGER);
+        assert_eq!(reader.match_templates("J1", "desconhecido").unwrap(), SCORE_DIFFERENT_FINGER);
+
+        assert_eq!(reader.merge_templates("J1", "J2", "J1").unwrap(), "J1");
+        assert_eq!(reader.merge_templates("J1", "J2", "M1").unwrap_err(), BiometricError::Merging);
+    }
+
+    #[test]
+    fn error_fixtures_fail_with_the_sdk_error_and_warnings_pass() {
+        let mut reader = reader(
+            "errors",
+            &[
+                ("a.json", json!({ "finger": "ninguem", "error": -21 })),
+                ("b.json", json!({ "finger": "joao", "template": "J1", "quality": 60, "error": 1 })),
+            ],
+        );
+
+        assert_eq!(reader.capture().unwrap_err(), BiometricError::NoFingerDetected);
+        assert_eq!(reader.capture().unwrap().template, "J1");
+    }
+
+    #[test]
+    fn fixture_image_keeps_its_size_and_dpi() {
+        let pixels = base64_engine().encode([0u8, 64, 128, 255, 32, 16]);
+        let fits = json!({ "finger": "joao", "template": "J1", "image": pixels, "width": 3, "height": 2, "dpi": 700 });
+        let mismatched = json!({ "finger": "joao", "template": "J2", "image": pixels, "width": 4, "height": 4 });
+        let mut reader = reader("image", &[("a.json", fits), ("b.json", mismatched)]);
+
+        let image = reader.capture().unwrap().image.expect("imagem da fixture");
+        assert_eq!((image.width, image.height, image.dpi), (3, 2, 700));
+        assert_eq!(image.pixels, [0, 64, 128, 255, 32, 16]);
+        // Buffer que não bate com as dimensões: a captura sai sem imagem
+        assert!(reader.capture().unwrap().image.is_none());
+    }
+
+    #[test]
+    fn cancel_interrupts_a_delayed_capture() {
+        let delayed = json!({ "finger": "joao", "template": "J1", "delay_ms": 60_000 });
+        let mut reader = reader("cancel", &[("a.json", delayed)]);
+        let cancel = reader.cancel_handle();
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            cancel();
+        });
+
+        let started = Instant::now();
+        assert_eq!(reader.capture().unwrap_err(), BiometricError::CaptureCanceled);
+        assert!(started.elapsed() < Duration::from_secs(10));
+        canceller.join().unwrap();
+    }
+}
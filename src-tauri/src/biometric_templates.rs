@@ -0,0 +1,295 @@
+use serde::{Deserialize, Serialize};
+
+use crate::audit_log::{record_event, AuditCategory};
+use crate::biometric_error::BiometricError;
+use crate::supabase::Supabase;
+use crate::supabase_auth::SupabaseAuth;
+
+/// Dedo cadastrado quando o frontend não informa
+pub const DEFAULT_FINGER: &str = "right_index";
+
+/// Pontos percentuais acima de `min_percent` para uma verificação atualizar os templates
+pub const REFRESH_MIN_MARGIN: i32 = 15;
+
+/// Máximo de templates ativos por dedo; acima disso, o mais fraco é substituído
+pub const MAX_TEMPLATES_PER_FINGER: usize = 5;
+
+/// Nome do dedo como aparece para o funcionário (mesmos ids do select do cadastro).
+pub fn finger_label(finger: &str) -> &str {
+    match finger {
+        "right_thumb" => "Polegar Direito",
+        "right_index" => "Indicador Direito",
+        "right_middle" => "Médio Direito",
+        "right_ring" => "Anelar Direito",
+        "right_little" => "Mínimo Direito",
+        "left_thumb" => "Polegar Esquerdo",
+        "left_index" => "Indicador Esquerdo",
+        "left_middle" => "Médio Esquerdo",
+        "left_ring" => "Anelar Esquerdo",
+        "left_little" => "Mínimo Esquerdo",
+        other => other,
+    }
+}
+
+/// Template ativo do usuário, usado na verificação 1:1.
+#[derive(Deserialize, Clone, Debug)]
+pub struct RemoteTemplate {
+    pub id: String,
+    pub template: String,
+    pub quality: i32,
+    #[serde(default)]
+    pub finger: Option<String>,
+}
+
+impl RemoteTemplate {
+    fn finger(&self) -> &str {
+        self.finger.as_deref().unwrap_or(DEFAULT_FINGER)
+    }
+}
+
+/// O que a atualização adaptativa fez com os templates do dedo.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RefreshAction {
+    /// A captura entrou como mais uma amostra do dedo
+    Added,
+    /// A captura substituiu o template mais fraco do dedo (limite atingido)
+    Replaced,
+}
+
+/// Atualização de template feita depois de uma verificação com folga.
+#[derive(Serialize, Clone, Debug)]
+pub struct TemplateRefresh {
+    pub action: RefreshAction,
+    pub finger: String,
+    pub template_id: Option<String>,
+    pub replaced_template_id: Option<String>,
+    /// Qualidade da captura que virou template
+    pub quality: i32,
+    /// Qualidade do template que bateu com a captura
+    pub matched_quality: i32,
+    pub percent: i32,
+}
+
+/// Linha de `biometric_templates` sem o template (para listagem).
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct StoredTemplate {
+    pub id: String,
+    #[serde(default)]
+    pub finger: Option<String>,
+    #[serde(default)]
+    pub quality: Option<i32>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+/// Templates ativos de um dedo do usuário.
+#[derive(Serialize, Clone, Debug)]
+pub struct EnrolledFinger {
+    pub finger: String,
+    pub label: String,
+    pub templates: Vec<StoredTemplate>,
+    /// Melhor qualidade entre os templates do dedo
+    pub best_quality: Option<i32>,
+    /// Cadastro mais recente do dedo (RFC 3339)
+    pub last_enrolled_at: Option<String>,
+}
+
+/// Templates ativos do usuário (função `biometric_user_templates`, mais antigos primeiro).
+pub async fn load_user_templates(db: &Supabase, user_id: &str) -> Result<Vec<RemoteTemplate>, BiometricError> {
+    db.rpc("biometric_user_templates", &serde_json::json!({ "p_user_id": user_id }))
+        .await
+}
+
+/// Templates ativos do usuário, agrupados por dedo (na ordem do primeiro cadastro).
+pub async fn load_enrolled_fingers(db: &Supabase, user_id: &str) -> Result<Vec<EnrolledFinger>, BiometricError> {
+    let rows: Vec<StoredTemplate> = db
+        .rpc("biometric_user_templates", &serde_json::json!({ "p_user_id": user_id }))
+        .await?;
+
+    let mut fingers: Vec<EnrolledFinger> = Vec::new();
+    for row in rows {
+        let finger = row.finger.clone().unwrap_or_else(|| DEFAULT_FINGER.to_string());
+        let index = match fingers.iter().position(|f| f.finger == finger) {
+            Some(index) => index,
+            None => {
+                fingers.push(EnrolledFinger {
+                    label: finger_label(&finger).to_string(),
+                    finger,
+                    templates: Vec::new(),
+                    best_quality: None,
+                    last_enrolled_at: None,
+                });
+                fingers.len() - 1
+            }
+        };
+        let entry = &mut fingers[index];
+        entry.best_quality = entry.best_quality.max(row.quality);
+        if row.created_at > entry.last_enrolled_at {
+            entry.last_enrolled_at = row.created_at.clone();
+        }
+        entry.templates.push(row);
+    }
+    Ok(fingers)
+}
+
+/// Desativa (`ativo = false`) templates do usuário: um específico ou todos de um dedo
+/// (função `biometric_deactivate_templates`). Retorna quantos foram desativados.
+/// O histórico continua na tabela.
+pub async fn deactivate_templates(
+    db: &Supabase,
+    user_id: &str,
+    finger: &str,
+    template_id: Option<&str>,
+) -> Result<usize, BiometricError> {
+    // Pelo id: vale também para templates antigos gravados sem `finger`
+    let args = serde_json::json!({
+        "p_user_id": user_id,
+        "p_finger": template_id.is_none().then_some(finger),
+        "p_template_id": template_id
+    });
+    let removed: usize = db.rpc("biometric_deactivate_templates", &args).await?;
+    record_event(
+        AuditCategory::Enrollment,
+        "templates_deactivated",
+        Some(user_id),
+        serde_json::json!({ "finger": finger, "template_id": template_id, "count": removed }),
+    );
+    Ok(removed)
+}
+
+/// Grava templates de um dedo (função `biometric_store_templates`) e devolve os ids criados.
+pub async fn store_templates(
+    db: &Supabase,
+    user_id: &str,
+    finger: &str,
+    templates: &[String],
+    quality: i32,
+) -> Result<Vec<String>, BiometricError> {
+    log::debug!("rpc biometric_store_templates ({} template(s), dedo {})", templates.len(), finger);
+    db.rpc(
+        "biometric_store_templates",
+        &serde_json::json!({
+            "p_user_id": user_id,
+            "p_finger": finger,
+            "p_quality": quality,
+            "p_templates": templates
+        }),
+    )
+    .await
+}
+
+/// Confere no banco (função `biometric_is_supervisor`) se o usuário logado é supervisor.
+pub async fn require_supervisor(db: &Supabase) -> Result<(), BiometricError> {
+    let supervisor: bool = db.rpc("biometric_is_supervisor", &serde_json::json!({})).await?;
+    if !supervisor {
+        return Err(BiometricError::Forbidden("apenas supervisores podem fazer esta operação".to_string()));
+    }
+    Ok(())
+}
+
+/// Comando Tauri: dedos cadastrados do usuário, com qualidade e data de cada template.
+/// Só para supervisores.
+#[tauri::command]
+pub async fn list_enrolled_fingers(
+    auth: tauri::State<'_, SupabaseAuth>,
+    user_id: String,
+) -> Result<Vec<EnrolledFinger>, BiometricError> {
+    let db = auth.connect().await?;
+    require_supervisor(&db).await?;
+    load_enrolled_fingers(&db, &user_id).await
+}
+
+/// Comando Tauri: remove um template ruim (ou todos de um dedo, sem `template_id`)
+/// para que o dedo possa ser cadastrado de novo. Só para supervisores.
+#[tauri::command]
+pub async fn delete_finger_template(
+    auth: tauri::State<'_, SupabaseAuth>,
+    user_id: String,
+    finger: String,
+    template_id: Option<String>,
+) -> Result<usize, BiometricError> {
+    let db = auth.connect().await?;
+    require_supervisor(&db).await?;
+    let removed = deactivate_templates(&db, &user_id, &finger, template_id.as_deref()).await?;
+    log::debug!(
+        "delete_finger_template() user_id={} finger={} template_id={:?} removidos={}",
+        user_id, finger, template_id, removed
+    );
+    log::info!("🗑️ {} template(s) removido(s) do dedo {} ({})", removed, finger_label(&finger), user_id);
+    Ok(removed)
+}
+
+/// Atualização adaptativa: depois de uma verificação bem acima do mínimo, com qualidade
+/// melhor que a do template que bateu, a captura vira mais uma amostra do dedo.
+///
+/// Com `MAX_TEMPLATES_PER_FINGER` atingido, substitui o template mais fraco do dedo
+/// (se a captura for melhor que ele). Cada atualização é registrada em
+/// `biometric_template_refreshes`.
+#[allow(clippy::too_many_arguments)]
+pub async fn refresh_after_verification(
+    db: &Supabase,
+    verification_id: &str,
+    user_id: &str,
+    templates: &[RemoteTemplate],
+    matched: &RemoteTemplate,
+    live_template: &str,
+    live_quality: i32,
+    percent: i32,
+    min_percent: i32,
+) -> Result<Option<TemplateRefresh>, BiometricError> {
+    if percent < min_percent + REFRESH_MIN_MARGIN || live_quality <= matched.quality {
+        return Ok(None);
+    }
+
+    let finger = matched.finger();
+    let same_finger: Vec<&RemoteTemplate> = templates.iter().filter(|t| t.finger() == finger).collect();
+
+    let replaced = if same_finger.len() < MAX_TEMPLATES_PER_FINGER {
+        None
+    } else {
+        match same_finger.iter().min_by_key(|t| t.quality) {
+            Some(weakest) if weakest.quality < live_quality => Some(*weakest),
+            _ => return Ok(None),
+        }
+    };
+
+    // Grava a nova amostra e desativa a antiga numa transação (função
+    // `biometric_refresh_template`, que confere a verificação e registra a atualização)
+    let template_id: Option<String> = db
+        .rpc(
+            "biometric_refresh_template",
+            &serde_json::json!({
+                "p_verification_id": verification_id,
+                "p_finger": finger,
+                "p_quality": live_quality,
+                "p_template": live_template,
+                "p_matched_quality": matched.quality,
+                "p_replaced_template_id": replaced.map(|t| t.id.as_str())
+            }),
+        )
+        .await?;
+
+    let refresh = TemplateRefresh {
+        action: if replaced.is_some() { RefreshAction::Replaced } else { RefreshAction::Added },
+        finger: finger.to_string(),
+        template_id,
+        replaced_template_id: replaced.map(|t| t.id.clone()),
+        quality: live_quality,
+        matched_quality: matched.quality,
+        percent,
+    };
+    log::debug!(
+        "Template atualizado: user_id={} finger={} action={:?} quality={} (antes {}) replaced={:?}",
+        user_id, finger, refresh.action, live_quality, matched.quality, refresh.replaced_template_id
+    );
+    log::info!("🔄 Template do dedo {} atualizado ({:?}, qualidade {})", finger_label(finger), refresh.action, live_quality);
+    record_event(
+        AuditCategory::Enrollment,
+        "template_refreshed",
+        Some(user_id),
+        serde_json::to_value(&refresh).unwrap_or_default(),
+    );
+    Ok(Some(refresh))
+}
+
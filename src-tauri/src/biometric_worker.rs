@@ -0,0 +1,590 @@
+use serde::Serialize;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::biometric_error::BiometricError;
+use crate::biometric_reader::{CancelHandle, FingerprintCapture, FingerprintReader, ReaderInfo};
+
+/// Timeout padrão de captura (sobrescrito por `IDBIO_CAPTURE_TIMEOUT_SECS`)
+const DEFAULT_CAPTURE_TIMEOUT_SECS: u64 = 30;
+
+/// Tempo extra esperando a thread do SDK confirmar um cancelamento
+const CANCEL_GRACE: Duration = Duration::from_secs(5);
+
+/// Intervalo entre tentativas de cancelar uma captura que o SDK ainda não devolveu
+const CANCEL_RETRY: Duration = Duration::from_millis(200);
+
+/// Estado do leitor, mantido pela thread dona do SDK.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "state", content = "detail", rename_all = "snake_case")]
+pub enum ReaderState {
+    /// SDK não inicializado (início, após terminate ou após detecção de porta)
+    Uninitialized,
+    /// SDK inicializado e ocioso
+    Ready,
+    /// Captura em andamento
+    Capturing,
+    /// Falha do leitor; a próxima operação reinicializa o SDK
+    Faulted(BiometricError),
+}
+
+/// Por que uma captura foi interrompida.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AbortReason {
+    /// O operador cancelou (`cancel_biometric_capture`)
+    Cancelled,
+    /// Ninguém colocou o dedo dentro do timeout
+    Timeout,
+}
+
+impl AbortReason {
+    /// Motivo correspondente a um erro de captura, se for uma interrupção
+    pub fn from_error(error: &BiometricError) -> Option<Self> {
+        match error {
+            BiometricError::CaptureCanceled => Some(AbortReason::Cancelled),
+            BiometricError::CaptureTimeout => Some(AbortReason::Timeout),
+            _ => None,
+        }
+    }
+}
+
+impl From<AbortReason> for BiometricError {
+    fn from(reason: AbortReason) -> Self {
+        match reason {
+            AbortReason::Cancelled => BiometricError::CaptureCanceled,
+            AbortReason::Timeout => BiometricError::CaptureTimeout,
+        }
+    }
+}
+
+type Reply<T> = Sender<Result<T, BiometricError>>;
+
+/// Pedidos aceitos pela thread do SDK.
+enum Request {
+    Init { port: Option<String>, force: bool, reply: Reply<()> },
+    DetectPort { reply: Reply<Option<String>> },
+    Capture { timeout: Duration, started: Sender<()>, reply: Reply<FingerprintCapture> },
+    Match { stored: String, live: String, reply: Reply<i32> },
+    Merge { samples: [String; 3], reply: Reply<String> },
+    DeviceInfo { reply: Reply<ReaderInfo> },
+    Shutdown,
+}
+
+/// Capturas pendentes e pedidos de cancelamento, compartilhados entre quem
+/// chama e a thread do SDK.
+#[derive(Default)]
+struct CaptureControl {
+    state: Mutex<ControlState>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct ControlState {
+    /// Capturas enviadas cujo SDK ainda não devolveu (na fila ou em andamento)
+    pending: usize,
+    /// Cancelamento pedido; vale para todas as capturas pendentes
+    abort: Option<AbortReason>,
+    /// A thread do SDK está dentro de `reader.capture()`
+    in_sdk: bool,
+}
+
+impl CaptureControl {
+    fn lock(&self) -> MutexGuard<'_, ControlState> {
+        self.state.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    fn enqueue(&self) {
+        self.lock().pending += 1;
+    }
+
+    /// O SDK devolveu (ou a captura nem chegou nele). Sem outras pendentes,
+    /// um cancelamento que sobrou não pode derrubar a próxima captura.
+    fn finish(&self) {
+        let mut state = self.lock();
+        state.pending = state.pending.saturating_sub(1);
+        if state.pending == 0 {
+            state.abort = None;
+        }
+    }
+
+    /// `Busy` se houver captura na fila ou em andamento
+    fn ensure_idle(&self) -> Result<(), BiometricError> {
+        if self.lock().pending > 0 {
+            return Err(BiometricError::Busy);
+        }
+        Ok(())
+    }
+
+    fn abort_reason(&self) -> Option<AbortReason> {
+        self.lock().abort
+    }
+
+    fn set_in_sdk(&self, in_sdk: bool) {
+        self.lock().in_sdk = in_sdk;
+        self.changed.notify_all();
+    }
+}
+
+/// Vigia a captura em andamento na thread do SDK. No fim do `timeout` (ou num
+/// cancelamento) cancela no SDK, repetindo até ele devolver: um cancelamento que
+/// chega antes de o SDK começar a esperar o dedo se perderia.
+/// Retorna `true` se a captura estourou o timeout.
+fn watch_capture(control: &CaptureControl, cancel: &CancelHandle, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    let mut timed_out = false;
+    let mut state = control.lock();
+
+    while state.in_sdk {
+        if state.abort.is_none() && !timed_out {
+            let now = Instant::now();
+            if now < deadline {
+                state = control.changed.wait_timeout(state, deadline - now).unwrap_or_else(|p| p.into_inner()).0;
+                continue;
+            }
+            log::warn!("⏱️ [SDK] Captura excedeu {:?}; cancelando", timeout);
+            timed_out = true;
+        }
+
+        drop(state);
+        cancel();
+        state = control.lock();
+        if state.in_sdk {
+            state = control.changed.wait_timeout(state, CANCEL_RETRY).unwrap_or_else(|p| p.into_inner()).0;
+        }
+    }
+
+    timed_out
+}
+
+/// Handle para a thread que é a única dona do leitor.
+///
+/// Todas as chamadas à libcidbio passam por aqui, em ordem de chegada.
+/// Operações de diagnóstico (init forçado, detecção de porta) são rejeitadas
+/// enquanto houver captura pendente; capturas e comparações ficam na fila.
+#[derive(Clone)]
+pub struct BiometricWorker {
+    tx: Sender<Request>,
+    state: Arc<Mutex<ReaderState>>,
+    reader_name: &'static str,
+    control: Arc<CaptureControl>,
+    capture_timeout: Duration,
+}
+
+impl BiometricWorker {
+    /// Inicia a thread dona do leitor. `on_state` é chamado a cada mudança de estado.
+    pub fn spawn(
+        reader: Box<dyn FingerprintReader>,
+        on_state: impl Fn(&ReaderState) + Send + 'static,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let state = Arc::new(Mutex::new(ReaderState::Uninitialized));
+        let reader_name = reader.name();
+        let cancel = reader.cancel_handle();
+        let control = Arc::new(CaptureControl::default());
+
+        let capture_timeout = std::env::var("IDBIO_CAPTURE_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs)
+            .unwrap_or(Duration::from_secs(DEFAULT_CAPTURE_TIMEOUT_SECS));
+
+        let thread_state = state.clone();
+        let thread_control = control.clone();
+        std::thread::Builder::new()
+            .name("biometric-sdk".into())
+            .spawn(move || run(reader, rx, thread_state, thread_control, cancel, on_state))
+            .expect("falha ao iniciar thread do SDK biométrico");
+
+        Self {
+            tx,
+            state,
+            reader_name,
+            control,
+            capture_timeout,
+        }
+    }
+
+    /// Nome do backend do leitor (libcidbio ou simulador)
+    pub fn reader_name(&self) -> &'static str {
+        self.reader_name
+    }
+
+    /// Estado atual do leitor
+    pub fn state(&self) -> ReaderState {
+        self.state.lock().unwrap_or_else(|p| p.into_inner()).clone()
+    }
+
+    /// Inicializa o SDK se ainda não estiver pronto.
+    pub fn ensure_ready(&self, port: Option<String>) -> Result<(), BiometricError> {
+        self.call(|reply| Request::Init { port, force: false, reply })
+    }
+
+    /// Termina e inicializa o SDK novamente (rejeitado com captura pendente).
+    pub fn reinitialize(&self, port: Option<String>) -> Result<(), BiometricError> {
+        // Sem esperar na fila atrás de uma captura; a thread do SDK confere de novo
+        self.control.ensure_idle()?;
+        self.call(|reply| Request::Init { port, force: true, reply })
+    }
+
+    /// Procura a porta do leitor (rejeitado com captura pendente).
+    pub fn detect_port(&self) -> Result<Option<String>, BiometricError> {
+        self.control.ensure_idle()?;
+        self.call(|reply| Request::DetectPort { reply })
+    }
+
+    /// Captura uma digital (fica na fila se outra captura estiver em andamento).
+    ///
+    /// O `timeout` (ou o timeout da estação) conta a partir de quando a thread do
+    /// SDK pega a captura, não inclui a fila. Estourado, a captura é cancelada no
+    /// SDK e retorna `BiometricError::CaptureTimeout`.
+    pub fn capture(&self, timeout: Option<Duration>) -> Result<FingerprintCapture, BiometricError> {
+        let timeout = timeout.unwrap_or(self.capture_timeout);
+        let (reply_tx, reply_rx) = mpsc::channel();
+        let (started_tx, started_rx) = mpsc::channel();
+
+        self.control.enqueue();
+        let request = Request::Capture { timeout, started: started_tx, reply: reply_tx };
+        if self.tx.send(request).is_err() {
+            self.control.finish();
+            return Err(BiometricError::WorkerUnavailable);
+        }
+
+        // Sem aviso de início, a captura foi respondida sem chegar ao SDK
+        if started_rx.recv().is_err() {
+            return reply_rx.recv().unwrap_or(Err(BiometricError::WorkerUnavailable));
+        }
+
+        // A thread do SDK cancela sozinha no timeout; isto só cobre um SDK que
+        // não devolve a captura cancelada. Ela continua pendente até ele devolver.
+        match reply_rx.recv_timeout(timeout + CANCEL_GRACE) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => {
+                log::warn!("⚠️ [SDK] Captura cancelada não devolvida em {:?}; resultado será descartado", CANCEL_GRACE);
+                Err(BiometricError::CaptureTimeout)
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(BiometricError::WorkerUnavailable),
+        }
+    }
+
+    /// Cancela a captura em andamento e as que estão na fila.
+    /// Retorna `false` se não havia captura para cancelar.
+    pub fn cancel_capture(&self) -> bool {
+        let mut state = self.control.lock();
+        if state.pending == 0 {
+            return false;
+        }
+        state.abort = Some(AbortReason::Cancelled);
+        drop(state);
+        self.control.changed.notify_all();
+        true
+    }
+
+    /// Compara dois templates e retorna o score bruto.
+    pub fn match_templates(&self, stored: &str, live: &str) -> Result<i32, BiometricError> {
+        self.call(|reply| Request::Match {
+            stored: stored.to_string(),
+            live: live.to_string(),
+            reply,
+        })
+    }
+
+    /// Combina três capturas do mesmo dedo em um único template.
+    pub fn merge_templates(&self, samples: [String; 3]) -> Result<String, BiometricError> {
+        self.call(|reply| Request::Merge { samples, reply })
+    }
+
+    /// Identificação do leitor (consultada uma vez por inicialização).
+    pub fn reader_info(&self) -> Result<ReaderInfo, BiometricError> {
+        self.call(|reply| Request::DeviceInfo { reply })
+    }
+
+    /// Pede para a thread liberar o SDK e encerrar. Não espera a resposta.
+    pub fn shutdown(&self) {
+        let _ = self.tx.send(Request::Shutdown);
+    }
+
+    fn call<T>(&self, make: impl FnOnce(Reply<T>) -> Request) -> Result<T, BiometricError> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        self.tx
+            .send(make(reply_tx))
+            .map_err(|_| BiometricError::WorkerUnavailable)?;
+        reply_rx.recv().map_err(|_| BiometricError::WorkerUnavailable)?
+    }
+}
+
+fn run(
+    mut reader: Box<dyn FingerprintReader>,
+    rx: Receiver<Request>,
+    state: Arc<Mutex<ReaderState>>,
+    control: Arc<CaptureControl>,
+    cancel: CancelHandle,
+    on_state: impl Fn(&ReaderState),
+) {
+    let set_state = |new_state: ReaderState| {
+        let mut current = state.lock().unwrap_or_else(|p| p.into_inner());
+        if *current != new_state {
+            log::info!("🔁 [SDK] Estado do leitor: {:?} -> {:?}", *current, new_state);
+            *current = new_state.clone();
+            drop(current);
+            on_state(&new_state);
+        }
+    };
+    let current_state = || state.lock().unwrap_or_else(|p| p.into_inner()).clone();
+
+    // Garante o SDK inicializado antes de capturar/comparar
+    let ensure_init = |reader: &mut Box<dyn FingerprintReader>| -> Result<(), BiometricError> {
+        if current_state() == ReaderState::Ready {
+            return Ok(());
+        }
+        match reader.init() {
+            Ok(()) => {
+                set_state(ReaderState::Ready);
+                Ok(())
+            }
+            Err(e) => {
+                set_state(ReaderState::Faulted(e.clone()));
+                Err(e)
+            }
+        }
+    };
+
+    // Identificação do leitor atual; some quando a porta ou a inicialização mudam
+    let mut info: Option<ReaderInfo> = None;
+
+    for request in rx {
+        match request {
+            Request::Init { port, force, reply } => {
+                if force {
+                    // Uma captura pode ter entrado na fila depois da checagem de quem chamou
+                    if let Err(e) = control.ensure_idle() {
+                        let _ = reply.send(Err(e));
+                        continue;
+                    }
+                }
+                if port.is_some() || force {
+                    info = None;
+                }
+                let result = (|| {
+                    if let Some(port) = port.as_deref() {
+                        reader.set_port(port)?;
+                    }
+                    if force {
+                        reader.terminate();
+                        set_state(ReaderState::Uninitialized);
+                        std::thread::sleep(std::time::Duration::from_millis(500));
+                    }
+                    ensure_init(&mut reader)
+                })();
+                let _ = reply.send(result);
+            }
+            Request::DetectPort { reply } => {
+                if let Err(e) = control.ensure_idle() {
+                    let _ = reply.send(Err(e));
+                    continue;
+                }
+                // A detecção testa portas com Terminate/Init: o SDK fica não inicializado
+                let port = reader.detect_port();
+                info = None;
+                set_state(ReaderState::Uninitialized);
+                let _ = reply.send(Ok(port));
+            }
+            Request::Capture { timeout, started, reply } => {
+                // Cancelada enquanto ainda estava na fila
+                let result = match control.abort_reason() {
+                    Some(reason) => Err(reason.into()),
+                    None => {
+                        let result = ensure_init(&mut reader).and_then(|_| {
+                            set_state(ReaderState::Capturing);
+                            let _ = started.send(());
+                            capture_watched(&mut reader, &control, &cancel, timeout)
+                        });
+                        match &result {
+                            Err(e) if e.is_device_fault() => set_state(ReaderState::Faulted(e.clone())),
+                            _ => set_state(ReaderState::Ready),
+                        }
+                        result
+                    }
+                };
+                control.finish();
+                let _ = reply.send(result);
+            }
+            Request::Match { stored, live, reply } => {
+                let result =
+                    ensure_init(&mut reader).and_then(|_| reader.match_templates(&stored, &live));
+                let _ = reply.send(result);
+            }
+            Request::Merge { samples, reply } => {
+                let [first, second, third] = &samples;
+                let result = ensure_init(&mut reader)
+                    .and_then(|_| reader.merge_templates(first, second, third));
+                let _ = reply.send(result);
+            }
+            Request::DeviceInfo { reply } => {
+                let result = match &info {
+                    Some(cached) => Ok(cached.clone()),
+                    None => ensure_init(&mut reader).and_then(|_| reader.device_info()),
+                };
+                if let Ok(fresh) = &result {
+                    info = Some(fresh.clone());
+                }
+                let _ = reply.send(result);
+            }
+            Request::Shutdown => {
+                reader.terminate();
+                set_state(ReaderState::Uninitialized);
+                break;
+            }
+        }
+    }
+
+    log::info!("Thread do SDK biométrico encerrada");
+}
+
+/// Captura no SDK com o vigia de timeout/cancelamento rodando ao lado.
+fn capture_watched(
+    reader: &mut Box<dyn FingerprintReader>,
+    control: &Arc<CaptureControl>,
+    cancel: &CancelHandle,
+    timeout: Duration,
+) -> Result<FingerprintCapture, BiometricError> {
+    // Cancelada entre sair da fila e chegar aqui
+    control.set_in_sdk(true);
+    if let Some(reason) = control.abort_reason() {
+        control.set_in_sdk(false);
+        return Err(reason.into());
+    }
+
+    let watchdog = {
+        let control = control.clone();
+        let cancel = cancel.clone();
+        std::thread::spawn(move || watch_capture(&control, &cancel, timeout))
+    };
+    let result = reader.capture();
+    control.set_in_sdk(false);
+    let timed_out = watchdog.join().unwrap_or(false);
+
+    // Um erro depois de um pedido de interrupção é a própria interrupção
+    match (result, control.abort_reason()) {
+        (Err(_), Some(reason)) => Err(reason.into()),
+        (Err(_), None) if timed_out => Err(BiometricError::CaptureTimeout),
+        (result, _) => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::biometric_reader::fake::{FakeCapture, FakeReader};
+
+    fn fake_worker(captures: Vec<FakeCapture>) -> BiometricWorker {
+        BiometricWorker::spawn(Box::new(FakeReader::new(captures)), |_| {})
+    }
+
+    #[test]
+    fn capture_initializes_the_reader_and_returns_the_sample() {
+        let worker = fake_worker(vec![FakeCapture::finger("joao", 77)]);
+        assert_eq!(worker.state(), ReaderState::Uninitialized);
+
+        let capture = worker.capture(None).unwrap();
+        assert_eq!(capture.quality, 77);
+        assert_eq!(worker.state(), ReaderState::Ready);
+    }
+
+    #[test]
+    fn capture_errors_reach_the_caller() {
+        let worker = fake_worker(vec![FakeCapture::error(BiometricError::NoFingerDetected)]);
+        assert_eq!(worker.capture(None).unwrap_err(), BiometricError::NoFingerDetected);
+    }
+
+    #[test]
+    fn nobody_at_the_reader_times_out() {
+        let worker = fake_worker(vec![FakeCapture::hang()]);
+        let result = worker.capture(Some(Duration::from_millis(100)));
+        assert_eq!(result.unwrap_err(), BiometricError::CaptureTimeout);
+        assert_eq!(worker.state(), ReaderState::Ready);
+    }
+
+    /// Dispara `capture` em outra thread e espera ela entrar na contagem de pendentes
+    fn capture_in_background(
+        worker: &BiometricWorker,
+        timeout: Duration,
+    ) -> std::thread::JoinHandle<Result<FingerprintCapture, BiometricError>> {
+        let before = worker.control.lock().pending;
+        let background = worker.clone();
+        let handle = std::thread::spawn(move || background.capture(Some(timeout)));
+        while worker.control.lock().pending == before {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        handle
+    }
+
+    #[test]
+    fn timeout_does_not_count_time_waiting_in_the_queue() {
+        let slow = Duration::from_millis(400);
+        let worker = fake_worker(vec![
+            FakeCapture::finger("joao", 80).delayed(slow),
+            FakeCapture::finger("maria", 80).delayed(slow),
+        ]);
+
+        let first = capture_in_background(&worker, Duration::from_secs(5));
+        // 400 ms na fila + 400 ms no leitor: só passa se a fila não contar
+        let second = worker.capture(Some(Duration::from_millis(600)));
+
+        assert!(first.join().unwrap().is_ok());
+        assert!(second.unwrap().template.starts_with("maria"));
+    }
+
+    #[test]
+    fn cancel_reaches_queued_captures_and_then_clears() {
+        let worker = fake_worker(vec![
+            FakeCapture::hang(),
+            FakeCapture::finger("fila", 80),
+            FakeCapture::finger("joao", 80),
+        ]);
+
+        let running = capture_in_background(&worker, Duration::from_secs(10));
+        let queued = capture_in_background(&worker, Duration::from_secs(10));
+        assert!(worker.cancel_capture());
+
+        assert_eq!(running.join().unwrap().unwrap_err(), BiometricError::CaptureCanceled);
+        assert_eq!(queued.join().unwrap().unwrap_err(), BiometricError::CaptureCanceled);
+        assert!(!worker.cancel_capture());
+
+        // O roteiro anda só quando o SDK captura: a da fila nem chegou nele
+        let next = worker.capture(Some(Duration::from_secs(5))).unwrap();
+        assert!(next.template.starts_with("fila"));
+    }
+
+    #[test]
+    fn cancel_right_after_the_request_is_not_lost() {
+        let worker = fake_worker(vec![FakeCapture::hang(), FakeCapture::hang()]);
+
+        // Cancela em pontos diferentes do caminho até o SDK (fila, init, captura)
+        for _ in 0..2 {
+            let capture = {
+                let worker = worker.clone();
+                std::thread::spawn(move || worker.capture(Some(Duration::from_secs(30))))
+            };
+            while !worker.cancel_capture() {
+                std::thread::yield_now();
+            }
+            assert_eq!(capture.join().unwrap().unwrap_err(), BiometricError::CaptureCanceled);
+        }
+    }
+
+    #[test]
+    fn diagnostics_are_rejected_while_a_capture_is_pending() {
+        let worker = fake_worker(vec![FakeCapture::hang()]);
+        let capture = capture_in_background(&worker, Duration::from_secs(10));
+
+        assert_eq!(worker.detect_port().unwrap_err(), BiometricError::Busy);
+        assert_eq!(worker.reinitialize(None).unwrap_err(), BiometricError::Busy);
+
+        worker.cancel_capture();
+        assert_eq!(capture.join().unwrap().unwrap_err(), BiometricError::CaptureCanceled);
+        assert_eq!(worker.detect_port().unwrap(), Some("FAKE".to_string()));
+    }
+}
@@ -0,0 +1,342 @@
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::Emitter;
+use tokio::sync::Notify;
+
+use crate::biometric_error::BiometricError;
+use crate::biometric_receipt::StationKey;
+use crate::reference_cache::ReferenceCache;
+use crate::supabase::http;
+use crate::supabase_auth::SupabaseAuth;
+use crate::sync_queue::SyncQueue;
+use crate::template_cache::{TemplateCache, TEMPLATE_SYNC_INTERVAL};
+
+/// Intervalo entre verificações com a conexão boa
+const PROBE_INTERVAL: Duration = Duration::from_secs(30);
+/// Intervalo entre verificações com a conexão ruim ou ausente (volta mais rápido)
+const RETRY_PROBE_INTERVAL: Duration = Duration::from_secs(10);
+/// Limite de cada verificação
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Acima disso a resposta conta como lenta (degradada)
+const SLOW_PROBE: Duration = Duration::from_secs(2);
+/// Falhas seguidas até considerar offline (uma só deixa degradado)
+const OFFLINE_AFTER_FAILURES: u32 = 2;
+/// Atualização periódica dos dados de referência
+const REFERENCE_REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Estado da conexão com o Supabase.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectivityState {
+    /// Supabase respondendo normalmente
+    Online,
+    /// Supabase responde, mas lento ou com erro; ou uma falha isolada
+    Degraded,
+    /// Sem resposta do Supabase
+    Offline,
+}
+
+/// Situação da conexão (evento `connectivity-changed` e comando `get_connectivity_status`).
+#[derive(Serialize, Clone, Debug)]
+pub struct ConnectivityStatus {
+    pub state: ConnectivityState,
+    /// Tempo de resposta da última verificação que teve resposta
+    pub latency_ms: Option<u64>,
+    /// Última verificação (RFC 3339); `None` antes da primeira
+    pub checked_at: Option<String>,
+    /// Desde quando está neste estado (RFC 3339)
+    pub since: String,
+    /// Motivo de estar degradado ou offline
+    pub detail: Option<String>,
+}
+
+struct Inner {
+    status: ConnectivityStatus,
+    failures: u32,
+    /// Pedido de sincronização completa (`sync_now` ou conexão recuperada)
+    sync_requested: bool,
+}
+
+/// Resultado de uma verificação.
+enum Probe {
+    Ok(Duration),
+    /// Respondeu, mas não como o Supabase (erro do servidor, portal cativo, proxy)
+    BadResponse(Duration, String),
+    /// Não respondeu (sem rede, DNS, timeout, TLS)
+    Unreachable(String),
+}
+
+/// Monitor da conexão com o Supabase (gerenciado pelo Tauri).
+///
+/// Não confia em `navigator.onLine`, que continua `true` em rede sem saída ou com portal
+/// cativo: consulta o endpoint de saúde do próprio projeto (`/auth/v1/health`). Cada
+/// mudança de estado vai para o frontend em `connectivity-changed`.
+#[derive(Clone)]
+pub struct ConnectivityMonitor {
+    inner: Arc<Mutex<Inner>>,
+    wake: Arc<Notify>,
+}
+
+impl Default for ConnectivityMonitor {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                // Otimista até a primeira verificação, para a tela não abrir em modo offline
+                status: ConnectivityStatus {
+                    state: ConnectivityState::Online,
+                    latency_ms: None,
+                    checked_at: None,
+                    since: chrono::Utc::now().to_rfc3339(),
+                    detail: None,
+                },
+                failures: 0,
+                sync_requested: true,
+            })),
+            wake: Arc::new(Notify::new()),
+        }
+    }
+}
+
+async fn probe(auth: &SupabaseAuth) -> Probe {
+    let (url, anon_key) = match auth.project() {
+        Ok(project) => project,
+        Err(e) => return Probe::Unreachable(e.to_string()),
+    };
+    let started = Instant::now();
+    let response = http()
+        .get(format!("{}/auth/v1/health", url))
+        .header("apikey", anon_key)
+        .timeout(PROBE_TIMEOUT)
+        .send()
+        .await;
+    let elapsed = started.elapsed();
+
+    let response = match response {
+        Ok(response) => response,
+        Err(e) => return Probe::Unreachable(BiometricError::from(e).to_string()),
+    };
+    let status = response.status();
+    if !status.is_success() {
+        return Probe::BadResponse(elapsed, format!("Supabase respondeu HTTP {}", status.as_u16()));
+    }
+    // Portal cativo ou proxy costumam responder 200 com HTML
+    match response.json::<serde_json::Value>().await {
+        Ok(body) if body.is_object() => Probe::Ok(elapsed),
+        _ => Probe::BadResponse(elapsed, "Resposta inesperada (portal cativo ou proxy?)".to_string()),
+    }
+}
+
+impl ConnectivityMonitor {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    pub fn status(&self) -> ConnectivityStatus {
+        self.lock().status.clone()
+    }
+
+    /// Pede uma verificação e uma sincronização completa agora.
+    pub fn request_sync(&self) {
+        self.lock().sync_requested = true;
+        self.wake.notify_one();
+    }
+
+    fn take_sync_request(&self) -> bool {
+        std::mem::take(&mut self.lock().sync_requested)
+    }
+
+    /// Aplica o resultado da verificação; devolve o novo status se o estado mudou.
+    fn apply(&self, probe: Probe) -> Option<ConnectivityStatus> {
+        let mut inner = self.lock();
+        let (state, latency, detail) = match probe {
+            Probe::Ok(latency) => {
+                inner.failures = 0;
+                if latency > SLOW_PROBE {
+                    let detail = format!("Supabase lento ({} ms)", latency.as_millis());
+                    (ConnectivityState::Degraded, Some(latency), Some(detail))
+                } else {
+                    (ConnectivityState::Online, Some(latency), None)
+                }
+            }
+            Probe::BadResponse(latency, detail) => {
+                inner.failures = 0;
+                (ConnectivityState::Degraded, Some(latency), Some(detail))
+            }
+            Probe::Unreachable(detail) => {
+                inner.failures += 1;
+                let state = if inner.failures >= OFFLINE_AFTER_FAILURES
+                    || inner.status.state == ConnectivityState::Offline
+                {
+                    ConnectivityState::Offline
+                } else {
+                    ConnectivityState::Degraded
+                };
+                (state, None, Some(detail))
+            }
+        };
+
+        let now = chrono::Utc::now().to_rfc3339();
+        let changed = state != inner.status.state;
+        let status = &mut inner.status;
+        if changed {
+            status.since = now.clone();
+        }
+        status.state = state;
+        status.latency_ms = latency.map(|l| l.as_millis() as u64).or(status.latency_ms);
+        status.checked_at = Some(now);
+        status.detail = detail;
+        changed.then(|| status.clone())
+    }
+}
+
+/// Serviço em segundo plano da estação: verifica a conexão e, quando ela volta ou na hora
+/// marcada, manda a fila de sincronização rodar e atualiza os dados de referência e o cache
+/// de templates. Roda no Tokio, independente de janela em foco.
+pub async fn run_background_service(
+    app: tauri::AppHandle,
+    monitor: ConnectivityMonitor,
+    auth: SupabaseAuth,
+    queue: SyncQueue,
+    references: ReferenceCache,
+    templates: TemplateCache,
+    station_key: StationKey,
+) {
+    let mut references_at: Option<Instant> = None;
+    let mut templates_at: Option<Instant> = None;
+
+    loop {
+        let result = probe(&auth).await;
+        if let Some(status) = monitor.apply(result) {
+            match status.state {
+                ConnectivityState::Online => log::info!("🌐 Conexão com o Supabase: online"),
+                ConnectivityState::Degraded => {
+                    log::warn!("⚠️ Conexão com o Supabase degradada: {}", status.detail.as_deref().unwrap_or("-"))
+                }
+                ConnectivityState::Offline => {
+                    log::warn!("📴 Conexão com o Supabase perdida: {}", status.detail.as_deref().unwrap_or("-"))
+                }
+            }
+            if status.state != ConnectivityState::Offline {
+                monitor.request_sync();
+            }
+            let _ = app.emit("connectivity-changed", &status);
+        }
+
+        let state = monitor.status().state;
+        if monitor.take_sync_request() {
+            references_at = None;
+            templates_at = None;
+            queue.wake();
+        }
+        let due = |at: Option<Instant>, every: Duration| at.is_none_or(|at| at.elapsed() >= every);
+
+        if state != ConnectivityState::Offline && auth.user_id().is_some() {
+            if due(references_at, REFERENCE_REFRESH_INTERVAL) {
+                match references.refresh(&auth).await {
+                    Ok(names) => {
+                        references_at = Some(Instant::now());
+                        let _ = app.emit("reference-cache-updated", &names);
+                    }
+                    Err(e) => log::debug!("Atualização dos dados de referência adiada: {}", e),
+                }
+            }
+            if due(templates_at, TEMPLATE_SYNC_INTERVAL) {
+                match templates.sync(&auth, &station_key).await {
+                    Ok(_) => templates_at = Some(Instant::now()),
+                    Err(e) if e.is_offline() => log::debug!("Sincronização do cache adiada (sem conexão): {}", e),
+                    Err(e) => {
+                        // Erro do servidor: não insiste a cada verificação
+                        templates_at = Some(Instant::now());
+                        log::warn!("⚠️ Falha na sincronização do cache de templates: {}", e);
+                    }
+                }
+            }
+        }
+
+        let interval = if state == ConnectivityState::Online { PROBE_INTERVAL } else { RETRY_PROBE_INTERVAL };
+        let _ = tokio::time::timeout(interval, monitor.wake.notified()).await;
+    }
+}
+
+/// Comando Tauri: situação atual da conexão com o Supabase.
+#[tauri::command]
+pub fn get_connectivity_status(monitor: tauri::State<'_, ConnectivityMonitor>) -> ConnectivityStatus {
+    monitor.status()
+}
+
+/// Comando Tauri: verifica a conexão e sincroniza tudo agora (fila, referências, templates).
+#[tauri::command]
+pub fn sync_now(monitor: tauri::State<'_, ConnectivityMonitor>) {
+    monitor.request_sync();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unreachable() -> Probe {
+        Probe::Unreachable("sem rede".to_string())
+    }
+
+    #[test]
+    fn single_failure_degrades_and_second_goes_offline() {
+        let monitor = ConnectivityMonitor::default();
+
+        let status = monitor.apply(unreachable()).expect("mudou para degradado");
+        assert_eq!(status.state, ConnectivityState::Degraded);
+        assert_eq!(status.detail.as_deref(), Some("sem rede"));
+
+        let status = monitor.apply(unreachable()).expect("mudou para offline");
+        assert_eq!(status.state, ConnectivityState::Offline);
+        assert!(monitor.apply(unreachable()).is_none());
+    }
+
+    #[test]
+    fn recovery_returns_online_and_resets_failures() {
+        let monitor = ConnectivityMonitor::default();
+        monitor.apply(unreachable());
+        monitor.apply(unreachable());
+
+        let status = monitor.apply(Probe::Ok(Duration::from_millis(120))).expect("voltou");
+        assert_eq!(status.state, ConnectivityState::Online);
+        assert_eq!(status.latency_ms, Some(120));
+        assert!(status.detail.is_none());
+
+        // Contagem zerada: uma nova falha isolada só degrada
+        let status = monitor.apply(unreachable()).expect("mudou para degradado");
+        assert_eq!(status.state, ConnectivityState::Degraded);
+    }
+
+    #[test]
+    fn slow_or_bad_response_degrades_without_counting_failures() {
+        let monitor = ConnectivityMonitor::default();
+
+        let status = monitor.apply(Probe::Ok(SLOW_PROBE + Duration::from_millis(1))).expect("lento");
+        assert_eq!(status.state, ConnectivityState::Degraded);
+
+        monitor.apply(unreachable());
+        let bad = Probe::BadResponse(Duration::from_millis(50), "HTTP 502".to_string());
+        assert!(monitor.apply(bad).is_none());
+        assert_eq!(monitor.status().detail.as_deref(), Some("HTTP 502"));
+
+        // A falha anterior foi zerada pela resposta: continua degradado, não offline
+        assert!(monitor.apply(unreachable()).is_none());
+        assert_eq!(monitor.status().state, ConnectivityState::Degraded);
+    }
+
+    #[test]
+    fn unchanged_state_keeps_since_and_last_latency() {
+        let monitor = ConnectivityMonitor::default();
+        let since = monitor.status().since;
+
+        assert!(monitor.apply(Probe::Ok(Duration::from_millis(80))).is_none());
+        let status = monitor.status();
+        assert_eq!(status.since, since);
+        assert_eq!(status.latency_ms, Some(80));
+        assert!(status.checked_at.is_some());
+
+        monitor.apply(unreachable());
+        assert_eq!(monitor.status().latency_ms, Some(80));
+    }
+}
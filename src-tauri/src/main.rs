@@ -1,15 +1,51 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod app_log;
+mod audit_log;
+mod biometric_enroll;
+mod biometric_error;
+mod biometric_identify;
+mod biometric_image;
+mod biometric_lockout;
+mod biometric_reader;
+mod biometric_receipt;
 mod biometric_sdk;
 mod biometric_service;
+mod biometric_simulator;
+mod biometric_templates;
+mod biometric_worker;
+mod connectivity;
+mod realtime;
+mod reference_cache;
 mod sdk_manager;
+mod secret_store;
+mod station_config;
+mod supabase;
+mod supabase_auth;
+mod sync_queue;
+mod template_cache;
+mod util;
+mod verification_policy;
 mod cleanup;
 
-use tauri::{AppHandle, Manager};
+use biometric_error::BiometricError;
+use tauri::{AppHandle, Emitter, Manager};
 
+/// Apaga a pasta de dados (antes de desinstalar). Recusa enquanto houver operações
+/// offline não sincronizadas, a menos que `force` seja passado.
 #[tauri::command]
-fn cleanup_app_data() -> Result<(), String> {
+fn cleanup_app_data(queue: tauri::State<'_, sync_queue::SyncQueue>, force: Option<bool>) -> Result<(), String> {
+    if !force.unwrap_or(false) {
+        let snapshot = queue.snapshot(false).map_err(|e| e.to_string())?;
+        let unsynced = snapshot.pending + snapshot.failed;
+        if unsynced > 0 {
+            return Err(format!(
+                "{} operação(ões) offline ainda não sincronizada(s). Sincronize antes ou use force para apagar mesmo assim.",
+                unsynced
+            ));
+        }
+    }
     cleanup::cleanup_app_data()
 }
 
@@ -50,13 +86,13 @@ async fn check_updates_manual(app: tauri::AppHandle) -> Result<String, String> {
 
 /// Comando para executar o instalador do driver do leitor biométrico
 #[tauri::command]
-fn install_biometric_driver(app: AppHandle) -> Result<(), String> {
+fn install_biometric_driver(app: AppHandle) -> Result<(), BiometricError> {
     use tauri::Manager;
     
     let resource_dir = app
         .path()
         .resource_dir()
-        .map_err(|e| format!("Erro ao obter resource_dir: {e}"))?;
+        .map_err(|e| BiometricError::Io(format!("Erro ao obter resource_dir: {e}")))?;
 
     // O Tauri mantém a estrutura de pastas, então o arquivo está em resources/controlidbio.inf
     let inf_path = resource_dir.join("resources").join("controlidbio.inf");
@@ -91,10 +127,10 @@ fn install_biometric_driver(app: AppHandle) -> Result<(), String> {
             }
         }
         
-        return Err(format!(
+        return Err(BiometricError::Io(format!(
             "Arquivo INF do driver não encontrado. Tentou:\n- {:?}\n- src-tauri/resources/controlidbio.inf\nVerifique se o arquivo existe.",
             inf_path
-        ));
+        )));
     }
 
     install_driver_with_pnputil(&inf_path)?;
@@ -103,7 +139,7 @@ fn install_biometric_driver(app: AppHandle) -> Result<(), String> {
 
 /// Instala o driver usando pnputil com elevação de privilégios (Windows)
 #[cfg(windows)]
-fn install_driver_with_pnputil(inf_path: &std::path::Path) -> Result<(), String> {
+fn install_driver_with_pnputil(inf_path: &std::path::Path) -> Result<(), BiometricError> {
     let inf_str = inf_path.to_string_lossy().to_string();
     
     log::info!("Instalando driver com pnputil: {}", inf_str);
@@ -118,7 +154,7 @@ fn install_driver_with_pnputil(inf_path: &std::path::Path) -> Result<(), String>
     );
     
     std::fs::write(&script_path, script_content)
-        .map_err(|e| format!("Erro ao criar script de instalação: {}", e))?;
+        .map_err(|e| BiometricError::Io(format!("Erro ao criar script de instalação: {}", e)))?;
     
     log::info!("Script criado em: {:?}", script_path);
     
@@ -130,9 +166,9 @@ fn install_driver_with_pnputil(inf_path: &std::path::Path) -> Result<(), String>
     );
     
     std::process::Command::new("powershell")
-        .args(&["-ExecutionPolicy", "Bypass", "-WindowStyle", "Hidden", "-Command", &ps_command])
+        .args(["-ExecutionPolicy", "Bypass", "-WindowStyle", "Hidden", "-Command", &ps_command])
         .spawn()
-        .map_err(|e| format!("Erro ao executar instalador do driver: {}", e))?;
+        .map_err(|e| BiometricError::Io(format!("Erro ao executar instalador do driver: {}", e)))?;
 
     log::info!("Instalador do driver iniciado. Aguarde o prompt UAC e a janela de instalação.");
     Ok(())
@@ -140,48 +176,34 @@ fn install_driver_with_pnputil(inf_path: &std::path::Path) -> Result<(), String>
 
 /// Instala o driver (não-Windows - fallback)
 #[cfg(not(windows))]
-fn install_driver_with_pnputil(inf_path: &std::path::Path) -> Result<(), String> {
-    Err("Instalação de driver não suportada nesta plataforma".to_string())
+fn install_driver_with_pnputil(_inf_path: &std::path::Path) -> Result<(), BiometricError> {
+    Err(BiometricError::Config("Instalação de driver não suportada nesta plataforma".to_string()))
+}
+
+/// Libera o leitor biométrico (chamado no fechamento da janela principal e na saída)
+fn terminate_reader(app: &AppHandle) {
+    if let Some(worker) = app.try_state::<biometric_worker::BiometricWorker>() {
+        worker.shutdown();
+    }
 }
 
 fn main() {
-    // Load .env file (try multiple paths)
-    let current_dir = std::env::current_dir().unwrap_or_default();
-    println!("CWD: {:?}", current_dir);
-    
-    if dotenv::dotenv().is_err() {
-        println!("dotenv() failed to find .env in CWD or parents.");
-        
-        // Try explicitly in the parent dir (project root) if we are in src-tauri
-        let parent_env = std::path::Path::new("..").join(".env");
-        if parent_env.exists() {
-             println!("Found .env in parent: {:?}", parent_env);
-             if let Err(e) = dotenv::from_path(&parent_env) {
-                 println!("Failed to load ../.env: {}", e);
-             } else {
-                 println!("Loaded ../.env successfully.");
-             }
-        } else {
-            // Try absolute path based on CWD if needed, or check normal .env
-            let root_env = std::path::Path::new(".env");
-            if root_env.exists() {
-                 println!("Found .env in CWD (manual check).");
-            } else {
-                 println!(".env NOT found in CWD or parent.");
-            }
-        }
-    } else {
-        println!("dotenv loaded successfully.");
+    // Pasta de dados (com acesso restrito) antes de tudo que grava nela
+    if let Err(e) = station_config::prepare_app_data_dir() {
+        eprintln!("❌ {}", e);
+        std::process::exit(1);
     }
-    
-    // Validar se SUPABASE_URL carregou
-    match std::env::var("SUPABASE_URL") {
-        Ok(val) => println!("SUPABASE_URL loaded: {}...", &val.chars().take(10).collect::<String>()),
-        Err(_) => println!("SUPABASE_URL NOT FOUND in env vars after dotenv check."),
+
+    // Logger primeiro: tudo daqui em diante vai para `logs` na pasta de dados
+    app_log::init();
+
+    // Recriar o cofre de segredos só a pedido (ele guarda a chave da estação)
+    if std::env::args().any(|arg| arg == secret_store::RESET_ARG) {
+        if let Err(e) = secret_store::SecretStore::reset() {
+            log::error!("❌ Não foi possível descartar o cofre de segredos: {}", e);
+        }
     }
 
-    env_logger::init();
-    
     #[cfg(feature = "biometric")]
     log::info!("BIOMETRIC FEATURE: ENABLED ✅");
     #[cfg(not(feature = "biometric"))]
@@ -194,6 +216,36 @@ fn main() {
         .plugin(tauri_plugin_http::init())
         .invoke_handler(tauri::generate_handler![
             biometric_service::validate_or_enroll_fingerprint,
+            biometric_service::cancel_biometric_capture,
+            biometric_identify::identify_fingerprint,
+            biometric_enroll::enroll_fingers,
+            biometric_templates::list_enrolled_fingers,
+            biometric_templates::delete_finger_template,
+            biometric_lockout::get_biometric_lockouts,
+            biometric_lockout::override_biometric_lockout,
+            biometric_receipt::verify_receipt,
+            biometric_receipt::verify_receipt_offline,
+            biometric_receipt::get_station_public_key,
+            biometric_receipt::register_station_key,
+            audit_log::verify_audit_log,
+            audit_log::export_audit_log,
+            audit_log::record_audit_event,
+            app_log::get_recent_logs,
+            supabase_auth::set_supabase_session,
+            supabase_auth::clear_supabase_session,
+            template_cache::sync_template_cache,
+            template_cache::get_template_cache_status,
+            sync_queue::enqueue_operation,
+            sync_queue::get_sync_queue,
+            sync_queue::retry_failed_operations,
+            sync_queue::discard_failed_operations,
+            sync_queue::resolve_sync_conflict,
+            reference_cache::get_reference_cache,
+            connectivity::get_connectivity_status,
+            connectivity::sync_now,
+            realtime::get_realtime_status,
+            station_config::get_station_base,
+            station_config::set_station_base,
             install_biometric_driver,
             cleanup_app_data,
             check_updates_manual,
@@ -202,9 +254,61 @@ fn main() {
             biometric_sdk::initialize_biometric_sdk,
             biometric_sdk::reinitialize_biometric_sdk,
             biometric_sdk::test_biometric_connection,
-            biometric_sdk::list_com_ports
+            biometric_sdk::list_com_ports,
+            biometric_sdk::get_biometric_state,
+            biometric_sdk::get_reader_info
         ])
         .setup(|app| {
+            app.manage(biometric_identify::CandidateCache::default());
+            app.manage(verification_policy::PolicyCache::default());
+            app.manage(biometric_lockout::LockoutTracker::open()?);
+            // O cofre guarda a chave que assina a auditoria: o que ele registrar ao abrir (ou ao
+            // ser recriado acima) fica em memória até `audit_log::init`, ou sai sem assinatura
+            // se a chave não puder ser lida
+            let secrets = secret_store::SecretStore::open().inspect_err(|_| audit_log::flush_pending())?;
+            let station_key = biometric_receipt::StationKey::load_or_create(&secrets)
+                .inspect_err(|_| audit_log::flush_pending())?;
+            audit_log::init(&station_key);
+            let auth = supabase_auth::SupabaseAuth::new(app.handle().clone(), secrets.clone());
+            let template_cache = template_cache::TemplateCache::open(&secrets)?;
+            let sync_queue = sync_queue::SyncQueue::open()?;
+            tauri::async_runtime::spawn(sync_queue::run_sync_worker(sync_queue.clone(), auth.clone()));
+            let reference_cache = reference_cache::ReferenceCache::open(&secrets)?;
+            let connectivity = connectivity::ConnectivityMonitor::default();
+            tauri::async_runtime::spawn(connectivity::run_background_service(
+                app.handle().clone(),
+                connectivity.clone(),
+                auth.clone(),
+                sync_queue.clone(),
+                reference_cache.clone(),
+                template_cache.clone(),
+                station_key.clone(),
+            ));
+            let realtime = realtime::RealtimeClient::default();
+            tauri::async_runtime::spawn(realtime::run_realtime(
+                app.handle().clone(),
+                realtime.clone(),
+                auth.clone(),
+                connectivity.clone(),
+            ));
+            app.manage(station_key);
+            app.manage(auth);
+            app.manage(template_cache);
+            app.manage(sync_queue);
+            app.manage(reference_cache);
+            app.manage(connectivity);
+            app.manage(realtime);
+            app.manage(secrets);
+
+            // Thread única dona do SDK biométrico; mudanças de estado vão para o frontend
+            let state_handle = app.handle().clone();
+            app.manage(biometric_worker::BiometricWorker::spawn(
+                biometric_reader::create_reader(),
+                move |state| {
+                    let _ = state_handle.emit("biometric-state", state);
+                },
+            ));
+
             // Adicionar logs para o updater
             log::info!("🔄 [UPDATER] Inicializando sistema de atualização");
             
@@ -241,11 +345,11 @@ fn main() {
             });
             
             // Copiar DLL para a raiz na primeira execução
-            let status = sdk_manager::get_sdk_status(Some(&app.handle()));
+            let status = sdk_manager::get_sdk_status(Some(app.handle()));
             
             if !status.dll_found {
                 log::warn!("DLL do SDK não encontrada. Copiando para a raiz...");
-                match sdk_manager::sync_sdk_dll(&app.handle()) {
+                match sdk_manager::sync_sdk_dll(app.handle()) {
                     Ok(path) => log::info!("DLL copiada com sucesso para: {:?}", path),
                     Err(e) => log::error!("Erro ao copiar DLL: {}", e),
                 }
@@ -316,17 +420,16 @@ fn main() {
         .on_window_event(|window, event| {
             if let tauri::WindowEvent::CloseRequested { .. } = event {
                 if window.label() == "main" {
-                    let _ = cleanup::cleanup_app_data();
-                    biometric_sdk::terminate_sdk();
+                    terminate_reader(window.app_handle());
                     window.app_handle().exit(0);
                 }
             }
         })
         .build(tauri::generate_context!())
         .expect("error while running tauri application")
-        .run(|_app_handle, event| {
+        .run(|app_handle, event| {
             if let tauri::RunEvent::Exit = event {
-                biometric_sdk::terminate_sdk();
+                terminate_reader(app_handle);
             }
         });
 }
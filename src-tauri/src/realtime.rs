@@ -0,0 +1,857 @@
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::Emitter;
+use tokio::net::TcpStream;
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::biometric_error::BiometricError;
+use crate::connectivity::{ConnectivityMonitor, ConnectivityState};
+use crate::station_config::StationConfig;
+use crate::supabase_auth::SupabaseAuth;
+
+/// Variável de ambiente que troca a URL do websocket (ex.: servidor local de testes)
+const URL_ENV: &str = "ALMOX_REALTIME_URL";
+/// Heartbeat do Phoenix; o servidor derruba a conexão sem ele (limite de 60 s)
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(25);
+/// Espera entre tentativas de reconexão: dobra a cada falha até o limite
+const RECONNECT_BASE: Duration = Duration::from_secs(1);
+const RECONNECT_MAX: Duration = Duration::from_secs(30);
+/// Sem sessão, sem base ou sem conexão: espera até verificar de novo
+const IDLE_INTERVAL: Duration = Duration::from_secs(15);
+/// Limite para abrir o websocket
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Estado da assinatura (evento `realtime-status` e comando `get_realtime_status`).
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RealtimeState {
+    /// Sem sessão ou sem base configurada na estação
+    Idle,
+    Connecting,
+    /// Canal da base assinado; eventos chegando
+    Subscribed,
+    /// Conexão caiu; reconectando
+    Disconnected,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RealtimeStatus {
+    pub state: RealtimeState,
+    pub base_id: Option<String>,
+    /// Desde quando está neste estado (RFC 3339)
+    pub since: String,
+    /// Motivo da última queda
+    pub detail: Option<String>,
+}
+
+/// Tipo da mudança no banco
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// Payload dos eventos `solicitacao-created`, `solicitacao-updated` e
+/// `transferencia-received`.
+#[derive(Serialize, Clone, Debug)]
+pub struct RealtimeChange {
+    pub change: ChangeKind,
+    pub table: String,
+    /// Linha como ficou (vazia em exclusões)
+    pub record: serde_json::Value,
+    /// Linha anterior; só traz todas as colunas com `replica identity full`
+    pub old_record: Option<serde_json::Value>,
+    pub commit_timestamp: Option<String>,
+}
+
+/// Mensagem do protocolo Phoenix (formato JSON, vsn 1.0.0).
+#[derive(Serialize, Deserialize, Debug)]
+struct PhoenixMessage {
+    topic: String,
+    event: String,
+    #[serde(default)]
+    payload: serde_json::Value,
+    #[serde(rename = "ref", default)]
+    reference: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    join_ref: Option<String>,
+}
+
+/// `payload.data` de um evento `postgres_changes`
+#[derive(Deserialize)]
+struct PostgresChange {
+    #[serde(rename = "type")]
+    kind: String,
+    table: String,
+    #[serde(default)]
+    record: serde_json::Value,
+    #[serde(default)]
+    old_record: Option<serde_json::Value>,
+    #[serde(default)]
+    commit_timestamp: Option<String>,
+}
+
+/// Por que a conexão terminou.
+enum Closed {
+    /// Sessão encerrada ou base trocada: reconecta sem esperar
+    Restart(String),
+    /// Queda, erro do servidor ou heartbeat sem resposta
+    Lost(String),
+}
+
+/// Assinatura do Supabase Realtime da base da estação (gerenciada pelo Tauri).
+///
+/// Mantém um websocket (protocolo Phoenix) com as mudanças de `solicitacoes_itens` e
+/// `transferencias_bases` da base configurada em `station.json`, e repassa cada uma às
+/// duas janelas como evento do Tauri. Reconecta e reassina sozinha, renova o token do
+/// canal junto com a sessão e para quando o usuário sai.
+#[derive(Clone)]
+pub struct RealtimeClient {
+    status: Arc<Mutex<RealtimeStatus>>,
+    /// Intervalo do heartbeat (`HEARTBEAT_INTERVAL`)
+    heartbeat: Duration,
+}
+
+impl Default for RealtimeClient {
+    fn default() -> Self {
+        Self {
+            status: Arc::new(Mutex::new(RealtimeStatus {
+                state: RealtimeState::Idle,
+                base_id: None,
+                since: chrono::Utc::now().to_rfc3339(),
+                detail: None,
+            })),
+            heartbeat: HEARTBEAT_INTERVAL,
+        }
+    }
+}
+
+/// Falha no websocket; conta como sem conexão (`is_offline`).
+fn socket_error(detail: impl Into<String>) -> BiometricError {
+    BiometricError::Http {
+        status: None,
+        detail: detail.into(),
+    }
+}
+
+/// URL do websocket: `ALMOX_REALTIME_URL` ou o endpoint Realtime do projeto.
+fn socket_url(project_url: &str, anon_key: &str) -> String {
+    if let Some(url) = std::env::var(URL_ENV).ok().filter(|u| !u.trim().is_empty()) {
+        return url;
+    }
+    let ws = project_url
+        .replacen("https://", "wss://", 1)
+        .replacen("http://", "ws://", 1);
+    format!("{}/realtime/v1/websocket?apikey={}&vsn=1.0.0", ws, anon_key)
+}
+
+/// Assinaturas do canal: solicitações da base e transferências que chegam nela.
+fn join_payload(base_id: &str, access_token: &str) -> serde_json::Value {
+    serde_json::json!({
+        "config": {
+            "broadcast": { "ack": false, "self": false },
+            "presence": { "key": "" },
+            "postgres_changes": [
+                {
+                    "event": "*",
+                    "schema": "public",
+                    "table": "solicitacoes_itens",
+                    "filter": format!("base_id=eq.{}", base_id),
+                },
+                {
+                    "event": "*",
+                    "schema": "public",
+                    "table": "transferencias_bases",
+                    "filter": format!("base_destino_id=eq.{}", base_id),
+                },
+            ],
+            "private": false,
+        },
+        "access_token": access_token,
+    })
+}
+
+/// Evento do Tauri para uma mudança; `None` para as que não interessam às telas.
+fn event_for(change: &PostgresChange) -> Option<(&'static str, RealtimeChange)> {
+    let kind = match change.kind.as_str() {
+        "INSERT" => ChangeKind::Insert,
+        "UPDATE" => ChangeKind::Update,
+        "DELETE" => ChangeKind::Delete,
+        _ => return None,
+    };
+    let event = match (change.table.as_str(), kind) {
+        ("solicitacoes_itens", ChangeKind::Insert) => "solicitacao-created",
+        ("solicitacoes_itens", _) => "solicitacao-updated",
+        ("transferencias_bases", ChangeKind::Insert | ChangeKind::Update) => "transferencia-received",
+        _ => return None,
+    };
+    Some((
+        event,
+        RealtimeChange {
+            change: kind,
+            table: change.table.clone(),
+            record: change.record.clone(),
+            old_record: change.old_record.clone(),
+            commit_timestamp: change.commit_timestamp.clone(),
+        },
+    ))
+}
+
+/// Conexão aberta com o canal da base.
+struct Channel {
+    socket: Socket,
+    topic: String,
+    next_ref: u64,
+    join_ref: String,
+}
+
+impl Channel {
+    fn make_ref(&mut self) -> String {
+        self.next_ref += 1;
+        self.next_ref.to_string()
+    }
+
+    async fn send(&mut self, topic: &str, event: &str, payload: serde_json::Value) -> Result<String, String> {
+        let reference = self.make_ref();
+        let message = PhoenixMessage {
+            topic: topic.to_string(),
+            event: event.to_string(),
+            payload,
+            reference: Some(reference.clone()),
+            join_ref: (topic == self.topic).then(|| self.join_ref.clone()),
+        };
+        let text = serde_json::to_string(&message).map_err(|e| e.to_string())?;
+        self.socket
+            .send(Message::text(text))
+            .await
+            .map_err(|e| format!("Erro ao enviar ao Realtime: {}", e))?;
+        Ok(reference)
+    }
+
+    /// Próxima mensagem do Phoenix até `deadline`; `Ok(None)` quando o prazo passa.
+    async fn next(&mut self, deadline: Instant) -> Result<Option<PhoenixMessage>, String> {
+        loop {
+            let message = match tokio::time::timeout_at(deadline, self.socket.next()).await {
+                Err(_) => return Ok(None),
+                Ok(None) => return Err("Realtime fechou a conexão".to_string()),
+                Ok(Some(Err(e))) => return Err(format!("Erro na conexão com o Realtime: {}", e)),
+                Ok(Some(Ok(message))) => message,
+            };
+            match message {
+                Message::Text(text) => match serde_json::from_str(text.as_str()) {
+                    Ok(message) => return Ok(Some(message)),
+                    Err(e) => log::debug!("Mensagem do Realtime ignorada ({}): {}", e, text.as_str()),
+                },
+                Message::Close(frame) => {
+                    return Err(match frame {
+                        Some(frame) => format!("Realtime fechou a conexão: {} {}", frame.code, frame.reason),
+                        None => "Realtime fechou a conexão".to_string(),
+                    })
+                }
+                // Ping é respondido pelo tungstenite na próxima escrita
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Como está a sessão a cada heartbeat.
+enum Session {
+    /// O token do canal continua valendo
+    Current,
+    /// Sessão renovada: o canal passa a usar este token
+    Renewed(String),
+    /// Sessão encerrada ou base trocada: o canal reinicia
+    Ended(String),
+}
+
+/// O que a assinatura usa do app: base e sessão da estação, conexão e para onde vão os
+/// eventos. No app é o `TauriHost`; nos testes, um servidor local.
+trait RealtimeHost {
+    /// Base da estação, quando há sessão e base configurada
+    fn base_id(&self) -> Option<String>;
+    fn is_offline(&self) -> bool;
+    /// URL do websocket e token de acesso do usuário
+    async fn credentials(&self) -> Result<(String, String), BiometricError>;
+    async fn session(&self, base_id: &str, access_token: &str) -> Session;
+    fn emit_status(&self, status: &RealtimeStatus);
+    fn emit_change(&self, event: &'static str, change: &RealtimeChange);
+}
+
+struct TauriHost {
+    app: tauri::AppHandle,
+    auth: SupabaseAuth,
+    connectivity: ConnectivityMonitor,
+}
+
+impl RealtimeHost for TauriHost {
+    fn base_id(&self) -> Option<String> {
+        StationConfig::load().base_id.filter(|_| self.auth.user_id().is_some())
+    }
+
+    fn is_offline(&self) -> bool {
+        self.connectivity.status().state == ConnectivityState::Offline
+    }
+
+    async fn credentials(&self) -> Result<(String, String), BiometricError> {
+        let (project_url, anon_key) = self.auth.project()?;
+        let db = self.auth.connect().await?;
+        Ok((socket_url(&project_url, &anon_key), db.access_token().to_string()))
+    }
+
+    async fn session(&self, base_id: &str, access_token: &str) -> Session {
+        if StationConfig::load().base_id.as_deref() != Some(base_id) {
+            return Session::Ended("Base da estação trocada".to_string());
+        }
+        match self.auth.connect().await {
+            Ok(db) if db.access_token() != access_token => Session::Renewed(db.access_token().to_string()),
+            Ok(_) => Session::Current,
+            Err(BiometricError::Unauthenticated) => Session::Ended("Sessão encerrada".to_string()),
+            // Sem renovar agora: o token atual ainda vale até expirar
+            Err(e) => {
+                log::debug!("Renovação do token do Realtime adiada: {}", e);
+                Session::Current
+            }
+        }
+    }
+
+    fn emit_status(&self, status: &RealtimeStatus) {
+        let _ = self.app.emit("realtime-status", status);
+    }
+
+    fn emit_change(&self, event: &'static str, change: &RealtimeChange) {
+        let _ = self.app.emit(event, change);
+    }
+}
+
+/// Abre o websocket e entra no canal da base; espera a confirmação do servidor.
+async fn join(url: &str, base_id: &str, access_token: &str) -> Result<Channel, BiometricError> {
+    let (socket, _) = tokio::time::timeout(CONNECT_TIMEOUT, tokio_tungstenite::connect_async(url))
+        .await
+        .map_err(|_| socket_error("Tempo esgotado ao conectar ao Realtime"))?
+        .map_err(|e| socket_error(format!("Erro ao conectar ao Realtime: {}", e)))?;
+
+    let topic = format!("realtime:almoxarifado-base-{}", base_id);
+    let mut channel = Channel {
+        socket,
+        topic: topic.clone(),
+        next_ref: 0,
+        join_ref: String::new(),
+    };
+    channel.join_ref = channel.make_ref();
+    let join_ref = channel.join_ref.clone();
+    let message = PhoenixMessage {
+        topic: topic.clone(),
+        event: "phx_join".to_string(),
+        payload: join_payload(base_id, access_token),
+        reference: Some(join_ref.clone()),
+        join_ref: Some(join_ref.clone()),
+    };
+    let text = serde_json::to_string(&message)?;
+    channel
+        .socket
+        .send(Message::text(text))
+        .await
+        .map_err(|e| socket_error(format!("Erro ao enviar ao Realtime: {}", e)))?;
+
+    let deadline = Instant::now() + CONNECT_TIMEOUT;
+    loop {
+        let reply = channel
+            .next(deadline)
+            .await
+            .map_err(socket_error)?
+            .ok_or_else(|| socket_error("Realtime não confirmou a assinatura"))?;
+        if reply.event != "phx_reply" || reply.reference.as_deref() != Some(join_ref.as_str()) {
+            continue;
+        }
+        return match reply.payload.get("status").and_then(|s| s.as_str()) {
+            Some("ok") => Ok(channel),
+            _ => Err(socket_error(format!(
+                "Realtime recusou a assinatura: {}",
+                reply.payload["response"]
+            ))),
+        };
+    }
+}
+
+impl RealtimeClient {
+    fn lock(&self) -> std::sync::MutexGuard<'_, RealtimeStatus> {
+        self.status.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    pub fn status(&self) -> RealtimeStatus {
+        self.lock().clone()
+    }
+
+    fn set_state(&self, host: &impl RealtimeHost, state: RealtimeState, base_id: Option<&str>, detail: Option<String>) {
+        let status = {
+            let mut status = self.lock();
+            if status.state == state && status.base_id.as_deref() == base_id && status.detail == detail {
+                return;
+            }
+            if status.state != state {
+                status.since = chrono::Utc::now().to_rfc3339();
+            }
+            status.state = state;
+            status.base_id = base_id.map(str::to_string);
+            status.detail = detail;
+            status.clone()
+        };
+        host.emit_status(&status);
+    }
+
+    /// Repassa as mudanças até a conexão cair, a sessão acabar ou a base mudar.
+    async fn listen(
+        &self,
+        host: &impl RealtimeHost,
+        mut channel: Channel,
+        base_id: &str,
+        mut access_token: String,
+    ) -> Closed {
+        let mut next_heartbeat = Instant::now() + self.heartbeat;
+        let mut pending_heartbeat: Option<String> = None;
+
+        loop {
+            let message = match channel.next(next_heartbeat).await {
+                Err(e) => return Closed::Lost(e),
+                Ok(Some(message)) => message,
+                Ok(None) => {
+                    if pending_heartbeat.is_some() {
+                        return Closed::Lost("Realtime não respondeu ao heartbeat".to_string());
+                    }
+                    match host.session(base_id, &access_token).await {
+                        Session::Current => {}
+                        Session::Renewed(token) => {
+                            access_token = token;
+                            let topic = channel.topic.clone();
+                            let payload = serde_json::json!({ "access_token": access_token });
+                            if let Err(e) = channel.send(&topic, "access_token", payload).await {
+                                return Closed::Lost(e);
+                            }
+                            log::debug!("Token do canal Realtime renovado");
+                        }
+                        Session::Ended(reason) => {
+                            let _ = channel.socket.close(None).await;
+                            return Closed::Restart(reason);
+                        }
+                    }
+                    match channel.send("phoenix", "heartbeat", serde_json::json!({})).await {
+                        Ok(reference) => pending_heartbeat = Some(reference),
+                        Err(e) => return Closed::Lost(e),
+                    }
+                    next_heartbeat = Instant::now() + self.heartbeat;
+                    continue;
+                }
+            };
+
+            match message.event.as_str() {
+                "phx_reply" if message.topic == "phoenix" && message.reference == pending_heartbeat => {
+                    pending_heartbeat = None;
+                }
+                "postgres_changes" if message.topic == channel.topic => {
+                    let change = match serde_json::from_value::<PostgresChange>(message.payload["data"].clone()) {
+                        Ok(change) => change,
+                        Err(e) => {
+                            log::warn!("⚠️ Mudança do Realtime com formato inesperado: {}", e);
+                            continue;
+                        }
+                    };
+                    if let Some((event, payload)) = event_for(&change) {
+                        log::debug!("📡 Realtime: {} ({:?} em {})", event, payload.change, payload.table);
+                        host.emit_change(event, &payload);
+                    }
+                }
+                "system" if message.payload["status"] == "error" => {
+                    log::warn!("⚠️ Realtime informou erro no canal: {}", message.payload["message"]);
+                }
+                "phx_error" | "phx_close" if message.topic == channel.topic => {
+                    return Closed::Lost(format!("Canal Realtime encerrado pelo servidor ({})", message.event));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Assina, repassa e reconecta, para sempre.
+    async fn run(&self, host: &impl RealtimeHost) {
+        let mut backoff = RECONNECT_BASE;
+
+        loop {
+            let Some(base_id) = host.base_id() else {
+                self.set_state(host, RealtimeState::Idle, None, None);
+                tokio::time::sleep(IDLE_INTERVAL).await;
+                continue;
+            };
+            if host.is_offline() {
+                self.set_state(host, RealtimeState::Disconnected, Some(&base_id), Some("Sem conexão".to_string()));
+                tokio::time::sleep(IDLE_INTERVAL).await;
+                continue;
+            }
+
+            self.set_state(host, RealtimeState::Connecting, Some(&base_id), None);
+            let joined = match host.credentials().await {
+                Ok((url, access_token)) => join(&url, &base_id, &access_token)
+                    .await
+                    .map(|channel| (channel, access_token)),
+                Err(e) => Err(e),
+            };
+            let (channel, access_token) = match joined {
+                Ok(joined) => joined,
+                Err(BiometricError::Unauthenticated) => {
+                    tokio::time::sleep(IDLE_INTERVAL).await;
+                    continue;
+                }
+                Err(e) => {
+                    log::warn!("⚠️ Assinatura do Realtime falhou (nova tentativa em {:?}): {}", backoff, e);
+                    self.set_state(host, RealtimeState::Disconnected, Some(&base_id), Some(e.to_string()));
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(RECONNECT_MAX);
+                    continue;
+                }
+            };
+
+            log::info!("📡 Realtime assinado para a base {}", base_id);
+            backoff = RECONNECT_BASE;
+            self.set_state(host, RealtimeState::Subscribed, Some(&base_id), None);
+
+            match self.listen(host, channel, &base_id, access_token).await {
+                Closed::Restart(reason) => log::info!("📡 Realtime reiniciado: {}", reason),
+                Closed::Lost(reason) => {
+                    log::warn!("⚠️ Conexão com o Realtime perdida: {}", reason);
+                    self.set_state(host, RealtimeState::Disconnected, Some(&base_id), Some(reason));
+                    tokio::time::sleep(backoff).await;
+                }
+            }
+        }
+    }
+}
+
+/// Mantém a assinatura da base enquanto houver sessão e base configurada. Roda no Tokio
+/// durante toda a vida do app.
+pub async fn run_realtime(
+    app: tauri::AppHandle,
+    client: RealtimeClient,
+    auth: SupabaseAuth,
+    connectivity: ConnectivityMonitor,
+) {
+    let host = TauriHost { app, auth, connectivity };
+    client.run(&host).await;
+}
+
+/// Comando Tauri: estado da assinatura do Realtime.
+#[tauri::command]
+pub fn get_realtime_status(client: tauri::State<'_, RealtimeClient>) -> RealtimeStatus {
+    client.status()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    /// O que a assinatura repassou ao "app"
+    #[derive(Debug)]
+    enum Seen {
+        State(RealtimeState),
+        Change(&'static str, RealtimeChange),
+    }
+
+    struct TestHost {
+        url: String,
+        /// Token devolvido no heartbeat; diferente do inicial, vira renovação
+        token: Mutex<String>,
+        seen: mpsc::UnboundedSender<Seen>,
+    }
+
+    impl RealtimeHost for TestHost {
+        fn base_id(&self) -> Option<String> {
+            Some("b1".to_string())
+        }
+
+        fn is_offline(&self) -> bool {
+            false
+        }
+
+        async fn credentials(&self) -> Result<(String, String), BiometricError> {
+            Ok((self.url.clone(), "t1".to_string()))
+        }
+
+        async fn session(&self, _base_id: &str, access_token: &str) -> Session {
+            let token = self.token.lock().unwrap().clone();
+            if token == access_token {
+                Session::Current
+            } else {
+                Session::Renewed(token)
+            }
+        }
+
+        fn emit_status(&self, status: &RealtimeStatus) {
+            let _ = self.seen.send(Seen::State(status.state));
+        }
+
+        fn emit_change(&self, event: &'static str, change: &RealtimeChange) {
+            let _ = self.seen.send(Seen::Change(event, change.clone()));
+        }
+    }
+
+    type ServerSocket = WebSocketStream<TcpStream>;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    async fn server() -> (TcpListener, String) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/realtime/v1/websocket", listener.local_addr().unwrap());
+        (listener, url)
+    }
+
+    fn host(url: &str) -> (TestHost, mpsc::UnboundedReceiver<Seen>) {
+        let (seen, rx) = mpsc::unbounded_channel();
+        let host = TestHost {
+            url: url.to_string(),
+            token: Mutex::new("t1".to_string()),
+            seen,
+        };
+        (host, rx)
+    }
+
+    async fn accept(listener: &TcpListener) -> ServerSocket {
+        let (stream, _) = tokio::time::timeout(Duration::from_secs(5), listener.accept())
+            .await
+            .expect("cliente não conectou")
+            .unwrap();
+        tokio_tungstenite::accept_async(stream).await.unwrap()
+    }
+
+    async fn receive(socket: &mut ServerSocket) -> PhoenixMessage {
+        loop {
+            let message = tokio::time::timeout(Duration::from_secs(5), socket.next())
+                .await
+                .expect("cliente não enviou nada")
+                .unwrap()
+                .unwrap();
+            if let Message::Text(text) = message {
+                return serde_json::from_str(text.as_str()).unwrap();
+            }
+        }
+    }
+
+    async fn send(
+        socket: &mut ServerSocket,
+        topic: &str,
+        event: &str,
+        payload: serde_json::Value,
+        reference: Option<String>,
+    ) {
+        let message = PhoenixMessage {
+            topic: topic.to_string(),
+            event: event.to_string(),
+            payload,
+            reference,
+            join_ref: None,
+        };
+        socket.send(Message::text(serde_json::to_string(&message).unwrap())).await.unwrap();
+    }
+
+    /// Recebe o `phx_join` e responde com `status`.
+    async fn accept_join(socket: &mut ServerSocket, status: &str) -> PhoenixMessage {
+        let join = receive(socket).await;
+        assert_eq!(join.event, "phx_join");
+        let payload = serde_json::json!({ "status": status, "response": {} });
+        send(socket, &join.topic, "phx_reply", payload, join.reference.clone()).await;
+        join
+    }
+
+    fn change(kind: &str, table: &str) -> serde_json::Value {
+        serde_json::json!({
+            "data": {
+                "type": kind,
+                "table": table,
+                "schema": "public",
+                "record": { "id": "r1", "base_id": "b1" },
+                "old_record": null,
+                "commit_timestamp": "2026-10-18T12:00:00Z",
+            },
+            "ids": [1],
+        })
+    }
+
+    async fn next_seen(rx: &mut mpsc::UnboundedReceiver<Seen>) -> Seen {
+        tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("nada repassado ao app")
+            .unwrap()
+    }
+
+    #[test]
+    fn join_subscribes_the_station_base() {
+        block_on(async {
+            let (listener, url) = server().await;
+            let client = tokio::spawn(async move { join(&url, "b1", "t1").await.map(|channel| channel.topic) });
+
+            let mut socket = accept(&listener).await;
+            let join = accept_join(&mut socket, "ok").await;
+            assert_eq!(join.topic, "realtime:almoxarifado-base-b1");
+            assert_eq!(join.join_ref, join.reference);
+            assert_eq!(join.payload["access_token"], "t1");
+            let changes = &join.payload["config"]["postgres_changes"];
+            assert_eq!(changes[0]["table"], "solicitacoes_itens");
+            assert_eq!(changes[0]["filter"], "base_id=eq.b1");
+            assert_eq!(changes[1]["table"], "transferencias_bases");
+            assert_eq!(changes[1]["filter"], "base_destino_id=eq.b1");
+
+            assert_eq!(client.await.unwrap().unwrap(), "realtime:almoxarifado-base-b1");
+        });
+    }
+
+    #[test]
+    fn refused_join_is_an_error() {
+        block_on(async {
+            let (listener, url) = server().await;
+            let client = tokio::spawn(async move { join(&url, "b1", "t1").await.map(|_| ()) });
+
+            let mut socket = accept(&listener).await;
+            accept_join(&mut socket, "error").await;
+
+            let error = client.await.unwrap().unwrap_err();
+            assert!(error.to_string().contains("recusou"));
+        });
+    }
+
+    #[test]
+    fn heartbeat_renews_the_token_and_drops_a_silent_server() {
+        block_on(async {
+            let (listener, url) = server().await;
+            let (host, _rx) = host(&url);
+            let host = Arc::new(host);
+            let realtime = RealtimeClient {
+                heartbeat: Duration::from_millis(100),
+                ..Default::default()
+            };
+            let client_host = host.clone();
+            let client = tokio::spawn(async move {
+                let channel = join(&url, "b1", "t1").await.unwrap();
+                realtime.listen(&*client_host, channel, "b1", "t1".to_string()).await
+            });
+
+            let mut socket = accept(&listener).await;
+            let join = accept_join(&mut socket, "ok").await;
+
+            let heartbeat = receive(&mut socket).await;
+            assert_eq!((heartbeat.topic.as_str(), heartbeat.event.as_str()), ("phoenix", "heartbeat"));
+            send(&mut socket, "phoenix", "phx_reply", serde_json::json!({ "status": "ok" }), heartbeat.reference).await;
+
+            *host.token.lock().unwrap() = "t2".to_string();
+            let renewal = receive(&mut socket).await;
+            assert_eq!(renewal.event, "access_token");
+            assert_eq!(renewal.topic, join.topic);
+            assert_eq!(renewal.payload["access_token"], "t2");
+            let heartbeat = receive(&mut socket).await;
+            assert_eq!(heartbeat.event, "heartbeat");
+
+            // Sem resposta ao heartbeat: a conexão é dada como perdida
+            match client.await.unwrap() {
+                Closed::Lost(reason) => assert!(reason.contains("heartbeat")),
+                Closed::Restart(reason) => panic!("reiniciou em vez de cair: {}", reason),
+            }
+        });
+    }
+
+    #[test]
+    fn changes_become_tauri_events() {
+        block_on(async {
+            let (listener, url) = server().await;
+            let (host, mut rx) = host(&url);
+            let client = tokio::spawn(async move {
+                let channel = join(&url, "b1", "t1").await.unwrap();
+                RealtimeClient::default().listen(&host, channel, "b1", "t1".to_string()).await
+            });
+
+            let mut socket = accept(&listener).await;
+            let join = accept_join(&mut socket, "ok").await;
+            for (kind, table) in [
+                ("INSERT", "solicitacoes_itens"),
+                ("DELETE", "transferencias_bases"),
+                ("UPDATE", "solicitacoes_itens"),
+                ("INSERT", "transferencias_bases"),
+            ] {
+                send(&mut socket, &join.topic, "postgres_changes", change(kind, table), None).await;
+            }
+
+            let Seen::Change(event, created) = next_seen(&mut rx).await else { panic!("esperava mudança") };
+            assert_eq!(event, "solicitacao-created");
+            assert_eq!(created.change, ChangeKind::Insert);
+            assert_eq!(created.record["id"], "r1");
+            assert_eq!(created.commit_timestamp.as_deref(), Some("2026-10-18T12:00:00Z"));
+            // A exclusão de transferência não vira evento
+            let Seen::Change(event, _) = next_seen(&mut rx).await else { panic!("esperava mudança") };
+            assert_eq!(event, "solicitacao-updated");
+            let Seen::Change(event, received) = next_seen(&mut rx).await else { panic!("esperava mudança") };
+            assert_eq!(event, "transferencia-received");
+            assert_eq!(received.table, "transferencias_bases");
+
+            send(&mut socket, &join.topic, "phx_close", serde_json::json!({}), None).await;
+            assert!(matches!(client.await.unwrap(), Closed::Lost(_)));
+        });
+    }
+
+    #[test]
+    fn reconnects_and_joins_again_after_the_server_drops() {
+        block_on(async {
+            let (listener, url) = server().await;
+            let (host, mut rx) = host(&url);
+            let client = tokio::spawn(async move { RealtimeClient::default().run(&host).await });
+
+            let mut socket = accept(&listener).await;
+            accept_join(&mut socket, "ok").await;
+            assert!(matches!(next_seen(&mut rx).await, Seen::State(RealtimeState::Connecting)));
+            assert!(matches!(next_seen(&mut rx).await, Seen::State(RealtimeState::Subscribed)));
+
+            socket.close(None).await.unwrap();
+            drop(socket);
+            assert!(matches!(next_seen(&mut rx).await, Seen::State(RealtimeState::Disconnected)));
+
+            let mut socket = accept(&listener).await;
+            let join = accept_join(&mut socket, "ok").await;
+            assert_eq!(join.topic, "realtime:almoxarifado-base-b1");
+            assert!(matches!(next_seen(&mut rx).await, Seen::State(RealtimeState::Connecting)));
+            assert!(matches!(next_seen(&mut rx).await, Seen::State(RealtimeState::Subscribed)));
+
+            client.abort();
+        });
+    }
+
+    #[test]
+    fn event_for_maps_tables_and_kinds() {
+        let change = |kind: &str, table: &str| PostgresChange {
+            kind: kind.to_string(),
+            table: table.to_string(),
+            record: serde_json::json!({}),
+            old_record: None,
+            commit_timestamp: None,
+        };
+        let event = |kind: &str, table: &str| event_for(&change(kind, table)).map(|(event, _)| event);
+        assert_eq!(event("INSERT", "solicitacoes_itens"), Some("solicitacao-created"));
+        assert_eq!(event("UPDATE", "solicitacoes_itens"), Some("solicitacao-updated"));
+        assert_eq!(event("DELETE", "solicitacoes_itens"), Some("solicitacao-updated"));
+        assert_eq!(event("INSERT", "transferencias_bases"), Some("transferencia-received"));
+        assert_eq!(event("UPDATE", "transferencias_bases"), Some("transferencia-received"));
+        assert_eq!(event("DELETE", "transferencias_bases"), None);
+        assert_eq!(event("INSERT", "movimentacoes"), None);
+        assert_eq!(event("TRUNCATE", "solicitacoes_itens"), None);
+    }
+}
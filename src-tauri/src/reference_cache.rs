@@ -0,0 +1,254 @@
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+
+use crate::biometric_error::BiometricError;
+use crate::secret_store::{SecretStore, REFERENCE_CACHE_KEY};
+use crate::station_config::app_data_dir;
+use crate::supabase::Supabase;
+use crate::supabase_auth::SupabaseAuth;
+use crate::template_cache::cache_key;
+use crate::util::random_bytes;
+
+/// Conjuntos de dados de referência mantidos na estação
+pub const REFERENCES: &[&str] = &["solicitacoes", "itens_estoque", "bases", "usuarios"];
+
+/// Solicitações em aberto, com os dados usados nas telas (mesmo `select` do frontend)
+const SOLICITACOES_SELECT: &str = "*,\
+    item:itens_estoque!solicitacoes_itens_item_id_fkey(id,nome,codigo,estoque_atual),\
+    solicitante:usuarios!solicitacoes_itens_solicitante_id_fkey(id,nome,email),\
+    destinatario:funcionarios_ativos!solicitacoes_itens_destinatario_id_fkey(id,nome,matricula),\
+    base:bases!solicitacoes_itens_base_id_fkey(id,nome)";
+/// Solicitações mais recentes guardadas
+const SOLICITACOES_LIMIT: usize = 500;
+/// Colunas de `usuarios` usadas pelas telas offline (nada de dados pessoais além disso)
+const USUARIOS_SELECT: &str = "id,nome,email,matricula";
+
+/// `reference_sets` guarda cada conjunto cifrado; a antiga `reference_data`, em texto puro,
+/// é descartada (os dados voltam na próxima atualização).
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS reference_sets (
+        name TEXT PRIMARY KEY,
+        nonce BLOB NOT NULL,
+        rows BLOB NOT NULL,
+        refreshed_at TEXT NOT NULL
+    );
+";
+
+/// Conteúdo guardado de um conjunto (comando `get_reference_cache`).
+#[derive(Serialize, Clone, Debug)]
+pub struct ReferenceData {
+    pub name: String,
+    pub refreshed_at: String,
+    pub rows: Vec<serde_json::Value>,
+}
+
+/// Cópia local dos dados de referência (gerenciada pelo Tauri).
+///
+/// Fica em `reference_cache.db` (SQLite) na pasta de dados, com cada conjunto cifrado com
+/// AES-256-GCM (chave no cofre de segredos), como o cache de templates. É atualizada pelo
+/// serviço de conectividade, com ou sem janela em foco. O frontend copia para o IndexedDB
+/// quando recebe `reference-cache-updated`.
+#[derive(Clone)]
+pub struct ReferenceCache {
+    conn: Arc<Mutex<Connection>>,
+    cipher: Arc<Aes256Gcm>,
+}
+
+/// Remove a tabela em texto puro de versões anteriores e limpa as páginas que ela ocupava.
+fn drop_plain_table(conn: &Connection) -> Result<(), BiometricError> {
+    let exists: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'reference_data'",
+        [],
+        |row| row.get(0),
+    )?;
+    if exists {
+        conn.execute_batch("DROP TABLE reference_data; VACUUM;")?;
+        log::info!("🗂️ Cópia dos dados de referência em texto puro descartada");
+    }
+    Ok(())
+}
+
+async fn fetch_reference(db: &Supabase, name: &str) -> Result<Vec<serde_json::Value>, BiometricError> {
+    match name {
+        "solicitacoes" => {
+            db.from("solicitacoes_itens")
+                .select(SOLICITACOES_SELECT)
+                .in_list("status", &["pendente", "aprovada", "aguardando_estoque"])
+                .order("criado_em", false)
+                .range(0, SOLICITACOES_LIMIT)
+                .fetch()
+                .await
+        }
+        "itens_estoque" => db.from("itens_estoque").select("*").order("nome", true).fetch_all().await,
+        "bases" => db.from("bases").select("*").eq("ativa", true).order("nome", true).fetch_all().await,
+        "usuarios" => {
+            db.from("usuarios")
+                .select(USUARIOS_SELECT)
+                .eq("ativo", true)
+                .order("nome", true)
+                .fetch_all()
+                .await
+        }
+        other => Err(BiometricError::Config(format!("Dados de referência desconhecidos: {}", other))),
+    }
+}
+
+impl ReferenceCache {
+    pub fn open(store: &SecretStore) -> Result<Self, BiometricError> {
+        let path = app_data_dir().join("reference_cache.db");
+        std::fs::create_dir_all(app_data_dir())?;
+        Self::with_connection(Connection::open(&path)?, cache_key(store, REFERENCE_CACHE_KEY)?)
+    }
+
+    fn with_connection(conn: Connection, key: [u8; 32]) -> Result<Self, BiometricError> {
+        drop_plain_table(&conn)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            cipher: Arc::new(Aes256Gcm::new(&key.into())),
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    /// Cifra e guarda um conjunto; o nome entra como dado associado, então trocar o conteúdo
+    /// de dois conjuntos não passa na decifragem.
+    fn store(&self, name: &str, rows: &[serde_json::Value]) -> Result<(), BiometricError> {
+        let nonce = random_bytes::<12>()?;
+        let plain = serde_json::to_vec(rows)?;
+        let sealed = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: &plain, aad: name.as_bytes() })
+            .map_err(|_| BiometricError::Io("Erro ao cifrar dados de referência".to_string()))?;
+        self.lock().execute(
+            "INSERT OR REPLACE INTO reference_sets (name, nonce, rows, refreshed_at) VALUES (?1, ?2, ?3, ?4)",
+            params![name, &nonce[..], sealed, chrono::Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Result<Option<ReferenceData>, BiometricError> {
+        let row: Option<(Vec<u8>, Vec<u8>, String)> = self
+            .lock()
+            .query_row(
+                "SELECT nonce, rows, refreshed_at FROM reference_sets WHERE name = ?1",
+                params![name],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+        let Some((nonce, sealed, refreshed_at)) = row else {
+            return Ok(None);
+        };
+        if nonce.len() != 12 {
+            return Err(BiometricError::Parse(format!("Nonce inválido nos dados de referência '{}'", name)));
+        }
+        let plain = self
+            .cipher
+            .decrypt(Nonce::from_slice(&nonce), Payload { msg: &sealed, aad: name.as_bytes() })
+            .map_err(|_| BiometricError::Parse(format!("Dados de referência '{}' não decifram", name)))?;
+        Ok(Some(ReferenceData {
+            name: name.to_string(),
+            refreshed_at,
+            rows: serde_json::from_slice(&plain)?,
+        }))
+    }
+
+    /// Baixa todos os conjuntos; devolve os nomes dos que foram atualizados. Um conjunto
+    /// com erro não impede os demais, mas sem conexão ou sem sessão a rodada para.
+    pub async fn refresh(&self, auth: &SupabaseAuth) -> Result<Vec<String>, BiometricError> {
+        let db = auth.connect().await?;
+        let mut refreshed = Vec::new();
+        for name in REFERENCES {
+            match fetch_reference(&db, name).await {
+                Ok(rows) => {
+                    self.store(name, &rows)?;
+                    log::debug!("Dados de referência '{}' atualizados ({} linhas)", name, rows.len());
+                    refreshed.push(name.to_string());
+                }
+                Err(e) if e.is_offline() || e == BiometricError::Unauthenticated => return Err(e),
+                Err(e) => log::warn!("⚠️ Erro ao atualizar dados de referência '{}': {}", name, e),
+            }
+        }
+        log::info!("🗂️ Dados de referência atualizados: {}", refreshed.join(", "));
+        Ok(refreshed)
+    }
+}
+
+/// Comando Tauri: conteúdo guardado de um conjunto de referência (`None` se nunca baixado).
+#[tauri::command]
+pub fn get_reference_cache(
+    cache: tauri::State<'_, ReferenceCache>,
+    name: String,
+) -> Result<Option<ReferenceData>, BiometricError> {
+    cache.get(&name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache() -> ReferenceCache {
+        ReferenceCache::with_connection(Connection::open_in_memory().unwrap(), [3; 32]).unwrap()
+    }
+
+    #[test]
+    fn sets_are_stored_encrypted_and_read_back() {
+        let cache = cache();
+        assert!(cache.get("usuarios").unwrap().is_none());
+
+        let rows = vec![serde_json::json!({ "id": "u1", "nome": "Ana Souza", "matricula": "123" })];
+        cache.store("usuarios", &rows).unwrap();
+
+        let data = cache.get("usuarios").unwrap().expect("conjunto guardado");
+        assert_eq!(data.rows, rows);
+        let stored: Vec<u8> = cache
+            .lock()
+            .query_row("SELECT rows FROM reference_sets WHERE name = 'usuarios'", [], |row| row.get(0))
+            .unwrap();
+        assert!(!String::from_utf8_lossy(&stored).contains("Ana Souza"));
+    }
+
+    #[test]
+    fn swapped_sets_do_not_decrypt() {
+        let cache = cache();
+        cache.store("usuarios", &[serde_json::json!({ "id": "u1" })]).unwrap();
+        cache.store("bases", &[serde_json::json!({ "id": "b1" })]).unwrap();
+        cache
+            .lock()
+            .execute_batch(
+                "UPDATE reference_sets SET rows = (SELECT rows FROM reference_sets WHERE name = 'usuarios'),
+                                           nonce = (SELECT nonce FROM reference_sets WHERE name = 'usuarios')
+                 WHERE name = 'bases'",
+            )
+            .unwrap();
+        assert!(cache.get("bases").is_err());
+
+        let other_key = ReferenceCache {
+            conn: cache.conn.clone(),
+            cipher: Arc::new(Aes256Gcm::new(&[4; 32].into())),
+        };
+        assert!(other_key.get("usuarios").is_err());
+    }
+
+    #[test]
+    fn plain_copy_from_older_versions_is_dropped() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE reference_data (name TEXT PRIMARY KEY, rows TEXT NOT NULL, refreshed_at TEXT NOT NULL);
+             INSERT INTO reference_data VALUES ('usuarios', '[{\"nome\":\"Ana\"}]', '2026-01-01T00:00:00Z');",
+        )
+        .unwrap();
+        let cache = ReferenceCache::with_connection(conn, [3; 32]).unwrap();
+        let plain_tables: i64 = cache
+            .lock()
+            .query_row("SELECT COUNT(*) FROM sqlite_master WHERE name = 'reference_data'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(plain_tables, 0);
+        assert!(cache.get("usuarios").unwrap().is_none());
+    }
+}
@@ -2,6 +2,8 @@ use serde::Serialize;
 use std::path::PathBuf;
 use tauri::AppHandle;
 
+use crate::biometric_error::BiometricError;
+
 #[derive(Serialize, Clone)]
 pub struct SdkStatus {
     pub dll_found: bool,
@@ -44,7 +46,7 @@ pub fn check_dll_exists() -> (bool, Option<PathBuf>) {
 pub fn check_driver_installed() -> bool {
     // Método 1: Verificar usando pnputil para listar drivers instalados
     if let Ok(output) = std::process::Command::new("pnputil")
-        .args(&["/enum-drivers"])
+        .args(["/enum-drivers"])
         .output()
     {
         // Usar lossy conversion para evitar erro se o terminal Windows usar encoding não-UTF8 (ex: CP850)
@@ -102,37 +104,37 @@ pub fn check_driver_installed() -> bool {
 }
 
 /// Copia a DLL do SDK bundled para o diretório do aplicativo
-pub fn sync_sdk_dll(app: &AppHandle) -> Result<PathBuf, String> {
+pub fn sync_sdk_dll(app: &AppHandle) -> Result<PathBuf, BiometricError> {
     use tauri::Manager;
     
     // Obter o diretório de recursos do app
     let resource_dir = app
         .path()
         .resource_dir()
-        .map_err(|e| format!("Erro ao obter resource_dir: {}", e))?;
+        .map_err(|e| BiometricError::Io(format!("Erro ao obter resource_dir: {}", e)))?;
 
     // Procurar a DLL em resources/ primeiro
     let source_dll = resource_dir.join("libcidbio.dll");
     
     if !source_dll.exists() {
-        return Err(format!(
+        return Err(BiometricError::Io(format!(
             "DLL do SDK não encontrada em: {:?}",
             source_dll
-        ));
+        )));
     }
 
     // Obter o diretório do executável
     let exe_dir = std::env::current_exe()
-        .map_err(|e| format!("Erro ao obter caminho do executável: {}", e))?
+        .map_err(|e| BiometricError::Io(format!("Erro ao obter caminho do executável: {}", e)))?
         .parent()
-        .ok_or("Erro ao obter diretório do executável")?
+        .ok_or_else(|| BiometricError::Io("Erro ao obter diretório do executável".to_string()))?
         .to_path_buf();
 
     let dest_dll = exe_dir.join("libcidbio.dll");
 
     // Copiar a DLL
     std::fs::copy(&source_dll, &dest_dll)
-        .map_err(|e| format!("Erro ao copiar DLL: {}", e))?;
+        .map_err(|e| BiometricError::Io(format!("Erro ao copiar DLL: {}", e)))?;
 
     log::info!("DLL copiada com sucesso para: {:?}", dest_dll);
     Ok(dest_dll)
@@ -171,13 +173,13 @@ pub fn get_sdk_status(_app: Option<&AppHandle>) -> SdkStatus {
 
 /// Comando Tauri: Verifica o status do SDK
 #[tauri::command]
-pub fn check_sdk_status(app: AppHandle) -> Result<SdkStatus, String> {
+pub fn check_sdk_status(app: AppHandle) -> Result<SdkStatus, BiometricError> {
     Ok(get_sdk_status(Some(&app)))
 }
 
 /// Comando Tauri: Sincroniza os arquivos do SDK
 #[tauri::command]
-pub fn sync_sdk_files(app: AppHandle) -> Result<SdkStatus, String> {
+pub fn sync_sdk_files(app: AppHandle) -> Result<SdkStatus, BiometricError> {
     // Tentar copiar a DLL
     match sync_sdk_dll(&app) {
         Ok(path) => {
@@ -0,0 +1,416 @@
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::audit_log::{record_event, AuditCategory};
+use crate::biometric_error::BiometricError;
+use crate::station_config::app_data_dir;
+use crate::util::{base64_engine, random_bytes};
+
+/// URL do projeto Supabase
+pub const SUPABASE_URL: &str = "supabase_url";
+/// Chave `anon` do projeto (pública, mas fica junto das demais)
+pub const SUPABASE_ANON_KEY: &str = "supabase_anon_key";
+/// Sessão do usuário logado (JSON de `AuthSession`)
+pub const SUPABASE_SESSION: &str = "supabase_session";
+/// Chave Ed25519 da estação (base64)
+pub const STATION_SIGNING_KEY: &str = "station_signing_key";
+/// Chave AES do cache local de templates (base64)
+pub const TEMPLATE_CACHE_KEY: &str = "template_cache_key";
+/// Chave AES do cache local de dados de referência (base64)
+pub const REFERENCE_CACHE_KEY: &str = "reference_cache_key";
+
+/// Argumento de linha de comando que recria um cofre que não decifra (`SecretStore::reset`)
+pub const RESET_ARG: &str = "--reset-secret-store";
+
+/// Versão do formato de `secrets.bin`
+const STORE_VERSION: u32 = 2;
+/// Versão com a chave derivada do `MachineGuid`, lida só para migrar para a atual
+#[cfg(windows)]
+const LEGACY_VERSION: u32 = 1;
+
+/// Conteúdo de `secrets.bin`: a chave protegida (no Windows) e o texto cifrado.
+#[derive(Serialize, Deserialize)]
+struct SealedFile {
+    version: u32,
+    /// Chave do cofre protegida pelo DPAPI da conta do Windows (base64); nos outros sistemas
+    /// ela fica em `secrets.key`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    protected_key: Option<String>,
+    /// Sal (base64) da versão 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    salt: Option<String>,
+    /// Nonce AES-GCM (base64), novo a cada gravação
+    nonce: String,
+    /// JSON dos segredos cifrado com AES-256-GCM (base64)
+    ciphertext: String,
+}
+
+struct Inner {
+    key: [u8; 32],
+    /// `protected_key` gravado junto do cofre
+    protected_key: Option<String>,
+    entries: BTreeMap<String, String>,
+}
+
+/// Cofre de segredos da estação (gerenciado pelo Tauri).
+///
+/// Guarda tokens de sessão, a chave de assinatura da estação e as credenciais do projeto em
+/// `secrets.bin` na pasta de dados, cifrados com AES-256-GCM sob uma chave aleatória. No
+/// Windows a chave é protegida com o DPAPI da conta que roda o app (`CryptProtectData`), então
+/// só essa conta, nesta máquina, abre o cofre. Nos outros sistemas a chave fica em `secrets.key`
+/// na pasta de dados do usuário, e o que a protege são as permissões (0700/0600).
+#[derive(Clone)]
+pub struct SecretStore {
+    inner: Arc<Mutex<Inner>>,
+}
+
+fn store_path() -> PathBuf {
+    app_data_dir().join("secrets.bin")
+}
+
+fn invalid(what: &str) -> BiometricError {
+    BiometricError::Config(format!("Cofre de segredos inválido: {}", what))
+}
+
+/// DPAPI do Windows: protege dados com a credencial da conta logada.
+#[cfg(windows)]
+mod dpapi {
+    use windows_sys::Win32::Foundation::LocalFree;
+    use windows_sys::Win32::Security::Cryptography::{
+        CryptProtectData, CryptUnprotectData, CRYPTPROTECT_UI_FORBIDDEN, CRYPT_INTEGER_BLOB,
+    };
+
+    use crate::biometric_error::BiometricError;
+
+    /// Entropia adicional: outro programa da mesma conta não abre o blob só chamando o DPAPI
+    const ENTROPY: &[u8] = b"almoxarifado-desktop/secret-store/v2";
+
+    fn blob(data: &[u8]) -> CRYPT_INTEGER_BLOB {
+        CRYPT_INTEGER_BLOB {
+            cbData: data.len() as u32,
+            pbData: data.as_ptr() as *mut u8,
+        }
+    }
+
+    /// Copia a saída do DPAPI e libera o buffer que ele alocou.
+    ///
+    /// # Safety
+    /// `output` precisa ter vindo de uma chamada bem-sucedida do DPAPI.
+    unsafe fn take(output: CRYPT_INTEGER_BLOB) -> Vec<u8> {
+        let bytes = std::slice::from_raw_parts(output.pbData, output.cbData as usize).to_vec();
+        LocalFree(output.pbData.cast());
+        bytes
+    }
+
+    pub fn protect(data: &[u8]) -> Result<Vec<u8>, BiometricError> {
+        let (input, entropy) = (blob(data), blob(ENTROPY));
+        let mut output = CRYPT_INTEGER_BLOB::default();
+        // SAFETY: as entradas apontam para buffers vivos durante a chamada
+        unsafe {
+            if CryptProtectData(
+                &input,
+                std::ptr::null(),
+                &entropy,
+                std::ptr::null(),
+                std::ptr::null(),
+                CRYPTPROTECT_UI_FORBIDDEN,
+                &mut output,
+            ) == 0
+            {
+                return Err(BiometricError::Config(format!(
+                    "Erro ao proteger a chave do cofre (DPAPI): {}",
+                    std::io::Error::last_os_error()
+                )));
+            }
+            Ok(take(output))
+        }
+    }
+
+    /// Falha se o blob foi protegido por outra conta ou em outra máquina.
+    pub fn unprotect(data: &[u8]) -> Result<Vec<u8>, BiometricError> {
+        let (input, entropy) = (blob(data), blob(ENTROPY));
+        let mut output = CRYPT_INTEGER_BLOB::default();
+        // SAFETY: as entradas apontam para buffers vivos durante a chamada
+        unsafe {
+            if CryptUnprotectData(
+                &input,
+                std::ptr::null_mut(),
+                &entropy,
+                std::ptr::null(),
+                std::ptr::null(),
+                CRYPTPROTECT_UI_FORBIDDEN,
+                &mut output,
+            ) == 0
+            {
+                return Err(super::invalid(&format!(
+                    "a chave não abre nesta conta do Windows ({})",
+                    std::io::Error::last_os_error()
+                )));
+            }
+            Ok(take(output))
+        }
+    }
+}
+
+/// Guarda uma chave nova do cofre: protegida pelo DPAPI, para ir em `secrets.bin`.
+#[cfg(windows)]
+fn seal_key(key: &[u8; 32]) -> Result<Option<String>, BiometricError> {
+    Ok(Some(base64_engine().encode(dpapi::protect(key)?)))
+}
+
+/// Recupera a chave do cofre gravada por `seal_key`.
+#[cfg(windows)]
+fn unseal_key(sealed: &SealedFile) -> Result<[u8; 32], BiometricError> {
+    let protected = sealed
+        .protected_key
+        .as_deref()
+        .and_then(|k| base64_engine().decode(k).ok())
+        .ok_or_else(|| invalid("chave"))?;
+    dpapi::unprotect(&protected)?
+        .try_into()
+        .map_err(|_| invalid("chave"))
+}
+
+/// Arquivo da chave do cofre fora do Windows
+#[cfg(not(windows))]
+fn key_path() -> PathBuf {
+    app_data_dir().join("secrets.key")
+}
+
+/// Guarda uma chave nova do cofre: em `secrets.key`, legível só pelo usuário.
+#[cfg(not(windows))]
+fn seal_key(key: &[u8; 32]) -> Result<Option<String>, BiometricError> {
+    write_private(&key_path(), base64_engine().encode(key).as_bytes())?;
+    Ok(None)
+}
+
+/// Recupera a chave do cofre gravada por `seal_key`.
+#[cfg(not(windows))]
+fn unseal_key(_sealed: &SealedFile) -> Result<[u8; 32], BiometricError> {
+    let text = std::fs::read_to_string(key_path()).map_err(|e| invalid(&format!("secrets.key ({})", e)))?;
+    base64_engine()
+        .decode(text.trim())
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| invalid("secrets.key"))
+}
+
+/// Chave da versão 1, derivada do `MachineGuid` e de um sal. Qualquer um que lesse o registro
+/// chegava nela, então só serve para ler um cofre antigo e regravá-lo na versão atual.
+#[cfg(windows)]
+fn legacy_key(salt: &str) -> Result<[u8; 32], BiometricError> {
+    use sha2::{Digest, Sha256};
+    use winreg::enums::{HKEY_LOCAL_MACHINE, KEY_READ, KEY_WOW64_64KEY};
+    use winreg::RegKey;
+
+    let salt = base64_engine().decode(salt).map_err(|_| invalid("sal"))?;
+    let machine: String = RegKey::predef(HKEY_LOCAL_MACHINE)
+        .open_subkey_with_flags("SOFTWARE\\Microsoft\\Cryptography", KEY_READ | KEY_WOW64_64KEY)
+        .and_then(|key| key.get_value("MachineGuid"))
+        .map_err(|e| BiometricError::Config(format!("Erro ao ler MachineGuid: {}", e)))?;
+    let mut hasher = Sha256::new();
+    hasher.update(b"almoxarifado-desktop/secret-store/v1");
+    hasher.update(machine.as_bytes());
+    hasher.update(&salt);
+    Ok(hasher.finalize().into())
+}
+
+/// Grava trocando o arquivo de uma vez (via `.tmp`), com permissão só do dono no Unix.
+fn write_private(path: &Path, content: &[u8]) -> Result<(), BiometricError> {
+    use std::io::Write;
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("tmp");
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(&tmp)?.write_all(content)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn decrypt(key: &[u8; 32], sealed: &SealedFile) -> Result<BTreeMap<String, String>, BiometricError> {
+    let nonce: [u8; 12] = base64_engine()
+        .decode(&sealed.nonce)
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| invalid("nonce"))?;
+    let ciphertext = base64_engine().decode(&sealed.ciphertext).map_err(|_| invalid("conteúdo"))?;
+    let plain = Aes256Gcm::new(&(*key).into())
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+        .map_err(|_| invalid("não foi possível decifrar (chave de outra conta ou arquivo alterado)"))?;
+    Ok(serde_json::from_slice(&plain)?)
+}
+
+fn open_sealed(sealed: &SealedFile) -> Result<Inner, BiometricError> {
+    #[cfg(windows)]
+    if sealed.version == LEGACY_VERSION {
+        let legacy = legacy_key(sealed.salt.as_deref().ok_or_else(|| invalid("sal"))?)?;
+        let entries = decrypt(&legacy, sealed)?;
+        let key = random_bytes::<32>()?;
+        let inner = Inner {
+            key,
+            protected_key: seal_key(&key)?,
+            entries,
+        };
+        persist(&inner)?;
+        log::info!("🔐 Cofre de segredos migrado para a chave protegida pelo DPAPI");
+        return Ok(inner);
+    }
+
+    if sealed.version != STORE_VERSION {
+        return Err(invalid("versão desconhecida"));
+    }
+    let key = unseal_key(sealed)?;
+    let entries = decrypt(&key, sealed)?;
+    Ok(Inner {
+        key,
+        protected_key: sealed.protected_key.clone(),
+        entries,
+    })
+}
+
+impl SecretStore {
+    /// Abre o cofre da estação, criando um vazio na primeira execução.
+    ///
+    /// Um cofre que não decifra (de outra conta ou máquina, ou corrompido) não é substituído:
+    /// ele guarda a chave da estação, então o app não abre até alguém recriá-lo de propósito
+    /// com `RESET_ARG`. A falha vai para a auditoria.
+    pub fn open() -> Result<Self, BiometricError> {
+        let path = store_path();
+
+        let opened = match std::fs::read(&path) {
+            Ok(bytes) => match serde_json::from_slice::<SealedFile>(&bytes)
+                .map_err(BiometricError::from)
+                .and_then(|sealed| open_sealed(&sealed))
+            {
+                Ok(inner) => Some(inner),
+                Err(e) => {
+                    log::error!("❌ {}; o cofre em {:?} não foi alterado", e, path);
+                    record_event(
+                        AuditCategory::Security,
+                        "secret_store_unreadable",
+                        None,
+                        serde_json::json!({ "path": path, "error": e.to_string() }),
+                    );
+                    return Err(BiometricError::Config(format!(
+                        "{}. Para descartá-lo e criar um cofre vazio (sessão e chave da estação \
+                         novas), abra o app com {}",
+                        e, RESET_ARG
+                    )));
+                }
+            },
+            Err(_) => None,
+        };
+
+        let inner = match opened {
+            Some(inner) => inner,
+            None => {
+                let key = random_bytes::<32>()?;
+                let inner = Inner {
+                    key,
+                    protected_key: seal_key(&key)?,
+                    entries: BTreeMap::new(),
+                };
+                persist(&inner)?;
+                log::info!("🔐 Cofre de segredos criado em {:?}", path);
+                inner
+            }
+        };
+
+        let store = Self {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+        store.seed_project_config()?;
+        Ok(store)
+    }
+
+    /// Descarta o cofre atual, guardando-o como `secrets.bin.invalido-<data>`; o próximo
+    /// `open` cria um vazio. A estação ganha uma chave nova, que um administrador precisa
+    /// trocar em `station_keys`.
+    pub fn reset() -> Result<(), BiometricError> {
+        let path = store_path();
+        if !path.exists() {
+            return Ok(());
+        }
+        let backup = path.with_extension(format!("bin.invalido-{}", chrono::Local::now().format("%Y%m%d%H%M%S")));
+        std::fs::rename(&path, &backup)?;
+        log::warn!("⚠️ Cofre de segredos descartado a pedido ({:?})", backup);
+        record_event(
+            AuditCategory::Security,
+            "secret_store_reset",
+            None,
+            serde_json::json!({ "backup": backup }),
+        );
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    /// Credenciais do projeto embutidas no build (lidas do `.env` pelo `build.rs`).
+    fn seed_project_config(&self) -> Result<(), BiometricError> {
+        let built = [
+            (SUPABASE_URL, option_env!("ALMOX_SUPABASE_URL")),
+            (SUPABASE_ANON_KEY, option_env!("ALMOX_SUPABASE_ANON_KEY")),
+        ];
+        for (name, value) in built {
+            if let Some(value) = value.filter(|v| !v.trim().is_empty()) {
+                if self.get(name).as_deref() != Some(value) {
+                    self.set(name, value)?;
+                }
+            }
+        }
+        if self.get(SUPABASE_URL).is_none() {
+            log::warn!("⚠️ Projeto Supabase não configurado no build (VITE_SUPABASE_URL)");
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.lock().entries.get(name).cloned()
+    }
+
+    pub fn set(&self, name: &str, value: &str) -> Result<(), BiometricError> {
+        let mut inner = self.lock();
+        inner.entries.insert(name.to_string(), value.to_string());
+        persist(&inner)
+    }
+
+    pub fn remove(&self, name: &str) -> Result<(), BiometricError> {
+        let mut inner = self.lock();
+        if inner.entries.remove(name).is_some() {
+            persist(&inner)?;
+        }
+        Ok(())
+    }
+}
+
+/// Cifra e grava o cofre inteiro (com nonce novo).
+fn persist(inner: &Inner) -> Result<(), BiometricError> {
+    let nonce = random_bytes::<12>()?;
+    let plain = serde_json::to_vec(&inner.entries)?;
+    let ciphertext = Aes256Gcm::new(&inner.key.into())
+        .encrypt(Nonce::from_slice(&nonce), plain.as_ref())
+        .map_err(|_| BiometricError::Io("Erro ao cifrar o cofre de segredos".to_string()))?;
+    let sealed = SealedFile {
+        version: STORE_VERSION,
+        protected_key: inner.protected_key.clone(),
+        salt: None,
+        nonce: base64_engine().encode(nonce),
+        ciphertext: base64_engine().encode(ciphertext),
+    };
+    write_private(&store_path(), &serde_json::to_vec(&sealed)?)
+}
@@ -0,0 +1,293 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::audit_log::{record_event, AuditCategory};
+use crate::biometric_error::BiometricError;
+use crate::biometric_templates::require_supervisor;
+use crate::supabase_auth::SupabaseAuth;
+
+/// Onde fica a pasta de dados: `%PROGRAMDATA%\AlmoxarifadoDesktop` no Windows e a pasta de
+/// configuração do usuário (`$XDG_CONFIG_HOME` ou `~/.config`) nos outros sistemas.
+#[cfg(windows)]
+fn data_dir_location() -> Option<PathBuf> {
+    std::env::var_os("PROGRAMDATA")
+        .filter(|p| !p.is_empty())
+        .map(|p| PathBuf::from(p).join("AlmoxarifadoDesktop"))
+}
+
+#[cfg(not(windows))]
+fn data_dir_location() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|p| !p.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .filter(|p| !p.is_empty())
+                .map(|home| PathBuf::from(home).join(".config"))
+        })
+        .map(|p| p.join("almoxarifado-desktop"))
+}
+
+/// Pasta de dados da estação. A inicialização confere que ela existe com `prepare_app_data_dir`.
+pub fn app_data_dir() -> PathBuf {
+    data_dir_location().expect("pasta de dados indisponível (PROGRAMDATA/HOME não definidos)")
+}
+
+/// Cria a pasta de dados e restringe o acesso a ela antes de qualquer gravação: no Windows só
+/// o sistema, os administradores e a conta que roda o app; nos outros sistemas, permissão 0700.
+/// Sem `PROGRAMDATA`/`HOME` o app não abre (nada vai para a pasta temporária, que todos gravam).
+pub fn prepare_app_data_dir() -> Result<PathBuf, BiometricError> {
+    let dir = data_dir_location().ok_or_else(|| {
+        BiometricError::Config("Pasta de dados indisponível: PROGRAMDATA/HOME não definidos".to_string())
+    })?;
+    std::fs::create_dir_all(&dir)?;
+    restrict_dir(&dir)?;
+    Ok(dir)
+}
+
+#[cfg(unix)]
+fn restrict_dir(dir: &Path) -> Result<(), BiometricError> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+    Ok(())
+}
+
+/// Troca a ACL da pasta por uma protegida (sem herdar de `ProgramData`, onde qualquer usuário
+/// cria arquivos); os arquivos que já estão nela passam a herdar a nova.
+#[cfg(windows)]
+fn restrict_dir(dir: &Path) -> Result<(), BiometricError> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Foundation::{LocalFree, ERROR_SUCCESS};
+    use windows_sys::Win32::Security::Authorization::{
+        ConvertStringSecurityDescriptorToSecurityDescriptorW, SetNamedSecurityInfoW, SDDL_REVISION_1, SE_FILE_OBJECT,
+    };
+    use windows_sys::Win32::Security::{
+        GetSecurityDescriptorDacl, ACL, DACL_SECURITY_INFORMATION, PROTECTED_DACL_SECURITY_INFORMATION,
+        PSECURITY_DESCRIPTOR,
+    };
+
+    let wide = |text: &std::ffi::OsStr| text.encode_wide().chain(Some(0)).collect::<Vec<u16>>();
+    let os_error = |call: &str| {
+        BiometricError::Config(format!(
+            "Erro ao restringir a pasta de dados ({}): {}",
+            call,
+            std::io::Error::last_os_error()
+        ))
+    };
+
+    // Sistema, administradores e a conta atual, com herança para arquivos e subpastas
+    let sddl = format!("D:P(A;OICI;FA;;;SY)(A;OICI;FA;;;BA)(A;OICI;FA;;;{})", current_user_sid()?);
+    let sddl = wide(std::ffi::OsStr::new(&sddl));
+    let path = wide(dir.as_os_str());
+
+    let mut descriptor: PSECURITY_DESCRIPTOR = std::ptr::null_mut();
+    // SAFETY: `sddl` termina em zero; o descritor alocado é liberado com LocalFree abaixo
+    if unsafe {
+        ConvertStringSecurityDescriptorToSecurityDescriptorW(
+            sddl.as_ptr(),
+            SDDL_REVISION_1,
+            &mut descriptor,
+            std::ptr::null_mut(),
+        )
+    } == 0
+    {
+        return Err(os_error("ConvertStringSecurityDescriptorToSecurityDescriptorW"));
+    }
+
+    let mut present = 0;
+    let mut defaulted = 0;
+    let mut dacl: *mut ACL = std::ptr::null_mut();
+    // SAFETY: `descriptor` veio da conversão acima e `path` termina em zero
+    let result = unsafe {
+        if GetSecurityDescriptorDacl(descriptor, &mut present, &mut dacl, &mut defaulted) == 0 {
+            Err(os_error("GetSecurityDescriptorDacl"))
+        } else {
+            let status = SetNamedSecurityInfoW(
+                path.as_ptr(),
+                SE_FILE_OBJECT,
+                DACL_SECURITY_INFORMATION | PROTECTED_DACL_SECURITY_INFORMATION,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                dacl,
+                std::ptr::null(),
+            );
+            if status == ERROR_SUCCESS {
+                Ok(())
+            } else {
+                Err(BiometricError::Config(format!(
+                    "Erro ao restringir a pasta de dados {:?}: {}",
+                    dir,
+                    std::io::Error::from_raw_os_error(status as i32)
+                )))
+            }
+        }
+    };
+    // SAFETY: alocado por ConvertStringSecurityDescriptorToSecurityDescriptorW
+    unsafe { LocalFree(descriptor) };
+    result
+}
+
+/// SID (texto `S-1-5-...`) da conta que roda o app.
+#[cfg(windows)]
+fn current_user_sid() -> Result<String, BiometricError> {
+    use windows_sys::Win32::Foundation::{CloseHandle, LocalFree, HANDLE};
+    use windows_sys::Win32::Security::Authorization::ConvertSidToStringSidW;
+    use windows_sys::Win32::Security::{GetTokenInformation, TokenUser, TOKEN_QUERY, TOKEN_USER};
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    let os_error = |call: &str| {
+        BiometricError::Config(format!(
+            "Erro ao ler a conta do Windows ({}): {}",
+            call,
+            std::io::Error::last_os_error()
+        ))
+    };
+
+    let mut token: HANDLE = std::ptr::null_mut();
+    // SAFETY: GetCurrentProcess devolve um pseudo-handle; o token aberto é fechado no fim
+    if unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) } == 0 {
+        return Err(os_error("OpenProcessToken"));
+    }
+
+    // u64 para o buffer ficar alinhado como TOKEN_USER
+    let mut buffer = vec![0u64; 64];
+    let mut needed = 0u32;
+    // SAFETY: `buffer` tem o tamanho informado; o SID aponta para dentro dele enquanto existir
+    let result = unsafe {
+        if GetTokenInformation(
+            token,
+            TokenUser,
+            buffer.as_mut_ptr().cast(),
+            (buffer.len() * std::mem::size_of::<u64>()) as u32,
+            &mut needed,
+        ) == 0
+        {
+            Err(os_error("GetTokenInformation"))
+        } else {
+            let user = &*(buffer.as_ptr() as *const TOKEN_USER);
+            let mut text = std::ptr::null_mut();
+            if ConvertSidToStringSidW(user.User.Sid, &mut text) == 0 {
+                Err(os_error("ConvertSidToStringSidW"))
+            } else {
+                let len = (0..).take_while(|&i| *text.add(i) != 0).count();
+                let sid = String::from_utf16_lossy(std::slice::from_raw_parts(text, len));
+                LocalFree(text.cast());
+                Ok(sid)
+            }
+        }
+    };
+    // SAFETY: aberto por OpenProcessToken acima
+    unsafe { CloseHandle(token) };
+    result
+}
+
+/// Nome da estação (nome do computador), para rastrear de onde veio cada operação.
+pub fn station_id() -> String {
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_else(|_| "desconhecida".to_string())
+}
+
+/// Configuração local da estação, em `station.json` na pasta de dados.
+///
+/// Guarda o que só vale para este computador (ex.: em qual porta COM o leitor
+/// foi encontrado), para não refazer a detecção a cada inicialização.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct StationConfig {
+    /// Última porta onde o leitor respondeu (ex.: "COM3")
+    #[serde(default)]
+    pub biometric_port: Option<String>,
+    /// Quando a porta foi detectada (RFC 3339)
+    #[serde(default)]
+    pub biometric_port_detected_at: Option<String>,
+    /// Base atendida por esta estação (templates mantidos no cache local), definida por um
+    /// supervisor em `set_station_base`
+    #[serde(default)]
+    pub base_id: Option<String>,
+}
+
+impl StationConfig {
+    pub fn path() -> PathBuf {
+        app_data_dir().join("station.json")
+    }
+
+    /// Lê a configuração; arquivo ausente ou inválido vira a configuração padrão.
+    pub fn load() -> Self {
+        let path = Self::path();
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) => return Self::default(),
+        };
+        match serde_json::from_str(&content) {
+            Ok(config) => config,
+            Err(e) => {
+                log::warn!("⚠️ Configuração da estação inválida em {:?}: {}. Usando padrão.", path, e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<(), BiometricError> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(&path, content)?;
+        record_event(AuditCategory::Config, "station_config_saved", None, serde_json::to_value(self)?);
+        Ok(())
+    }
+
+    /// Salva a porta onde o leitor foi encontrado.
+    pub fn remember_biometric_port(port: &str) {
+        let mut config = Self::load();
+        if config.biometric_port.as_deref() == Some(port) {
+            return;
+        }
+        config.biometric_port = Some(port.to_string());
+        config.biometric_port_detected_at = Some(chrono::Local::now().to_rfc3339());
+        match config.save() {
+            Ok(()) => log::info!("💾 Porta do leitor salva na configuração da estação: {}", port),
+            Err(e) => log::warn!("⚠️ Não foi possível salvar a porta do leitor: {}", e),
+        }
+    }
+}
+
+/// Comando Tauri: base atendida pela estação (`None` = ainda não definida).
+#[tauri::command]
+pub fn get_station_base() -> Option<String> {
+    StationConfig::load().base_id
+}
+
+/// Comando Tauri: define a base atendida pela estação. Só para supervisores: a base decide
+/// quais templates ficam no cache offline e quais mudanças chegam pelo Realtime, então não é
+/// aprendida das operações feitas na estação. `None` desfaz a escolha.
+#[tauri::command]
+pub async fn set_station_base(
+    auth: tauri::State<'_, SupabaseAuth>,
+    base_id: Option<String>,
+) -> Result<Option<String>, BiometricError> {
+    let db = auth.connect().await?;
+    require_supervisor(&db).await?;
+    if let Some(base_id) = &base_id {
+        let bases: Vec<serde_json::Value> = db.from("bases").select("id").eq("id", base_id).fetch_all().await?;
+        if bases.is_empty() {
+            return Err(BiometricError::Config(format!("Base {} não encontrada", base_id)));
+        }
+    }
+
+    let mut config = StationConfig::load();
+    if config.base_id == base_id {
+        return Ok(base_id);
+    }
+    config.base_id = base_id.clone();
+    config.save()?;
+    log::info!("🏢 Base da estação definida: {:?}", base_id);
+    record_event(
+        AuditCategory::Config,
+        "station_base_set",
+        auth.user_id().as_deref(),
+        serde_json::json!({ "base_id": base_id }),
+    );
+    Ok(base_id)
+}
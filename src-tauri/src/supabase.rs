@@ -0,0 +1,416 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::biometric_error::BiometricError;
+
+/// Linhas por página em `fetch_all`
+pub const PAGE_SIZE: usize = 1000;
+
+/// Códigos do PostgREST para JWT ausente, inválido ou expirado
+const JWT_ERROR_CODES: &[&str] = &["PGRST301", "PGRST302", "PGRST303"];
+
+/// `insufficient_privilege`: levantado pelas funções que conferem o papel do usuário
+const PRIVILEGE_ERROR_CODE: &str = "42501";
+
+/// Cliente HTTP da aplicação: um só, com pool de conexões, para todas as chamadas ao Supabase.
+pub fn http() -> &'static reqwest::Client {
+    static HTTP: OnceLock<reqwest::Client> = OnceLock::new();
+    HTTP.get_or_init(|| {
+        reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(10))
+            .timeout(Duration::from_secs(30))
+            .pool_idle_timeout(Duration::from_secs(90))
+            .build()
+            .expect("cliente HTTP com configuração fixa")
+    })
+}
+
+/// Roda uma chamada assíncrona a partir do código bloqueante (threads de `spawn_blocking`,
+/// que conversam com o leitor e não podem usar `.await`).
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    tauri::async_runtime::block_on(future)
+}
+
+/// Corpo de erro do PostgREST (`{ code, message, details, hint }`).
+#[derive(Deserialize, Default)]
+struct PostgrestError {
+    #[serde(default)]
+    code: Option<String>,
+    #[serde(default)]
+    message: Option<String>,
+    #[serde(default)]
+    details: Option<String>,
+    #[serde(default)]
+    hint: Option<String>,
+}
+
+/// Erro para uma resposta sem sucesso do Supabase, a partir do corpo de erro do PostgREST.
+pub async fn api_error(context: &str, resp: reqwest::Response) -> BiometricError {
+    let status = resp.status().as_u16();
+    let body = resp.text().await.unwrap_or_default();
+    let error: PostgrestError = serde_json::from_str(&body).unwrap_or_default();
+
+    // JWT expirado ou revogado: o frontend precisa refazer o login
+    let jwt_rejected = error.code.as_deref().is_some_and(|c| JWT_ERROR_CODES.contains(&c));
+    if status == 401 || jwt_rejected {
+        log::warn!("⚠️ {}: sessão recusada pelo Supabase ({})", context, body);
+        return BiometricError::Unauthenticated;
+    }
+    if error.code.as_deref() == Some(PRIVILEGE_ERROR_CODE) {
+        return BiometricError::Forbidden(error.message.unwrap_or(body));
+    }
+
+    let mut detail = match (&error.message, &error.code) {
+        (Some(message), Some(code)) => format!("{}: {} ({})", context, message, code),
+        (Some(message), None) => format!("{}: {}", context, message),
+        _ => format!("{}: {}", context, body),
+    };
+    if let Some(details) = error.details.filter(|d| !d.is_empty()) {
+        detail.push_str(&format!(" - {}", details));
+    }
+    if let Some(hint) = error.hint.filter(|h| !h.is_empty()) {
+        detail.push_str(&format!(" (dica: {})", hint));
+    }
+    BiometricError::Http {
+        status: Some(status),
+        detail,
+    }
+}
+
+async fn parse_json<T: DeserializeOwned>(resp: reqwest::Response) -> Result<T, BiometricError> {
+    resp.json().await.map_err(|e| BiometricError::Parse(e.to_string()))
+}
+
+/// Conexão com o projeto: cliente compartilhado, URL, chave anon e JWT do usuário logado.
+///
+/// Obtida com `SupabaseAuth::connect`; barata de clonar.
+#[derive(Clone)]
+pub struct Supabase {
+    url: String,
+    anon_key: String,
+    access_token: String,
+}
+
+impl Supabase {
+    pub fn new(url: &str, anon_key: &str, access_token: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            anon_key: anon_key.to_string(),
+            access_token: access_token.to_string(),
+        }
+    }
+
+    /// JWT do usuário (canal do Realtime).
+    pub fn access_token(&self) -> &str {
+        &self.access_token
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        http()
+            .request(method, format!("{}/rest/v1/{}", self.url, path))
+            .header("apikey", &self.anon_key)
+            .bearer_auth(&self.access_token)
+    }
+
+    /// Consulta/escrita em uma tabela ou view.
+    pub fn from(&self, table: &str) -> Query<'_> {
+        Query::new(self, table.to_string())
+    }
+
+    /// Função `stable` chamada por GET, para filtrar e paginar o resultado; os argumentos
+    /// vão com `arg`.
+    pub fn rpc_query(&self, function: &str) -> Query<'_> {
+        Query::new(self, format!("rpc/{}", function))
+    }
+
+    /// Chama uma função do banco (`/rest/v1/rpc/<function>`) com argumentos nomeados.
+    ///
+    /// As operações privilegiadas com templates passam por funções `security definer`
+    /// (veja `supabase/biometric_rpc.sql`), que conferem o usuário logado.
+    pub async fn rpc<T: DeserializeOwned>(&self, function: &str, args: &serde_json::Value) -> Result<T, BiometricError> {
+        log::debug!("HTTP POST rpc/{}", function);
+        let resp = self
+            .request(reqwest::Method::POST, &format!("rpc/{}", function))
+            .json(args)
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            return Err(api_error(&format!("Falha em rpc/{}", function), resp).await);
+        }
+        parse_json(resp).await
+    }
+}
+
+/// Consulta PostgREST montada aos poucos: `select`, filtros, `order` e paginação.
+///
+/// Os valores vão pela query string codificada (`reqwest::RequestBuilder::query`), então
+/// ids e textos com `&`, `,` ou espaços não quebram a URL.
+pub struct Query<'a> {
+    db: &'a Supabase,
+    path: String,
+    params: Vec<(String, String)>,
+    order: Vec<String>,
+}
+
+/// Valor dentro de `in.(...)`: entre aspas, com `"` e `\` escapados
+fn quote_list_value(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl<'a> Query<'a> {
+    fn new(db: &'a Supabase, path: String) -> Self {
+        Self {
+            db,
+            path,
+            params: Vec::new(),
+            order: Vec::new(),
+        }
+    }
+
+    pub fn select(mut self, columns: &str) -> Self {
+        self.params.push(("select".to_string(), columns.to_string()));
+        self
+    }
+
+    /// Filtro genérico `coluna=operador.valor` (ex.: `filter("quality", "gte", 60)`).
+    pub fn filter(mut self, column: &str, operator: &str, value: impl std::fmt::Display) -> Self {
+        self.params.push((column.to_string(), format!("{}.{}", operator, value)));
+        self
+    }
+
+    pub fn eq(self, column: &str, value: impl std::fmt::Display) -> Self {
+        self.filter(column, "eq", value)
+    }
+
+    pub fn in_list<S: AsRef<str>>(self, column: &str, values: &[S]) -> Self {
+        let list: Vec<String> = values.iter().map(|v| quote_list_value(v.as_ref())).collect();
+        self.filter(column, "in", format!("({})", list.join(",")))
+    }
+
+    /// Argumento nomeado de uma função chamada com `rpc_query`.
+    pub fn arg(mut self, name: &str, value: impl std::fmt::Display) -> Self {
+        self.params.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Ordenação; chamadas seguidas viram critérios de desempate.
+    pub fn order(mut self, column: &str, ascending: bool) -> Self {
+        self.order
+            .push(format!("{}.{}", column, if ascending { "asc" } else { "desc" }));
+        self
+    }
+
+    /// Página `offset..offset + limit`.
+    pub fn range(mut self, offset: usize, limit: usize) -> Self {
+        self.params.push(("offset".to_string(), offset.to_string()));
+        self.params.push(("limit".to_string(), limit.to_string()));
+        self
+    }
+
+    fn build(&self, method: reqwest::Method, extra: &[(String, String)]) -> reqwest::RequestBuilder {
+        let mut params = self.params.clone();
+        if !self.order.is_empty() {
+            params.push(("order".to_string(), self.order.join(",")));
+        }
+        params.extend_from_slice(extra);
+        log::debug!("HTTP {} {} {:?}", method, self.path, params);
+        self.db.request(method, &self.path).query(&params)
+    }
+
+    async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+        action: &str,
+    ) -> Result<reqwest::Response, BiometricError> {
+        let resp = request.send().await?;
+        if !resp.status().is_success() {
+            return Err(api_error(&format!("Falha ao {} {}", action, self.path), resp).await);
+        }
+        Ok(resp)
+    }
+
+    /// Linhas da consulta (uma página, se houver `range`).
+    pub async fn fetch<T: DeserializeOwned>(self) -> Result<Vec<T>, BiometricError> {
+        let resp = self.send(self.build(reqwest::Method::GET, &[]), "consultar").await?;
+        parse_json(resp).await
+    }
+
+    /// Termina a ordenação por `id`: sem um critério único, o Postgres pode devolver linhas
+    /// empatadas em outra ordem a cada página, e o `offset` pula ou repete linhas.
+    fn unique_order(mut self) -> Self {
+        if !self.order.iter().any(|o| o.starts_with("id.")) {
+            self.order.push("id.asc".to_string());
+        }
+        self
+    }
+
+    fn page_params(offset: usize) -> [(String, String); 2] {
+        [
+            ("limit".to_string(), PAGE_SIZE.to_string()),
+            ("offset".to_string(), offset.to_string()),
+        ]
+    }
+
+    /// Todas as linhas, de `PAGE_SIZE` em `PAGE_SIZE`, até a última página. A tabela (ou o
+    /// resultado da função) precisa ter a coluna `id`, que fecha a ordenação.
+    pub async fn fetch_all<T: DeserializeOwned>(self) -> Result<Vec<T>, BiometricError> {
+        let query = self.unique_order();
+        let mut rows = Vec::new();
+        loop {
+            let page_params = Self::page_params(rows.len());
+            let resp = query
+                .send(query.build(reqwest::Method::GET, &page_params), "consultar")
+                .await?;
+            let page: Vec<T> = parse_json(resp).await?;
+            let last_page = page.len() < PAGE_SIZE;
+            rows.extend(page);
+            if last_page {
+                return Ok(rows);
+            }
+        }
+    }
+
+    /// Primeira linha da consulta, se houver.
+    pub async fn fetch_one<T: DeserializeOwned>(self) -> Result<Option<T>, BiometricError> {
+        let rows: Vec<T> = self.range(0, 1).fetch().await?;
+        Ok(rows.into_iter().next())
+    }
+
+    /// Insere uma linha (ou uma lista) sem pedir as linhas de volta.
+    pub async fn insert<B: Serialize + ?Sized>(self, body: &B) -> Result<(), BiometricError> {
+        let request = self
+            .build(reqwest::Method::POST, &[])
+            .header("Prefer", "return=minimal")
+            .json(body);
+        self.send(request, "inserir em").await?;
+        Ok(())
+    }
+
+    /// Insere ou atualiza pela chave `on_conflict`; com `ignore_duplicates`, linhas que já
+    /// existem ficam como estão.
+    pub async fn upsert<B: Serialize + ?Sized>(
+        self,
+        body: &B,
+        on_conflict: Option<&str>,
+        ignore_duplicates: bool,
+    ) -> Result<(), BiometricError> {
+        let extra: Vec<(String, String)> = on_conflict
+            .map(|columns| vec![("on_conflict".to_string(), columns.to_string())])
+            .unwrap_or_default();
+        let resolution = if ignore_duplicates {
+            "resolution=ignore-duplicates,return=minimal"
+        } else {
+            "resolution=merge-duplicates,return=minimal"
+        };
+        let request = self
+            .build(reqwest::Method::POST, &extra)
+            .header("Prefer", resolution)
+            .json(body);
+        self.send(request, "gravar em").await?;
+        Ok(())
+    }
+
+    /// Atualiza as linhas filtradas e devolve como ficaram.
+    pub async fn update<B: Serialize + ?Sized, T: DeserializeOwned>(self, body: &B) -> Result<Vec<T>, BiometricError> {
+        let request = self
+            .build(reqwest::Method::PATCH, &[])
+            .header("Prefer", "return=representation")
+            .json(body);
+        let resp = self.send(request, "atualizar").await?;
+        parse_json(resp).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn db() -> Supabase {
+        Supabase::new("https://projeto.supabase.co/", "anon", "token")
+    }
+
+    /// Parâmetros da query string já decodificados, na ordem em que foram montados
+    fn params(query: &Query, extra: &[(String, String)]) -> Vec<(String, String)> {
+        let request = query.build(reqwest::Method::GET, extra).build().unwrap();
+        assert_eq!(request.url().path(), format!("/rest/v1/{}", query.path));
+        request.url().query_pairs().map(|(k, v)| (k.into_owned(), v.into_owned())).collect()
+    }
+
+    fn pair(key: &str, value: &str) -> (String, String) {
+        (key.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn filters_select_and_order_go_into_the_query_string() {
+        let db = db();
+        let query = db
+            .from("solicitacoes_itens")
+            .select("id,status")
+            .eq("status", "pendente")
+            .order("criado_em", false)
+            .order("id", true)
+            .range(0, 50);
+        assert_eq!(
+            params(&query, &[]),
+            vec![
+                pair("select", "id,status"),
+                pair("status", "eq.pendente"),
+                pair("offset", "0"),
+                pair("limit", "50"),
+                pair("order", "criado_em.desc,id.asc"),
+            ]
+        );
+        let request = query.build(reqwest::Method::GET, &[]).build().unwrap();
+        assert_eq!(request.headers()["apikey"], "anon");
+        assert_eq!(request.headers()["authorization"], "Bearer token");
+    }
+
+    #[test]
+    fn values_with_reserved_characters_are_encoded() {
+        let db = db();
+        let query = db.from("usuarios").eq("nome", "Ana & Bia, 100%=ok?");
+        let request = query.build(reqwest::Method::GET, &[]).build().unwrap();
+        assert!(!request.url().query().unwrap().contains("Ana & Bia"));
+        assert_eq!(params(&query, &[]), vec![pair("nome", "eq.Ana & Bia, 100%=ok?")]);
+    }
+
+    #[test]
+    fn list_values_are_quoted_and_escaped() {
+        assert_eq!(quote_list_value("a,b"), "\"a,b\"");
+        assert_eq!(quote_list_value("diz \"oi\" \\ fim"), "\"diz \\\"oi\\\" \\\\ fim\"");
+
+        let db = db();
+        let query = db.from("sync").in_list("id", &["1", "a,b", "c\"d"]);
+        assert_eq!(params(&query, &[]), vec![pair("id", "in.(\"1\",\"a,b\",\"c\\\"d\")")]);
+    }
+
+    #[test]
+    fn rpc_query_sends_arguments_as_parameters() {
+        let db = db();
+        let query = db.rpc_query("biometric_base_templates").select("id,template").arg("p_base_id", "base-1");
+        assert_eq!(query.path, "rpc/biometric_base_templates");
+        assert_eq!(params(&query, &[]), vec![pair("select", "id,template"), pair("p_base_id", "base-1")]);
+    }
+
+    #[test]
+    fn fetch_all_pages_are_ordered_by_id() {
+        let db = db();
+        let query = db.from("itens_estoque").select("*").order("nome", true).unique_order();
+        assert_eq!(
+            params(&query, &Query::page_params(PAGE_SIZE)),
+            vec![
+                pair("select", "*"),
+                pair("order", "nome.asc,id.asc"),
+                pair("limit", &PAGE_SIZE.to_string()),
+                pair("offset", &PAGE_SIZE.to_string()),
+            ]
+        );
+
+        // `id` já na ordenação não se repete
+        let query = db.from("bases").order("id", false).unique_order();
+        assert_eq!(params(&query, &[]), vec![pair("order", "id.desc")]);
+    }
+}
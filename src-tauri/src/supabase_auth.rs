@@ -0,0 +1,210 @@
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tauri::Emitter;
+
+use crate::biometric_error::BiometricError;
+use crate::secret_store::{SecretStore, SUPABASE_ANON_KEY, SUPABASE_SESSION, SUPABASE_URL};
+use crate::supabase::{api_error, http, Supabase};
+
+/// Renova o token quando faltar menos que isso para expirar (segundos)
+const REFRESH_MARGIN_SECS: i64 = 60;
+
+/// Tokens da sessão do usuário logado (os mesmos do supabase-js).
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AuthSession {
+    pub access_token: String,
+    pub refresh_token: String,
+    /// Expiração do `access_token` (segundos desde 1970)
+    pub expires_at: i64,
+    pub user_id: Option<String>,
+}
+
+impl std::fmt::Debug for AuthSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthSession")
+            .field("user_id", &self.user_id)
+            .field("expires_at", &self.expires_at)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Resposta de `/auth/v1/token?grant_type=refresh_token`
+#[derive(Deserialize)]
+struct RefreshResponse {
+    access_token: String,
+    refresh_token: String,
+    #[serde(default)]
+    expires_at: Option<i64>,
+    #[serde(default)]
+    expires_in: Option<i64>,
+    #[serde(default)]
+    user: Option<RefreshUser>,
+}
+
+#[derive(Deserialize)]
+struct RefreshUser {
+    id: String,
+}
+
+/// Sessão do Supabase guardada no backend (gerenciada pelo Tauri).
+///
+/// O frontend entrega os tokens no login (`set_supabase_session`); daí em diante as
+/// chamadas REST do Rust usam o JWT do usuário, renovado aqui quando perto de expirar.
+/// URL, chave anon e a sessão ficam no cofre de segredos, então nenhum comando recebe
+/// credenciais como parâmetro e a sessão sobrevive a reiniciar o app.
+#[derive(Clone)]
+pub struct SupabaseAuth {
+    app: tauri::AppHandle,
+    store: SecretStore,
+    session: Arc<Mutex<Option<AuthSession>>>,
+    /// Só uma renovação por vez: duas chamadas não gastam o mesmo `refresh_token`
+    refreshing: Arc<tokio::sync::Mutex<()>>,
+}
+
+fn now_secs() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+impl SupabaseAuth {
+    /// Retoma a sessão guardada no cofre, se houver.
+    pub fn new(app: tauri::AppHandle, store: SecretStore) -> Self {
+        let session = store
+            .get(SUPABASE_SESSION)
+            .and_then(|json| serde_json::from_str::<AuthSession>(&json).ok());
+        if let Some(session) = &session {
+            log::info!("🔐 Sessão do Supabase retomada do cofre ({:?})", session.user_id);
+        }
+        Self {
+            app,
+            store,
+            session: Arc::new(Mutex::new(session)),
+            refreshing: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+
+    fn lock_session(&self) -> std::sync::MutexGuard<'_, Option<AuthSession>> {
+        self.session.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    fn save(&self, session: &AuthSession) -> Result<(), BiometricError> {
+        self.store.set(SUPABASE_SESSION, &serde_json::to_string(session)?)
+    }
+
+    pub fn set_session(&self, session: AuthSession) -> Result<(), BiometricError> {
+        log::info!("🔐 Sessão do Supabase recebida no backend ({:?})", session.user_id);
+        self.save(&session)?;
+        *self.lock_session() = Some(session);
+        Ok(())
+    }
+
+    pub fn clear(&self) -> Result<(), BiometricError> {
+        *self.lock_session() = None;
+        self.store.remove(SUPABASE_SESSION)?;
+        log::info!("🔐 Sessão do Supabase encerrada no backend");
+        Ok(())
+    }
+
+    /// Usuário da sessão atual, se houver.
+    pub fn user_id(&self) -> Option<String> {
+        self.lock_session().as_ref().and_then(|s| s.user_id.clone())
+    }
+
+    /// URL e chave anon do projeto, do cofre.
+    pub fn project(&self) -> Result<(String, String), BiometricError> {
+        match (self.store.get(SUPABASE_URL), self.store.get(SUPABASE_ANON_KEY)) {
+            (Some(url), Some(anon_key)) => Ok((url.trim_end_matches('/').to_string(), anon_key)),
+            _ => Err(BiometricError::Config("Projeto Supabase não configurado nesta instalação".to_string())),
+        }
+    }
+
+    /// Conexão autenticada; renova o token antes se ele estiver perto de expirar.
+    pub async fn connect(&self) -> Result<Supabase, BiometricError> {
+        let (supabase_url, anon_key) = self.project()?;
+        let _refreshing = self.refreshing.lock().await;
+        let mut session = self.lock_session().clone().ok_or(BiometricError::Unauthenticated)?;
+
+        if session.expires_at - now_secs() < REFRESH_MARGIN_SECS {
+            match refresh_session(&supabase_url, &anon_key, &session.refresh_token).await {
+                Ok(renewed) => {
+                    log::info!("🔐 Token do Supabase renovado ({:?})", renewed.user_id);
+                    // O supabase-js da janela principal adota os tokens novos (o refresh antigo
+                    // deixa de valer); as demais janelas não recebem os tokens
+                    let _ = self.app.emit_to("main", "supabase-session-refreshed", &renewed);
+                    session = AuthSession {
+                        user_id: renewed.user_id.clone().or(session.user_id),
+                        ..renewed
+                    };
+                    if let Err(e) = self.save(&session) {
+                        log::warn!("⚠️ Erro ao guardar sessão renovada no cofre: {}", e);
+                    }
+                    *self.lock_session() = Some(session.clone());
+                }
+                Err(BiometricError::Unauthenticated) => {
+                    log::warn!("⚠️ Renovação da sessão recusada; é preciso fazer login de novo");
+                    *self.lock_session() = None;
+                    let _ = self.store.remove(SUPABASE_SESSION);
+                    return Err(BiometricError::Unauthenticated);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(Supabase::new(&supabase_url, &anon_key, &session.access_token))
+    }
+}
+
+/// Troca o `refresh_token` por uma sessão nova no Supabase Auth.
+async fn refresh_session(supabase_url: &str, anon_key: &str, refresh_token: &str) -> Result<AuthSession, BiometricError> {
+    let resp = http()
+        .post(format!("{}/auth/v1/token?grant_type=refresh_token", supabase_url))
+        .header("apikey", anon_key)
+        .json(&serde_json::json!({ "refresh_token": refresh_token }))
+        .send()
+        .await?;
+    let status = resp.status();
+    if status == reqwest::StatusCode::BAD_REQUEST || status == reqwest::StatusCode::UNAUTHORIZED {
+        return Err(BiometricError::Unauthenticated);
+    }
+    if !status.is_success() {
+        return Err(api_error("Falha ao renovar sessão", resp).await);
+    }
+
+    let body: RefreshResponse = resp.json().await.map_err(|e| BiometricError::Parse(e.to_string()))?;
+    let expires_at = body
+        .expires_at
+        .or_else(|| body.expires_in.map(|secs| now_secs() + secs))
+        .unwrap_or_else(|| now_secs() + 3600);
+    Ok(AuthSession {
+        access_token: body.access_token,
+        refresh_token: body.refresh_token,
+        expires_at,
+        user_id: body.user.map(|u| u.id),
+    })
+}
+
+/// Comando Tauri: entrega ao backend a sessão do usuário logado (login e renovações
+/// feitas pelo supabase-js).
+#[tauri::command]
+pub fn set_supabase_session(
+    auth: tauri::State<'_, SupabaseAuth>,
+    access_token: String,
+    refresh_token: String,
+    expires_at: i64,
+    user_id: Option<String>,
+) -> Result<(), BiometricError> {
+    if access_token.trim().is_empty() || refresh_token.trim().is_empty() {
+        return Err(BiometricError::Config("Sessão do Supabase incompleta".to_string()));
+    }
+    auth.set_session(AuthSession {
+        access_token,
+        refresh_token,
+        expires_at,
+        user_id,
+    })
+}
+
+/// Comando Tauri: descarta a sessão no backend (logout).
+#[tauri::command]
+pub fn clear_supabase_session(auth: tauri::State<'_, SupabaseAuth>) -> Result<(), BiometricError> {
+    auth.clear()
+}
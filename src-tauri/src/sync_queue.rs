@@ -0,0 +1,1183 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+use crate::audit_log::{record_event, AuditCategory};
+use crate::biometric_error::BiometricError;
+use crate::station_config::{app_data_dir, station_id};
+use crate::supabase::Supabase;
+use crate::supabase_auth::SupabaseAuth;
+use crate::util::{new_uuid, random_hex_id};
+
+/// Tentativas antes de a operação ir para a fila de falhas (dead-letter)
+pub const MAX_ATTEMPTS: u32 = 8;
+/// Espera depois da primeira falha; dobra a cada nova falha
+const BASE_BACKOFF: Duration = Duration::from_secs(30);
+/// Teto da espera entre tentativas
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+/// Sem operação vencida, o worker confere a fila neste intervalo
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// Operações concluídas ficam este tempo na fila (idempotência local) antes de sair
+const DONE_RETENTION_DAYS: i64 = 30;
+/// Versão do esquema de `sync_queue.db` (`PRAGMA user_version`)
+const SCHEMA_VERSION: i32 = 2;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS sync_operations (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        idempotency_key TEXT NOT NULL UNIQUE,
+        kind TEXT NOT NULL,
+        target_table TEXT NOT NULL,
+        payload TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        status TEXT NOT NULL DEFAULT 'pending',
+        attempts INTEGER NOT NULL DEFAULT 0,
+        next_attempt_at TEXT,
+        last_error TEXT,
+        completed_at TEXT
+    );
+    CREATE INDEX IF NOT EXISTS sync_operations_due ON sync_operations (status, next_attempt_at);
+";
+
+/// Mudanças no esquema desde a primeira versão, por versão de destino. A fila sobrevive
+/// às atualizações do app: nunca recriar a tabela, só acrescentar.
+const MIGRATIONS: &[(i32, &str)] = &[(
+    2,
+    "ALTER TABLE sync_operations ADD COLUMN base_version TEXT;
+     ALTER TABLE sync_operations ADD COLUMN conflict TEXT;
+     ALTER TABLE sync_operations ADD COLUMN force INTEGER NOT NULL DEFAULT 0;",
+)];
+
+const COLUMNS: &str = "id, idempotency_key, kind, target_table, payload, created_at, status, attempts, \
+                       next_attempt_at, last_error, completed_at, base_version, conflict, force";
+
+/// Tipo de operação feita sem conexão.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationKind {
+    /// Aprovação de solicitação
+    Approve,
+    /// Entrega de solicitação (debita estoque)
+    Deliver,
+    /// Rejeição de solicitação
+    Reject,
+    /// Inserção de uma linha em `table`; sem `id`, a fila gera um UUID (repetir não duplica)
+    Create,
+    /// Atualização da linha `id` de `table`
+    Update,
+}
+
+impl OperationKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            OperationKind::Approve => "approve",
+            OperationKind::Deliver => "deliver",
+            OperationKind::Reject => "reject",
+            OperationKind::Create => "create",
+            OperationKind::Update => "update",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "approve" => Some(OperationKind::Approve),
+            "deliver" => Some(OperationKind::Deliver),
+            "reject" => Some(OperationKind::Reject),
+            "create" => Some(OperationKind::Create),
+            "update" => Some(OperationKind::Update),
+            _ => None,
+        }
+    }
+
+    /// Operações de solicitação, aplicadas pela função `sync_apply_operation`
+    fn is_solicitacao(self) -> bool {
+        matches!(self, OperationKind::Approve | OperationKind::Deliver | OperationKind::Reject)
+    }
+}
+
+/// Situação de uma operação na fila.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationStatus {
+    /// Aguardando envio (ou nova tentativa)
+    Pending,
+    /// Aplicada no Supabase
+    Done,
+    /// Desistiu: erro permanente ou tentativas esgotadas (dead-letter)
+    Failed,
+    /// Conflito com o que outra estação já fez; aguarda revisão do almoxarife
+    Conflict,
+    /// Descartada na revisão de conflito
+    Discarded,
+}
+
+impl OperationStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            OperationStatus::Pending => "pending",
+            OperationStatus::Done => "done",
+            OperationStatus::Failed => "failed",
+            OperationStatus::Conflict => "conflict",
+            OperationStatus::Discarded => "discarded",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "done" => OperationStatus::Done,
+            "failed" => OperationStatus::Failed,
+            "conflict" => OperationStatus::Conflict,
+            "discarded" => OperationStatus::Discarded,
+            _ => OperationStatus::Pending,
+        }
+    }
+}
+
+/// Operação da fila (comandos `enqueue_operation` e `get_sync_queue`).
+#[derive(Serialize, Clone, Debug)]
+pub struct QueuedOperation {
+    pub id: i64,
+    pub idempotency_key: String,
+    pub kind: OperationKind,
+    pub table: String,
+    pub payload: serde_json::Value,
+    /// Momento da operação na estação (RFC 3339); vai para o Supabase como data da operação
+    pub created_at: String,
+    pub status: OperationStatus,
+    pub attempts: u32,
+    pub next_attempt_at: Option<String>,
+    pub last_error: Option<String>,
+    pub completed_at: Option<String>,
+    /// Versão da linha que a estação tinha quando fez a operação (ex.: `atualizado_em`,
+    /// `status` e `estoque_atual` da solicitação no cache)
+    pub base_version: Option<serde_json::Value>,
+    /// Conflito encontrado na sincronização (situação `conflict`)
+    pub conflict: Option<SyncConflict>,
+    /// Reenviada na revisão com "aplicar mesmo assim": o servidor não compara versões
+    pub force: bool,
+}
+
+/// Conflito entre a operação offline e o estado atual no Supabase.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SyncConflict {
+    /// Código estável: `already_delivered`, `already_rejected`, `already_approved`,
+    /// `cancelled`, `status_changed`, `insufficient_stock`, `row_changed`, `not_found`
+    pub reason: String,
+    /// Explicação para o almoxarife (pt-BR)
+    pub message: String,
+    /// Como a linha está agora no Supabase
+    #[serde(default)]
+    pub current: Option<serde_json::Value>,
+    #[serde(default)]
+    pub detected_at: Option<String>,
+}
+
+/// Decisão do almoxarife sobre uma operação em conflito.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictResolution {
+    /// Descarta a operação offline; vale o que está no Supabase
+    Discard,
+    /// Tenta de novo com as mesmas regras (ex.: depois de ajustar o estoque)
+    Retry,
+    /// Aplica mesmo com a linha alterada em outra estação (o estoque continua conferido)
+    Force,
+}
+
+/// Conteúdo da fila (comando `get_sync_queue`).
+#[derive(Serialize, Clone, Debug)]
+pub struct SyncQueueSnapshot {
+    pub pending: usize,
+    pub failed: usize,
+    /// Operações aguardando revisão de conflito
+    pub conflicts: usize,
+    pub operations: Vec<QueuedOperation>,
+}
+
+/// Resposta de `sync_apply_operation`.
+#[derive(Deserialize)]
+struct ApplyResponse {
+    /// `applied`, `duplicate`, `resolved` ou `conflict`
+    outcome: String,
+    #[serde(default)]
+    reason: Option<String>,
+    #[serde(default)]
+    message: Option<String>,
+    #[serde(default)]
+    current: Option<serde_json::Value>,
+}
+
+/// Resultado de uma operação enviada ao Supabase.
+enum ApplyOutcome {
+    Applied,
+    /// Já estava aplicada (mesma chave de idempotência)
+    Duplicate,
+    /// A linha já estava como a operação deixaria (ex.: aprovada em outra estação)
+    Resolved(String),
+    Conflict(SyncConflict),
+}
+
+/// Fila de sincronização das operações feitas offline (gerenciada pelo Tauri).
+///
+/// Fica em `sync_queue.db` (SQLite) na pasta de dados, fora do armazenamento do webview, e
+/// sobrevive a reinícios e atualizações. Cada operação tem uma chave de idempotência: a
+/// mesma chave não entra duas vezes na fila e, para as operações de solicitação, não é
+/// aplicada duas vezes no Supabase (`sync_applied_operations`). Falhas temporárias voltam
+/// com espera exponencial; erros permanentes e tentativas esgotadas vão para `failed`, de
+/// onde só saem com `retry_failed_operations`. Operações que batem com o que outra estação
+/// já fez vão para `conflict` e esperam a decisão do almoxarife (`resolve_sync_conflict`).
+#[derive(Clone)]
+pub struct SyncQueue {
+    conn: Arc<Mutex<Connection>>,
+    /// Acorda o worker quando uma operação entra (ou volta) na fila
+    wake: Arc<Notify>,
+}
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    // Formato fixo: a comparação de texto no SQLite segue a ordem das datas
+    at.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Registro que a operação altera (`tabela:id`). Operações do mesmo registro são
+/// enviadas na ordem: uma só vai quando as anteriores saíram da fila.
+fn entity(operation: &QueuedOperation) -> Option<String> {
+    let (table, id) = if operation.kind.is_solicitacao() {
+        ("solicitacoes", operation.payload.get("solicitacao_id")?)
+    } else {
+        (operation.table.as_str(), operation.payload.get("id")?)
+    };
+    if id.is_null() {
+        return None;
+    }
+    let id = id.as_str().map(str::to_string).unwrap_or_else(|| id.to_string());
+    Some(format!("{}:{}", table, id))
+}
+
+/// Espera antes da tentativa seguinte a `attempts` falhas.
+fn backoff(attempts: u32) -> Duration {
+    let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+    BASE_BACKOFF.saturating_mul(factor).min(MAX_BACKOFF)
+}
+
+/// Falhas que podem passar sozinhas: rede, timeout, limite de requisições e erro do servidor.
+fn is_transient(error: &BiometricError) -> bool {
+    match error {
+        BiometricError::Http { status: None, .. } => true,
+        BiometricError::Http { status: Some(status), .. } => matches!(status, 408 | 429 | 500..=599),
+        _ => false,
+    }
+}
+
+/// Sem conexão, sem sessão ou sem projeto configurado: nenhuma operação vai passar agora,
+/// então a rodada para sem contar tentativa.
+fn blocks_all(error: &BiometricError) -> bool {
+    error.is_offline() || matches!(error, BiometricError::Unauthenticated | BiometricError::Config(_))
+}
+
+/// Nome de tabela aceito na fila (o PostgREST recebe o nome na URL).
+fn valid_table(table: &str) -> bool {
+    !table.is_empty() && table.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Lê uma linha da fila. Um tipo desconhecido (ex.: gravado por uma versão mais nova do app)
+/// é erro: tratá-lo como outro tipo aplicaria no Supabase uma operação diferente da feita.
+fn read_operation(row: &Row) -> rusqlite::Result<QueuedOperation> {
+    let kind: String = row.get(2)?;
+    let payload: String = row.get(4)?;
+    let status: String = row.get(6)?;
+    let kind = OperationKind::parse(&kind).ok_or_else(|| {
+        rusqlite::Error::FromSqlConversionFailure(
+            2,
+            rusqlite::types::Type::Text,
+            format!("Tipo de operação desconhecido na fila: {:?}", kind).into(),
+        )
+    })?;
+    Ok(QueuedOperation {
+        id: row.get(0)?,
+        idempotency_key: row.get(1)?,
+        kind,
+        table: row.get(3)?,
+        payload: serde_json::from_str(&payload).unwrap_or(serde_json::Value::Null),
+        created_at: row.get(5)?,
+        status: OperationStatus::parse(&status),
+        attempts: row.get(7)?,
+        next_attempt_at: row.get(8)?,
+        last_error: row.get(9)?,
+        completed_at: row.get(10)?,
+        base_version: row
+            .get::<_, Option<String>>(11)?
+            .and_then(|json| serde_json::from_str(&json).ok()),
+        conflict: row
+            .get::<_, Option<String>>(12)?
+            .and_then(|json| serde_json::from_str(&json).ok()),
+        force: row.get(13)?,
+    })
+}
+
+/// Leva o esquema de uma fila antiga até `SCHEMA_VERSION`, sem perder as operações.
+fn migrate(conn: &mut Connection) -> Result<(), BiometricError> {
+    let version: i32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version > SCHEMA_VERSION {
+        log::warn!("⚠️ sync_queue.db é de uma versão mais nova do app (esquema {})", version);
+        return Ok(());
+    }
+    let tx = conn.transaction()?;
+    for (target, sql) in MIGRATIONS.iter().filter(|(target, _)| *target > version.max(1)) {
+        tx.execute_batch(sql)?;
+        log::info!("📋 Fila de sincronização migrada para o esquema {}", target);
+    }
+    tx.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    tx.commit()?;
+    Ok(())
+}
+
+impl SyncQueue {
+    pub fn open() -> Result<Self, BiometricError> {
+        let path = app_data_dir().join("sync_queue.db");
+        std::fs::create_dir_all(app_data_dir())?;
+        let queue = Self::with_connection(Connection::open(&path)?)?;
+        let pending = queue.count(OperationStatus::Pending)?;
+        if pending > 0 {
+            log::info!("📋 Fila de sincronização retomada com {} operação(ões) pendente(s)", pending);
+        }
+        let conflicts = queue.count(OperationStatus::Conflict)?;
+        if conflicts > 0 {
+            log::warn!("⚠️ {} operação(ões) offline aguardando revisão de conflito", conflicts);
+        }
+        Ok(queue)
+    }
+
+    fn with_connection(mut conn: Connection) -> Result<Self, BiometricError> {
+        conn.execute_batch(SCHEMA)?;
+        migrate(&mut conn)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            wake: Arc::new(Notify::new()),
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    /// Acorda o worker para tentar as pendentes já vencidas (ex.: a conexão voltou).
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+
+    fn count(&self, status: OperationStatus) -> Result<usize, BiometricError> {
+        let count: i64 = self.lock().query_row(
+            "SELECT COUNT(*) FROM sync_operations WHERE status = ?1",
+            params![status.as_str()],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    fn by_key(&self, key: &str) -> Result<Option<QueuedOperation>, BiometricError> {
+        let operation = self
+            .lock()
+            .query_row(
+                &format!("SELECT {} FROM sync_operations WHERE idempotency_key = ?1", COLUMNS),
+                params![key],
+                read_operation,
+            )
+            .optional()?;
+        Ok(operation)
+    }
+
+    /// Coloca uma operação na fila. Com uma chave já conhecida, devolve a operação que
+    /// já estava lá (na situação em que estiver) sem criar outra. Sem `occurred_at`, a
+    /// operação fica com a hora atual. `base_version` é a versão da linha que a estação
+    /// leu, usada para detectar conflito na sincronização. Inserções sem `id` recebem um
+    /// UUID aqui, para que um reenvio depois de uma resposta perdida não duplique a linha.
+    pub fn enqueue(
+        &self,
+        kind: OperationKind,
+        table: &str,
+        mut payload: serde_json::Value,
+        idempotency_key: Option<String>,
+        occurred_at: Option<&str>,
+        base_version: Option<serde_json::Value>,
+    ) -> Result<QueuedOperation, BiometricError> {
+        if !valid_table(table) {
+            return Err(BiometricError::Config(format!("Tabela inválida para a fila: {:?}", table)));
+        }
+        if !payload.is_object() {
+            return Err(BiometricError::Parse("Dados da operação devem ser um objeto JSON".to_string()));
+        }
+        if kind.is_solicitacao() && payload.get("solicitacao_id").and_then(|v| v.as_str()).is_none() {
+            return Err(BiometricError::Parse("Operação de solicitação sem solicitacao_id".to_string()));
+        }
+        if kind == OperationKind::Update && payload.get("id").is_none_or(|v| v.is_null()) {
+            return Err(BiometricError::Parse("Atualização sem id da linha".to_string()));
+        }
+        if kind == OperationKind::Create && payload.get("id").is_none_or(|v| v.is_null()) {
+            payload["id"] = serde_json::Value::String(new_uuid()?);
+        }
+
+        let key = match idempotency_key.filter(|k| !k.trim().is_empty()) {
+            Some(key) => key,
+            None => random_hex_id()?,
+        };
+        let created_at = match occurred_at {
+            Some(at) => DateTime::parse_from_rfc3339(at)
+                .map(|at| at.with_timezone(&Utc))
+                .map_err(|e| BiometricError::Parse(format!("Data da operação inválida ({}): {}", at, e)))?,
+            None => Utc::now(),
+        };
+        let base_version = base_version
+            .filter(|base| !base.is_null())
+            .map(|base| serde_json::to_string(&base))
+            .transpose()?;
+        let inserted = self.lock().execute(
+            "INSERT INTO sync_operations
+                 (idempotency_key, kind, target_table, payload, created_at, next_attempt_at, base_version)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT (idempotency_key) DO NOTHING",
+            params![
+                key,
+                kind.as_str(),
+                table,
+                serde_json::to_string(&payload)?,
+                timestamp(created_at),
+                timestamp(Utc::now()),
+                base_version
+            ],
+        )?;
+
+        let operation = self
+            .by_key(&key)?
+            .ok_or_else(|| BiometricError::Io("Operação sumiu da fila de sincronização".to_string()))?;
+        if inserted > 0 {
+            log::info!("📥 Operação {} em {} na fila de sincronização (#{})", kind.as_str(), table, operation.id);
+            self.wake.notify_one();
+        } else {
+            log::debug!("Operação com chave {} já estava na fila (#{})", key, operation.id);
+        }
+        Ok(operation)
+    }
+
+    /// Operações pendentes, com falha e em conflito (e as concluídas e descartadas, com
+    /// `include_done`), da mais antiga para a mais nova.
+    pub fn snapshot(&self, include_done: bool) -> Result<SyncQueueSnapshot, BiometricError> {
+        let filter = if include_done { "" } else { "WHERE status NOT IN ('done', 'discarded')" };
+        let operations = {
+            let conn = self.lock();
+            let mut stmt = conn.prepare(&format!("SELECT {} FROM sync_operations {} ORDER BY id", COLUMNS, filter))?;
+            let rows = stmt.query_map([], read_operation)?.collect::<Result<Vec<_>, _>>()?;
+            rows
+        };
+        Ok(SyncQueueSnapshot {
+            pending: self.count(OperationStatus::Pending)?,
+            failed: self.count(OperationStatus::Failed)?,
+            conflicts: self.count(OperationStatus::Conflict)?,
+            operations,
+        })
+    }
+
+    /// Devolve à fila as operações com falha (todas, ou só as de `ids`), com as tentativas
+    /// zeradas. Retorna quantas voltaram.
+    pub fn retry_failed(&self, ids: Option<&[i64]>) -> Result<usize, BiometricError> {
+        let now = timestamp(Utc::now());
+        let conn = self.lock();
+        let retried = match ids {
+            None => conn.execute(
+                "UPDATE sync_operations SET status = 'pending', attempts = 0, next_attempt_at = ?1
+                 WHERE status = 'failed'",
+                params![now],
+            )?,
+            Some(ids) => {
+                let mut stmt = conn.prepare(
+                    "UPDATE sync_operations SET status = 'pending', attempts = 0, next_attempt_at = ?1
+                     WHERE status = 'failed' AND id = ?2",
+                )?;
+                let mut total = 0;
+                for id in ids {
+                    total += stmt.execute(params![now, id])?;
+                }
+                total
+            }
+        };
+        drop(conn);
+        if retried > 0 {
+            log::info!("🔁 {} operação(ões) com falha devolvida(s) à fila de sincronização", retried);
+            self.wake.notify_one();
+        }
+        Ok(retried)
+    }
+
+    /// Descarta operações com falha (só as de `ids`), liberando as seguintes do mesmo
+    /// registro. Retorna quantas foram descartadas; cada uma vai para a auditoria.
+    pub fn discard_failed(&self, ids: &[i64], user_id: Option<&str>) -> Result<usize, BiometricError> {
+        let now = timestamp(Utc::now());
+        let mut discarded = 0;
+        for id in ids {
+            let operation = self
+                .lock()
+                .query_row(
+                    &format!("SELECT {} FROM sync_operations WHERE id = ?1 AND status = 'failed'", COLUMNS),
+                    params![id],
+                    read_operation,
+                )
+                .optional()?;
+            let Some(operation) = operation else {
+                continue;
+            };
+            self.lock().execute(
+                "UPDATE sync_operations SET status = 'discarded', next_attempt_at = NULL, completed_at = ?2
+                 WHERE id = ?1",
+                params![id, now],
+            )?;
+            discarded += 1;
+            log::info!("🗑️ Operação #{} com falha descartada", id);
+            let category = match operation.kind {
+                OperationKind::Deliver => AuditCategory::Delivery,
+                _ => AuditCategory::Stock,
+            };
+            record_event(
+                category,
+                "sync_failed_discarded",
+                user_id,
+                serde_json::json!({
+                    "operation_id": operation.id,
+                    "idempotency_key": operation.idempotency_key,
+                    "kind": operation.kind,
+                    "table": operation.table,
+                    "payload": operation.payload,
+                    "error": operation.last_error,
+                }),
+            );
+        }
+        if discarded > 0 {
+            self.wake.notify_one();
+        }
+        Ok(discarded)
+    }
+
+    /// Decide uma operação em conflito: descarta, ou devolve à fila para nova tentativa
+    /// (com `Force`, sem comparar versões).
+    pub fn resolve_conflict(
+        &self,
+        id: i64,
+        resolution: ConflictResolution,
+        user_id: Option<&str>,
+    ) -> Result<QueuedOperation, BiometricError> {
+        let operation = self
+            .lock()
+            .query_row(
+                &format!("SELECT {} FROM sync_operations WHERE id = ?1", COLUMNS),
+                params![id],
+                read_operation,
+            )
+            .optional()?
+            .ok_or_else(|| BiometricError::Config(format!("Operação #{} não está na fila", id)))?;
+        if operation.status != OperationStatus::Conflict {
+            return Err(BiometricError::Config(format!("Operação #{} não está em conflito", id)));
+        }
+
+        let now = timestamp(Utc::now());
+        match resolution {
+            ConflictResolution::Discard => self.lock().execute(
+                "UPDATE sync_operations SET status = 'discarded', next_attempt_at = NULL, completed_at = ?2
+                 WHERE id = ?1",
+                params![id, now],
+            )?,
+            ConflictResolution::Retry | ConflictResolution::Force => self.lock().execute(
+                "UPDATE sync_operations SET status = 'pending', attempts = 0, next_attempt_at = ?2, force = ?3
+                 WHERE id = ?1",
+                params![id, now, resolution == ConflictResolution::Force],
+            )?,
+        };
+        log::info!("🧭 Conflito da operação #{} resolvido: {:?}", id, resolution);
+
+        let category = match operation.kind {
+            OperationKind::Deliver => AuditCategory::Delivery,
+            _ => AuditCategory::Stock,
+        };
+        record_event(
+            category,
+            "sync_conflict_resolved",
+            user_id,
+            serde_json::json!({
+                "operation_id": operation.id,
+                "idempotency_key": operation.idempotency_key,
+                "kind": operation.kind,
+                "payload": operation.payload,
+                "conflict": operation.conflict,
+                "resolution": format!("{:?}", resolution).to_lowercase(),
+            }),
+        );
+        if resolution != ConflictResolution::Discard {
+            self.wake.notify_one();
+        }
+        self.lock()
+            .query_row(
+                &format!("SELECT {} FROM sync_operations WHERE id = ?1", COLUMNS),
+                params![id],
+                read_operation,
+            )
+            .map_err(BiometricError::from)
+    }
+
+    /// Pendentes cuja hora de tentar já chegou, na ordem em que foram feitas. Uma operação
+    /// espera enquanto outra mais antiga do mesmo registro aguarda nova tentativa, revisão
+    /// de conflito ou está em `failed` (até voltar à fila ou ser descartada).
+    fn due(&self) -> Result<Vec<QueuedOperation>, BiometricError> {
+        let open = {
+            let conn = self.lock();
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM sync_operations WHERE status IN ('pending', 'conflict', 'failed') ORDER BY id",
+                COLUMNS
+            ))?;
+            let rows = stmt.query_map([], read_operation)?.collect::<Result<Vec<_>, _>>()?;
+            rows
+        };
+        let now = timestamp(Utc::now());
+        let mut waiting = HashSet::new();
+        let mut due = Vec::new();
+        for operation in open {
+            let entity = entity(&operation);
+            if entity.as_ref().is_some_and(|entity| waiting.contains(entity)) {
+                continue;
+            }
+            let ready = operation.status == OperationStatus::Pending
+                && operation.next_attempt_at.as_deref().is_some_and(|at| at <= now.as_str());
+            if ready {
+                due.push(operation);
+            } else if let Some(entity) = entity {
+                waiting.insert(entity);
+            }
+        }
+        Ok(due)
+    }
+
+    /// Tempo até a próxima operação pendente vencer (`None` sem pendentes).
+    fn next_due_in(&self) -> Option<Duration> {
+        let next: Option<String> = self
+            .lock()
+            .query_row(
+                "SELECT MIN(next_attempt_at) FROM sync_operations WHERE status = 'pending'",
+                [],
+                |row| row.get(0),
+            )
+            .ok()
+            .flatten();
+        let next = DateTime::parse_from_rfc3339(&next?).ok()?.with_timezone(&Utc);
+        Some((next - Utc::now()).to_std().unwrap_or(Duration::ZERO))
+    }
+
+    fn mark_done(&self, id: i64) -> Result<(), BiometricError> {
+        self.lock().execute(
+            "UPDATE sync_operations SET status = 'done', attempts = attempts + 1, next_attempt_at = NULL,
+                    last_error = NULL, completed_at = ?2
+             WHERE id = ?1",
+            params![id, timestamp(Utc::now())],
+        )?;
+        Ok(())
+    }
+
+    fn mark_retry(&self, id: i64, attempts: u32, error: &str) -> Result<DateTime<Utc>, BiometricError> {
+        let next = Utc::now() + chrono::Duration::from_std(backoff(attempts)).unwrap_or_default();
+        self.lock().execute(
+            "UPDATE sync_operations SET attempts = ?2, next_attempt_at = ?3, last_error = ?4 WHERE id = ?1",
+            params![id, attempts, timestamp(next), error],
+        )?;
+        Ok(next)
+    }
+
+    fn mark_failed(&self, id: i64, attempts: u32, error: &str) -> Result<(), BiometricError> {
+        self.lock().execute(
+            "UPDATE sync_operations SET status = 'failed', attempts = ?2, next_attempt_at = NULL, last_error = ?3
+             WHERE id = ?1",
+            params![id, attempts, error],
+        )?;
+        Ok(())
+    }
+
+    fn mark_conflict(&self, id: i64, conflict: &SyncConflict) -> Result<(), BiometricError> {
+        self.lock().execute(
+            "UPDATE sync_operations SET status = 'conflict', attempts = attempts + 1, next_attempt_at = NULL,
+                    last_error = ?2, conflict = ?3, force = 0
+             WHERE id = ?1",
+            params![id, conflict.message, serde_json::to_string(conflict)?],
+        )?;
+        Ok(())
+    }
+
+    /// Apaga as concluídas e descartadas há mais de `DONE_RETENTION_DAYS`.
+    fn purge_done(&self) -> Result<(), BiometricError> {
+        let cutoff = Utc::now() - chrono::Duration::days(DONE_RETENTION_DAYS);
+        self.lock().execute(
+            "DELETE FROM sync_operations WHERE status IN ('done', 'discarded') AND completed_at < ?1",
+            params![timestamp(cutoff)],
+        )?;
+        Ok(())
+    }
+
+    /// Envia as operações vencidas, uma a uma, na ordem. Retorna quantas foram aplicadas.
+    ///
+    /// Sem conexão ou sem sessão, para na primeira operação sem contar tentativa: a
+    /// próxima rodada continua de onde parou. Se uma operação falha (volta para nova
+    /// tentativa ou vai para `failed`) ou entra em conflito, as seguintes do mesmo registro
+    /// ficam para depois dela, para não serem aplicadas fora de ordem.
+    pub async fn process(&self, auth: &SupabaseAuth) -> Result<usize, BiometricError> {
+        let due = self.due()?;
+        if due.is_empty() {
+            return Ok(0);
+        }
+        let db = auth.connect().await?;
+
+        let mut applied = 0;
+        let mut held = HashSet::new();
+        for operation in due {
+            let entity = entity(&operation);
+            if entity.as_ref().is_some_and(|entity| held.contains(entity)) {
+                log::debug!("Operação #{} aguarda a anterior do mesmo registro", operation.id);
+                continue;
+            }
+            let held_back = match apply(&db, &operation).await {
+                Ok(ApplyOutcome::Applied) => {
+                    self.mark_done(operation.id)?;
+                    applied += 1;
+                    false
+                }
+                Ok(ApplyOutcome::Duplicate) => {
+                    log::info!("♻️ Operação #{} já tinha sido aplicada no Supabase", operation.id);
+                    self.mark_done(operation.id)?;
+                    false
+                }
+                Ok(ApplyOutcome::Resolved(message)) => {
+                    log::info!("✅ Operação #{} resolvida sem alterar o Supabase: {}", operation.id, message);
+                    self.mark_done(operation.id)?;
+                    false
+                }
+                Ok(ApplyOutcome::Conflict(conflict)) => {
+                    self.record_conflict(&operation, conflict)?;
+                    true
+                }
+                Err(e) if blocks_all(&e) => return Err(e),
+                Err(e) => {
+                    self.record_failure(&operation, &e)?;
+                    true
+                }
+            };
+            if let Some(entity) = entity.filter(|_| held_back) {
+                held.insert(entity);
+            }
+        }
+        if applied > 0 {
+            log::info!("📤 {} operação(ões) offline sincronizada(s) com o Supabase", applied);
+        }
+        self.purge_done()?;
+        Ok(applied)
+    }
+
+    fn record_conflict(&self, operation: &QueuedOperation, mut conflict: SyncConflict) -> Result<(), BiometricError> {
+        conflict.detected_at = Some(timestamp(Utc::now()));
+        self.mark_conflict(operation.id, &conflict)?;
+        log::warn!(
+            "⚠️ Conflito na operação #{} ({}): {} [{}]",
+            operation.id,
+            operation.kind.as_str(),
+            conflict.message,
+            conflict.reason
+        );
+        let category = match operation.kind {
+            OperationKind::Deliver => AuditCategory::Delivery,
+            _ => AuditCategory::Stock,
+        };
+        record_event(
+            category,
+            "sync_conflict_detected",
+            None,
+            serde_json::json!({
+                "operation_id": operation.id,
+                "idempotency_key": operation.idempotency_key,
+                "kind": operation.kind,
+                "payload": operation.payload,
+                "base_version": operation.base_version,
+                "conflict": conflict,
+            }),
+        );
+        Ok(())
+    }
+
+    /// Conta a falha. Temporária volta com espera e retorna `true` (a operação continua
+    /// pendente); permanente ou com tentativas esgotadas vai para `failed`.
+    fn record_failure(&self, operation: &QueuedOperation, error: &BiometricError) -> Result<bool, BiometricError> {
+        let attempts = operation.attempts + 1;
+        let message = error.to_string();
+        if is_transient(error) && attempts < MAX_ATTEMPTS {
+            let next = self.mark_retry(operation.id, attempts, &message)?;
+            log::warn!(
+                "⚠️ Operação #{} ({}) falhou (tentativa {}/{}); nova tentativa às {}: {}",
+                operation.id,
+                operation.kind.as_str(),
+                attempts,
+                MAX_ATTEMPTS,
+                next.with_timezone(&chrono::Local).format("%H:%M:%S"),
+                message
+            );
+            return Ok(true);
+        }
+
+        self.mark_failed(operation.id, attempts, &message)?;
+        log::error!(
+            "🚨 Operação #{} ({} em {}) movida para falhas após {} tentativa(s): {}",
+            operation.id,
+            operation.kind.as_str(),
+            operation.table,
+            attempts,
+            message
+        );
+        let category = match operation.kind {
+            OperationKind::Deliver => AuditCategory::Delivery,
+            _ => AuditCategory::Stock,
+        };
+        record_event(
+            category,
+            "sync_operation_failed",
+            None,
+            serde_json::json!({
+                "operation_id": operation.id,
+                "idempotency_key": operation.idempotency_key,
+                "kind": operation.kind,
+                "table": operation.table,
+                "payload": operation.payload,
+                "attempts": attempts,
+                "error": message,
+            }),
+        );
+        Ok(false)
+    }
+}
+
+/// Aplica uma operação no Supabase com o JWT do usuário logado.
+///
+/// As de solicitação passam por `sync_apply_operation`, que compara o estado atual com
+/// `base_version` e aplica as regras de conflito de cada tipo (veja
+/// `supabase/sync_queue.sql`). Nas atualizações genéricas, um `atualizado_em` em
+/// `base_version` vira condição do `PATCH`: se a linha mudou, é conflito.
+async fn apply(db: &Supabase, operation: &QueuedOperation) -> Result<ApplyOutcome, BiometricError> {
+    match operation.kind {
+        OperationKind::Approve | OperationKind::Deliver | OperationKind::Reject => {
+            let response: ApplyResponse = db
+                .rpc(
+                    "sync_apply_operation",
+                    &serde_json::json!({
+                        "p_idempotency_key": operation.idempotency_key,
+                        "p_kind": operation.kind,
+                        "p_payload": operation.payload,
+                        "p_occurred_at": operation.created_at,
+                        "p_station_id": station_id(),
+                        "p_base": operation.base_version,
+                        "p_force": operation.force,
+                    }),
+                )
+                .await?;
+            let message = response.message.unwrap_or_default();
+            Ok(match response.outcome.as_str() {
+                "duplicate" => ApplyOutcome::Duplicate,
+                "resolved" => ApplyOutcome::Resolved(message),
+                "conflict" => ApplyOutcome::Conflict(SyncConflict {
+                    reason: response.reason.unwrap_or_else(|| "status_changed".to_string()),
+                    message,
+                    current: response.current,
+                    detected_at: None,
+                }),
+                _ => ApplyOutcome::Applied,
+            })
+        }
+        OperationKind::Create => {
+            let query = db.from(&operation.table);
+            if operation.payload.get("id").is_some_and(|id| !id.is_null()) {
+                query.upsert(&operation.payload, Some("id"), true).await?;
+            } else {
+                query.insert(&operation.payload).await?;
+            }
+            Ok(ApplyOutcome::Applied)
+        }
+        OperationKind::Update => {
+            let mut body = operation.payload.clone();
+            let id = body
+                .as_object_mut()
+                .and_then(|fields| fields.remove("id"))
+                .ok_or_else(|| BiometricError::Parse("Atualização sem id da linha".to_string()))?;
+            let id = id.as_str().map(str::to_string).unwrap_or_else(|| id.to_string());
+            let base_updated_at = operation
+                .base_version
+                .as_ref()
+                .and_then(|base| base.get("atualizado_em"))
+                .and_then(|at| at.as_str())
+                .filter(|_| !operation.force);
+
+            let mut query = db.from(&operation.table).eq("id", &id);
+            if let Some(updated_at) = base_updated_at {
+                query = query.eq("atualizado_em", updated_at);
+            }
+            let rows: Vec<serde_json::Value> = query.update(&body).await?;
+            if !rows.is_empty() {
+                return Ok(ApplyOutcome::Applied);
+            }
+
+            // Nada atualizado: a linha sumiu ou mudou desde a leitura
+            let current: Option<serde_json::Value> = db.from(&operation.table).eq("id", &id).fetch_one().await?;
+            Ok(ApplyOutcome::Conflict(match current {
+                Some(current) if base_updated_at.is_some() => SyncConflict {
+                    reason: "row_changed".to_string(),
+                    message: format!("O registro {} de {} foi alterado em outra estação", id, operation.table),
+                    current: Some(current),
+                    detected_at: None,
+                },
+                _ => SyncConflict {
+                    reason: "not_found".to_string(),
+                    message: format!("O registro {} de {} não existe mais (ou está sem permissão)", id, operation.table),
+                    current: None,
+                    detected_at: None,
+                },
+            }))
+        }
+    }
+}
+
+/// Worker da fila: envia as operações vencidas assim que entram na fila, na hora da
+/// próxima tentativa ou, no máximo, a cada `POLL_INTERVAL`.
+pub async fn run_sync_worker(queue: SyncQueue, auth: SupabaseAuth) {
+    loop {
+        if auth.user_id().is_some() {
+            if let Err(e) = queue.process(&auth).await {
+                if blocks_all(&e) {
+                    log::debug!("Fila de sincronização aguardando ({})", e);
+                } else {
+                    log::warn!("⚠️ Erro ao processar a fila de sincronização: {}", e);
+                }
+            }
+        }
+        let wait = queue.next_due_in().map_or(POLL_INTERVAL, |due| due.min(POLL_INTERVAL));
+        let _ = tokio::time::timeout(wait.max(Duration::from_secs(1)), queue.wake.notified()).await;
+    }
+}
+
+/// Comando Tauri: coloca uma operação feita offline na fila de sincronização.
+#[tauri::command]
+pub fn enqueue_operation(
+    queue: tauri::State<'_, SyncQueue>,
+    kind: OperationKind,
+    table: String,
+    data: serde_json::Value,
+    idempotency_key: Option<String>,
+    occurred_at: Option<String>,
+    base_version: Option<serde_json::Value>,
+) -> Result<QueuedOperation, BiometricError> {
+    queue.enqueue(kind, &table, data, idempotency_key, occurred_at.as_deref(), base_version)
+}
+
+/// Comando Tauri: operações pendentes, com falha e em conflito (e concluídas, com `includeDone`).
+#[tauri::command]
+pub fn get_sync_queue(
+    queue: tauri::State<'_, SyncQueue>,
+    include_done: Option<bool>,
+) -> Result<SyncQueueSnapshot, BiometricError> {
+    queue.snapshot(include_done.unwrap_or(false))
+}
+
+/// Comando Tauri: devolve à fila as operações com falha (todas, ou só as de `ids`).
+#[tauri::command]
+pub fn retry_failed_operations(
+    queue: tauri::State<'_, SyncQueue>,
+    ids: Option<Vec<i64>>,
+) -> Result<usize, BiometricError> {
+    queue.retry_failed(ids.as_deref())
+}
+
+/// Comando Tauri: descarta operações com falha (as de `ids`), que deixam de segurar as
+/// seguintes do mesmo registro.
+#[tauri::command]
+pub fn discard_failed_operations(
+    queue: tauri::State<'_, SyncQueue>,
+    auth: tauri::State<'_, SupabaseAuth>,
+    ids: Vec<i64>,
+) -> Result<usize, BiometricError> {
+    queue.discard_failed(&ids, auth.user_id().as_deref())
+}
+
+/// Comando Tauri: decisão do almoxarife sobre uma operação em conflito
+/// (`discard`, `retry` ou `force`).
+#[tauri::command]
+pub fn resolve_sync_conflict(
+    queue: tauri::State<'_, SyncQueue>,
+    auth: tauri::State<'_, SupabaseAuth>,
+    id: i64,
+    resolution: ConflictResolution,
+) -> Result<QueuedOperation, BiometricError> {
+    queue.resolve_conflict(id, resolution, auth.user_id().as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue() -> SyncQueue {
+        SyncQueue::with_connection(Connection::open_in_memory().unwrap()).unwrap()
+    }
+
+    fn approve(queue: &SyncQueue, solicitacao_id: &str) -> QueuedOperation {
+        queue
+            .enqueue(
+                OperationKind::Approve,
+                "solicitacoes",
+                serde_json::json!({ "solicitacao_id": solicitacao_id }),
+                None,
+                None,
+                None,
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        assert_eq!(backoff(1), BASE_BACKOFF);
+        assert_eq!(backoff(2), BASE_BACKOFF * 2);
+        assert_eq!(backoff(4), BASE_BACKOFF * 8);
+        assert_eq!(backoff(7), BASE_BACKOFF * 64);
+        assert_eq!(backoff(8), MAX_BACKOFF);
+        assert_eq!(backoff(u32::MAX), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn migrates_a_version_one_queue_without_losing_operations() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        conn.execute(
+            "INSERT INTO sync_operations (idempotency_key, kind, target_table, payload, created_at, next_attempt_at)
+             VALUES ('k1', 'approve', 'solicitacoes', '{\"solicitacao_id\":\"s1\"}', '2026-01-01T00:00:00.000Z',
+                     '2026-01-01T00:00:00.000Z')",
+            [],
+        )
+        .unwrap();
+
+        migrate(&mut conn).unwrap();
+        let version: i32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
+
+        let queue = SyncQueue::with_connection(conn).unwrap();
+        let operation = queue.by_key("k1").unwrap().unwrap();
+        assert_eq!(operation.kind, OperationKind::Approve);
+        assert_eq!(operation.status, OperationStatus::Pending);
+        assert!(operation.base_version.is_none());
+        assert!(!operation.force);
+    }
+
+    #[test]
+    fn migrate_leaves_a_newer_schema_alone() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1).unwrap();
+        migrate(&mut conn).unwrap();
+        let version: i32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+        assert_eq!(version, SCHEMA_VERSION + 1);
+    }
+
+    #[test]
+    fn create_without_id_gets_a_uuid() {
+        let queue = queue();
+        let operation = queue
+            .enqueue(OperationKind::Create, "movimentacoes", serde_json::json!({ "quantidade": 2 }), None, None, None)
+            .unwrap();
+        let id = operation.payload["id"].as_str().unwrap();
+        assert_eq!(id.len(), 36);
+        assert_eq!(&id[14..15], "4");
+        assert!(matches!(&id[19..20], "8" | "9" | "a" | "b"));
+
+        let kept = queue
+            .enqueue(OperationKind::Create, "movimentacoes", serde_json::json!({ "id": "abc" }), None, None, None)
+            .unwrap();
+        assert_eq!(kept.payload["id"], "abc");
+    }
+
+    #[test]
+    fn same_key_is_queued_once() {
+        let queue = queue();
+        let payload = serde_json::json!({ "solicitacao_id": "s1" });
+        let first = queue
+            .enqueue(OperationKind::Approve, "solicitacoes", payload.clone(), Some("k1".into()), None, None)
+            .unwrap();
+        let again = queue
+            .enqueue(OperationKind::Approve, "solicitacoes", payload, Some("k1".into()), None, None)
+            .unwrap();
+        assert_eq!(first.id, again.id);
+        assert_eq!(queue.count(OperationStatus::Pending).unwrap(), 1);
+    }
+
+    #[test]
+    fn retry_holds_later_operations_of_the_same_record() {
+        let queue = queue();
+        let first = approve(&queue, "s1");
+        let second = approve(&queue, "s1");
+        let other = approve(&queue, "s2");
+        let due: Vec<i64> = queue.due().unwrap().iter().map(|op| op.id).collect();
+        assert_eq!(due, vec![first.id, second.id, other.id]);
+
+        let error = BiometricError::Http { status: Some(503), detail: "indisponível".into() };
+        assert!(queue.record_failure(&first, &error).unwrap());
+        let due: Vec<i64> = queue.due().unwrap().iter().map(|op| op.id).collect();
+        assert_eq!(due, vec![other.id]);
+    }
+
+    #[test]
+    fn conflict_holds_later_operations_of_the_same_record() {
+        let queue = queue();
+        let first = approve(&queue, "s1");
+        let second = approve(&queue, "s1");
+        let conflict = SyncConflict {
+            reason: "status_changed".into(),
+            message: "mudou".into(),
+            current: None,
+            detected_at: None,
+        };
+        queue.mark_conflict(first.id, &conflict).unwrap();
+        assert!(queue.due().unwrap().is_empty());
+
+        queue
+            .lock()
+            .execute("UPDATE sync_operations SET status = 'discarded' WHERE id = ?1", params![first.id])
+            .unwrap();
+        let due: Vec<i64> = queue.due().unwrap().iter().map(|op| op.id).collect();
+        assert_eq!(due, vec![second.id]);
+    }
+
+    #[test]
+    fn failed_operation_holds_later_operations_until_it_is_back_in_the_queue() {
+        let queue = queue();
+        let first = approve(&queue, "s1");
+        let second = approve(&queue, "s1");
+        let other = approve(&queue, "s2");
+
+        let error = BiometricError::Http { status: Some(400), detail: "recusada".into() };
+        assert!(!queue.record_failure(&first, &error).unwrap());
+        let due: Vec<i64> = queue.due().unwrap().iter().map(|op| op.id).collect();
+        assert_eq!(due, vec![other.id]);
+
+        assert_eq!(queue.retry_failed(Some(&[first.id])).unwrap(), 1);
+        let due: Vec<i64> = queue.due().unwrap().iter().map(|op| op.id).collect();
+        assert_eq!(due, vec![first.id, second.id, other.id]);
+    }
+
+    #[test]
+    fn unknown_kind_is_an_error() {
+        let queue = queue();
+        let first = approve(&queue, "s1");
+        queue
+            .lock()
+            .execute("UPDATE sync_operations SET kind = 'transfer' WHERE id = ?1", params![first.id])
+            .unwrap();
+
+        assert!(queue.due().is_err());
+        assert!(queue.by_key(&first.idempotency_key).is_err());
+    }
+}
@@ -0,0 +1,701 @@
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::Engine;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::biometric_error::BiometricError;
+use crate::biometric_receipt::{StationKey, VerificationReceipt};
+use crate::biometric_service::insert_verification;
+use crate::biometric_templates::RemoteTemplate;
+use crate::secret_store::{SecretStore, TEMPLATE_CACHE_KEY};
+use crate::station_config::{app_data_dir, StationConfig};
+use crate::supabase::Supabase;
+use crate::supabase_auth::SupabaseAuth;
+use crate::util::{base64_engine, new_uuid, random_bytes};
+
+/// Intervalo da sincronização em segundo plano (feita pelo serviço de conectividade)
+pub const TEMPLATE_SYNC_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS cached_templates (
+        id TEXT PRIMARY KEY,
+        user_id TEXT NOT NULL,
+        finger TEXT,
+        quality INTEGER NOT NULL,
+        nonce BLOB NOT NULL,
+        template BLOB NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS cached_templates_user ON cached_templates (user_id);
+    CREATE TABLE IF NOT EXISTS offline_verifications (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        verification_id TEXT,
+        created_at TEXT NOT NULL,
+        record TEXT NOT NULL,
+        receipt TEXT
+    );
+    CREATE TABLE IF NOT EXISTS cache_meta (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+";
+
+/// Verificação offline esperando envio
+struct PendingVerification {
+    row_id: i64,
+    /// `id` da linha em `biometric_verifications`, gerado quando ela foi guardada
+    verification_id: String,
+    /// Quando a verificação foi feita (RFC 3339)
+    occurred_at: String,
+    record: String,
+    receipt: Option<String>,
+}
+
+/// Linha de `biometric_base_templates`
+#[derive(Deserialize)]
+struct BaseTemplate {
+    id: String,
+    user_id: String,
+    template: String,
+    quality: i32,
+    #[serde(default)]
+    finger: Option<String>,
+}
+
+/// Situação do cache local (comando `get_template_cache_status`).
+#[derive(Serialize, Clone, Debug)]
+pub struct TemplateCacheStatus {
+    pub base_id: Option<String>,
+    pub templates: usize,
+    pub users: usize,
+    /// Verificações feitas offline ainda não enviadas
+    pub pending_verifications: usize,
+    /// Última sincronização completa (RFC 3339)
+    pub last_sync_at: Option<String>,
+    pub last_error: Option<String>,
+}
+
+/// Cache local de templates da estação (gerenciado pelo Tauri).
+///
+/// Fica em `template_cache.db` (SQLite) na pasta de dados, com cada template cifrado com
+/// AES-256-GCM (chave no cofre de segredos). É mantido em segundo plano com os templates da
+/// base da estação e, a cada verificação online, com os do usuário conferido. Sem internet,
+/// `validate_or_enroll_fingerprint` confere a digital contra ele e guarda a verificação em
+/// `offline_verifications` até a próxima sincronização.
+#[derive(Clone)]
+pub struct TemplateCache {
+    conn: Arc<Mutex<Connection>>,
+    cipher: Arc<Aes256Gcm>,
+}
+
+/// Chave AES de um cache local, guardada no cofre como `name`; gerada na primeira execução.
+pub fn cache_key(store: &SecretStore, name: &str) -> Result<[u8; 32], BiometricError> {
+    if let Some(encoded) = store.get(name) {
+        return base64_engine()
+            .decode(encoded)
+            .ok()
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| BiometricError::Config(format!("Chave {} inválida no cofre", name)));
+    }
+    let key = random_bytes::<32>()?;
+    store.set(name, &base64_engine().encode(key))?;
+    Ok(key)
+}
+
+/// Cache de uma versão sem `verification_id`: ganha a coluna, e cada verificação pendente
+/// recebe o id que vai usar em todos os envios.
+fn migrate(conn: &Connection) -> Result<(), BiometricError> {
+    let has_id: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('offline_verifications') WHERE name = 'verification_id'",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_id {
+        conn.execute("ALTER TABLE offline_verifications ADD COLUMN verification_id TEXT", [])?;
+    }
+    let missing = conn
+        .prepare("SELECT id FROM offline_verifications WHERE verification_id IS NULL")?
+        .query_map([], |row| row.get::<_, i64>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    for id in missing {
+        conn.execute(
+            "UPDATE offline_verifications SET verification_id = ?1 WHERE id = ?2",
+            params![new_uuid()?, id],
+        )?;
+    }
+    Ok(())
+}
+
+impl TemplateCache {
+    pub fn open(store: &SecretStore) -> Result<Self, BiometricError> {
+        let path = app_data_dir().join("template_cache.db");
+        std::fs::create_dir_all(app_data_dir())?;
+        Self::with_connection(Connection::open(&path)?, cache_key(store, TEMPLATE_CACHE_KEY)?)
+    }
+
+    fn with_connection(conn: Connection, key: [u8; 32]) -> Result<Self, BiometricError> {
+        conn.execute_batch(SCHEMA)?;
+        migrate(&conn)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            cipher: Arc::new(Aes256Gcm::new(&key.into())),
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    /// Cifra o template; o id entra como dado associado, então trocar o conteúdo de
+    /// linha não passa na decifragem.
+    fn seal(&self, id: &str, template: &str) -> Result<([u8; 12], Vec<u8>), BiometricError> {
+        let nonce = random_bytes::<12>()?;
+        let sealed = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: template.as_bytes(), aad: id.as_bytes() })
+            .map_err(|_| BiometricError::Io("Erro ao cifrar template".to_string()))?;
+        Ok((nonce, sealed))
+    }
+
+    fn open_template(&self, id: &str, nonce: &[u8], sealed: &[u8]) -> Result<String, BiometricError> {
+        if nonce.len() != 12 {
+            return Err(BiometricError::Parse(format!("Nonce inválido no template {}", id)));
+        }
+        let plain = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: sealed, aad: id.as_bytes() })
+            .map_err(|_| BiometricError::Parse(format!("Template {} do cache não decifra", id)))?;
+        String::from_utf8(plain).map_err(|e| BiometricError::Parse(e.to_string()))
+    }
+
+    fn insert(
+        conn: &Connection,
+        id: &str,
+        user_id: &str,
+        finger: Option<&str>,
+        quality: i32,
+        nonce: &[u8],
+        sealed: &[u8],
+    ) -> Result<(), BiometricError> {
+        conn.execute(
+            "INSERT OR REPLACE INTO cached_templates (id, user_id, finger, quality, nonce, template)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![id, user_id, finger, quality, nonce, sealed],
+        )?;
+        Ok(())
+    }
+
+    /// Templates do usuário guardados no cache.
+    pub fn user_templates(&self, user_id: &str) -> Result<Vec<RemoteTemplate>, BiometricError> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT id, finger, quality, nonce, template FROM cached_templates WHERE user_id = ?1 ORDER BY rowid",
+        )?;
+        let rows = stmt.query_map(params![user_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, i32>(2)?,
+                row.get::<_, Vec<u8>>(3)?,
+                row.get::<_, Vec<u8>>(4)?,
+            ))
+        })?;
+
+        let mut templates = Vec::new();
+        for row in rows {
+            let (id, finger, quality, nonce, sealed) = row?;
+            match self.open_template(&id, &nonce, &sealed) {
+                Ok(template) => templates.push(RemoteTemplate { id, template, quality, finger }),
+                // Uma linha corrompida não pode impedir a verificação com as demais
+                Err(e) => log::warn!("⚠️ {}", e),
+            }
+        }
+        Ok(templates)
+    }
+
+    /// Substitui os templates de um usuário pelos que acabaram de vir do Supabase.
+    pub fn store_user(&self, user_id: &str, templates: &[RemoteTemplate]) -> Result<(), BiometricError> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM cached_templates WHERE user_id = ?1", params![user_id])?;
+        for t in templates {
+            let (nonce, sealed) = self.seal(&t.id, &t.template)?;
+            Self::insert(&tx, &t.id, user_id, t.finger.as_deref(), t.quality, &nonce, &sealed)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Troca o conteúdo do cache pelos templates da base.
+    fn replace_all(&self, templates: &[BaseTemplate]) -> Result<(), BiometricError> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM cached_templates", [])?;
+        for t in templates {
+            let (nonce, sealed) = self.seal(&t.id, &t.template)?;
+            Self::insert(&tx, &t.id, &t.user_id, t.finger.as_deref(), t.quality, &nonce, &sealed)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Guarda uma verificação feita offline (linha de `biometric_verifications` e o
+    /// comprovante) para enviar na próxima sincronização. Devolve o id da linha, fixo em
+    /// todos os envios.
+    pub fn queue_verification(
+        &self,
+        record: &serde_json::Value,
+        receipt: Option<&VerificationReceipt>,
+    ) -> Result<String, BiometricError> {
+        let id = new_uuid()?;
+        let receipt = receipt.map(serde_json::to_string).transpose()?;
+        self.lock().execute(
+            "INSERT INTO offline_verifications (verification_id, created_at, record, receipt) VALUES (?1, ?2, ?3, ?4)",
+            params![id, chrono::Utc::now().to_rfc3339(), serde_json::to_string(record)?, receipt],
+        )?;
+        Ok(id)
+    }
+
+    fn pending(&self) -> Result<Vec<PendingVerification>, BiometricError> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT id, verification_id, created_at, record, receipt FROM offline_verifications ORDER BY id",
+        )?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(PendingVerification {
+                    row_id: row.get(0)?,
+                    verification_id: row.get(1)?,
+                    occurred_at: row.get(2)?,
+                    record: row.get(3)?,
+                    receipt: row.get(4)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Envia uma verificação offline e o comprovante dela. Os dois envios podem se repetir
+    /// sem duplicar nada, então uma falha no meio só deixa a linha para a próxima vez.
+    async fn send_pending(
+        db: &Supabase,
+        station_key: &StationKey,
+        pending: &PendingVerification,
+    ) -> Result<(), BiometricError> {
+        let record: serde_json::Value = serde_json::from_str(&pending.record)?;
+        let receipt: Option<VerificationReceipt> = pending.receipt.as_deref().map(serde_json::from_str).transpose()?;
+        insert_verification(db, &pending.verification_id, Some(&pending.occurred_at), &record, receipt.as_ref()).await?;
+        if let Some(receipt) = &receipt {
+            station_key.store(db, receipt).await?;
+        }
+        Ok(())
+    }
+
+    /// Envia as verificações feitas offline. Uma que falha (inclusive só no comprovante)
+    /// fica guardada e não impede as outras; devolve quantas foram enviadas ou o primeiro erro.
+    async fn flush_pending(&self, db: &Supabase, station_key: &StationKey) -> Result<usize, BiometricError> {
+        let mut sent = 0;
+        let mut first_error = None;
+        for pending in self.pending()? {
+            match Self::send_pending(db, station_key, &pending).await {
+                Ok(()) => {
+                    self.lock()
+                        .execute("DELETE FROM offline_verifications WHERE id = ?1", params![pending.row_id])?;
+                    sent += 1;
+                }
+                Err(e) => {
+                    log::warn!("⚠️ Verificação offline {} não enviada: {}", pending.verification_id, e);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        if sent > 0 {
+            log::info!("📤 {} verificação(ões) offline enviada(s) ao Supabase", sent);
+        }
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(sent),
+        }
+    }
+
+    fn meta(&self, key: &str) -> Option<String> {
+        self.lock()
+            .query_row("SELECT value FROM cache_meta WHERE key = ?1", params![key], |row| row.get(0))
+            .optional()
+            .ok()
+            .flatten()
+    }
+
+    fn set_meta(&self, key: &str, value: Option<&str>) {
+        let conn = self.lock();
+        let result = match value {
+            Some(value) => conn.execute(
+                "INSERT OR REPLACE INTO cache_meta (key, value) VALUES (?1, ?2)",
+                params![key, value],
+            ),
+            None => conn.execute("DELETE FROM cache_meta WHERE key = ?1", params![key]),
+        };
+        if let Err(e) = result {
+            log::warn!("⚠️ Erro ao gravar estado do cache de templates: {}", e);
+        }
+    }
+
+    /// Envia as verificações pendentes e baixa os templates da base da estação.
+    pub async fn sync(&self, auth: &SupabaseAuth, station_key: &StationKey) -> Result<TemplateCacheStatus, BiometricError> {
+        let result = self.sync_inner(auth, station_key).await;
+        match &result {
+            Ok(()) => {
+                self.set_meta("last_sync_at", Some(&chrono::Utc::now().to_rfc3339()));
+                self.set_meta("last_error", None);
+            }
+            Err(e) => self.set_meta("last_error", Some(&e.to_string())),
+        }
+        result?;
+        self.status()
+    }
+
+    async fn sync_inner(&self, auth: &SupabaseAuth, station_key: &StationKey) -> Result<(), BiometricError> {
+        let db = auth.connect().await?;
+        // Uma verificação que não sobe não pode deixar a estação sem os templates da base
+        let flushed = self.flush_pending(&db, station_key).await;
+        self.download_base(&db).await?;
+        flushed.map(|_| ())
+    }
+
+    /// Troca o cache pelos templates da base da estação.
+    async fn download_base(&self, db: &Supabase) -> Result<(), BiometricError> {
+        let Some(base_id) = StationConfig::load().base_id else {
+            log::debug!("Estação sem base definida; cache de templates mantido só pelas verificações");
+            return Ok(());
+        };
+        let templates: Vec<BaseTemplate> = db
+            .rpc_query("biometric_base_templates")
+            .select("id,user_id,template,quality,finger")
+            .arg("p_base_id", &base_id)
+            .fetch_all()
+            .await?;
+        self.replace_all(&templates)?;
+        self.set_meta("base_id", Some(&base_id));
+        log::info!("🗄️ Cache de templates sincronizado: {} template(s) da base {}", templates.len(), base_id);
+        Ok(())
+    }
+
+    pub fn status(&self) -> Result<TemplateCacheStatus, BiometricError> {
+        let (templates, users, pending): (i64, i64, i64) = self.lock().query_row(
+            "SELECT (SELECT COUNT(*) FROM cached_templates),
+                    (SELECT COUNT(DISTINCT user_id) FROM cached_templates),
+                    (SELECT COUNT(*) FROM offline_verifications)",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        Ok(TemplateCacheStatus {
+            base_id: self.meta("base_id"),
+            templates: templates as usize,
+            users: users as usize,
+            pending_verifications: pending as usize,
+            last_sync_at: self.meta("last_sync_at"),
+            last_error: self.meta("last_error"),
+        })
+    }
+}
+
+/// Comando Tauri: sincroniza o cache de templates agora.
+#[tauri::command]
+pub async fn sync_template_cache(
+    cache: tauri::State<'_, TemplateCache>,
+    auth: tauri::State<'_, SupabaseAuth>,
+    station_key: tauri::State<'_, StationKey>,
+) -> Result<TemplateCacheStatus, BiometricError> {
+    cache.sync(&auth, &station_key).await
+}
+
+/// Comando Tauri: situação do cache local de templates.
+#[tauri::command]
+pub fn get_template_cache_status(cache: tauri::State<'_, TemplateCache>) -> Result<TemplateCacheStatus, BiometricError> {
+    cache.status()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verification_policy::{AppliedPolicy, Operation, PolicySource};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    fn cache() -> TemplateCache {
+        TemplateCache::with_connection(Connection::open_in_memory().unwrap(), [7; 32]).unwrap()
+    }
+
+    fn template(id: &str, content: &str) -> RemoteTemplate {
+        RemoteTemplate {
+            id: id.to_string(),
+            template: content.to_string(),
+            quality: 80,
+            finger: Some("right_index".to_string()),
+        }
+    }
+
+    fn ids(templates: &[RemoteTemplate]) -> Vec<&str> {
+        templates.iter().map(|t| t.id.as_str()).collect()
+    }
+
+    fn receipt(key: &StationKey) -> VerificationReceipt {
+        let policy = AppliedPolicy {
+            rule_id: None,
+            source: PolicySource::BuiltIn,
+            operation: Operation::Delivery,
+            base_id: None,
+            criticality: None,
+            min_percent: 60,
+            requested_min_percent: None,
+        };
+        key.issue("ana", "dGVtcGxhdGU=", None, 16000, 80, &policy, None, None, None).unwrap()
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    /// PostgREST de mentira: guarda (caminho, corpo) de cada pedido, responde 500 aos caminhos
+    /// que contêm algum item de `failing` e devolve o `p_id` das chamadas de função.
+    struct Stub {
+        db: Supabase,
+        requests: Arc<Mutex<Vec<(String, serde_json::Value)>>>,
+        failing: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    impl Stub {
+        fn sent(&self, path: &str) -> Vec<serde_json::Value> {
+            let requests = self.requests.lock().unwrap();
+            requests.iter().filter(|(p, _)| p.contains(path)).map(|(_, body)| body.clone()).collect()
+        }
+    }
+
+    async fn stub() -> Stub {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests: Arc<Mutex<Vec<(String, serde_json::Value)>>> = Arc::default();
+        let failing: Arc<Mutex<Vec<&'static str>>> = Arc::default();
+        let (seen, fail) = (requests.clone(), failing.clone());
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let (seen, fail) = (seen.clone(), fail.clone());
+                tokio::spawn(async move {
+                    while let Some((path, body)) = read_request(&mut socket).await {
+                        let failed = fail.lock().unwrap().iter().any(|p| path.contains(p));
+                        let reply = body.get("p_id").map(|id| id.to_string()).unwrap_or_default();
+                        seen.lock().unwrap().push((path, body));
+                        let (status, reply) = if failed {
+                            ("500 Internal Server Error", r#"{"message":"falha"}"#.to_string())
+                        } else {
+                            ("200 OK", reply)
+                        };
+                        let response = format!(
+                            "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                            status,
+                            reply.len(),
+                            reply
+                        );
+                        if socket.write_all(response.as_bytes()).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        Stub {
+            db: Supabase::new(&url, "anon", "token"),
+            requests,
+            failing,
+        }
+    }
+
+    async fn read_request(socket: &mut TcpStream) -> Option<(String, serde_json::Value)> {
+        let mut buffer = Vec::new();
+        let mut chunk = [0u8; 4096];
+        let head_end = loop {
+            if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+                break pos + 4;
+            }
+            let n = socket.read(&mut chunk).await.ok().filter(|&n| n > 0)?;
+            buffer.extend_from_slice(&chunk[..n]);
+        };
+        let head = String::from_utf8_lossy(&buffer[..head_end]).to_string();
+        let path = head.split_whitespace().nth(1)?.to_string();
+        let length = head
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+            .unwrap_or(0);
+        while buffer.len() < head_end + length {
+            let n = socket.read(&mut chunk).await.ok().filter(|&n| n > 0)?;
+            buffer.extend_from_slice(&chunk[..n]);
+        }
+        let body = serde_json::from_slice(&buffer[head_end..head_end + length]).unwrap_or_default();
+        Some((path, body))
+    }
+
+    #[test]
+    fn templates_are_stored_encrypted_and_read_back() {
+        let cache = cache();
+        cache
+            .store_user("ana", &[template("t1", "TEMPLATE-UM"), template("t2", "TEMPLATE-DOIS")])
+            .unwrap();
+
+        let read = cache.user_templates("ana").unwrap();
+        assert_eq!(ids(&read), ["t1", "t2"]);
+        assert_eq!(read[0].template, "TEMPLATE-UM");
+        assert_eq!(read[1].finger.as_deref(), Some("right_index"));
+
+        let raw: Vec<u8> = cache
+            .lock()
+            .query_row("SELECT template FROM cached_templates WHERE id = 't1'", [], |row| row.get(0))
+            .unwrap();
+        assert!(!String::from_utf8_lossy(&raw).contains("TEMPLATE-UM"));
+    }
+
+    #[test]
+    fn storing_a_user_replaces_only_that_user() {
+        let cache = cache();
+        cache.store_user("ana", &[template("t1", "a1"), template("t2", "a2")]).unwrap();
+        cache.store_user("bia", &[template("t3", "b1")]).unwrap();
+
+        cache.store_user("ana", &[template("t4", "a3")]).unwrap();
+
+        assert_eq!(ids(&cache.user_templates("ana").unwrap()), ["t4"]);
+        assert_eq!(ids(&cache.user_templates("bia").unwrap()), ["t3"]);
+    }
+
+    #[test]
+    fn template_copied_to_another_row_does_not_decrypt() {
+        let cache = cache();
+        cache.store_user("ana", &[template("t1", "a1"), template("t2", "a2")]).unwrap();
+        cache
+            .lock()
+            .execute(
+                "UPDATE cached_templates SET
+                    nonce = (SELECT nonce FROM cached_templates WHERE id = 't1'),
+                    template = (SELECT template FROM cached_templates WHERE id = 't1')
+                 WHERE id = 't2'",
+                [],
+            )
+            .unwrap();
+
+        assert_eq!(ids(&cache.user_templates("ana").unwrap()), ["t1"]);
+    }
+
+    #[test]
+    fn base_download_replaces_the_whole_cache() {
+        let cache = cache();
+        cache.store_user("ana", &[template("t1", "a1")]).unwrap();
+
+        let base = BaseTemplate {
+            id: "t9".to_string(),
+            user_id: "bia".to_string(),
+            template: "b1".to_string(),
+            quality: 70,
+            finger: None,
+        };
+        cache.replace_all(&[base]).unwrap();
+
+        assert!(cache.user_templates("ana").unwrap().is_empty());
+        assert_eq!(ids(&cache.user_templates("bia").unwrap()), ["t9"]);
+        let status = cache.status().unwrap();
+        assert_eq!((status.templates, status.users), (1, 1));
+    }
+
+    #[test]
+    fn queued_verifications_get_their_own_id() {
+        let cache = cache();
+        let first = cache.queue_verification(&serde_json::json!({ "mode": "verify" }), None).unwrap();
+        let second = cache.queue_verification(&serde_json::json!({ "mode": "verify" }), None).unwrap();
+
+        assert_ne!(first, second);
+        assert_eq!(first.len(), 36);
+        let pending = cache.pending().unwrap();
+        assert_eq!(
+            pending.iter().map(|p| p.verification_id.as_str()).collect::<Vec<_>>(),
+            [first.as_str(), second.as_str()]
+        );
+        assert!(chrono::DateTime::parse_from_rfc3339(&pending[0].occurred_at).is_ok());
+        assert_eq!(cache.status().unwrap().pending_verifications, 2);
+    }
+
+    #[test]
+    fn pending_rows_from_an_older_cache_get_an_id() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE offline_verifications (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                created_at TEXT NOT NULL,
+                record TEXT NOT NULL,
+                receipt TEXT
+            );
+            INSERT INTO offline_verifications (created_at, record) VALUES ('2026-01-01T00:00:00Z', '{}');",
+        )
+        .unwrap();
+
+        let cache = TemplateCache::with_connection(conn, [7; 32]).unwrap();
+
+        let pending = cache.pending().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].verification_id.len(), 36);
+    }
+
+    #[test]
+    fn flush_sends_each_verification_with_its_id_and_time() {
+        block_on(async {
+            let stub = stub().await;
+            let cache = cache();
+            let key = StationKey::from_bytes([1; 32]);
+            let with_receipt = cache
+                .queue_verification(&serde_json::json!({ "success": true }), Some(&receipt(&key)))
+                .unwrap();
+            let without = cache.queue_verification(&serde_json::json!({ "success": false }), None).unwrap();
+
+            assert_eq!(cache.flush_pending(&stub.db, &key).await.unwrap(), 2);
+
+            let sent: Vec<_> = stub.sent("rpc/biometric_record_verification");
+            let sent_ids: Vec<_> = sent.iter().map(|body| body["p_id"].as_str().unwrap()).collect();
+            assert_eq!(sent_ids, [with_receipt.as_str(), without.as_str()]);
+            assert!(sent.iter().all(|body| body["p_occurred_at"].is_string()));
+            assert_eq!(stub.sent("biometric_receipts").len(), 1);
+            assert!(cache.pending().unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn failed_receipt_keeps_the_verification_and_the_rest_goes_on() {
+        block_on(async {
+            let stub = stub().await;
+            let cache = cache();
+            let key = StationKey::from_bytes([1; 32]);
+            let with_receipt = cache
+                .queue_verification(&serde_json::json!({ "success": true }), Some(&receipt(&key)))
+                .unwrap();
+            cache.queue_verification(&serde_json::json!({ "success": false }), None).unwrap();
+            stub.failing.lock().unwrap().push("biometric_receipts");
+
+            assert!(cache.flush_pending(&stub.db, &key).await.is_err());
+            let pending = cache.pending().unwrap();
+            assert_eq!(pending.len(), 1);
+            assert_eq!(pending[0].verification_id, with_receipt);
+
+            // O próximo envio repete a mesma verificação (mesmo id) e o comprovante
+            stub.failing.lock().unwrap().clear();
+            assert_eq!(cache.flush_pending(&stub.db, &key).await.unwrap(), 1);
+            let sent = stub.sent("rpc/biometric_record_verification");
+            assert_eq!(sent.len(), 3);
+            assert_eq!(sent[2]["p_id"], with_receipt.as_str());
+            assert_eq!(stub.sent("biometric_receipts").len(), 2);
+            assert!(cache.pending().unwrap().is_empty());
+        });
+    }
+}
@@ -0,0 +1,56 @@
+use base64::engine::general_purpose::GeneralPurpose;
+
+use crate::biometric_error::BiometricError;
+
+/// Base64 padrão (com `=`), usado em chaves, nonces, assinaturas e imagens.
+pub fn base64_engine() -> &'static GeneralPurpose {
+    &base64::engine::general_purpose::STANDARD
+}
+
+/// `N` bytes do gerador do sistema operacional (chaves, nonces e ids).
+pub fn random_bytes<const N: usize>() -> Result<[u8; N], BiometricError> {
+    let mut bytes = [0u8; N];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|e| BiometricError::Io(format!("Erro ao gerar bytes aleatórios: {}", e)))?;
+    Ok(bytes)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Id aleatório de 128 bits em hexadecimal (comprovantes, chaves de idempotência).
+pub fn random_hex_id() -> Result<String, BiometricError> {
+    Ok(to_hex(&random_bytes::<16>()?))
+}
+
+/// UUID v4 para o `id` das inserções: gerado na estação, o mesmo a cada reenvio.
+pub fn new_uuid() -> Result<String, BiometricError> {
+    let mut bytes = random_bytes::<16>()?;
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = to_hex(&bytes);
+    Ok(format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uuid_is_version_4_with_the_rfc_variant() {
+        let uuid = new_uuid().unwrap();
+        let groups: Vec<&str> = uuid.split('-').collect();
+        assert_eq!(groups.iter().map(|g| g.len()).collect::<Vec<_>>(), [8, 4, 4, 4, 12]);
+        assert!(groups[2].starts_with('4'));
+        assert!(matches!(groups[3].chars().next(), Some('8' | '9' | 'a' | 'b')));
+        assert_ne!(uuid, new_uuid().unwrap());
+    }
+
+    #[test]
+    fn hex_id_has_128_bits() {
+        let id = random_hex_id().unwrap();
+        assert_eq!(id.len(), 32);
+        assert!(id.chars().all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase()));
+    }
+}
@@ -0,0 +1,436 @@
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::biometric_error::BiometricError;
+use crate::supabase::Supabase;
+
+/// Por quanto tempo a política baixada do Supabase fica em memória
+const POLICY_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Nenhuma regra, mesmo vinda do Supabase, exige menos que isso
+pub const POLICY_FLOOR_PERCENT: i32 = 40;
+
+/// Exigência de quando nenhuma regra se aplica
+pub const DEFAULT_POLICY_PERCENT: i32 = 60;
+
+/// Operação que a biometria está autorizando.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    /// Entrega de EPI/material
+    Delivery,
+    /// Devolução
+    Return,
+    /// Ordem de desconto em folha
+    DiscountOrder,
+    /// Empréstimo
+    Loan,
+}
+
+/// Criticidade do item envolvido na operação.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Criticality {
+    Low,
+    Normal,
+    High,
+}
+
+const ALL_OPERATIONS: [Operation; 4] =
+    [Operation::Delivery, Operation::Return, Operation::DiscountOrder, Operation::Loan];
+const ALL_CRITICALITIES: [Option<Criticality>; 4] =
+    [None, Some(Criticality::Low), Some(Criticality::Normal), Some(Criticality::High)];
+
+/// Uma regra da política. Campos ausentes valem para qualquer valor.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PolicyRule {
+    pub id: String,
+    #[serde(default)]
+    pub operation: Option<Operation>,
+    #[serde(default)]
+    pub base_id: Option<String>,
+    #[serde(default)]
+    pub criticality: Option<Criticality>,
+    pub min_percent: i32,
+}
+
+impl PolicyRule {
+    fn matches(&self, operation: Operation, base_id: Option<&str>, criticality: Option<Criticality>) -> bool {
+        self.operation.map(|o| o == operation).unwrap_or(true)
+            && self.base_id.as_deref().map(|b| Some(b) == base_id).unwrap_or(true)
+            && self.criticality.map(|c| Some(c) == criticality).unwrap_or(true)
+    }
+
+    /// Quantos campos a regra fixa (a mais específica vence)
+    fn specificity(&self) -> usize {
+        [self.operation.is_some(), self.base_id.is_some(), self.criticality.is_some()]
+            .iter()
+            .filter(|&&set| set)
+            .count()
+    }
+}
+
+/// De onde veio a política aplicada.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicySource {
+    /// Tabela `biometric_policies` do Supabase
+    Supabase,
+    /// Regras embutidas no app (Supabase fora do ar ou sem regras)
+    BuiltIn,
+}
+
+/// Conjunto de regras de verificação.
+#[derive(Clone, Debug)]
+pub struct VerificationPolicy {
+    pub source: PolicySource,
+    pub rules: Vec<PolicyRule>,
+}
+
+/// Política efetivamente aplicada em uma verificação (vai no resultado).
+#[derive(Serialize, Clone, Debug)]
+pub struct AppliedPolicy {
+    /// Regra escolhida (`None` = padrão do app)
+    pub rule_id: Option<String>,
+    pub source: PolicySource,
+    pub operation: Operation,
+    pub base_id: Option<String>,
+    pub criticality: Option<Criticality>,
+    /// Mínimo exigido nesta verificação
+    pub min_percent: i32,
+    /// Mínimo pedido pelo frontend (só pode aumentar a exigência)
+    pub requested_min_percent: Option<i32>,
+}
+
+impl VerificationPolicy {
+    /// Regras embutidas: valem quando o Supabase não responde.
+    pub fn built_in() -> Self {
+        let rule = |id: &str, operation: Option<Operation>, criticality: Option<Criticality>, min_percent| PolicyRule {
+            id: id.to_string(),
+            operation,
+            base_id: None,
+            criticality,
+            min_percent,
+        };
+        Self {
+            source: PolicySource::BuiltIn,
+            rules: vec![
+                rule("builtin:discount_order", Some(Operation::DiscountOrder), None, 80),
+                rule("builtin:loan", Some(Operation::Loan), None, 70),
+                rule("builtin:high_criticality", None, Some(Criticality::High), 80),
+            ],
+        }
+    }
+
+    /// Escolhe a regra mais específica que casa (empate: a mais exigente).
+    ///
+    /// A `operation` é obrigatória: sem ela, uma tela poderia escapar das regras por
+    /// operação. O `requested_min_percent` do frontend só pode aumentar a exigência, e nada
+    /// fica abaixo de `POLICY_FLOOR_PERCENT`.
+    pub fn resolve(
+        &self,
+        operation: Operation,
+        base_id: Option<&str>,
+        criticality: Option<Criticality>,
+        requested_min_percent: Option<i32>,
+    ) -> AppliedPolicy {
+        let rule = self
+            .rules
+            .iter()
+            .filter(|r| r.matches(operation, base_id, criticality))
+            .max_by_key(|r| (r.specificity(), r.min_percent));
+
+        let policy_percent = rule.map(|r| r.min_percent).unwrap_or(DEFAULT_POLICY_PERCENT);
+        let min_percent = policy_percent
+            .max(requested_min_percent.unwrap_or(0))
+            .clamp(POLICY_FLOOR_PERCENT, 100);
+
+        if let Some(requested) = requested_min_percent.filter(|&r| r < policy_percent) {
+            log::warn!("⚠️ min_percent {} abaixo da política ({}); usando a política", requested, policy_percent);
+        }
+
+        AppliedPolicy {
+            rule_id: rule.map(|r| r.id.clone()),
+            source: self.source,
+            operation,
+            base_id: base_id.map(str::to_string),
+            criticality,
+            min_percent,
+            requested_min_percent,
+        }
+    }
+
+    /// Política de uma verificação ligada a uma entidade que não pôde ser conferida no Supabase
+    /// (offline): a mais exigente da base entre todas as operações e criticidades, já que as
+    /// informadas pela tela não valem. A `operation` da tela só fica registrada no resultado.
+    pub fn strictest(
+        &self,
+        operation: Operation,
+        base_id: Option<&str>,
+        requested_min_percent: Option<i32>,
+    ) -> AppliedPolicy {
+        let strictest = self
+            .every_case(base_id)
+            .max_by_key(|applied| applied.min_percent)
+            .unwrap_or_else(|| self.resolve(operation, base_id, None, None));
+        AppliedPolicy {
+            operation,
+            criticality: None,
+            min_percent: strictest
+                .min_percent
+                .max(requested_min_percent.unwrap_or(0))
+                .clamp(POLICY_FLOOR_PERCENT, 100),
+            requested_min_percent,
+            ..strictest
+        }
+    }
+
+    /// Similaridade (%) com a digital de outro usuário que bloqueia um cadastro na base: o
+    /// menor mínimo que a política aceita ali, entre todas as operações e criticidades.
+    /// Acima dele, a digital nova já passaria na verificação de outra pessoa em alguma tela.
+    pub fn duplicate_percent(&self, base_id: Option<&str>) -> i32 {
+        self.every_case(base_id)
+            .map(|applied| applied.min_percent)
+            .min()
+            .unwrap_or(DEFAULT_POLICY_PERCENT)
+    }
+
+    /// A política resolvida para cada operação e criticidade na base
+    fn every_case<'a>(&'a self, base_id: Option<&'a str>) -> impl Iterator<Item = AppliedPolicy> + 'a {
+        ALL_OPERATIONS
+            .iter()
+            .flat_map(|&operation| ALL_CRITICALITIES.iter().map(move |&criticality| (operation, criticality)))
+            .map(move |(operation, criticality)| self.resolve(operation, base_id, criticality, None))
+    }
+}
+
+/// Operação, base e criticidade de uma entidade autorizada pela biometria (`operation_id`),
+/// lidas do Supabase (`biometric_operation_context`) em vez de vir da tela.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct OperationContext {
+    pub operation: Operation,
+    #[serde(default)]
+    pub base_id: Option<String>,
+    #[serde(default)]
+    pub criticality: Option<Criticality>,
+}
+
+/// Contexto de `operation_id` no Supabase. `None` se a entidade não existe.
+pub async fn load_operation_context(
+    db: &Supabase,
+    operation_id: &str,
+) -> Result<Option<OperationContext>, BiometricError> {
+    let rows: Vec<OperationContext> = db
+        .rpc("biometric_operation_context", &serde_json::json!({ "p_operation_id": operation_id }))
+        .await?;
+    Ok(rows.into_iter().next())
+}
+
+struct CachedPolicy {
+    loaded_at: Instant,
+    policy: Arc<VerificationPolicy>,
+}
+
+/// Política de verificação em memória (gerenciada pelo Tauri).
+///
+/// A única fonte das regras é a tabela `biometric_policies` do Supabase (editável só por
+/// administradores, pela RLS). Sem conexão, vale a última política carregada nesta
+/// execução ou a embutida; não existe arquivo de configuração local.
+#[derive(Clone, Default)]
+pub struct PolicyCache {
+    entry: Arc<Mutex<Option<CachedPolicy>>>,
+}
+
+impl PolicyCache {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<CachedPolicy>> {
+        self.entry.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    /// Política atual: do cache, do Supabase ou, se ele falhar, a última conhecida
+    /// (ou a embutida).
+    pub async fn get(&self, db: &Supabase) -> Arc<VerificationPolicy> {
+        if let Some(cached) = self
+            .lock()
+            .as_ref()
+            .filter(|c| c.loaded_at.elapsed() < POLICY_CACHE_TTL)
+        {
+            return cached.policy.clone();
+        }
+
+        let loaded = load_policy(db).await;
+        let mut entry = self.lock();
+        match loaded {
+            Ok(policy) => {
+                let policy = Arc::new(policy);
+                *entry = Some(CachedPolicy {
+                    loaded_at: Instant::now(),
+                    policy: policy.clone(),
+                });
+                policy
+            }
+            Err(e) => {
+                log::warn!("⚠️ Política de verificação indisponível ({}); usando a última conhecida", e);
+                entry
+                    .as_ref()
+                    .map(|cached| cached.policy.clone())
+                    .unwrap_or_else(|| Arc::new(VerificationPolicy::built_in()))
+            }
+        }
+    }
+
+    /// Política em memória (mesmo vencida) ou a embutida, para quando não há conexão.
+    pub fn cached(&self) -> Arc<VerificationPolicy> {
+        self.lock()
+            .as_ref()
+            .map(|cached| cached.policy.clone())
+            .unwrap_or_else(|| Arc::new(VerificationPolicy::built_in()))
+    }
+}
+
+/// Baixa as regras ativas de `biometric_policies`. Sem regras, usa as embutidas.
+async fn load_policy(db: &Supabase) -> Result<VerificationPolicy, BiometricError> {
+    let rules: Vec<PolicyRule> = db
+        .from("biometric_policies")
+        .select("id,operation,base_id,criticality,min_percent")
+        .eq("ativo", true)
+        .fetch_all()
+        .await?;
+    log::debug!("Política de verificação: {} regras do Supabase", rules.len());
+
+    if rules.is_empty() {
+        return Ok(VerificationPolicy::built_in());
+    }
+    Ok(VerificationPolicy {
+        source: PolicySource::Supabase,
+        rules,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: &str, operation: Option<Operation>, base_id: Option<&str>, min_percent: i32) -> PolicyRule {
+        PolicyRule {
+            id: id.to_string(),
+            operation,
+            base_id: base_id.map(str::to_string),
+            criticality: None,
+            min_percent,
+        }
+    }
+
+    fn supabase(rules: Vec<PolicyRule>) -> VerificationPolicy {
+        VerificationPolicy {
+            source: PolicySource::Supabase,
+            rules,
+        }
+    }
+
+    #[test]
+    fn built_in_rules_per_operation_and_criticality() {
+        let policy = VerificationPolicy::built_in();
+
+        let delivery = policy.resolve(Operation::Delivery, None, None, None);
+        assert_eq!(delivery.min_percent, DEFAULT_POLICY_PERCENT);
+        assert_eq!(delivery.rule_id, None);
+
+        let discount = policy.resolve(Operation::DiscountOrder, None, Some(Criticality::Low), None);
+        assert_eq!(discount.min_percent, 80);
+        assert_eq!(discount.rule_id.as_deref(), Some("builtin:discount_order"));
+
+        // Empate de especificidade: vence a mais exigente
+        let loan = policy.resolve(Operation::Loan, None, Some(Criticality::High), None);
+        assert_eq!(loan.min_percent, 80);
+        assert_eq!(loan.rule_id.as_deref(), Some("builtin:high_criticality"));
+    }
+
+    #[test]
+    fn most_specific_rule_wins_even_if_lower() {
+        let policy = supabase(vec![
+            rule("entrega", Some(Operation::Delivery), None, 75),
+            rule("entrega-base-a", Some(Operation::Delivery), Some("base-a"), 65),
+        ]);
+
+        let base_a = policy.resolve(Operation::Delivery, Some("base-a"), None, None);
+        assert_eq!((base_a.rule_id.as_deref(), base_a.min_percent), (Some("entrega-base-a"), 65));
+
+        let base_b = policy.resolve(Operation::Delivery, Some("base-b"), None, None);
+        assert_eq!((base_b.rule_id.as_deref(), base_b.min_percent), (Some("entrega"), 75));
+
+        let devolucao = policy.resolve(Operation::Return, Some("base-a"), None, None);
+        assert_eq!((devolucao.rule_id, devolucao.min_percent), (None, DEFAULT_POLICY_PERCENT));
+        assert_eq!(devolucao.source, PolicySource::Supabase);
+    }
+
+    #[test]
+    fn frontend_can_only_raise_the_minimum() {
+        let policy = VerificationPolicy::built_in();
+
+        assert_eq!(policy.resolve(Operation::Loan, None, None, Some(0)).min_percent, 70);
+        assert_eq!(policy.resolve(Operation::Loan, None, None, Some(-10)).min_percent, 70);
+        assert_eq!(policy.resolve(Operation::Loan, None, None, Some(90)).min_percent, 90);
+        assert_eq!(policy.resolve(Operation::Loan, None, None, Some(150)).min_percent, 100);
+    }
+
+    #[test]
+    fn nothing_goes_below_the_floor() {
+        let policy = supabase(vec![rule("frouxa", None, None, 10)]);
+        let applied = policy.resolve(Operation::Delivery, None, None, None);
+        assert_eq!(applied.min_percent, POLICY_FLOOR_PERCENT);
+        assert_eq!(applied.rule_id.as_deref(), Some("frouxa"));
+    }
+
+    #[test]
+    fn duplicate_threshold_is_the_lowest_minimum_accepted_at_the_base() {
+        assert_eq!(VerificationPolicy::built_in().duplicate_percent(None), DEFAULT_POLICY_PERCENT);
+
+        let policy = supabase(vec![
+            rule("entrega", Some(Operation::Delivery), None, 75),
+            rule("devolucao-base-a", Some(Operation::Return), Some("base-a"), 50),
+            rule("padrao", None, None, 70),
+        ]);
+        assert_eq!(policy.duplicate_percent(Some("base-a")), 50);
+        assert_eq!(policy.duplicate_percent(Some("base-b")), 70);
+
+        let loose = supabase(vec![rule("frouxa", None, None, 10)]);
+        assert_eq!(loose.duplicate_percent(None), POLICY_FLOOR_PERCENT);
+    }
+
+    #[test]
+    fn strictest_ignores_the_operation_and_criticality_from_the_screen() {
+        let policy = VerificationPolicy::built_in();
+        let applied = policy.strictest(Operation::Delivery, None, None);
+        assert_eq!(applied.min_percent, 80);
+        assert_eq!(applied.operation, Operation::Delivery);
+        assert_eq!(applied.criticality, None);
+        assert_eq!(policy.strictest(Operation::Delivery, None, Some(95)).min_percent, 95);
+
+        let policy = supabase(vec![
+            rule("entrega", Some(Operation::Delivery), None, 60),
+            rule("emprestimo-base-a", Some(Operation::Loan), Some("base-a"), 85),
+        ]);
+        let base_a = policy.strictest(Operation::Delivery, Some("base-a"), None);
+        assert_eq!((base_a.rule_id.as_deref(), base_a.min_percent), (Some("emprestimo-base-a"), 85));
+        assert_eq!(policy.strictest(Operation::Delivery, Some("base-b"), None).min_percent, DEFAULT_POLICY_PERCENT);
+    }
+
+    #[test]
+    fn operation_context_from_the_rpc() {
+        let context: OperationContext = serde_json::from_value(serde_json::json!({
+            "operation": "delivery",
+            "base_id": "base-a",
+            "criticality": null
+        }))
+        .unwrap();
+        assert_eq!(
+            context,
+            OperationContext {
+                operation: Operation::Delivery,
+                base_id: Some("base-a".to_string()),
+                criticality: None,
+            }
+        );
+    }
+}
@@ -1,5 +1,9 @@
-import { useState } from 'react';
+import { useEffect, useState } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { baseService } from '../services/baseService';
+import type { Base } from '../types/contratos';
+import { biometricErrorMessage } from '../types/biometria';
+import type { LogEntry, ReaderInfo } from '../types/biometria';
 
 export function BiometricDiagnostic() {
   const [testing, setTesting] = useState(false);
@@ -8,6 +12,39 @@ export function BiometricDiagnostic() {
     message: string;
     details?: any;
   } | null>(null);
+  const [bases, setBases] = useState<Base[]>([]);
+  const [stationBase, setStationBase] = useState<string>('');
+
+  useEffect(() => {
+    invoke<string | null>('get_station_base')
+      .then(baseId => setStationBase(baseId ?? ''))
+      .catch(error => console.error('❌ [BiometricDiagnostic] Erro ao ler a base da estação:', error));
+    baseService.getBasesAtivas()
+      .then(setBases)
+      .catch(error => console.error('❌ [BiometricDiagnostic] Erro ao carregar bases:', error));
+  }, []);
+
+  // Só supervisores: o backend recusa os demais
+  const saveStationBase = async () => {
+    setTesting(true);
+    setResult(null);
+
+    try {
+      await invoke<string | null>('set_station_base', { baseId: stationBase || null });
+      await invoke('sync_template_cache');
+      setResult({
+        success: true,
+        message: stationBase ? 'Base da estação definida' : 'Base da estação removida'
+      });
+    } catch (error) {
+      setResult({
+        success: false,
+        message: biometricErrorMessage(error)
+      });
+    } finally {
+      setTesting(false);
+    }
+  };
 
   const testConnection = async () => {
     setTesting(true);
@@ -24,7 +61,7 @@ export function BiometricDiagnostic() {
     } catch (error) {
       setResult({
         success: false,
-        message: String(error)
+        message: biometricErrorMessage(error)
       });
     } finally {
       setTesting(false);
@@ -47,7 +84,7 @@ export function BiometricDiagnostic() {
     } catch (error) {
       setResult({
         success: false,
-        message: String(error)
+        message: biometricErrorMessage(error)
       });
     } finally {
       setTesting(false);
@@ -67,7 +104,7 @@ export function BiometricDiagnostic() {
     } catch (error) {
       setResult({
         success: false,
-        message: String(error)
+        message: biometricErrorMessage(error)
       });
     } finally {
       setTesting(false);
@@ -87,7 +124,52 @@ export function BiometricDiagnostic() {
     } catch (error) {
       setResult({
         success: false,
-        message: String(error)
+        message: biometricErrorMessage(error)
+      });
+    } finally {
+      setTesting(false);
+    }
+  };
+
+  const getReaderInfo = async () => {
+    setTesting(true);
+    setResult(null);
+
+    try {
+      const info = await invoke<ReaderInfo>('get_reader_info');
+      setResult({
+        success: true,
+        message: `${info.model} • Série ${info.serial_number} • Firmware ${info.firmware_version} • SDK ${info.sdk_version}${info.port ? ` • ${info.port}` : ''}`,
+        details: info
+      });
+    } catch (error) {
+      setResult({
+        success: false,
+        message: biometricErrorMessage(error)
+      });
+    } finally {
+      setTesting(false);
+    }
+  };
+
+  const getRecentLogs = async () => {
+    setTesting(true);
+    setResult(null);
+
+    try {
+      const entries = await invoke<LogEntry[]>('get_recent_logs', { limit: 100, minLevel: 'debug' });
+      setResult({
+        success: true,
+        message: `${entries.length} linhas de log recentes`,
+        details: entries.map(e => {
+          const context = Object.entries(e.context ?? {}).map(([k, v]) => ` ${k}=${v}`).join('');
+          return `${e.timestamp} ${e.level} ${e.message}${context}`;
+        })
+      });
+    } catch (error) {
+      setResult({
+        success: false,
+        message: biometricErrorMessage(error)
       });
     } finally {
       setTesting(false);
@@ -109,7 +191,7 @@ export function BiometricDiagnostic() {
     } catch (error) {
       setResult({
         success: false,
-        message: String(error)
+        message: biometricErrorMessage(error)
       });
     } finally {
       setTesting(false);
@@ -137,6 +219,14 @@ export function BiometricDiagnostic() {
           {testing ? '⏳ Verificando...' : '📊 Verificar Status do SDK'}
         </button>
 
+        <button
+          onClick={getReaderInfo}
+          disabled={testing}
+          className="w-full px-4 py-2 bg-teal-600 text-white rounded-lg hover:bg-teal-700 disabled:bg-gray-400 transition-colors text-sm font-medium"
+        >
+          {testing ? '⏳ Consultando...' : '🪪 Informações do Leitor'}
+        </button>
+
         <button
           onClick={testConnection}
           disabled={testing}
@@ -160,6 +250,35 @@ export function BiometricDiagnostic() {
         >
           {testing ? '⏳ Instalando...' : '💾 Instalar Driver'}
         </button>
+
+        <div className="flex gap-2">
+          <select
+            value={stationBase}
+            onChange={e => setStationBase(e.target.value)}
+            disabled={testing}
+            className="flex-1 px-2 py-2 border border-gray-300 rounded-lg text-sm"
+          >
+            <option value="">Sem base definida</option>
+            {bases.map(base => (
+              <option key={base.id} value={base.id}>{base.nome}</option>
+            ))}
+          </select>
+          <button
+            onClick={saveStationBase}
+            disabled={testing}
+            className="px-4 py-2 bg-orange-600 text-white rounded-lg hover:bg-orange-700 disabled:bg-gray-400 transition-colors text-sm font-medium"
+          >
+            🏢 Definir Base
+          </button>
+        </div>
+
+        <button
+          onClick={getRecentLogs}
+          disabled={testing}
+          className="w-full px-4 py-2 bg-gray-600 text-white rounded-lg hover:bg-gray-700 disabled:bg-gray-400 transition-colors text-sm font-medium"
+        >
+          {testing ? '⏳ Carregando...' : '📜 Logs Recentes'}
+        </button>
       </div>
 
       {result && (
@@ -1,5 +1,6 @@
 import { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { biometricErrorMessage } from '../types/biometria';
 import { AlertCircle, CheckCircle, Loader2 } from 'lucide-react';
 
 interface BiometricStatus {
@@ -60,7 +61,7 @@ export function BiometricStatusIndicator() {
       alert('Driver instalado com sucesso!\n\nIMPORTANTE: Reinicie o Windows para ativar o driver.');
       await checkBiometricStatus();
     } catch (error) {
-      alert('Erro ao instalar driver: ' + error);
+      alert('Erro ao instalar driver: ' + biometricErrorMessage(error));
     } finally {
       setInstalling(false);
     }
@@ -1,5 +1,8 @@
 import { useState, useEffect } from "react";
 import { invoke } from "@tauri-apps/api/core";
+import { biometricErrorMessage } from "../types/biometria";
+import type { EnrolledFinger, EnrollmentSessionResult, IdentificationResult, LockoutStatus } from "../types/biometria";
+import { useAuth } from "../hooks/useAuth";
 import { Fingerprint, CheckCircle, XCircle, RefreshCw, AlertTriangle, User } from "lucide-react";
 import { supabase } from "../lib/supabase";
 
@@ -28,6 +31,13 @@ export default function BiometricTest() {
     const [selectedUserId, setSelectedUserId] = useState<string>("");
     const [loadingUsers, setLoadingUsers] = useState(false);
 
+    // Dedos cadastrados do usuário selecionado
+    const [enrolledFingers, setEnrolledFingers] = useState<EnrolledFinger[] | null>(null);
+
+    // Bloqueios por falhas (liberados pelo supervisor logado)
+    const { user: supervisor } = useAuth();
+    const [lockouts, setLockouts] = useState<LockoutStatus | null>(null);
+
     useEffect(() => {
         loadUsers();
     }, []);
@@ -79,6 +89,7 @@ export default function BiometricTest() {
 
             const res = await invoke<ValidationResult>("validate_or_enroll_fingerprint", {
                 userId: selectedUserId,
+                operation: 'delivery',
                 minPercent: 90, // Threshold aumentado para 90% (Alta Segurança)
             });
 
@@ -94,7 +105,145 @@ export default function BiometricTest() {
         } catch (error) {
             console.error("Erro no teste:", error);
             setStatus("error");
-            setMessage(`Erro técnico: ${typeof error === 'string' ? error : JSON.stringify(error)}`);
+            setMessage(`Erro técnico: ${biometricErrorMessage(error)}`);
+        } finally {
+            if (unlisten) unlisten();
+        }
+    };
+
+    const loadEnrolledFingers = async () => {
+        if (!selectedUserId) return;
+        try {
+            const fingers = await invoke<EnrolledFinger[]>("list_enrolled_fingers", {
+                userId: selectedUserId,
+            });
+            setEnrolledFingers(fingers);
+        } catch (error) {
+            console.error("Erro ao listar dedos:", error);
+            setStatus("error");
+            setMessage(`Erro ao listar dedos: ${biometricErrorMessage(error)}`);
+        }
+    };
+
+    const removeFinger = async (finger: EnrolledFinger) => {
+        if (!confirm(`Remover a digital do ${finger.label}?`)) return;
+        try {
+            await invoke<number>("delete_finger_template", {
+                userId: selectedUserId,
+                finger: finger.finger,
+            });
+            await loadEnrolledFingers();
+        } catch (error) {
+            console.error("Erro ao remover dedo:", error);
+            setStatus("error");
+            setMessage(`Erro ao remover dedo: ${biometricErrorMessage(error)}`);
+        }
+    };
+
+    const loadLockouts = async () => {
+        try {
+            setLockouts(await invoke<LockoutStatus>("get_biometric_lockouts"));
+        } catch (error) {
+            console.error("Erro ao listar bloqueios:", error);
+        }
+    };
+
+    const releaseLockout = async (userId?: string) => {
+        if (!supervisor) {
+            alert("Faça login como supervisor para liberar bloqueios.");
+            return;
+        }
+        const reason = prompt("Motivo da liberação:");
+        if (!reason) return;
+        try {
+            await invoke<boolean>("override_biometric_lockout", {
+                userId,
+                reason,
+            });
+            await loadLockouts();
+        } catch (error) {
+            console.error("Erro ao liberar bloqueio:", error);
+            setStatus("error");
+            setMessage(`Erro ao liberar bloqueio: ${biometricErrorMessage(error)}`);
+        }
+    };
+
+    // Registra a chave desta estação em station_keys (uma vez, por um supervisor)
+    const registerStationKey = async () => {
+        try {
+            const registered = await invoke<boolean>("register_station_key");
+            setStatus("success");
+            setMessage(registered ? "Chave da estação registrada." : "A chave desta estação já estava registrada.");
+        } catch (error) {
+            console.error("Erro ao registrar chave da estação:", error);
+            setStatus("error");
+            setMessage(`Erro ao registrar chave da estação: ${biometricErrorMessage(error)}`);
+        }
+    };
+
+    // Cadastra os dois indicadores em uma sessão (dedos já cadastrados são pulados)
+    const enrollFingers = async () => {
+        if (!selectedUserId) return;
+        let unlisten: (() => void) | undefined;
+
+        try {
+            setStatus("capturing");
+            setMessage("Iniciando cadastro...");
+            setResult(null);
+
+            const { listen } = await import('@tauri-apps/api/event');
+            unlisten = await listen<string>('biometric-instruction', (event) => {
+                setMessage(event.payload);
+            });
+
+            const res = await invoke<EnrollmentSessionResult>("enroll_fingers", {
+                userId: selectedUserId,
+                fingers: ["right_index", "left_index"],
+            });
+
+            setStatus(res.enrolled > 0 ? "success" : "error");
+            setMessage(res.fingers.map(f => `${f.label}: ${f.reason}`).join(" • "));
+            await loadEnrolledFingers();
+        } catch (error) {
+            console.error("Erro no cadastro:", error);
+            setStatus("error");
+            setMessage(`Erro técnico: ${biometricErrorMessage(error)}`);
+        } finally {
+            if (unlisten) unlisten();
+        }
+    };
+
+    // Identificação 1:N: descobre o usuário pela digital, sem selecionar antes
+    const identifyUser = async () => {
+        let unlisten: (() => void) | undefined;
+
+        try {
+            setStatus("capturing");
+            setMessage("Iniciando identificação...");
+            setResult(null);
+
+            const { listen } = await import('@tauri-apps/api/event');
+            unlisten = await listen<string>('biometric-instruction', (event) => {
+                setMessage(event.payload);
+            });
+
+            const res = await invoke<IdentificationResult>("identify_fingerprint", {
+                operation: 'delivery',
+                minPercent: 90,
+            });
+
+            setStatus(res.success ? "success" : "error");
+            if (res.success && res.user_id) {
+                setSelectedUserId(res.user_id);
+                const user = users.find(u => u.id === res.user_id);
+                setMessage(`${res.reason}: ${user?.nome ?? res.user_id} (margem ${res.margin}%)`);
+            } else {
+                setMessage(`Não identificado: ${res.reason}`);
+            }
+        } catch (error) {
+            console.error("Erro na identificação:", error);
+            setStatus("error");
+            setMessage(`Erro técnico: ${biometricErrorMessage(error)}`);
         } finally {
             if (unlisten) unlisten();
         }
@@ -163,6 +312,109 @@ export default function BiometricTest() {
                         </>
                     )}
                 </button>
+
+                <button
+                    onClick={identifyUser}
+                    disabled={status === "capturing"}
+                    className={`
+            ml-3 flex items-center gap-3 px-6 py-4 rounded-xl text-lg font-semibold transition-all border-2
+            ${status === "capturing"
+                            ? "border-gray-300 text-gray-400 cursor-not-allowed"
+                            : "border-blue-600 text-blue-700 hover:bg-blue-50 active:transform active:scale-95"}
+          `}
+                >
+                    <User className="w-6 h-6" />
+                    Identificar (1:N)
+                </button>
+            </div>
+
+            <div className="mb-8 rounded-xl border border-gray-200 p-4">
+                <div className="flex items-center justify-between mb-3">
+                    <h4 className="text-sm font-semibold text-gray-900">Bloqueios por falhas</h4>
+                    <div className="flex gap-2">
+                        <button
+                            onClick={registerStationKey}
+                            className="px-3 py-1.5 text-sm rounded-lg border border-gray-300 text-gray-700 hover:bg-gray-50"
+                        >
+                            Registrar chave da estação
+                        </button>
+                        <button
+                            onClick={loadLockouts}
+                            className="px-3 py-1.5 text-sm rounded-lg border border-gray-300 text-gray-700 hover:bg-gray-50"
+                        >
+                            Atualizar
+                        </button>
+                    </div>
+                </div>
+                {lockouts === null ? (
+                    <p className="text-sm text-gray-500 mb-4">Clique em Atualizar para ver os bloqueios desta estação.</p>
+                ) : lockouts.users.length === 0 && !lockouts.station ? (
+                    <p className="text-sm text-gray-500 mb-4">Nenhum bloqueio ativo em {lockouts.station_id}.</p>
+                ) : (
+                    <ul className="divide-y divide-gray-100 mb-4">
+                        {lockouts.station && (
+                            <li className="flex items-center justify-between py-2 text-sm">
+                                <span className="font-medium text-red-700">Estação bloqueada ({lockouts.station.failures} falhas)</span>
+                                <button onClick={() => releaseLockout()} className="text-blue-600 hover:underline">Liberar</button>
+                            </li>
+                        )}
+                        {lockouts.users.map(lock => (
+                            <li key={lock.user_id} className="flex items-center justify-between py-2 text-sm">
+                                <span className="text-gray-900">
+                                    {users.find(u => u.id === lock.user_id)?.nome ?? lock.user_id}
+                                    <span className="text-gray-500 ml-2">{lock.failures} falhas • {new Date(lock.locked_at).toLocaleString('pt-BR')}</span>
+                                </span>
+                                <button onClick={() => releaseLockout(lock.user_id)} className="text-blue-600 hover:underline">Liberar</button>
+                            </li>
+                        ))}
+                    </ul>
+                )}
+
+                <div className="flex items-center justify-between mb-3">
+                    <h4 className="text-sm font-semibold text-gray-900">Dedos cadastrados</h4>
+                    <div className="flex gap-2">
+                        <button
+                            onClick={loadEnrolledFingers}
+                            disabled={!selectedUserId || status === "capturing"}
+                            className="px-3 py-1.5 text-sm rounded-lg border border-gray-300 text-gray-700 hover:bg-gray-50 disabled:opacity-50"
+                        >
+                            Atualizar
+                        </button>
+                        <button
+                            onClick={enrollFingers}
+                            disabled={!selectedUserId || status === "capturing"}
+                            className="px-3 py-1.5 text-sm rounded-lg bg-blue-600 text-white hover:bg-blue-700 disabled:opacity-50"
+                        >
+                            Cadastrar indicadores
+                        </button>
+                    </div>
+                </div>
+                {enrolledFingers === null ? (
+                    <p className="text-sm text-gray-500">Clique em Atualizar para ver as digitais do usuário.</p>
+                ) : enrolledFingers.length === 0 ? (
+                    <p className="text-sm text-gray-500">Nenhuma digital cadastrada.</p>
+                ) : (
+                    <ul className="divide-y divide-gray-100">
+                        {enrolledFingers.map(finger => (
+                            <li key={finger.finger} className="flex items-center justify-between py-2 text-sm">
+                                <div>
+                                    <span className="font-medium text-gray-900">{finger.label}</span>
+                                    <span className="text-gray-500 ml-2">
+                                        {finger.templates.length} template(s) • qualidade {finger.best_quality ?? '-'}%
+                                        {finger.last_enrolled_at && ` • ${new Date(finger.last_enrolled_at).toLocaleDateString('pt-BR')}`}
+                                    </span>
+                                </div>
+                                <button
+                                    onClick={() => removeFinger(finger)}
+                                    disabled={status === "capturing"}
+                                    className="text-red-600 hover:underline disabled:opacity-50"
+                                >
+                                    Remover
+                                </button>
+                            </li>
+                        ))}
+                    </ul>
+                )}
             </div>
 
             {status !== "idle" && (
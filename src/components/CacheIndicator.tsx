@@ -134,15 +134,8 @@ export function CacheIndicator({ userId }: CacheIndicatorProps) {
 
       if (invError) throw invError;
 
-      // 6. Buscar templates biométricos
-      const { data: biometricTemplates, error: bioError } = await supabase
-        .from('biometric_templates')
-        .select('*')
-        .limit(1000);
-
-      if (bioError) {
-        console.warn('⚠️ [CacheIndicator] Could not fetch biometric templates:', bioError);
-      }
+      // Templates biométricos não vão para o cache do navegador: o backend mantém o cache
+      // offline cifrado da base da estação (sync_template_cache)
 
       // Salvar no cache
       await Promise.all([
@@ -151,7 +144,6 @@ export function CacheIndicator({ userId }: CacheIndicatorProps) {
         offlineCache.cacheBases(bases || []),
         offlineCache.cacheUsuarios(usuarios || []),
         offlineCache.cacheInventarioFuncionario(inventario || []),
-        offlineCache.cacheBiometricTemplates(biometricTemplates || []),
       ]);
 
       // Atualizar timestamp
@@ -163,7 +155,6 @@ export function CacheIndicator({ userId }: CacheIndicatorProps) {
       console.log(`   - Bases: ${bases?.length || 0}`);
       console.log(`   - Usuários: ${usuarios?.length || 0}`);
       console.log(`   - Inventário: ${inventario?.length || 0}`);
-      console.log(`   - Templates biométricos: ${biometricTemplates?.length || 0}`);
 
       await loadCacheStatus();
     } catch (error) {
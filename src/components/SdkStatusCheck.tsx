@@ -1,5 +1,6 @@
 import { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { biometricErrorMessage } from '../types/biometria';
 import { AlertCircle, CheckCircle, Loader2, RefreshCw, Download } from 'lucide-react';
 
 interface SdkStatus {
@@ -31,7 +32,7 @@ export default function SdkStatusCheck({ onReady }: SdkStatusCheckProps) {
         onReady();
       }
     } catch (err) {
-      setError(err as string);
+      setError(biometricErrorMessage(err));
       console.error('Erro ao verificar status do SDK:', err);
     } finally {
       setLoading(false);
@@ -49,7 +50,7 @@ export default function SdkStatusCheck({ onReady }: SdkStatusCheckProps) {
         onReady();
       }
     } catch (err) {
-      setError(err as string);
+      setError(biometricErrorMessage(err));
       console.error('Erro ao sincronizar SDK:', err);
     } finally {
       setSyncing(false);
@@ -64,7 +65,7 @@ export default function SdkStatusCheck({ onReady }: SdkStatusCheckProps) {
         checkStatus();
       }, 2000);
     } catch (err) {
-      setError(err as string);
+      setError(biometricErrorMessage(err));
       console.error('Erro ao instalar driver:', err);
     }
   };
@@ -0,0 +1,104 @@
+import { useState } from 'react';
+import type { ConflictResolution, QueuedOperation } from '../types/sync';
+
+interface SyncConflictsPanelProps {
+  conflicts: QueuedOperation[];
+  onResolve: (id: number, resolution: ConflictResolution) => Promise<void>;
+}
+
+const KIND_LABELS: Record<string, string> = {
+  approve: 'Aprovação',
+  deliver: 'Entrega',
+  reject: 'Rejeição',
+  create: 'Cadastro',
+  update: 'Alteração',
+};
+
+// Conflitos que "aplicar mesmo assim" não resolve (o servidor recusa de novo)
+const HARD_CONFLICTS = ['already_delivered', 'cancelled', 'insufficient_stock', 'not_found'];
+
+/**
+ * Lista de revisão das operações offline que bateram com o que outra estação já fez.
+ * O almoxarife descarta, tenta de novo (ex.: depois de ajustar o estoque) ou aplica
+ * mesmo assim.
+ */
+export function SyncConflictsPanel({ conflicts, onResolve }: SyncConflictsPanelProps) {
+  const [busyId, setBusyId] = useState<number | null>(null);
+  const [error, setError] = useState<string | null>(null);
+
+  const resolve = async (id: number, resolution: ConflictResolution) => {
+    setBusyId(id);
+    setError(null);
+    try {
+      await onResolve(id, resolution);
+    } catch (err: any) {
+      setError(err?.message || String(err));
+    } finally {
+      setBusyId(null);
+    }
+  };
+
+  if (conflicts.length === 0) return null;
+
+  return (
+    <div className="bg-white border border-amber-300 rounded-lg shadow-sm divide-y divide-amber-100">
+      {error && (
+        <p className="px-4 py-2 text-xs text-red-700 bg-red-50">{error}</p>
+      )}
+      {conflicts.map((operation) => {
+        const payload = operation.payload as Record<string, any>;
+        const hard = HARD_CONFLICTS.includes(operation.conflict?.reason ?? '');
+        const busy = busyId === operation.id;
+
+        return (
+          <div key={operation.id} className="p-4 flex items-start gap-4">
+            <div className="flex-1 min-w-0">
+              <p className="text-sm font-medium text-gray-900">
+                {KIND_LABELS[operation.kind] ?? operation.kind} offline
+                {payload.solicitacao_id && (
+                  <span className="ml-2 text-xs font-normal text-gray-500">
+                    solicitação {String(payload.solicitacao_id).slice(0, 8)}
+                  </span>
+                )}
+              </p>
+              <p className="text-xs text-amber-800 mt-1">
+                {operation.conflict?.message || operation.last_error || 'Conflito com outra estação'}
+              </p>
+              <p className="text-xs text-gray-500 mt-1">
+                Feita em {new Date(operation.created_at).toLocaleString('pt-BR')}
+                {operation.conflict?.current?.status != null && (
+                  <> · situação atual: {String(operation.conflict.current.status)}</>
+                )}
+              </p>
+            </div>
+            <div className="flex flex-col gap-1 shrink-0">
+              <button
+                onClick={() => resolve(operation.id, 'discard')}
+                disabled={busy}
+                className="px-3 py-1 text-xs font-medium text-gray-700 bg-gray-100 rounded hover:bg-gray-200 disabled:opacity-50"
+              >
+                Descartar
+              </button>
+              <button
+                onClick={() => resolve(operation.id, 'retry')}
+                disabled={busy}
+                className="px-3 py-1 text-xs font-medium text-blue-800 bg-blue-100 rounded hover:bg-blue-200 disabled:opacity-50"
+              >
+                Tentar de novo
+              </button>
+              {!hard && (
+                <button
+                  onClick={() => resolve(operation.id, 'force')}
+                  disabled={busy}
+                  className="px-3 py-1 text-xs font-medium text-amber-900 bg-amber-100 rounded hover:bg-amber-200 disabled:opacity-50"
+                >
+                  Aplicar mesmo assim
+                </button>
+              )}
+            </div>
+          </div>
+        );
+      })}
+    </div>
+  );
+}
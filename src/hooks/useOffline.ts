@@ -7,48 +7,62 @@ import { useState, useEffect } from 'react';
 import { offlineCache } from '../services/offlineCache';
 import { offlineSync } from '../services/offlineSync';
 import { supabase } from '../lib/supabase';
+import type { BaseVersion, ConflictResolution, ConnectivityStatus, QueuedOperation } from '../types/sync';
+
+/** Versão da solicitação no cache, enviada junto da operação offline */
+function baseVersionOf(solicitacao: any): BaseVersion | undefined {
+  if (!solicitacao || solicitacao._pendingSync) return undefined;
+  return {
+    status: solicitacao.status,
+    atualizado_em: solicitacao.atualizado_em,
+    estoque_atual: solicitacao.item?.estoque_atual,
+  };
+}
 
 export function useOffline() {
-  const [isOnline, setIsOnline] = useState(navigator.onLine);
+  const [isOnline, setIsOnline] = useState(true);
+  const [connectivity, setConnectivity] = useState<ConnectivityStatus | null>(null);
   const [syncQueueCount, setSyncQueueCount] = useState(0);
+  const [failedSyncCount, setFailedSyncCount] = useState(0);
+  const [syncConflicts, setSyncConflicts] = useState<QueuedOperation[]>([]);
   const [isSyncing, setIsSyncing] = useState(false);
 
   useEffect(() => {
-    // Inicializar cache
-    offlineCache.init().catch(console.error);
-
-    // Listeners de conexão
-    const handleOnline = () => {
-      setIsOnline(true);
-      syncWithServer();
+    // Inicializar cache, levar a fila antiga do IndexedDB para o backend e copiar os
+    // dados de referência que o backend já tem
+    offlineCache.init()
+      .then(() => offlineSync.migrateLegacyQueue())
+      .then(() => offlineSync.applyReferenceCache())
+      .then(updateQueueCount)
+      .catch(console.error);
+
+    // Estado da conexão vem do backend, que consulta o Supabase de verdade
+    const applyConnectivity = (status: ConnectivityStatus) => {
+      setConnectivity(status);
+      setIsOnline(status.state !== 'offline');
     };
-
-    const handleOffline = () => {
-      setIsOnline(false);
-    };
-
-    window.addEventListener('online', handleOnline);
-    window.addEventListener('offline', handleOffline);
+    offlineSync.getConnectivityStatus().then(applyConnectivity).catch(console.error);
+    const unlistenConnectivity = offlineSync.onConnectivityChanged((status) => {
+      applyConnectivity(status);
+      updateQueueCount();
+    });
 
     // Atualizar contador da fila periodicamente
     const interval = setInterval(updateQueueCount, 5000);
     updateQueueCount();
 
-    // Iniciar sync automático
-    offlineSync.startAutoSync(5); // A cada 5 minutos
-
     return () => {
-      window.removeEventListener('online', handleOnline);
-      window.removeEventListener('offline', handleOffline);
+      unlistenConnectivity.then((unlisten) => unlisten());
       clearInterval(interval);
-      offlineSync.stopAutoSync();
     };
   }, []);
 
   const updateQueueCount = async () => {
     try {
-      const queue = await offlineCache.getSyncQueue();
-      setSyncQueueCount(queue.length);
+      const queue = await offlineSync.getSyncQueue();
+      setSyncQueueCount(queue.pending);
+      setFailedSyncCount(queue.failed);
+      setSyncConflicts(queue.operations.filter((operation) => operation.status === 'conflict'));
     } catch (error) {
       console.error('Error updating queue count:', error);
     }
@@ -66,6 +80,34 @@ export function useOffline() {
     }
   };
 
+  const retryFailedOperations = async (): Promise<void> => {
+    try {
+      await offlineSync.retryFailedOperations();
+      await updateQueueCount();
+    } catch (error) {
+      console.error('Error retrying failed operations:', error);
+    }
+  };
+
+  // Descarta as operações com falha; as seguintes da mesma solicitação voltam a ser enviadas
+  const discardFailedOperations = async (): Promise<void> => {
+    try {
+      const snapshot = await offlineSync.getSyncQueue();
+      const ids = snapshot.operations.filter(op => op.status === 'failed').map(op => op.id);
+      if (ids.length > 0) {
+        await offlineSync.discardFailedOperations(ids);
+      }
+      await updateQueueCount();
+    } catch (error) {
+      console.error('Error discarding failed operations:', error);
+    }
+  };
+
+  const resolveSyncConflict = async (id: number, resolution: ConflictResolution): Promise<void> => {
+    await offlineSync.resolveConflict(id, resolution);
+    await updateQueueCount();
+  };
+
   // ============================================================================
   // OPERAÇÕES OFFLINE
   // ============================================================================
@@ -103,19 +145,15 @@ export function useOffline() {
           }]);
         }
       } else {
-        // Offline: adicionar à fila
-        await offlineCache.addToSyncQueue({
-          type: 'approve',
-          table: 'solicitacoes_itens',
-          data: {
-            solicitacao_id: solicitacaoId,
-            quantidade_aprovada: quantidadeAprovada,
-            aprovado_por: aprovadoPor,
-          },
-        });
+        // Offline: adicionar à fila, com a versão que a estação leu (detecta conflito ao sincronizar)
+        const solicitacao = await offlineCache.getSolicitacaoById(solicitacaoId);
+        await offlineSync.enqueueOperation('approve', 'solicitacoes_itens', {
+          solicitacao_id: solicitacaoId,
+          quantidade_aprovada: quantidadeAprovada,
+          aprovado_por: aprovadoPor,
+        }, { baseVersion: baseVersionOf(solicitacao) });
 
         // Atualizar cache local otimisticamente
-        const solicitacao = await offlineCache.getSolicitacaoById(solicitacaoId);
         if (solicitacao) {
           await offlineCache.cacheSolicitacoes([{
             ...solicitacao,
@@ -176,22 +214,18 @@ export function useOffline() {
           }]);
         }
       } else {
-        // Offline: adicionar à fila
-        await offlineCache.addToSyncQueue({
-          type: 'deliver',
-          table: 'solicitacoes_itens',
-          data: {
-            solicitacao_id: solicitacaoId,
-            entregador_id: entregadorId,
-            quantidade_entregue: quantidadeEntregue,
-            observacoes,
-            numero_laudo: numeroLaudo,
-            validade_laudo: validadeLaudo,
-          },
-        });
+        // Offline: adicionar à fila, com a versão que a estação leu (detecta conflito ao sincronizar)
+        const solicitacao = await offlineCache.getSolicitacaoById(solicitacaoId);
+        await offlineSync.enqueueOperation('deliver', 'solicitacoes_itens', {
+          solicitacao_id: solicitacaoId,
+          entregador_id: entregadorId,
+          quantidade_entregue: quantidadeEntregue,
+          observacoes,
+          numero_laudo: numeroLaudo,
+          validade_laudo: validadeLaudo,
+        }, { baseVersion: baseVersionOf(solicitacao) });
 
         // Atualizar cache otimisticamente
-        const solicitacao = await offlineCache.getSolicitacaoById(solicitacaoId);
         if (solicitacao) {
           await offlineCache.cacheSolicitacoes([{
             ...solicitacao,
@@ -244,19 +278,15 @@ export function useOffline() {
           }]);
         }
       } else {
-        // Offline: adicionar à fila
-        await offlineCache.addToSyncQueue({
-          type: 'reject',
-          table: 'solicitacoes_itens',
-          data: {
-            solicitacao_id: solicitacaoId,
-            motivo_rejeicao: motivoRejeicao,
-            rejeitado_por: rejeitadoPor,
-          },
-        });
+        // Offline: adicionar à fila, com a versão que a estação leu (detecta conflito ao sincronizar)
+        const solicitacao = await offlineCache.getSolicitacaoById(solicitacaoId);
+        await offlineSync.enqueueOperation('reject', 'solicitacoes_itens', {
+          solicitacao_id: solicitacaoId,
+          motivo_rejeicao: motivoRejeicao,
+          rejeitado_por: rejeitadoPor,
+        }, { baseVersion: baseVersionOf(solicitacao) });
 
         // Atualizar cache otimisticamente
-        const solicitacao = await offlineCache.getSolicitacaoById(solicitacaoId);
         if (solicitacao) {
           await offlineCache.cacheSolicitacoes([{
             ...solicitacao,
@@ -318,9 +348,15 @@ export function useOffline() {
 
   return {
     isOnline,
+    connectivity,
     isSyncing,
     syncQueueCount,
+    failedSyncCount,
+    syncConflicts,
     syncWithServer,
+    retryFailedOperations,
+    discardFailedOperations,
+    resolveSyncConflict,
     approveSolicitacao,
     deliverSolicitacao,
     rejectSolicitacao,
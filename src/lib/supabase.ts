@@ -1,4 +1,6 @@
-import { createClient } from "@supabase/supabase-js";
+import { createClient, type Session } from "@supabase/supabase-js";
+import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 
 // As variáveis de ambiente serão configuradas via .env
 const supabaseUrl = import.meta.env.VITE_SUPABASE_URL || "";
@@ -17,3 +19,43 @@ export const supabase = createClient(supabaseUrl, supabaseAnonKey, {
     detectSessionInUrl: false,
   },
 });
+
+// O backend (Rust) faz as chamadas REST da biometria com o JWT do usuário logado:
+// toda sessão nova ou renovada é entregue a ele, e o logout a descarta.
+const syncBackendSession = async (session: Session | null) => {
+  try {
+    if (session) {
+      await invoke("set_supabase_session", {
+        accessToken: session.access_token,
+        refreshToken: session.refresh_token,
+        expiresAt: session.expires_at ?? Math.floor(Date.now() / 1000) + (session.expires_in ?? 3600),
+        userId: session.user?.id,
+      });
+    } else {
+      await invoke("clear_supabase_session");
+    }
+  } catch (error) {
+    console.warn("⚠️ Não foi possível sincronizar a sessão com o backend:", error);
+  }
+};
+
+supabase.auth.onAuthStateChange((_event, session) => {
+  void syncBackendSession(session);
+});
+
+// Quando o backend renova o token, o refresh_token antigo deixa de valer: adota os novos
+interface BackendSession {
+  access_token: string;
+  refresh_token: string;
+  expires_at: number;
+  user_id?: string | null;
+}
+
+listen<BackendSession>("supabase-session-refreshed", ({ payload }) => {
+  void supabase.auth.setSession({
+    access_token: payload.access_token,
+    refresh_token: payload.refresh_token,
+  });
+}).catch(() => {
+  // Fora do Tauri (ex.: navegador) não há backend para ouvir
+});
@@ -7,6 +7,9 @@ import { invoke } from "@tauri-apps/api/core";
 import { Package, Fingerprint, LogOut, ClipboardList, CheckCircle, FolderOpen, FileText, PackagePlus, PackageMinus, ArrowLeftRight, Users } from "lucide-react";
 import UpdateButton from "../components/UpdateButton";
 import type { SolicitacaoItem, EntregaData } from "../types";
+import { isBiometricError, fingerprintImageSrc } from "../types/biometria";
+import type { AppliedPolicy, EnrollmentReport, FingerprintImage, VerificationReceipt } from "../types/biometria";
+import type { RealtimeChange } from "../types/realtime";
 import { estoqueService } from "../services/estoqueService";
 import { discountOrderService } from "../services/discountOrderService";
 import SolicitacoesView from "./SolicitacoesView";
@@ -27,6 +30,7 @@ import DeliveryValidationModal from '../components/DeliveryValidationModal';
 import UpdateChecker from "../components/UpdateChecker";
 import { useAuth } from "../hooks/useAuth";
 import { useOffline } from "../hooks/useOffline";
+import { SyncConflictsPanel } from "../components/SyncConflictsPanel";
 import { CacheIndicator } from "../components/CacheIndicator";
 
 interface AlmoxarifeViewProps {
@@ -35,7 +39,19 @@ interface AlmoxarifeViewProps {
 
 export default function AlmoxarifeView({ onLogout }: AlmoxarifeViewProps) {
   const { user } = useAuth();
-  const { isOnline, isSyncing, syncQueueCount, deliverSolicitacao: deliverOffline } = useOffline();
+  const {
+    isOnline,
+    connectivity,
+    isSyncing,
+    syncQueueCount,
+    failedSyncCount,
+    syncConflicts,
+    retryFailedOperations,
+    discardFailedOperations,
+    resolveSyncConflict,
+    deliverSolicitacao: deliverOffline,
+  } = useOffline();
+  const [showSyncConflicts, setShowSyncConflicts] = useState(false);
   const [activeTab, setActiveTab] = useState<'solicitacoes' | 'modulos' | 'inventarios' | 'ordens-desconto' | 'entrada-material' | 'devolucoes' | 'transferencias' | 'emprestimos-terceiros'>('solicitacoes');
   const [inventarioSubPage, setInventarioSubPage] = useState<'hub' | 'equipes' | 'funcionarios' | 'detalhes' | 'detalhes-equipe'>('hub');
   const [selectedFuncionario, setSelectedFuncionario] = useState<{ id: string; nome: string } | null>(null);
@@ -58,6 +74,8 @@ export default function AlmoxarifeView({ onLogout }: AlmoxarifeViewProps) {
 
   // Last update trigger for SolicitacoesView
   const [lastUpdate, setLastUpdate] = useState<number>(0);
+  // Transferência que chegou pela assinatura do Realtime
+  const [incomingTransfer, setIncomingTransfer] = useState<Record<string, any> | null>(null);
 
   // BIOMETRIC MODAL STATE
   const [biometricModal, setBiometricModal] = useState<{
@@ -72,7 +90,7 @@ export default function AlmoxarifeView({ onLogout }: AlmoxarifeViewProps) {
     processing: boolean;
     validationResult?: 'success' | 'failure' | null;
     validationMessage?: string;
-    fingerprintImage?: string;
+    fingerprintImage?: FingerprintImage;
   }>({
     open: false,
     userId: '',
@@ -113,6 +131,22 @@ export default function AlmoxarifeView({ onLogout }: AlmoxarifeViewProps) {
     setupEmployeeWindow();
   }, []);
 
+  // Mudanças da base vindas do Realtime (assinatura mantida pelo backend)
+  useEffect(() => {
+    const unlisteners = [
+      listen<RealtimeChange>('solicitacao-created', () => setLastUpdate(Date.now())),
+      listen<RealtimeChange>('solicitacao-updated', () => setLastUpdate(Date.now())),
+      listen<RealtimeChange>('transferencia-received', (event) => {
+        if (event.payload.record?.status === 'em_transito') {
+          setIncomingTransfer(event.payload.record);
+        }
+      }),
+    ];
+    return () => {
+      unlisteners.forEach((unlisten) => unlisten.then((fn) => fn()));
+    };
+  }, []);
+
   const setupEmployeeWindow = async () => {
     try {
       // Check if window already exists
@@ -330,18 +364,15 @@ export default function AlmoxarifeView({ onLogout }: AlmoxarifeViewProps) {
 
       console.log('🔍 [AlmoxarifeView] Checking biometric enrollment for user:', userIdToValidate, userNameToValidate);
 
-      // Check if user has biometrics
+      // Check if user has biometrics (só os dedos: os templates não saem do banco para a tela)
       const { data, error } = await supabase
-        .from('biometric_templates')
-        .select('finger, ativo')
-        .eq('user_id', userIdToValidate)
-        .eq('ativo', true) // Only active templates
+        .rpc('biometric_enrolled_fingers', { p_user_id: userIdToValidate })
         .limit(1);
 
       console.log('📊 [AlmoxarifeView] Biometric query result:', { data, error, userIdToValidate });
 
       if (error) {
-        console.error('❌ [AlmoxarifeView] Error querying biometric_templates:', error);
+        console.error('❌ [AlmoxarifeView] Error querying biometric_enrolled_fingers:', error);
         throw error;
       }
 
@@ -435,8 +466,10 @@ export default function AlmoxarifeView({ onLogout }: AlmoxarifeViewProps) {
     });
 
     // Listen to fingerprint images
-    const unlistenImage = await listen<string>('biometric-image', (event) => {
+    const unlistenImage = await listen<FingerprintImage>('biometric-image', (event) => {
       setBiometricModal(prev => ({ ...prev, fingerprintImage: event.payload }));
+      // Forward to employee window
+      emitTo("employee", "biometric-image", event.payload).catch(console.error);
     });
 
     
@@ -449,22 +482,42 @@ export default function AlmoxarifeView({ onLogout }: AlmoxarifeViewProps) {
     try {
       const result = await invoke<{
         success: boolean;
+        outcome: 'validated' | 'enrolled' | 'rejected' | 'cancelled' | 'timeout' | 'duplicate' | 'locked' | 'replay_suspected';
         reason: string;
         score?: number;
         percent?: number;
         quality?: number;
         enrolled: boolean;
+        fingerprint_image?: FingerprintImage | null;
+        enrollment?: EnrollmentReport | null;
+        policy?: AppliedPolicy | null;
+        receipt?: VerificationReceipt | null;
+        offline?: boolean;
       }>("validate_or_enroll_fingerprint", {
         userId: userId,
-        minPercent: 50,
+        operation: 'delivery',
+        operationId: targetSolicitacao.id,
         fingerId: biometricModal.isEnrolled ? undefined : biometricModal.selectedFinger,
-        supabaseUrl: import.meta.env.VITE_SUPABASE_URL,
-        serviceKey: import.meta.env.VITE_SUPABASE_SERVICE_ROLE_KEY,
+        baseId: targetSolicitacao.base_id ?? undefined,
       });
 
       unlisten(); // Stop listening
       unlistenImage();
 
+      if (result.outcome === 'cancelled' || result.outcome === 'timeout') {
+        setBiometricModal(prev => ({
+          ...prev,
+          processing: false,
+          validationResult: 'failure',
+          validationMessage: result.outcome === 'timeout'
+            ? 'Tempo esgotado aguardando a digital. Tente novamente.'
+            : 'Captura cancelada.',
+          message: ''
+        }));
+        setIsDelivering(false);
+        return;
+      }
+
       if (!result.success) {
         // Show failure in modal with retry option
         setBiometricModal(prev => ({
@@ -478,6 +531,10 @@ export default function AlmoxarifeView({ onLogout }: AlmoxarifeViewProps) {
         return;
       }
 
+      if (result.receipt) {
+        console.log('🧾 [AlmoxarifeView] Comprovante biométrico', result.receipt.payload.receipt_id);
+      }
+
       // Show success
       let validationMessage = "Biometria validada com sucesso!";
 
@@ -487,6 +544,11 @@ export default function AlmoxarifeView({ onLogout }: AlmoxarifeViewProps) {
         validationMessage = `Biometria validada com sucesso! Similaridade: ${result.percent}%`;
       }
 
+      if (result.offline) {
+        // Conferida contra o cache local; o registro sobe quando a conexão voltar
+        validationMessage += " (offline, será sincronizada ao reconectar)";
+      }
+
       setBiometricModal(prev => ({
         ...prev,
         processing: false,
@@ -508,16 +570,30 @@ export default function AlmoxarifeView({ onLogout }: AlmoxarifeViewProps) {
       
       // Melhorar mensagem de erro baseada no tipo de erro
       let errorMessage = 'Erro ao comunicar com o sensor biométrico';
-      const errorStr = String(error);
-      
-      if (errorStr.includes('não foi possível detectar o leitor')) {
-        errorMessage = 'Leitor biométrico não detectado. Verifique se está conectado na porta USB.';
-      } else if (errorStr.includes('código de erro -1')) {
-        errorMessage = 'Sensor não inicializado. Verifique a conexão USB e tente novamente.';
-      } else if (errorStr.includes('Verifique se o leitor está conectado')) {
-        errorMessage = 'Não foi possível comunicar com o leitor. Verifique a conexão USB.';
-      } else if (errorStr.includes('CIDBIO')) {
-        errorMessage = 'Erro no sensor biométrico. Tente reconectar o leitor USB.';
+
+      if (isBiometricError(error)) {
+        switch (error.code) {
+          case 'READER_NOT_FOUND':
+          case 'NO_DEVICE':
+            errorMessage = 'Leitor biométrico não detectado. Verifique se está conectado na porta USB.';
+            break;
+          case 'SDK_UNKNOWN':
+            errorMessage = 'Sensor não inicializado. Verifique a conexão USB e tente novamente.';
+            break;
+          case 'COMM_USB':
+          case 'IO_ON_HOST':
+            errorMessage = 'Não foi possível comunicar com o leitor. Verifique a conexão USB.';
+            break;
+          case 'DRIVER_NOT_INSTALLED':
+          case 'DRIVER_MALFUNCTION':
+            errorMessage = 'Driver do leitor biométrico com problema. Use o painel de diagnóstico para reinstalar.';
+            break;
+          case 'HTTP':
+            errorMessage = 'Falha ao comunicar com o servidor. Verifique a conexão com a internet.';
+            break;
+          default:
+            errorMessage = error.message;
+        }
       }
       
       setBiometricModal(prev => ({
@@ -553,6 +629,11 @@ export default function AlmoxarifeView({ onLogout }: AlmoxarifeViewProps) {
             user!.id
           );
           console.log('✅ [AlmoxarifeView] Return processed successfully');
+          invoke("record_audit_event", {
+            action: 'return_processed',
+            userId: user!.id,
+            data: { solicitacao_id: pendingReturnData.solicitacao.id }
+          }).catch(console.error);
         } catch (returnError) {
           console.error('❌ [AlmoxarifeView] Error processing return:', returnError);
           alert('Erro ao processar devolução do item antigo. Operação cancelada.');
@@ -597,6 +678,17 @@ export default function AlmoxarifeView({ onLogout }: AlmoxarifeViewProps) {
         );
 
         console.log('✅ [AlmoxarifeView] Entrega processada com sucesso');
+        invoke("record_audit_event", {
+          action: 'delivery_completed',
+          userId: user!.id,
+          data: {
+            solicitacao_id: solicitacao.id,
+            item_id: solicitacao.item_id,
+            base_id: solicitacao.base_id,
+            quantidade: quantidadeEntregue,
+            offline: !isOnline
+          }
+        }).catch(console.error);
         
         // Mostrar mensagem apropriada
         if (!isOnline) {
@@ -911,6 +1003,124 @@ export default function AlmoxarifeView({ onLogout }: AlmoxarifeViewProps) {
             </div>
           )}
 
+          {/* Incoming Transfer Indicator */}
+          {incomingTransfer && (
+            <div className="bg-indigo-50 border-l-4 border-indigo-400 p-4 rounded-lg shadow-sm">
+              <div className="flex items-center">
+                <div className="flex-shrink-0">
+                  <span className="text-2xl">🚚</span>
+                </div>
+                <div className="ml-3 flex-1">
+                  <p className="text-sm font-medium text-indigo-800">
+                    Transferência {incomingTransfer.numero_transferencia || ''} a caminho desta base
+                  </p>
+                  <p className="text-xs text-indigo-700 mt-1">
+                    Confira o recebimento em Transferências.
+                  </p>
+                </div>
+                <button
+                  onClick={() => {
+                    setActiveTab('transferencias');
+                    setIncomingTransfer(null);
+                  }}
+                  className="ml-3 px-3 py-1 text-xs font-medium text-indigo-900 bg-indigo-100 rounded hover:bg-indigo-200"
+                >
+                  Ver
+                </button>
+                <button
+                  onClick={() => setIncomingTransfer(null)}
+                  className="ml-2 px-3 py-1 text-xs font-medium text-gray-700 bg-gray-100 rounded hover:bg-gray-200"
+                >
+                  Fechar
+                </button>
+              </div>
+            </div>
+          )}
+
+          {/* Degraded Connection Indicator */}
+          {connectivity?.state === 'degraded' && (
+            <div className="bg-orange-50 border-l-4 border-orange-400 p-4 rounded-lg shadow-sm">
+              <div className="flex items-center">
+                <div className="flex-shrink-0">
+                  <span className="text-2xl">📶</span>
+                </div>
+                <div className="ml-3 flex-1">
+                  <p className="text-sm font-medium text-orange-800">
+                    Conexão instável
+                  </p>
+                  <p className="text-xs text-orange-700 mt-1">
+                    {connectivity.detail || 'O servidor está respondendo com dificuldade.'}
+                  </p>
+                </div>
+              </div>
+            </div>
+          )}
+
+          {/* Failed Sync Indicator */}
+          {failedSyncCount > 0 && (
+            <div className="bg-red-50 border-l-4 border-red-400 p-4 rounded-lg shadow-sm">
+              <div className="flex items-center">
+                <div className="flex-shrink-0">
+                  <span className="text-2xl">🚨</span>
+                </div>
+                <div className="ml-3 flex-1">
+                  <p className="text-sm font-medium text-red-800">
+                    {failedSyncCount} operação(ões) offline não sincronizada(s)
+                  </p>
+                  <p className="text-xs text-red-700 mt-1">
+                    O servidor recusou ou não respondeu após várias tentativas. Verifique e tente novamente.
+                  </p>
+                </div>
+                <button
+                  onClick={retryFailedOperations}
+                  className="ml-3 px-3 py-1 text-xs font-medium text-red-800 bg-red-100 rounded hover:bg-red-200"
+                >
+                  Tentar novamente
+                </button>
+                <button
+                  onClick={() => {
+                    if (window.confirm('Descartar as operações com falha? Elas não serão enviadas ao servidor.')) {
+                      discardFailedOperations();
+                    }
+                  }}
+                  className="ml-2 px-3 py-1 text-xs font-medium text-red-800 bg-white border border-red-200 rounded hover:bg-red-50"
+                >
+                  Descartar
+                </button>
+              </div>
+            </div>
+          )}
+
+          {/* Sync Conflicts Review */}
+          {syncConflicts.length > 0 && (
+            <div className="space-y-2">
+              <div className="bg-amber-50 border-l-4 border-amber-400 p-4 rounded-lg shadow-sm">
+                <div className="flex items-center">
+                  <div className="flex-shrink-0">
+                    <span className="text-2xl">⚖️</span>
+                  </div>
+                  <div className="ml-3 flex-1">
+                    <p className="text-sm font-medium text-amber-800">
+                      {syncConflicts.length} operação(ões) offline em conflito
+                    </p>
+                    <p className="text-xs text-amber-700 mt-1">
+                      Outra estação alterou as mesmas solicitações. Revise antes de sincronizar.
+                    </p>
+                  </div>
+                  <button
+                    onClick={() => setShowSyncConflicts(prev => !prev)}
+                    className="ml-3 px-3 py-1 text-xs font-medium text-amber-900 bg-amber-100 rounded hover:bg-amber-200"
+                  >
+                    {showSyncConflicts ? 'Ocultar' : 'Revisar'}
+                  </button>
+                </div>
+              </div>
+              {showSyncConflicts && (
+                <SyncConflictsPanel conflicts={syncConflicts} onResolve={resolveSyncConflict} />
+              )}
+            </div>
+          )}
+
           {/* Syncing Indicator */}
           {isOnline && isSyncing && (
             <div className="bg-blue-50 border-l-4 border-blue-400 p-4 rounded-lg shadow-sm">
@@ -1051,6 +1261,19 @@ export default function AlmoxarifeView({ onLogout }: AlmoxarifeViewProps) {
                   <div className="col-span-2 flex flex-col items-center justify-center p-4 bg-blue-50 rounded-xl space-y-3">
                     <div className="animate-spin rounded-full h-8 w-8 border-b-2 border-blue-600"></div>
                     <p className="text-blue-700 font-bold text-lg animate-pulse">{biometricModal.message || "Aguardando..."}</p>
+                    {fingerprintImageSrc(biometricModal.fingerprintImage) && (
+                      <img
+                        src={fingerprintImageSrc(biometricModal.fingerprintImage)}
+                        alt="Digital capturada"
+                        className="h-32 object-contain rounded-lg border border-blue-200 bg-white"
+                      />
+                    )}
+                    <button
+                      onClick={() => invoke('cancel_biometric_capture').catch(console.error)}
+                      className="px-4 py-2 rounded-xl border border-gray-300 text-gray-700 font-semibold hover:bg-gray-50 transition-colors"
+                    >
+                      Cancelar Captura
+                    </button>
                   </div>
                 ) : biometricModal.validationResult === 'success' ? (
                   <div className="col-span-2 flex flex-col items-center justify-center p-6 bg-green-50 rounded-xl border border-green-200 animate-in zoom-in-95">
@@ -2,6 +2,8 @@ import { useState, useEffect } from "react";
 import { listen, emit } from "@tauri-apps/api/event";
 import { Package, User, Clock, CheckCircle, Fingerprint, ShieldCheck } from "lucide-react";
 import type { EntregaData, SolicitacaoItem } from "../types";
+import { fingerprintImageSrc } from "../types/biometria";
+import type { EnrollmentFingerEvent, FingerprintImage } from "../types/biometria";
 
 interface ValidationData {
   type: 'validation-start' | 'validation-success' | 'validation-error' | 'validation-cancelled';
@@ -27,6 +29,8 @@ export default function EmployeeView() {
   const [entregaData, setEntregaData] = useState<EntregaData | null>(null);
   const [validationData, setValidationData] = useState<ValidationData | null>(null);
   const [biometricInstruction, setBiometricInstruction] = useState<string>("");
+  const [enrollmentFinger, setEnrollmentFinger] = useState<EnrollmentFingerEvent | null>(null);
+  const [fingerprintImage, setFingerprintImage] = useState<FingerprintImage | null>(null);
   const [status, setStatus] = useState<
     "aguardando" | "em_andamento" | "validacao" | "concluida" | "mostrando_inventario"
   >("aguardando");
@@ -53,6 +57,8 @@ export default function EmployeeView() {
             setStatus("validacao");
             // Set initial instruction - wait for operator to start
             setBiometricInstruction("Aguarde a inicialização pelo almoxarife...");
+            setFingerprintImage(null);
+            setEnrollmentFinger(null);
           } else if (event.payload.type === 'validation-cancelled') {
             setStatus("aguardando");
             setEntregaData(null);
@@ -77,6 +83,28 @@ export default function EmployeeView() {
         }
       );
 
+      const unlistenImage = await listen<FingerprintImage>(
+        "biometric-image",
+        (event) => {
+          setFingerprintImage(event.payload);
+        }
+      );
+
+      const unlistenAborted = await listen<{ reason: 'cancelled' | 'timeout'; message: string }>(
+        "biometric-capture-aborted",
+        (event) => {
+          setBiometricInstruction(event.payload.message);
+        }
+      );
+
+      const unlistenEnrollmentFinger = await listen<EnrollmentFingerEvent>(
+        "biometric-enrollment-finger",
+        (event) => {
+          setEnrollmentFinger(event.payload);
+          setFingerprintImage(null);
+        }
+      );
+
       const unlistenShowInventory = await listen<InventoryDisplayData>(
         "show-inventory",
         (event) => {
@@ -90,6 +118,9 @@ export default function EmployeeView() {
         unlistenEntrega();
         unlistenValidation();
         unlistenBiometric();
+        unlistenImage();
+        unlistenAborted();
+        unlistenEnrollmentFinger();
         unlistenShowInventory();
       };
     };
@@ -244,9 +275,17 @@ export default function EmployeeView() {
 
               <div className="relative z-10">
                 <div className="mb-6 relative inline-block">
-                  <div className={`w-24 h-24 rounded-full flex items-center justify-center mx-auto mb-4 ${isEnrolled ? 'bg-blue-100 text-blue-600' : 'bg-yellow-100 text-yellow-600'} animate-bounce`}>
-                    <Fingerprint className="w-12 h-12" />
-                  </div>
+                  {fingerprintImageSrc(fingerprintImage) ? (
+                    <img
+                      src={fingerprintImageSrc(fingerprintImage)}
+                      alt="Digital capturada"
+                      className="h-32 mx-auto mb-4 object-contain rounded-xl border-2 border-blue-100"
+                    />
+                  ) : (
+                    <div className={`w-24 h-24 rounded-full flex items-center justify-center mx-auto mb-4 ${isEnrolled ? 'bg-blue-100 text-blue-600' : 'bg-yellow-100 text-yellow-600'} animate-bounce`}>
+                      <Fingerprint className="w-12 h-12" />
+                    </div>
+                  )}
                   <div className="absolute -bottom-1 -right-1 bg-green-500 text-white p-1.5 rounded-full shadow-lg animate-in zoom-in spin-in duration-500">
                     <ShieldCheck className="w-4 h-4" />
                   </div>
@@ -256,6 +295,12 @@ export default function EmployeeView() {
                   {isEnrolled ? "Validação" : "Cadastro"}
                 </h2>
 
+                {!isEnrolled && enrollmentFinger && (
+                  <p className="text-base font-bold text-blue-700 mb-2">
+                    {enrollmentFinger.label} ({enrollmentFinger.index}/{enrollmentFinger.total})
+                  </p>
+                )}
+
                 <p className="text-sm text-gray-600 font-medium leading-relaxed">
                   {biometricInstruction || "Aguarde a inicialização..."}
                 </p>
@@ -45,7 +45,7 @@ interface OfflineDB extends DBSchema {
     indexes: { 'by-user': string };
   };
   
-  // Fila de sincronização para operações offline
+  // Fila antiga de operações offline (migrada para o backend)
   sync_queue: {
     key: number;
     value: {
@@ -230,27 +230,11 @@ class OfflineCacheService {
   }
 
   // ============================================================================
-  // FILA DE SINCRONIZAÇÃO
+  // FILA DE SINCRONIZAÇÃO (ANTIGA)
+  // A fila agora fica no backend (sync_queue.db); esta só é lida para migrar o
+  // que ficou de versões anteriores.
   // ============================================================================
 
-  async addToSyncQueue(operation: {
-    type: 'approve' | 'deliver' | 'reject' | 'create' | 'update';
-    table: string;
-    data: any;
-  }): Promise<number> {
-    if (!this.db) await this.init();
-    
-    const queueItem = {
-      ...operation,
-      timestamp: new Date().toISOString(),
-      retries: 0,
-    };
-
-    const id = await this.db!.add('sync_queue', queueItem);
-    console.log(`📥 [OfflineCache] Added to sync queue: ${operation.type} on ${operation.table}`, id);
-    return id;
-  }
-
   async getSyncQueue(): Promise<any[]> {
     if (!this.db) await this.init();
     return await this.db!.getAll('sync_queue');
@@ -262,20 +246,6 @@ class OfflineCacheService {
     console.log(`✅ [OfflineCache] Removed from sync queue: ${id}`);
   }
 
-  async updateSyncQueueItem(id: number, updates: Partial<any>): Promise<void> {
-    if (!this.db) await this.init();
-    const item = await this.db!.get('sync_queue', id);
-    if (item) {
-      await this.db!.put('sync_queue', { ...item, ...updates });
-    }
-  }
-
-  async clearSyncQueue(): Promise<void> {
-    if (!this.db) await this.init();
-    await this.db!.clear('sync_queue');
-    console.log('✅ [OfflineCache] Sync queue cleared');
-  }
-
   // ============================================================================
   // METADADOS
   // ============================================================================
@@ -1,272 +1,142 @@
 /**
  * Serviço de Sincronização Offline
- * Gerencia sincronização automática quando a conexão retorna
+ * Conversa com o serviço de conectividade do backend, que verifica a conexão com o
+ * Supabase, envia a fila de operações e baixa os dados de referência em segundo plano.
  */
 
-import { supabase } from '../lib/supabase';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 import { offlineCache } from './offlineCache';
+import type {
+  BaseVersion,
+  ConflictResolution,
+  ConnectivityStatus,
+  QueuedOperation,
+  ReferenceData,
+  ReferenceName,
+  SyncOperationKind,
+  SyncQueueSnapshot,
+} from '../types/sync';
 
 class OfflineSyncService {
-  private isSyncing = false;
-  private syncInterval: NodeJS.Timeout | null = null;
-  private isOnline = navigator.onLine;
-
   constructor() {
-    this.setupOnlineListeners();
+    // O backend avisa quando baixou dados novos, mesmo com a janela sem foco
+    listen<ReferenceName[]>('reference-cache-updated', (event) => {
+      this.applyReferenceCache(event.payload).catch((error) =>
+        console.error('❌ [OfflineSync] Failed to apply reference cache:', error)
+      );
+    }).catch(console.error);
   }
 
-  private setupOnlineListeners(): void {
-    window.addEventListener('online', () => {
-      console.log('🌐 [OfflineSync] Connection restored - starting sync');
-      this.isOnline = true;
-      this.syncAll();
-    });
-
-    window.addEventListener('offline', () => {
-      console.log('📴 [OfflineSync] Connection lost - entering offline mode');
-      this.isOnline = false;
-    });
+  async getConnectivityStatus(): Promise<ConnectivityStatus> {
+    return await invoke<ConnectivityStatus>('get_connectivity_status');
   }
 
-  getOnlineStatus(): boolean {
-    return this.isOnline;
+  /** Recebe cada mudança de estado da conexão; devolve a função que para de ouvir. */
+  async onConnectivityChanged(handler: (status: ConnectivityStatus) => void): Promise<() => void> {
+    return await listen<ConnectivityStatus>('connectivity-changed', (event) => handler(event.payload));
   }
 
+  /** Pede ao backend uma verificação da conexão e uma sincronização completa agora. */
   async syncAll(): Promise<void> {
-    if (this.isSyncing) {
-      console.log('⏳ [OfflineSync] Sync already in progress');
-      return;
-    }
-
-    if (!this.isOnline) {
-      console.log('📴 [OfflineSync] Offline - skipping sync');
-      return;
-    }
-
-    this.isSyncing = true;
-    console.log('🔄 [OfflineSync] Starting full sync...');
-
-    try {
-      // 1. Processar fila de sincronização (operações offline)
-      await this.processSyncQueue();
-
-      // 2. Atualizar cache com dados mais recentes do servidor
-      await this.refreshCache();
-
-      console.log('✅ [OfflineSync] Sync completed successfully');
-    } catch (error) {
-      console.error('❌ [OfflineSync] Sync failed:', error);
-    } finally {
-      this.isSyncing = false;
-    }
-  }
-
-  private async processSyncQueue(): Promise<void> {
-    const queue = await offlineCache.getSyncQueue();
-    console.log(`📋 [OfflineSync] Processing ${queue.length} queued operations`);
-
-    for (const item of queue) {
-      try {
-        console.log(`🔄 [OfflineSync] Processing: ${item.type} on ${item.table}`);
-        
-        switch (item.type) {
-          case 'approve':
-            await this.syncApproval(item);
-            break;
-          case 'deliver':
-            await this.syncDelivery(item);
-            break;
-          case 'reject':
-            await this.syncRejection(item);
-            break;
-          case 'create':
-            await this.syncCreate(item);
-            break;
-          case 'update':
-            await this.syncUpdate(item);
-            break;
-        }
-
-        // Remover da fila após sucesso
-        await offlineCache.removeFromSyncQueue(item.id!);
-        console.log(`✅ [OfflineSync] Synced: ${item.type} on ${item.table}`);
-
-      } catch (error) {
-        console.error(`❌ [OfflineSync] Failed to sync item ${item.id}:`, error);
-        
-        // Incrementar contador de tentativas
-        await offlineCache.updateSyncQueueItem(item.id!, {
-          retries: item.retries + 1,
-          error: error instanceof Error ? error.message : 'Unknown error',
-        });
-
-        // Se falhou muitas vezes, marcar como erro permanente
-        if (item.retries >= 3) {
-          console.error(`🚨 [OfflineSync] Item ${item.id} failed after 3 retries - marking as permanent error`);
-        }
-      }
-    }
-  }
-
-  private async syncApproval(item: any): Promise<void> {
-    const { solicitacao_id, quantidade_aprovada, aprovado_por } = item.data;
-    
-    const { error } = await supabase
-      .from('solicitacoes_itens')
-      .update({
-        status: 'aprovada',
-        quantidade_aprovada,
-        aprovado_por,
-        aprovado_em: item.timestamp,
-        atualizado_em: item.timestamp,
-      })
-      .eq('id', solicitacao_id);
-
-    if (error) throw error;
-  }
-
-  private async syncDelivery(item: any): Promise<void> {
-    const { solicitacao_id, entregador_id, quantidade_entregue, observacoes, numero_laudo, validade_laudo } = item.data;
-    
-    // Usar a função SQL para garantir débito de estoque e inventário
-    const { error } = await supabase.rpc('entregar_item_estoque', {
-      p_solicitacao_id: solicitacao_id,
-      p_entregador_id: entregador_id,
-      p_quantidade_entregue: quantidade_entregue,
-      p_condicao_entrega: 'novo',
-      p_observacoes_entrega: observacoes || '',
-      p_entregue_em: item.timestamp,
-      p_atualizado_em: item.timestamp,
-      p_numero_laudo: numero_laudo || null,
-      p_validade_laudo: validade_laudo || null,
+    console.log('🔄 [OfflineSync] Requesting full sync from backend');
+    await invoke('sync_now');
+  }
+
+  // ============================================================================
+  // FILA DE SINCRONIZAÇÃO (no backend Rust, em sync_queue.db)
+  // ============================================================================
+
+  /**
+   * Coloca uma operação feita offline na fila do backend, que envia ao Supabase com
+   * novas tentativas. A chave de idempotência evita aplicar a mesma operação duas vezes;
+   * `baseVersion` (o que a estação leu da linha) permite detectar conflito ao sincronizar.
+   */
+  async enqueueOperation(
+    kind: SyncOperationKind,
+    table: string,
+    data: Record<string, unknown>,
+    options: { idempotencyKey?: string; occurredAt?: string; baseVersion?: BaseVersion } = {}
+  ): Promise<QueuedOperation> {
+    const operation = await invoke<QueuedOperation>('enqueue_operation', {
+      kind,
+      table,
+      data,
+      idempotencyKey: options.idempotencyKey ?? crypto.randomUUID(),
+      occurredAt: options.occurredAt,
+      baseVersion: options.baseVersion,
     });
-
-    if (error) throw error;
-  }
-
-  private async syncRejection(item: any): Promise<void> {
-    const { solicitacao_id, motivo_rejeicao, rejeitado_por } = item.data;
-    
-    const { error } = await supabase
-      .from('solicitacoes_itens')
-      .update({
-        status: 'rejeitada',
-        motivo_rejeicao,
-        rejeitado_por,
-        rejeitado_em: item.timestamp,
-        atualizado_em: item.timestamp,
-      })
-      .eq('id', solicitacao_id);
-
-    if (error) throw error;
-  }
-
-  private async syncCreate(item: any): Promise<void> {
-    const { error } = await supabase
-      .from(item.table)
-      .insert(item.data);
-
-    if (error) throw error;
-  }
-
-  private async syncUpdate(item: any): Promise<void> {
-    const { id, ...updateData } = item.data;
-    
-    const { error } = await supabase
-      .from(item.table)
-      .update(updateData)
-      .eq('id', id);
-
-    if (error) throw error;
+    console.log(`📥 [OfflineSync] Queued: ${kind} on ${table}`, operation.id);
+    return operation;
   }
 
-  private async refreshCache(): Promise<void> {
-    console.log('🔄 [OfflineSync] Refreshing cache from server...');
-
-    try {
-      // Buscar dados atualizados do servidor
-      const [solicitacoes, itens, bases] = await Promise.all([
-        this.fetchSolicitacoes(),
-        this.fetchItensEstoque(),
-        this.fetchBases(),
-      ]);
-
-      // Atualizar cache local
-      await Promise.all([
-        offlineCache.cacheSolicitacoes(solicitacoes),
-        offlineCache.cacheItensEstoque(itens),
-        offlineCache.cacheBases(bases),
-      ]);
-
-      // Atualizar timestamp de última sincronização
-      await offlineCache.setLastSync('full_sync');
-
-      console.log('✅ [OfflineSync] Cache refreshed');
-    } catch (error) {
-      console.error('❌ [OfflineSync] Failed to refresh cache:', error);
-      throw error;
-    }
+  async getSyncQueue(includeDone = false): Promise<SyncQueueSnapshot> {
+    return await invoke<SyncQueueSnapshot>('get_sync_queue', { includeDone });
   }
 
-  private async fetchSolicitacoes(): Promise<any[]> {
-    const { data, error } = await supabase
-      .from('solicitacoes_itens')
-      .select(`
-        *,
-        item:itens_estoque!solicitacoes_itens_item_id_fkey(id, nome, codigo, estoque_atual),
-        solicitante:usuarios!solicitacoes_itens_solicitante_id_fkey(id, nome, email),
-        destinatario:funcionarios_ativos!solicitacoes_itens_destinatario_id_fkey(id, nome, matricula),
-        base:bases!solicitacoes_itens_base_id_fkey(id, nome)
-      `)
-      .in('status', ['pendente', 'aprovada', 'aguardando_estoque'])
-      .order('criado_em', { ascending: false })
-      .limit(500);
-
-    if (error) throw error;
-    return data || [];
+  /** Devolve à fila as operações com falha (todas, ou só as de `ids`). */
+  async retryFailedOperations(ids?: number[]): Promise<number> {
+    return await invoke<number>('retry_failed_operations', { ids });
   }
 
-  private async fetchItensEstoque(): Promise<any[]> {
-    const { data, error } = await supabase
-      .from('itens_estoque')
-      .select('*')
-      .order('nome');
-
-    if (error) throw error;
-    return data || [];
+  /** Descarta operações com falha; as seguintes do mesmo registro voltam a ser enviadas. */
+  async discardFailedOperations(ids: number[]): Promise<number> {
+    return await invoke<number>('discard_failed_operations', { ids });
   }
 
-  private async fetchBases(): Promise<any[]> {
-    const { data, error } = await supabase
-      .from('bases')
-      .select('*')
-      .eq('ativa', true)
-      .order('nome');
-
-    if (error) throw error;
-    return data || [];
+  /** Decisão do almoxarife sobre uma operação em conflito. */
+  async resolveConflict(id: number, resolution: ConflictResolution): Promise<QueuedOperation> {
+    return await invoke<QueuedOperation>('resolve_sync_conflict', { id, resolution });
   }
 
-  startAutoSync(intervalMinutes: number = 5): void {
-    if (this.syncInterval) {
-      clearInterval(this.syncInterval);
-    }
+  /**
+   * Move para a fila do backend as operações que ficaram na fila antiga do IndexedDB.
+   * A chave de idempotência vem do id antigo, então repetir a migração não duplica.
+   */
+  async migrateLegacyQueue(): Promise<void> {
+    const legacy = await offlineCache.getSyncQueue();
+    if (legacy.length === 0) return;
 
-    this.syncInterval = setInterval(() => {
-      if (this.isOnline) {
-        this.syncAll();
+    console.log(`📋 [OfflineSync] Migrating ${legacy.length} operations from IndexedDB`);
+    for (const item of legacy) {
+      try {
+        await this.enqueueOperation(item.type, item.table, item.data, {
+          idempotencyKey: `indexeddb-${item.timestamp}-${item.id}`,
+          occurredAt: item.timestamp,
+        });
+        await offlineCache.removeFromSyncQueue(item.id!);
+      } catch (error) {
+        console.error(`❌ [OfflineSync] Failed to migrate item ${item.id}:`, error);
       }
-    }, intervalMinutes * 60 * 1000);
-
-    console.log(`✅ [OfflineSync] Auto-sync started (every ${intervalMinutes} minutes)`);
+    }
   }
 
-  stopAutoSync(): void {
-    if (this.syncInterval) {
-      clearInterval(this.syncInterval);
-      this.syncInterval = null;
-      console.log('⏹️ [OfflineSync] Auto-sync stopped');
+  /** Copia para o IndexedDB os dados de referência que o backend já baixou. */
+  async applyReferenceCache(
+    names: ReferenceName[] = ['solicitacoes', 'itens_estoque', 'bases', 'usuarios']
+  ): Promise<void> {
+    for (const name of names) {
+      const data = await invoke<ReferenceData | null>('get_reference_cache', { name });
+      if (!data) continue;
+
+      switch (name) {
+        case 'solicitacoes':
+          await offlineCache.cacheSolicitacoes(data.rows);
+          break;
+        case 'itens_estoque':
+          await offlineCache.cacheItensEstoque(data.rows);
+          break;
+        case 'bases':
+          await offlineCache.cacheBases(data.rows);
+          break;
+        case 'usuarios':
+          await offlineCache.cacheUsuarios(data.rows);
+          break;
+      }
     }
+
+    await offlineCache.setLastSync('full_sync');
+    console.log(`✅ [OfflineSync] Reference cache applied: ${names.join(', ')}`);
   }
 }
 
@@ -0,0 +1,324 @@
+// =============================================
+// Tipos para erros do leitor biométrico
+// =============================================
+
+// Códigos estáveis devolvidos pelo backend (BiometricError::code)
+export type BiometricErrorCode =
+  | 'SDK_UNKNOWN'
+  | 'NO_DEVICE'
+  | 'NULL_ARGUMENT'
+  | 'INVALID_ARGUMENT'
+  | 'CAPTURE'
+  | 'CAPTURE_TIMEOUT'
+  | 'COMM_USB'
+  | 'IO_ON_HOST'
+  | 'TEMPLATE_ALREADY_ENROLLED'
+  | 'MERGING'
+  | 'MATCHING'
+  | 'INVALID_FW_FILE'
+  | 'NO_SPACE_LEFT_ON_DEVICE'
+  | 'NO_TEMPLATE_WITH_ID'
+  | 'INVALID_ERRNO'
+  | 'UNAVAILABLE_FEATURE'
+  | 'PREVIOUS_FW_VERSION'
+  | 'NOT_IDENTIFIED'
+  | 'BUSY'
+  | 'CAPTURE_CANCELED'
+  | 'NO_FINGER_DETECTED'
+  | 'INVALID_TEMPLATE'
+  | 'SDK_UNDOCUMENTED'
+  | 'DRIVER_NOT_INSTALLED'
+  | 'DRIVER_MALFUNCTION'
+  | 'READER_NOT_FOUND'
+  | 'FEATURE_DISABLED'
+  | 'WORKER_UNAVAILABLE'
+  | 'UNAUTHENTICATED'
+  | 'FORBIDDEN'
+  | 'HTTP'
+  | 'PARSE'
+  | 'CONFIG'
+  | 'IO'
+
+export interface BiometricError {
+  code: BiometricErrorCode
+  message: string
+  sdk_code: number | null
+  detail: string | null
+}
+
+export function isBiometricError(error: unknown): error is BiometricError {
+  return typeof error === 'object' && error !== null && 'code' in error && 'message' in error
+}
+
+// Mensagem para exibir ao operador, seja qual for o formato do erro
+export function biometricErrorMessage(error: unknown): string {
+  if (isBiometricError(error)) return error.message
+  if (error instanceof Error) return error.message
+  return typeof error === 'string' ? error : JSON.stringify(error)
+}
+
+// =============================================
+// Imagem da digital (evento biometric-image)
+// =============================================
+
+export type FingerprintImageFormat = 'png' | 'raw'
+
+export interface FingerprintImage {
+  format: FingerprintImageFormat
+  width: number
+  height: number
+  dpi: number
+  data: string // base64
+  thumbnail: FingerprintImage | null
+}
+
+export interface FingerprintImageOptions {
+  format?: FingerprintImageFormat
+  thumbnail_max_side?: number
+}
+
+// Data URL para <img>; imagens cruas usam a miniatura PNG, se houver
+export function fingerprintImageSrc(image: FingerprintImage | null | undefined): string | undefined {
+  if (!image) return undefined
+  if (image.format === 'png') return `data:image/png;base64,${image.data}`
+  return image.thumbnail ? fingerprintImageSrc(image.thumbnail) : undefined
+}
+
+// =============================================
+// Identificação do leitor (get_reader_info)
+// =============================================
+
+export interface ReaderInfo {
+  backend: string
+  port: string | null
+  model: string
+  serial_number: string
+  firmware_version: string
+  sdk_version: string
+}
+
+// =============================================
+// Cadastro com múltiplas capturas (validate_or_enroll_fingerprint)
+// =============================================
+
+export interface EnrollmentPairScore {
+  first: number
+  second: number
+  score: number
+  percent: number
+}
+
+export interface EnrollmentReport {
+  sample_qualities: number[]
+  quality: number
+  pair_scores: EnrollmentPairScore[]
+  consistency: number
+  consistency_threshold: number
+  merged: boolean
+  stored_templates: number
+  /** Outro usuário com a mesma digital (cadastro bloqueado) */
+  duplicate: { user_id: string; score: number; percent: number } | null
+}
+
+// =============================================
+// Sessão de cadastro e gestão por dedo
+// (enroll_fingers, list_enrolled_fingers, delete_finger_template)
+// =============================================
+
+export interface FingerEnrollment {
+  finger: string
+  label: string
+  success: boolean
+  outcome: 'enrolled' | 'rejected' | 'duplicate' | 'skipped' | 'cancelled' | 'timeout'
+  reason: string
+  score: number | null
+  percent: number | null
+  quality: number | null
+  enrollment: EnrollmentReport | null
+}
+
+export interface EnrollmentSessionResult {
+  user_id: string
+  fingers: FingerEnrollment[]
+  enrolled: number
+  completed: boolean
+  reader: ReaderInfo | null
+}
+
+/** Evento `biometric-enrollment-finger`: dedo pedido agora na sessão */
+export interface EnrollmentFingerEvent {
+  finger: string
+  label: string
+  index: number
+  total: number
+}
+
+export interface StoredTemplate {
+  id: string
+  finger: string | null
+  quality: number | null
+  created_at: string | null
+}
+
+export interface EnrolledFinger {
+  finger: string
+  label: string
+  templates: StoredTemplate[]
+  best_quality: number | null
+  last_enrolled_at: string | null
+}
+
+/** Template atualizado depois de uma verificação com folga (`template_refresh`) */
+export interface TemplateRefresh {
+  action: 'added' | 'replaced'
+  finger: string
+  template_id: string | null
+  replaced_template_id: string | null
+  quality: number
+  matched_quality: number
+  percent: number
+}
+
+// =============================================
+// Política de verificação (min_percent decidido no backend)
+// =============================================
+
+export type VerificationOperation = 'delivery' | 'return' | 'discount_order' | 'loan'
+export type ItemCriticality = 'low' | 'normal' | 'high'
+
+export interface AppliedPolicy {
+  rule_id: string | null
+  source: 'supabase' | 'built_in'
+  operation: VerificationOperation
+  base_id: string | null
+  criticality: ItemCriticality | null
+  min_percent: number
+  requested_min_percent: number | null
+}
+
+// =============================================
+// Comprovante assinado (verify_receipt / verify_receipt_offline / get_station_public_key /
+// register_station_key)
+// =============================================
+
+export interface ReceiptPayload {
+  version: number
+  receipt_id: string
+  station_id: string
+  user_id: string
+  operation: VerificationOperation | null
+  operation_id: string | null
+  score: number
+  percent: number
+  min_percent: number
+  policy_rule: string | null
+  reader_serial: string | null
+  reader_model: string | null
+  issued_at: string
+  image_sha256: string | null
+  /** SHA-256 do template capturado (versão 2) */
+  template_sha256?: string
+}
+
+export interface VerificationReceipt {
+  payload: ReceiptPayload
+  public_key: string
+  signature: string
+}
+
+export interface ReceiptVerification {
+  valid: boolean
+  reason: string
+  receipt_id: string
+  station_id: string
+  user_id: string
+}
+
+// =============================================
+// Logs do app (get_recent_logs)
+// =============================================
+
+export interface LogEntry {
+  timestamp: string
+  level: 'ERROR' | 'WARN' | 'INFO' | 'DEBUG' | 'TRACE'
+  target: string
+  message: string
+  context?: Record<string, string>
+}
+
+// =============================================
+// Auditoria encadeada (verify_audit_log / export_audit_log / record_audit_event)
+// =============================================
+
+export type AuditCategory = 'enrollment' | 'verification' | 'delivery' | 'stock' | 'config' | 'security' | 'frontend'
+
+export interface AuditIssue {
+  line: number
+  seq: number | null
+  kind: 'unreadable' | 'hash_mismatch' | 'broken_chain' | 'gap' | 'truncated' | 'invalid_head'
+  message: string
+}
+
+export interface AuditVerification {
+  valid: boolean
+  records: number
+  last_seq: number | null
+  issues: AuditIssue[]
+}
+
+export interface AuditExport {
+  path: string
+  records: number
+  first_seq: number | null
+  last_seq: number | null
+}
+
+// =============================================
+// Bloqueio por falhas (get_biometric_lockouts / override_biometric_lockout)
+// =============================================
+
+export interface LockInfo {
+  locked_at: string
+  failures: number
+  reason: string
+}
+
+export interface LockoutStatus {
+  station_id: string
+  users: (LockInfo & { user_id: string })[]
+  station: LockInfo | null
+}
+
+// =============================================
+// Identificação 1:N (identify_fingerprint)
+// =============================================
+
+export interface IdentificationResult {
+  success: boolean
+  outcome: 'identified' | 'rejected' | 'cancelled' | 'timeout' | 'locked'
+  reason: string
+  user_id: string | null
+  score: number | null
+  percent: number | null
+  margin: number | null
+  runner_up_user_id: string | null
+  candidates: number
+  quality: number | null
+  fingerprint_image: FingerprintImage | null
+  reader: ReaderInfo | null
+  policy: AppliedPolicy | null
+  receipt: VerificationReceipt | null
+}
+
+// =============================================
+// Cache offline de templates (sync_template_cache / get_template_cache_status)
+// =============================================
+
+export interface TemplateCacheStatus {
+  base_id: string | null
+  templates: number
+  users: number
+  pending_verifications: number
+  last_sync_at: string | null
+  last_error: string | null
+}
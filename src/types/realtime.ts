@@ -0,0 +1,26 @@
+// =============================================
+// Assinatura do Supabase Realtime no backend (eventos solicitacao-created /
+// solicitacao-updated / transferencia-received / realtime-status)
+// =============================================
+
+export type RealtimeChangeKind = 'insert' | 'update' | 'delete'
+
+/** Payload dos eventos de mudança */
+export interface RealtimeChange<T = Record<string, unknown>> {
+  change: RealtimeChangeKind
+  table: string
+  /** Linha como ficou (vazia em exclusões) */
+  record: T
+  /** Linha anterior; só traz todas as colunas com `replica identity full` */
+  old_record: Partial<T> | null
+  commit_timestamp: string | null
+}
+
+export type RealtimeState = 'idle' | 'connecting' | 'subscribed' | 'disconnected'
+
+export interface RealtimeStatus {
+  state: RealtimeState
+  base_id: string | null
+  since: string
+  detail: string | null
+}
@@ -0,0 +1,80 @@
+// =============================================
+// Fila de sincronização offline (enqueue_operation / get_sync_queue / retry_failed_operations /
+// discard_failed_operations / resolve_sync_conflict)
+// =============================================
+
+export type SyncOperationKind = 'approve' | 'deliver' | 'reject' | 'create' | 'update'
+
+export type SyncOperationStatus = 'pending' | 'done' | 'failed' | 'conflict' | 'discarded'
+
+export type SyncConflictReason =
+  | 'already_delivered'
+  | 'already_rejected'
+  | 'already_approved'
+  | 'cancelled'
+  | 'status_changed'
+  | 'insufficient_stock'
+  | 'row_changed'
+  | 'not_found'
+
+export interface SyncConflict {
+  reason: SyncConflictReason
+  message: string
+  current: Record<string, unknown> | null
+  detected_at: string | null
+}
+
+/** Versão da linha que a estação leu quando fez a operação */
+export interface BaseVersion {
+  status?: string
+  atualizado_em?: string
+  estoque_atual?: number
+}
+
+export type ConflictResolution = 'discard' | 'retry' | 'force'
+
+export interface QueuedOperation {
+  id: number
+  idempotency_key: string
+  kind: SyncOperationKind
+  table: string
+  payload: Record<string, unknown>
+  created_at: string
+  status: SyncOperationStatus
+  attempts: number
+  next_attempt_at: string | null
+  last_error: string | null
+  completed_at: string | null
+  base_version: BaseVersion | null
+  conflict: SyncConflict | null
+  force: boolean
+}
+
+export interface SyncQueueSnapshot {
+  pending: number
+  failed: number
+  conflicts: number
+  operations: QueuedOperation[]
+}
+
+// =============================================
+// Conectividade (get_connectivity_status / connectivity-changed / sync_now)
+// =============================================
+
+export type ConnectivityState = 'online' | 'degraded' | 'offline'
+
+export interface ConnectivityStatus {
+  state: ConnectivityState
+  latency_ms: number | null
+  checked_at: string | null
+  since: string
+  detail: string | null
+}
+
+export type ReferenceName = 'solicitacoes' | 'itens_estoque' | 'bases' | 'usuarios'
+
+export interface ReferenceData {
+  name: ReferenceName
+  refreshed_at: string
+  rows: any[]
+}
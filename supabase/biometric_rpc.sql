@@ -0,0 +1,439 @@
+-- Funções usadas pelo app desktop para os templates biométricos.
+--
+-- O app chama o Supabase com o JWT do usuário logado (não mais com a chave service_role).
+-- As operações nos templates e o registro das verificações passam por estas funções
+-- `security definer`, que só rodam para um usuário autenticado cadastrado em `usuarios`.
+-- Aplique no SQL Editor do projeto, depois de `biometric_tables.sql` (tabelas e RLS).
+
+-- Assinaturas Ed25519 dos comprovantes (`crypto_sign_verify_detached`)
+create extension if not exists pgsodium;
+
+create or replace function public.biometric_require_usuario()
+returns void
+language plpgsql
+stable
+security definer
+set search_path = public
+as $$
+begin
+  if auth.uid() is null or not exists (select 1 from usuarios where id = auth.uid()) then
+    raise exception 'usuário não autorizado' using errcode = '42501';
+  end if;
+end;
+$$;
+
+-- Levanta `insufficient_privilege` se o usuário logado não for supervisor
+-- (`biometric_is_supervisor`, em `biometric_tables.sql`)
+create or replace function public.biometric_require_supervisor()
+returns void
+language plpgsql
+stable
+security definer
+set search_path = public
+as $$
+begin
+  if not biometric_is_supervisor() then
+    raise exception 'apenas supervisores podem fazer esta operação' using errcode = '42501';
+  end if;
+end;
+$$;
+
+-- Cadastro e remoção de digitais: o próprio funcionário ou um supervisor
+create or replace function public.biometric_require_owner_or_supervisor(p_user_id uuid)
+returns void
+language plpgsql
+stable
+security definer
+set search_path = public
+as $$
+begin
+  perform biometric_require_usuario();
+  if p_user_id is distinct from auth.uid() and not biometric_is_supervisor() then
+    raise exception 'só o próprio funcionário ou um supervisor altera as digitais' using errcode = '42501';
+  end if;
+end;
+$$;
+
+-- Quem lê os templates de um funcionário: ele mesmo, um supervisor ou o operador da estação de
+-- uma base do funcionário (`biometric_is_station_operator`, em `biometric_tables.sql`)
+create or replace function public.biometric_require_template_reader(p_user_id uuid)
+returns void
+language plpgsql
+stable
+security definer
+set search_path = public
+as $$
+begin
+  perform biometric_require_usuario();
+  if p_user_id is distinct from auth.uid()
+     and not biometric_is_supervisor()
+     and not exists (
+       select 1 from usuario_bases ub
+       where ub.usuario_id = p_user_id and ub.ativo and biometric_is_station_operator(ub.base_id)
+     ) then
+    raise exception 'sem permissão para ler as digitais deste funcionário' using errcode = '42501';
+  end if;
+end;
+$$;
+
+-- Templates ativos de um usuário (verificação 1:1 e lista de dedos cadastrados)
+create or replace function public.biometric_user_templates(p_user_id uuid)
+returns table (id uuid, template text, quality integer, finger text, created_at timestamptz)
+language plpgsql
+stable
+security definer
+set search_path = public
+as $$
+begin
+  perform biometric_require_template_reader(p_user_id);
+  return query
+    select t.id, t.template, t.quality, t.finger, t.created_at
+    from biometric_templates t
+    where t.user_id = p_user_id and t.ativo
+    order by t.created_at asc;
+end;
+$$;
+
+-- Dedos cadastrados de um funcionário, sem os templates (a tela confere se ele tem digital)
+create or replace function public.biometric_enrolled_fingers(p_user_id uuid)
+returns table (finger text)
+language plpgsql
+stable
+security definer
+set search_path = public
+as $$
+begin
+  perform biometric_require_template_reader(p_user_id);
+  return query
+    select distinct t.finger
+    from biometric_templates t
+    where t.user_id = p_user_id and t.ativo
+    order by t.finger;
+end;
+$$;
+
+-- Operação, base e criticidade de uma entidade que a biometria autoriza (hoje, uma solicitação
+-- de `solicitacoes_itens`, que é uma entrega). O app usa isto no lugar do que a tela informa, para
+-- que o mínimo da política não dependa do frontend. Sem linha: a entidade não existe.
+create or replace function public.biometric_operation_context(p_operation_id uuid)
+returns table (operation text, base_id uuid, criticality text)
+language plpgsql
+stable
+security definer
+set search_path = public
+as $$
+begin
+  perform biometric_require_usuario();
+  return query
+    select 'delivery'::text, s.base_id, i.biometric_criticality
+    from solicitacoes_itens s
+    left join itens_estoque i on i.id = s.item_id
+    where s.id = p_operation_id;
+end;
+$$;
+
+-- Candidatos da identificação 1:N e da checagem de duplicidade do cadastro: os funcionários de
+-- uma base, para o operador da estação dela; todos, sem base, só para supervisores.
+-- `stable`: o PostgREST aceita GET, então `limit`/`offset` funcionam na paginação; o `id` do
+-- template desempata a ordem entre as páginas.
+drop function if exists public.biometric_candidate_templates(uuid);
+create or replace function public.biometric_candidate_templates(p_base_id uuid default null)
+returns table (id uuid, user_id uuid, template text)
+language plpgsql
+stable
+security definer
+set search_path = public
+as $$
+begin
+  perform biometric_require_usuario();
+  if p_base_id is null then
+    perform biometric_require_supervisor();
+  elsif not biometric_is_station_operator(p_base_id) then
+    raise exception 'só o operador da estação da base lê os candidatos' using errcode = '42501';
+  end if;
+  return query
+    select t.id, t.user_id, t.template
+    from biometric_templates t
+    where t.ativo
+      and (
+        p_base_id is null
+        or exists (
+          select 1 from usuario_bases ub
+          where ub.usuario_id = t.user_id and ub.base_id = p_base_id and ub.ativo
+        )
+      )
+    order by t.user_id, t.created_at;
+end;
+$$;
+
+-- Grava as amostras de um dedo (cadastro); devolve os ids criados
+create or replace function public.biometric_store_templates(
+  p_user_id uuid,
+  p_finger text,
+  p_quality integer,
+  p_templates text[]
+)
+returns setof uuid
+language plpgsql
+security definer
+set search_path = public
+as $$
+begin
+  perform biometric_require_owner_or_supervisor(p_user_id);
+  return query
+    insert into biometric_templates (user_id, finger, quality, template)
+    select p_user_id, p_finger, p_quality, tpl
+    from unnest(p_templates) as tpl
+    returning id;
+end;
+$$;
+
+-- Desativa os templates de um dedo, ou um template pelo id; devolve quantos foram desativados.
+-- Só supervisores: é a remoção de um template ruim e a troca de um dedo já cadastrado.
+create or replace function public.biometric_deactivate_templates(
+  p_user_id uuid,
+  p_finger text default null,
+  p_template_id uuid default null
+)
+returns integer
+language plpgsql
+security definer
+set search_path = public
+as $$
+declare
+  v_count integer;
+begin
+  perform biometric_require_supervisor();
+  if p_finger is null and p_template_id is null then
+    raise exception 'informe o dedo ou o template' using errcode = '22023';
+  end if;
+  update biometric_templates
+     set ativo = false
+   where user_id = p_user_id
+     and ativo
+     and (p_template_id is null or id = p_template_id)
+     and (p_template_id is not null or finger = p_finger);
+  get diagnostics v_count = row_count;
+  return v_count;
+end;
+$$;
+
+-- Registra uma verificação (1:1 ou 1:N) e devolve o id da linha. A comparação das digitais é
+-- feita pelo SDK do leitor na estação, então o banco não aceita a palavra do cliente: uma
+-- verificação aceita só entra com o comprovante dela (`p_receipt_payload`, o JSON exato que
+-- foi assinado) e a assinatura Ed25519 conferida aqui com a chave que um supervisor registrou
+-- para a estação em `station_keys`. O comprovante precisa bater com a linha (usuário, score,
+-- percentual acima do mínimo) e, online, ter sido emitido há menos de 5 minutos; cada
+-- comprovante registra uma única verificação. A linha guarda o `session_id` do JWT, para que
+-- só a sessão que verificou possa usá-la em `biometric_refresh_template`. O id (`p_id`) é gerado
+-- na estação: reenviar a mesma verificação depois de uma falha não cria outra linha.
+drop function if exists public.biometric_record_verification(jsonb, text, text);
+
+create or replace function public.biometric_record_verification(
+  p_id uuid,
+  p_occurred_at timestamptz,
+  p_record jsonb,
+  p_receipt_payload text default null,
+  p_receipt_signature text default null
+)
+returns uuid
+language plpgsql
+security definer
+set search_path = public
+as $$
+declare
+  v_success boolean := coalesce((p_record->>'success')::boolean, false);
+  v_offline boolean := coalesce((p_record->>'offline')::boolean, false);
+  v_receipt jsonb;
+  v_key text;
+begin
+  perform biometric_require_usuario();
+  if v_success then
+    if p_receipt_payload is null or p_receipt_signature is null then
+      raise exception 'verificação aceita sem comprovante da estação' using errcode = '42501';
+    end if;
+    v_receipt := p_receipt_payload::jsonb;
+    select public_key into v_key from station_keys where station_id = v_receipt->>'station_id';
+    if v_key is null or not pgsodium.crypto_sign_verify_detached(
+      decode(p_receipt_signature, 'base64'),
+      convert_to(p_receipt_payload, 'UTF8'),
+      decode(v_key, 'base64')
+    ) then
+      raise exception 'comprovante sem assinatura válida de uma estação registrada' using errcode = '42501';
+    end if;
+    if v_receipt->>'user_id' is distinct from p_record->>'user_id'
+       or (v_receipt->>'score')::integer is distinct from (p_record->>'score')::integer
+       or (v_receipt->>'percent')::integer is distinct from (p_record->>'percent')::integer
+       or (v_receipt->>'percent')::integer < (v_receipt->>'min_percent')::integer then
+      raise exception 'comprovante não corresponde à verificação' using errcode = '22023';
+    end if;
+    if not v_offline and (v_receipt->>'issued_at')::timestamptz < now() - interval '5 minutes' then
+      raise exception 'comprovante antigo para uma verificação online' using errcode = '22023';
+    end if;
+  end if;
+
+  insert into biometric_verifications (
+    id, occurred_at, user_id, mode, success, outcome, score, percent, quality, operation, min_percent, policy_rule,
+    offline, reader_backend, reader_port, reader_model, reader_serial, reader_firmware, sdk_version,
+    receipt_id, template_sha256, session_id, recorded_by
+  )
+  values (
+    p_id, least(coalesce(p_occurred_at, now()), now()),
+    (p_record->>'user_id')::uuid, p_record->>'mode', v_success, p_record->>'outcome',
+    (p_record->>'score')::integer, (p_record->>'percent')::integer, (p_record->>'quality')::integer,
+    p_record->>'operation', (p_record->>'min_percent')::integer, p_record->>'policy_rule',
+    v_offline, p_record->>'reader_backend', p_record->>'reader_port', p_record->>'reader_model',
+    p_record->>'reader_serial', p_record->>'reader_firmware', p_record->>'sdk_version',
+    v_receipt->>'receipt_id', v_receipt->>'template_sha256', auth.jwt()->>'session_id', auth.uid()
+  )
+  on conflict do nothing;
+  return p_id;
+end;
+$$;
+
+-- Atualização adaptativa: grava a amostra de uma verificação 1:1 aceita há pouco, registrada
+-- com comprovante por esta mesma sessão, e desativa a amostra substituída. A amostra tem de ser
+-- a captura daquela verificação (`template_sha256` do comprovante). Cada verificação atualiza
+-- no máximo uma vez (`biometric_template_refreshes.verification_id`).
+create or replace function public.biometric_refresh_template(
+  p_verification_id uuid,
+  p_finger text,
+  p_quality integer,
+  p_template text,
+  p_matched_quality integer,
+  p_replaced_template_id uuid default null
+)
+returns uuid
+language plpgsql
+security definer
+set search_path = public
+as $$
+declare
+  v_verification biometric_verifications%rowtype;
+  v_id uuid;
+begin
+  perform biometric_require_usuario();
+  select * into v_verification
+    from biometric_verifications
+   where id = p_verification_id
+     and recorded_by = auth.uid()
+     and session_id = auth.jwt()->>'session_id'
+     and mode = 'verify'
+     and success
+     and not offline
+     and receipt_id is not null
+     and created_at > now() - interval '5 minutes';
+  if not found then
+    raise exception 'atualização de template sem verificação recente' using errcode = '42501';
+  end if;
+  if v_verification.template_sha256 is distinct from encode(sha256(convert_to(p_template, 'UTF8')), 'hex') then
+    raise exception 'amostra diferente da capturada na verificação' using errcode = '42501';
+  end if;
+  if exists (select 1 from biometric_template_refreshes where verification_id = p_verification_id) then
+    raise exception 'verificação já usada para atualizar template' using errcode = '42501';
+  end if;
+  if p_replaced_template_id is not null and not exists (
+    select 1 from biometric_templates
+    where id = p_replaced_template_id and user_id = v_verification.user_id and ativo
+  ) then
+    raise exception 'template substituído não é do funcionário verificado' using errcode = '22023';
+  end if;
+
+  insert into biometric_templates (user_id, finger, quality, template)
+  values (v_verification.user_id, p_finger, p_quality, p_template)
+  returning id into v_id;
+  if p_replaced_template_id is not null then
+    update biometric_templates set ativo = false where id = p_replaced_template_id;
+  end if;
+
+  insert into biometric_template_refreshes (
+    user_id, finger, action, template_id, replaced_template_id,
+    quality, matched_quality, percent, verification_id
+  )
+  values (
+    v_verification.user_id, p_finger,
+    case when p_replaced_template_id is null then 'added' else 'replaced' end,
+    v_id, p_replaced_template_id, p_quality, p_matched_quality, v_verification.percent,
+    p_verification_id
+  );
+  return v_id;
+end;
+$$;
+
+-- Templates ativos dos funcionários de uma base, para o cache offline da estação (só o operador
+-- da estação da base). `stable`: o PostgREST aceita GET, então `limit`/`offset` funcionam na
+-- paginação.
+create or replace function public.biometric_base_templates(p_base_id uuid)
+returns table (id uuid, user_id uuid, template text, quality integer, finger text)
+language plpgsql
+stable
+security definer
+set search_path = public
+as $$
+begin
+  perform biometric_require_usuario();
+  if not biometric_is_station_operator(p_base_id) then
+    raise exception 'só o operador da estação da base lê os templates dela' using errcode = '42501';
+  end if;
+  return query
+    select t.id, t.user_id, t.template, t.quality, t.finger
+    from biometric_templates t
+    where t.ativo
+      and exists (
+        select 1 from usuario_bases ub
+        where ub.usuario_id = t.user_id and ub.base_id = p_base_id and ub.ativo
+      )
+    order by t.user_id, t.created_at;
+end;
+$$;
+
+-- Registra a chave pública de uma estação (só supervisores). Uma vez registrada, a chave não
+-- muda por aqui: a mesma chave retorna `false`, outra chave é recusada.
+create or replace function public.biometric_register_station_key(p_station_id text, p_public_key text)
+returns boolean
+language plpgsql
+security definer
+set search_path = public
+as $$
+declare
+  v_current text;
+begin
+  perform biometric_require_supervisor();
+  select public_key into v_current from station_keys where station_id = p_station_id;
+  if v_current is null then
+    insert into station_keys (station_id, public_key, registered_by)
+    values (p_station_id, p_public_key, auth.uid());
+    return true;
+  end if;
+  if v_current <> p_public_key then
+    raise exception 'a estação % já tem outra chave registrada', p_station_id using errcode = '42501';
+  end if;
+  return false;
+end;
+$$;
+
+revoke all on function public.biometric_require_usuario() from public, anon;
+revoke all on function public.biometric_require_supervisor() from public, anon;
+revoke all on function public.biometric_require_owner_or_supervisor(uuid) from public, anon;
+revoke all on function public.biometric_require_template_reader(uuid) from public, anon;
+revoke all on function public.biometric_user_templates(uuid) from public, anon;
+revoke all on function public.biometric_enrolled_fingers(uuid) from public, anon;
+revoke all on function public.biometric_operation_context(uuid) from public, anon;
+revoke all on function public.biometric_candidate_templates(uuid) from public, anon;
+revoke all on function public.biometric_store_templates(uuid, text, integer, text[]) from public, anon;
+revoke all on function public.biometric_deactivate_templates(uuid, text, uuid) from public, anon;
+revoke all on function public.biometric_record_verification(uuid, timestamptz, jsonb, text, text) from public, anon;
+revoke all on function public.biometric_refresh_template(uuid, text, integer, text, integer, uuid) from public, anon;
+revoke all on function public.biometric_base_templates(uuid) from public, anon;
+revoke all on function public.biometric_register_station_key(text, text) from public, anon;
+
+grant execute on function public.biometric_user_templates(uuid) to authenticated;
+grant execute on function public.biometric_enrolled_fingers(uuid) to authenticated;
+grant execute on function public.biometric_operation_context(uuid) to authenticated;
+grant execute on function public.biometric_candidate_templates(uuid) to authenticated;
+grant execute on function public.biometric_store_templates(uuid, text, integer, text[]) to authenticated;
+grant execute on function public.biometric_deactivate_templates(uuid, text, uuid) to authenticated;
+grant execute on function public.biometric_record_verification(uuid, timestamptz, jsonb, text, text) to authenticated;
+grant execute on function public.biometric_refresh_template(uuid, text, integer, text, integer, uuid) to authenticated;
+grant execute on function public.biometric_base_templates(uuid) to authenticated;
+grant execute on function public.biometric_register_station_key(text, text) to authenticated;
@@ -0,0 +1,350 @@
+-- Tabelas da biometria usadas pelo app desktop, com RLS.
+--
+-- O app grava com o JWT do usuário logado, então cada tabela só aceita o que a estação
+-- precisa fazer: registros (anomalias, conflitos) entram com `recorded_by = auth.uid()` e não
+-- são alterados nem apagados pelo app; a leitura fica com os supervisores (e com o próprio
+-- funcionário, nas verificações dele). Verificações, templates e atualizações adaptativas só
+-- mudam pelas funções de `biometric_rpc.sql`. As regras de `biometric_policies` só são
+-- editadas por administradores.
+--
+-- Aplique no SQL Editor do projeto antes de `biometric_rpc.sql`. Os `if not exists` deixam
+-- reaplicar o arquivo em um projeto que já tenha parte das tabelas.
+
+-- Supervisores (e níveis acima) gerenciam as digitais de outros funcionários, liberam
+-- bloqueios e leem os registros. Mesma hierarquia de `nivel_acesso` do frontend
+-- (useUnifiedPermissions).
+create or replace function public.biometric_is_supervisor()
+returns boolean
+language sql
+stable
+security definer
+set search_path = public
+as $$
+  select exists (
+    select 1 from usuarios
+    where id = auth.uid()
+      and nivel_acesso in (
+        'admin', 'diretor', 'manager', 'gerente', 'gestor_frota',
+        'gestor', 'gestor_almoxarifado', 'coordenador', 'eng_seguranca', 'supervisor'
+      )
+  );
+$$;
+
+-- Administradores (acesso total no frontend) editam a política de verificação
+create or replace function public.biometric_is_admin()
+returns boolean
+language sql
+stable
+security definer
+set search_path = public
+as $$
+  select exists (
+    select 1 from usuarios
+    where id = auth.uid() and nivel_acesso in ('admin', 'diretor')
+  );
+$$;
+
+-- Operador da estação de uma base: supervisor, ou usuário do almoxarifado ligado à base em
+-- `usuario_bases`. É quem confere digitais de outros funcionários no balcão daquela base.
+create or replace function public.biometric_is_station_operator(p_base_id uuid)
+returns boolean
+language sql
+stable
+security definer
+set search_path = public
+as $$
+  select biometric_is_supervisor() or exists (
+    select 1 from usuarios u
+    join usuario_bases ub on ub.usuario_id = u.id
+    where u.id = auth.uid()
+      and u.nivel_acesso = 'almoxarifado'
+      and ub.base_id = p_base_id
+      and ub.ativo
+  );
+$$;
+
+revoke all on function public.biometric_is_supervisor() from public, anon;
+revoke all on function public.biometric_is_admin() from public, anon;
+revoke all on function public.biometric_is_station_operator(uuid) from public, anon;
+grant execute on function public.biometric_is_supervisor() to authenticated;
+grant execute on function public.biometric_is_admin() to authenticated;
+grant execute on function public.biometric_is_station_operator(uuid) to authenticated;
+
+-- ---------------------------------------------------------------------------
+-- Templates: leitura direta só do próprio funcionário ou de um supervisor; a estação lê os
+-- da sua base pelas funções de `biometric_rpc.sql`, que também fazem toda a escrita.
+-- ---------------------------------------------------------------------------
+create table if not exists public.biometric_templates (
+  id uuid primary key default gen_random_uuid(),
+  user_id uuid not null references public.usuarios (id) on delete cascade,
+  finger text not null default 'right_index',
+  quality integer,
+  template text not null,
+  created_at timestamptz not null default now()
+);
+
+alter table public.biometric_templates add column if not exists finger text not null default 'right_index';
+alter table public.biometric_templates add column if not exists ativo boolean not null default true;
+
+create index if not exists biometric_templates_user_ativo_idx
+  on public.biometric_templates (user_id) where ativo;
+
+alter table public.biometric_templates enable row level security;
+
+drop policy if exists biometric_templates_select on public.biometric_templates;
+create policy biometric_templates_select on public.biometric_templates
+  for select to authenticated
+  using (user_id = auth.uid() or biometric_is_supervisor());
+
+revoke insert, update, delete on public.biometric_templates from anon, authenticated;
+grant select on public.biometric_templates to authenticated;
+
+-- ---------------------------------------------------------------------------
+-- Política de verificação (lida pelo backend; nada abaixo de 40%, o piso do app)
+-- ---------------------------------------------------------------------------
+create table if not exists public.biometric_policies (
+  id uuid primary key default gen_random_uuid(),
+  operation text check (operation in ('delivery', 'return', 'discount_order', 'loan')),
+  base_id uuid references public.bases (id) on delete cascade,
+  criticality text check (criticality in ('low', 'normal', 'high')),
+  min_percent integer not null check (min_percent between 40 and 100),
+  ativo boolean not null default true,
+  updated_by uuid default auth.uid(),
+  updated_at timestamptz not null default now()
+);
+
+alter table public.biometric_policies enable row level security;
+
+drop policy if exists biometric_policies_select on public.biometric_policies;
+create policy biometric_policies_select on public.biometric_policies
+  for select to authenticated
+  using (true);
+
+drop policy if exists biometric_policies_admin on public.biometric_policies;
+create policy biometric_policies_admin on public.biometric_policies
+  for all to authenticated
+  using (biometric_is_admin())
+  with check (biometric_is_admin());
+
+grant select, insert, update, delete on public.biometric_policies to authenticated;
+
+-- Criticidade do item para a política: quem decide é o cadastro do item, não a tela que pede a
+-- verificação (veja `biometric_operation_context`). Sem valor, vale a regra sem criticidade.
+alter table public.itens_estoque add column if not exists biometric_criticality text
+  check (biometric_criticality in ('low', 'normal', 'high'));
+
+-- ---------------------------------------------------------------------------
+-- Verificações (1:1 e 1:N), inclusive as feitas offline e enviadas depois. Gravadas só por
+-- `biometric_record_verification`: uma verificação aceita precisa do comprovante assinado pela
+-- estação e fica presa à sessão que a registrou (`session_id`). O `id` vem da estação, então
+-- reenviar a mesma verificação não cria outra linha; `occurred_at` é quando ela foi feita.
+-- ---------------------------------------------------------------------------
+create table if not exists public.biometric_verifications (
+  id uuid primary key default gen_random_uuid(),
+  user_id uuid,
+  mode text not null check (mode in ('verify', 'identify')),
+  success boolean not null,
+  outcome text not null,
+  score integer,
+  percent integer,
+  quality integer,
+  operation text,
+  min_percent integer,
+  policy_rule text,
+  offline boolean not null default false,
+  reader_backend text,
+  reader_port text,
+  reader_model text,
+  reader_serial text,
+  reader_firmware text,
+  sdk_version text,
+  recorded_by uuid not null default auth.uid(),
+  occurred_at timestamptz not null default now(),
+  created_at timestamptz not null default now()
+);
+
+alter table public.biometric_verifications add column if not exists offline boolean not null default false;
+alter table public.biometric_verifications add column if not exists occurred_at timestamptz not null default now();
+alter table public.biometric_verifications add column if not exists recorded_by uuid default auth.uid();
+alter table public.biometric_verifications add column if not exists receipt_id text;
+alter table public.biometric_verifications add column if not exists template_sha256 text;
+alter table public.biometric_verifications add column if not exists session_id text;
+
+create unique index if not exists biometric_verifications_receipt_idx
+  on public.biometric_verifications (receipt_id);
+
+alter table public.biometric_verifications enable row level security;
+
+drop policy if exists biometric_verifications_insert on public.biometric_verifications;
+
+drop policy if exists biometric_verifications_select on public.biometric_verifications;
+create policy biometric_verifications_select on public.biometric_verifications
+  for select to authenticated
+  using (user_id = auth.uid() or recorded_by = auth.uid() or biometric_is_supervisor());
+
+revoke insert, update, delete on public.biometric_verifications from anon, authenticated;
+grant select on public.biometric_verifications to authenticated;
+
+-- ---------------------------------------------------------------------------
+-- Anomalias: bloqueios, reenvios e liberações do supervisor
+-- ---------------------------------------------------------------------------
+create table if not exists public.biometric_anomalies (
+  id uuid primary key default gen_random_uuid(),
+  kind text not null,
+  user_id uuid,
+  station_id text,
+  supervisor_id uuid,
+  detail jsonb not null default '{}'::jsonb,
+  recorded_by uuid not null default auth.uid(),
+  created_at timestamptz not null default now()
+);
+
+alter table public.biometric_anomalies enable row level security;
+
+drop policy if exists biometric_anomalies_insert on public.biometric_anomalies;
+create policy biometric_anomalies_insert on public.biometric_anomalies
+  for insert to authenticated
+  with check (recorded_by = auth.uid());
+
+drop policy if exists biometric_anomalies_select on public.biometric_anomalies;
+create policy biometric_anomalies_select on public.biometric_anomalies
+  for select to authenticated
+  using (biometric_is_supervisor());
+
+grant select, insert on public.biometric_anomalies to authenticated;
+
+-- ---------------------------------------------------------------------------
+-- Cadastros bloqueados por digital igual à de outro funcionário (revisão do supervisor)
+-- ---------------------------------------------------------------------------
+create table if not exists public.biometric_enrollment_conflicts (
+  id uuid primary key default gen_random_uuid(),
+  user_id uuid not null,
+  matched_user_id uuid not null,
+  base_id uuid,
+  score integer not null,
+  percent integer not null,
+  quality integer,
+  status text not null default 'pending' check (status in ('pending', 'resolved', 'dismissed')),
+  recorded_by uuid not null default auth.uid(),
+  created_at timestamptz not null default now(),
+  reviewed_by uuid,
+  reviewed_at timestamptz
+);
+
+alter table public.biometric_enrollment_conflicts enable row level security;
+
+drop policy if exists biometric_enrollment_conflicts_insert on public.biometric_enrollment_conflicts;
+create policy biometric_enrollment_conflicts_insert on public.biometric_enrollment_conflicts
+  for insert to authenticated
+  with check (recorded_by = auth.uid() and status = 'pending' and reviewed_by is null);
+
+drop policy if exists biometric_enrollment_conflicts_select on public.biometric_enrollment_conflicts;
+create policy biometric_enrollment_conflicts_select on public.biometric_enrollment_conflicts
+  for select to authenticated
+  using (biometric_is_supervisor());
+
+drop policy if exists biometric_enrollment_conflicts_review on public.biometric_enrollment_conflicts;
+create policy biometric_enrollment_conflicts_review on public.biometric_enrollment_conflicts
+  for update to authenticated
+  using (biometric_is_supervisor())
+  with check (biometric_is_supervisor() and reviewed_by = auth.uid());
+
+grant select, insert, update on public.biometric_enrollment_conflicts to authenticated;
+
+-- ---------------------------------------------------------------------------
+-- Atualizações adaptativas de template: gravadas só por `biometric_refresh_template`, uma
+-- por verificação
+-- ---------------------------------------------------------------------------
+create table if not exists public.biometric_template_refreshes (
+  id uuid primary key default gen_random_uuid(),
+  user_id uuid not null,
+  finger text not null,
+  action text not null check (action in ('added', 'replaced')),
+  template_id uuid,
+  replaced_template_id uuid,
+  quality integer not null,
+  matched_quality integer,
+  percent integer not null,
+  verification_id uuid references public.biometric_verifications (id),
+  recorded_by uuid not null default auth.uid(),
+  created_at timestamptz not null default now()
+);
+
+alter table public.biometric_template_refreshes
+  add column if not exists verification_id uuid references public.biometric_verifications (id);
+
+create unique index if not exists biometric_template_refreshes_verification_idx
+  on public.biometric_template_refreshes (verification_id);
+
+alter table public.biometric_template_refreshes enable row level security;
+
+drop policy if exists biometric_template_refreshes_insert on public.biometric_template_refreshes;
+
+drop policy if exists biometric_template_refreshes_select on public.biometric_template_refreshes;
+create policy biometric_template_refreshes_select on public.biometric_template_refreshes
+  for select to authenticated
+  using (biometric_is_supervisor());
+
+revoke insert, update, delete on public.biometric_template_refreshes from anon, authenticated;
+grant select on public.biometric_template_refreshes to authenticated;
+
+-- ---------------------------------------------------------------------------
+-- Chaves públicas das estações: registradas uma vez por um supervisor
+-- (`biometric_register_station_key`) e imutáveis para o app. Trocar a chave de uma estação
+-- (ex.: reinstalação) é feito por um administrador no SQL Editor.
+-- ---------------------------------------------------------------------------
+create table if not exists public.station_keys (
+  station_id text primary key,
+  public_key text not null,
+  registered_by uuid not null default auth.uid(),
+  registered_at timestamptz not null default now()
+);
+
+alter table public.station_keys enable row level security;
+
+drop policy if exists station_keys_select on public.station_keys;
+create policy station_keys_select on public.station_keys
+  for select to authenticated
+  using (true);
+
+revoke insert, update, delete on public.station_keys from anon, authenticated;
+grant select on public.station_keys to authenticated;
+
+-- ---------------------------------------------------------------------------
+-- Comprovantes assinados: só entram com a chave registrada da estação que os emitiu
+-- ---------------------------------------------------------------------------
+create table if not exists public.biometric_receipts (
+  receipt_id text primary key,
+  operation_id text,
+  user_id uuid not null,
+  station_id text not null,
+  payload jsonb not null,
+  public_key text not null,
+  signature text not null,
+  recorded_by uuid not null default auth.uid(),
+  created_at timestamptz not null default now()
+);
+
+create index if not exists biometric_receipts_operation_idx
+  on public.biometric_receipts (operation_id);
+
+alter table public.biometric_receipts enable row level security;
+
+drop policy if exists biometric_receipts_insert on public.biometric_receipts;
+create policy biometric_receipts_insert on public.biometric_receipts
+  for insert to authenticated
+  with check (
+    recorded_by = auth.uid()
+    and exists (
+      select 1 from public.station_keys k
+      where k.station_id = biometric_receipts.station_id
+        and k.public_key = biometric_receipts.public_key
+    )
+  );
+
+drop policy if exists biometric_receipts_select on public.biometric_receipts;
+create policy biometric_receipts_select on public.biometric_receipts
+  for select to authenticated
+  using (user_id = auth.uid() or biometric_is_supervisor());
+
+grant select, insert on public.biometric_receipts to authenticated;
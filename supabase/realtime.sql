@@ -0,0 +1,25 @@
+-- Tabelas assinadas pelo app desktop no Supabase Realtime.
+--
+-- O backend do app mantém um canal por base com as mudanças de `solicitacoes_itens`
+-- (filtro `base_id`) e `transferencias_bases` (filtro `base_destino_id`). O Realtime só
+-- envia mudanças de tabelas na publicação `supabase_realtime` e respeita o RLS do usuário
+-- logado. `replica identity full` faz as atualizações trazerem a linha anterior completa
+-- (`old_record`). Aplique no SQL Editor do projeto.
+
+do $$
+declare
+  v_table text;
+begin
+  foreach v_table in array array['solicitacoes_itens', 'transferencias_bases'] loop
+    if not exists (
+      select 1 from pg_publication_tables
+       where pubname = 'supabase_realtime' and schemaname = 'public' and tablename = v_table
+    ) then
+      execute format('alter publication supabase_realtime add table public.%I', v_table);
+    end if;
+  end loop;
+end;
+$$;
+
+alter table public.solicitacoes_itens replica identity full;
+alter table public.transferencias_bases replica identity full;
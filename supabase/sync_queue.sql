@@ -0,0 +1,218 @@
+-- Aplicação das operações feitas offline pela fila de sincronização do app desktop.
+--
+-- O app envia cada aprovação, entrega ou rejeição com uma chave de idempotência. A chave
+-- é gravada em `sync_applied_operations` na mesma transação da operação: se o app reenviar
+-- (resposta perdida, reinício no meio do envio), a função devolve 'duplicate' sem repetir
+-- a operação. Roda com as permissões do usuário logado (`security invoker`), como as
+-- mesmas operações feitas online. Aplique no SQL Editor do projeto.
+--
+-- Conflitos: a estação envia em `p_base` o que leu da solicitação (`status`,
+-- `atualizado_em`, `estoque_atual`). A solicitação e o item ficam travados (`for update`)
+-- durante a checagem, então duas estações sincronizando a mesma entrega não passam juntas.
+-- Regras por tipo de operação:
+--
+--   approve  já aprovada com a mesma quantidade -> resolved (nada a fazer)
+--            já aprovada com outra quantidade, entregue, rejeitada -> conflict
+--            cancelada -> conflict (mesmo com p_force)
+--   reject   já rejeitada -> resolved
+--            status mudou depois da leitura da estação (ex.: aprovada) -> conflict
+--            entregue ou cancelada -> conflict (mesmo com p_force)
+--   deliver  já entregue ou cancelada -> conflict (mesmo com p_force)
+--            rejeitada, ou aprovada com outra quantidade depois da leitura -> conflict
+--            estoque atual menor que a quantidade -> conflict (mesmo com p_force)
+--
+-- `p_force` é a decisão do almoxarife na revisão ("aplicar mesmo assim"): pula as regras
+-- que dependem do que mudou desde a leitura, mas nunca entrega duas vezes nem deixa o
+-- estoque negativo. Um conflito não grava a chave: a operação pode voltar depois.
+
+create table if not exists public.sync_applied_operations (
+  idempotency_key text primary key,
+  kind text not null,
+  station_id text,
+  applied_by uuid default auth.uid(),
+  applied_at timestamptz not null default now()
+);
+
+alter table public.sync_applied_operations enable row level security;
+
+drop policy if exists sync_applied_operations_insert on public.sync_applied_operations;
+create policy sync_applied_operations_insert on public.sync_applied_operations
+  for insert to authenticated
+  with check (applied_by = auth.uid());
+
+drop policy if exists sync_applied_operations_select on public.sync_applied_operations;
+create policy sync_applied_operations_select on public.sync_applied_operations
+  for select to authenticated
+  using (true);
+
+grant select, insert on public.sync_applied_operations to authenticated;
+
+-- A versão anterior devolvia text e não recebia a versão lida pela estação
+drop function if exists public.sync_apply_operation(text, text, jsonb, timestamptz, text);
+
+-- Aplica uma operação da fila; devolve { outcome, reason, message, current }, com
+-- outcome 'applied', 'duplicate', 'resolved' ou 'conflict'
+create or replace function public.sync_apply_operation(
+  p_idempotency_key text,
+  p_kind text,
+  p_payload jsonb,
+  p_occurred_at timestamptz,
+  p_station_id text default null,
+  p_base jsonb default null,
+  p_force boolean default false
+)
+returns jsonb
+language plpgsql
+security invoker
+set search_path = public
+as $$
+declare
+  v_rows integer;
+  v_solicitacao_id uuid := (p_payload->>'solicitacao_id')::uuid;
+  v_solicitacao solicitacoes_itens%rowtype;
+  v_estoque numeric;
+  v_quantidade numeric;
+  v_base_status text := p_base->>'status';
+  v_current jsonb;
+  v_function text;
+begin
+  if exists (select 1 from sync_applied_operations where idempotency_key = p_idempotency_key) then
+    return jsonb_build_object('outcome', 'duplicate');
+  end if;
+
+  select * into v_solicitacao
+    from solicitacoes_itens
+   where id = v_solicitacao_id
+   for update;
+  if not found then
+    return jsonb_build_object(
+      'outcome', 'conflict',
+      'reason', 'not_found',
+      'message', format('A solicitação %s não existe mais', v_solicitacao_id)
+    );
+  end if;
+
+  select estoque_atual into v_estoque
+    from itens_estoque
+   where id = v_solicitacao.item_id
+   for update;
+
+  v_current := jsonb_build_object(
+    'status', v_solicitacao.status,
+    'atualizado_em', v_solicitacao.atualizado_em,
+    'quantidade_aprovada', v_solicitacao.quantidade_aprovada,
+    'estoque_atual', v_estoque
+  );
+
+  if v_solicitacao.status = 'cancelada' then
+    return jsonb_build_object('outcome', 'conflict', 'reason', 'cancelled', 'current', v_current,
+      'message', 'A solicitação foi cancelada em outra estação');
+  end if;
+
+  if p_kind = 'approve' then
+    v_quantidade := (p_payload->>'quantidade_aprovada')::numeric;
+    if v_solicitacao.status = 'aprovada' and v_solicitacao.quantidade_aprovada = v_quantidade then
+      return jsonb_build_object('outcome', 'resolved', 'current', v_current,
+        'message', 'Solicitação já aprovada com a mesma quantidade');
+    end if;
+    if not p_force and v_solicitacao.status in ('aprovada', 'entregue', 'rejeitada') then
+      return jsonb_build_object('outcome', 'conflict', 'current', v_current,
+        'reason', case v_solicitacao.status
+          when 'entregue' then 'already_delivered'
+          when 'rejeitada' then 'already_rejected'
+          else 'already_approved' end,
+        'message', format('A solicitação já está %s em outra estação', v_solicitacao.status));
+    end if;
+    if v_solicitacao.status = 'entregue' then
+      return jsonb_build_object('outcome', 'conflict', 'reason', 'already_delivered', 'current', v_current,
+        'message', 'A solicitação já foi entregue; a aprovação não pode mais ser aplicada');
+    end if;
+  elsif p_kind = 'reject' then
+    if v_solicitacao.status = 'rejeitada' then
+      return jsonb_build_object('outcome', 'resolved', 'current', v_current,
+        'message', 'Solicitação já rejeitada');
+    end if;
+    if v_solicitacao.status = 'entregue' then
+      return jsonb_build_object('outcome', 'conflict', 'reason', 'already_delivered', 'current', v_current,
+        'message', 'A solicitação já foi entregue em outra estação; a rejeição não pode ser aplicada');
+    end if;
+    if not p_force and v_solicitacao.status is distinct from coalesce(v_base_status, v_solicitacao.status) then
+      return jsonb_build_object('outcome', 'conflict', 'reason', 'status_changed', 'current', v_current,
+        'message', format('A solicitação passou de %s para %s em outra estação', v_base_status, v_solicitacao.status));
+    end if;
+  elsif p_kind = 'deliver' then
+    v_quantidade := (p_payload->>'quantidade_entregue')::numeric;
+    if v_solicitacao.status = 'entregue' then
+      return jsonb_build_object('outcome', 'conflict', 'reason', 'already_delivered', 'current', v_current,
+        'message', 'A solicitação já foi entregue em outra estação');
+    end if;
+    if not p_force and v_solicitacao.status = 'rejeitada' then
+      return jsonb_build_object('outcome', 'conflict', 'reason', 'already_rejected', 'current', v_current,
+        'message', 'A solicitação foi rejeitada em outra estação');
+    end if;
+    if not p_force
+       and v_solicitacao.status is distinct from coalesce(v_base_status, v_solicitacao.status)
+       and v_solicitacao.quantidade_aprovada is distinct from v_quantidade then
+      return jsonb_build_object('outcome', 'conflict', 'reason', 'status_changed', 'current', v_current,
+        'message', format('A solicitação passou de %s para %s (quantidade aprovada %s) em outra estação',
+          v_base_status, v_solicitacao.status, v_solicitacao.quantidade_aprovada));
+    end if;
+    if v_estoque is not null and v_estoque < v_quantidade then
+      return jsonb_build_object('outcome', 'conflict', 'reason', 'insufficient_stock', 'current', v_current,
+        'message', format('Estoque insuficiente: há %s, a entrega pede %s', v_estoque, v_quantidade));
+    end if;
+  else
+    raise exception 'operação % não suportada', p_kind using errcode = '22023';
+  end if;
+
+  insert into sync_applied_operations (idempotency_key, kind, station_id)
+  values (p_idempotency_key, p_kind, p_station_id)
+  on conflict (idempotency_key) do nothing;
+  get diagnostics v_rows = row_count;
+  if v_rows = 0 then
+    return jsonb_build_object('outcome', 'duplicate');
+  end if;
+
+  if p_kind = 'approve' then
+    update solicitacoes_itens
+       set status = 'aprovada',
+           quantidade_aprovada = v_quantidade,
+           aprovado_por = (p_payload->>'aprovado_por')::uuid,
+           aprovado_em = p_occurred_at,
+           atualizado_em = p_occurred_at
+     where id = v_solicitacao_id;
+  elsif p_kind = 'reject' then
+    update solicitacoes_itens
+       set status = 'rejeitada',
+           motivo_rejeicao = p_payload->>'motivo_rejeicao',
+           rejeitado_por = (p_payload->>'rejeitado_por')::uuid,
+           rejeitado_em = p_occurred_at,
+           atualizado_em = p_occurred_at
+     where id = v_solicitacao_id;
+  else
+    v_function := case when v_solicitacao.destinatario_equipe_id is null
+      then 'entregar_item_estoque' else 'entregar_item_para_equipe' end;
+    -- Argumentos como literais: o banco escolhe os tipos como faz nas chamadas do PostgREST
+    execute format(
+      'select public.%I(p_solicitacao_id => %L, p_entregador_id => %L, p_quantidade_entregue => %L,
+         p_condicao_entrega => %L, p_observacoes_entrega => %L, p_entregue_em => %L,
+         p_atualizado_em => %L, p_numero_laudo => %L, p_validade_laudo => %L)',
+      v_function,
+      v_solicitacao_id,
+      p_payload->>'entregador_id',
+      p_payload->>'quantidade_entregue',
+      'novo',
+      coalesce(p_payload->>'observacoes', ''),
+      p_occurred_at,
+      p_occurred_at,
+      nullif(p_payload->>'numero_laudo', ''),
+      nullif(p_payload->>'validade_laudo', '')
+    );
+  end if;
+
+  return jsonb_build_object('outcome', 'applied');
+end;
+$$;
+
+revoke all on function public.sync_apply_operation(text, text, jsonb, timestamptz, text, jsonb, boolean) from public, anon;
+grant execute on function public.sync_apply_operation(text, text, jsonb, timestamptz, text, jsonb, boolean) to authenticated;